@@ -962,6 +962,7 @@ fn main() -> std::io::Result<()> {
                                         uvs,
                                         None,
                                         None,
+                                        Vec::new(),
                                     ));
                                     for id in 0..mesh.n_triangles {
                                         let triangle = Arc::new(Shape::Trngl(Triangle::new(
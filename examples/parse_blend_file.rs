@@ -281,6 +281,7 @@ impl SceneDescriptionBuilder {
             uv,
             None,
             None,
+            Vec::new(),
         ));
         self.meshes.push(triangle_mesh);
         self.triangle_colors.push(triangle_colors);
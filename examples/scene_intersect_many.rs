@@ -0,0 +1,103 @@
+use std::cell::Cell;
+use std::sync::Arc;
+
+use pbrt::accelerators::bvh::{BVHAccel, SplitMethod};
+use pbrt::core::geometry::{Point3f, Ray, Vector3f};
+use pbrt::core::interaction::SurfaceInteraction;
+use pbrt::core::pbrt::Float;
+use pbrt::core::primitive::{GeometricPrimitive, Primitive};
+use pbrt::core::scene::Scene;
+use pbrt::core::shape::Shape;
+use pbrt::core::transform::Transform;
+use pbrt::shapes::sphere::Sphere;
+
+fn main() {
+    // a 10x10 grid of spheres in the plane z = 0
+    let mut primitives: Vec<Arc<Primitive>> = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            let object_to_world: Transform = Transform::translate(&Vector3f {
+                x: 2.0 * i as Float - 9.0,
+                y: 2.0 * j as Float - 9.0,
+                z: 0.0,
+            });
+            let world_to_object: Transform = Transform::inverse(&object_to_world);
+            let radius: Float = 0.3 + 0.06 * ((i + j) % 10) as Float;
+            let sphere: Arc<Shape> = Arc::new(Shape::Sphr(Sphere::new(
+                object_to_world,
+                world_to_object,
+                false,
+                radius,
+                -radius,
+                radius,
+                360.0,
+            )));
+            primitives.push(Arc::new(Primitive::Geometric(Box::new(
+                GeometricPrimitive::new(sphere, None, None, None),
+            ))));
+        }
+    }
+    let aggregate: Arc<Primitive> = Arc::new(Primitive::BVH(Box::new(BVHAccel::new(
+        primitives,
+        1,
+        SplitMethod::SAH,
+        2,
+    ))));
+    let scene: Scene = Scene::new(aggregate, Vec::new());
+    // primary rays of a 64x64 pinhole camera, scanline by scanline
+    let origin: Point3f = Point3f {
+        x: 0.0,
+        y: 0.0,
+        z: 20.0,
+    };
+    let mut rays: Vec<Ray> = Vec::new();
+    for y in 0..64 {
+        for x in 0..64 {
+            rays.push(Ray {
+                o: origin,
+                d: Vector3f {
+                    x: (x as Float + 0.5) / 32.0 - 1.0,
+                    y: (y as Float + 0.5) / 32.0 - 1.0,
+                    z: -2.0,
+                },
+                t_max: Cell::new(Float::INFINITY),
+                ..Default::default()
+            });
+        }
+    }
+    let isects: Vec<Option<SurfaceInteraction>> = scene.intersect_many(&rays);
+    let occluded: Vec<bool> = scene.intersect_p_many(&rays);
+    // compare with tracing one ray at a time
+    let mut n_hits: usize = 0;
+    let mut n_mismatches: usize = 0;
+    for (r, ray) in rays.iter().enumerate() {
+        let mut single_ray: Ray = Ray {
+            o: ray.o,
+            d: ray.d,
+            t_max: Cell::new(Float::INFINITY),
+            ..Default::default()
+        };
+        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+        let hit: bool = scene.intersect(&single_ray, &mut isect);
+        let occluded_single: bool = scene.intersect_p(&mut single_ray);
+        match isects[r] {
+            Some(ref packet_isect) => {
+                n_hits += 1;
+                let (p0, p1): (Point3f, Point3f) = (packet_isect.common.p, isect.common.p);
+                if !hit || p0.x != p1.x || p0.y != p1.y || p0.z != p1.z {
+                    n_mismatches += 1;
+                }
+            }
+            None => {
+                if hit {
+                    n_mismatches += 1;
+                }
+            }
+        }
+        if occluded[r] != occluded_single {
+            n_mismatches += 1;
+        }
+    }
+    println!("{} of {} rays hit a sphere", n_hits, rays.len());
+    println!("{} mismatches with Scene::intersect()", n_mismatches);
+}
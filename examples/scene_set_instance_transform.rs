@@ -0,0 +1,72 @@
+use std::cell::Cell;
+use std::sync::Arc;
+
+use pbrt::accelerators::bvh::{BVHAccel, SplitMethod};
+use pbrt::core::geometry::{Point3f, Ray, Vector3f};
+use pbrt::core::interaction::SurfaceInteraction;
+use pbrt::core::pbrt::Float;
+use pbrt::core::primitive::{GeometricPrimitive, Primitive, TransformedPrimitive};
+use pbrt::core::scene::Scene;
+use pbrt::core::shape::Shape;
+use pbrt::core::transform::{AnimatedTransform, Transform};
+use pbrt::shapes::sphere::Sphere;
+
+fn translate(x: Float, y: Float) -> AnimatedTransform {
+    let t: Transform = Transform::translate(&Vector3f { x, y, z: 0.0 });
+    AnimatedTransform::new(&t, 0.0, &t, 1.0)
+}
+
+fn hit_point(scene: &Scene, x: Float) -> Option<Point3f> {
+    // shoot a ray downwards at x
+    let ray: Ray = Ray {
+        o: Point3f { x, y: 0.0, z: 10.0 },
+        d: Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        },
+        t_max: Cell::new(Float::INFINITY),
+        ..Default::default()
+    };
+    let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+    if scene.intersect(&ray, &mut isect) {
+        Some(isect.common.p)
+    } else {
+        None
+    }
+}
+
+fn main() {
+    // bottom level: a unit sphere at the origin
+    let identity: Transform = Transform::default();
+    let sphere: Arc<Shape> = Arc::new(Shape::Sphr(Sphere::new(
+        identity, identity, false, 1.0, -1.0, 1.0, 360.0,
+    )));
+    let object: Arc<Primitive> = Arc::new(Primitive::Geometric(Box::new(GeometricPrimitive::new(
+        sphere, None, None, None,
+    ))));
+    // top level: three instances of the sphere
+    let instances: Vec<Arc<Primitive>> = (0..3)
+        .map(|i| {
+            Arc::new(Primitive::Transformed(Box::new(TransformedPrimitive::new(
+                object.clone(),
+                translate(3.0 * i as Float, 0.0),
+            ))))
+        })
+        .collect();
+    let aggregate: Arc<Primitive> = Arc::new(Primitive::BVH(Box::new(BVHAccel::new(
+        instances,
+        1,
+        SplitMethod::SAH,
+        2,
+    ))));
+    let mut scene: Scene = Scene::new(aggregate, Vec::new());
+    for frame in 0..3 {
+        println!("frame {}: world_bound() = {:?}", frame, scene.world_bound());
+        println!("  hit at x = 3: {:?}", hit_point(&scene, 3.0));
+        println!("  hit at x = 10: {:?}", hit_point(&scene, 10.0));
+        // move the second instance along x and refit the top level
+        scene.set_instance_transform(1, &translate(3.0 + 3.5 * (frame + 1) as Float, 0.0));
+        scene.refit();
+    }
+}
@@ -0,0 +1,55 @@
+use pbrt::core::geometry::{Point2f, Point3f, Vector3f};
+use pbrt::core::pbrt::Float;
+use pbrt::core::transform::Transform;
+use pbrt::shapes::bilinearpatch::{BilinearPatch, BilinearPatchMesh};
+use std::sync::Arc;
+
+fn main() {
+    let translate: Transform = Transform::translate(&Vector3f {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    });
+    let inverse: Transform = Transform::inverse(&translate);
+    // a non-planar quad
+    let p: Vec<Point3f> = vec![
+        Point3f {
+            x: -1.0,
+            y: -1.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 1.0,
+            y: -1.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: -1.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        },
+    ];
+    let p_ws: Vec<Point3f> = p.iter().map(|p| translate.transform_point(p)).collect();
+    let mesh = Arc::new(BilinearPatchMesh::new(
+        translate,
+        inverse,
+        false,
+        1,
+        vec![0, 1, 2, 3],
+        4,
+        p_ws,
+        Vec::new(),
+        Vec::new(),
+    ));
+    let patch = BilinearPatch::new(mesh, 0);
+    let mut pdf: Float = 0.0;
+    let it = patch.sample(Point2f { x: 0.5, y: 0.5 }, &mut pdf);
+    println!("patch.area() = {:?}", patch.area());
+    println!("patch.world_bound() = {:?}", patch.world_bound());
+    println!("sampled point = {:?} (pdf = {:?})", it.p, pdf);
+}
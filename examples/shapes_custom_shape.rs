@@ -0,0 +1,123 @@
+use std::cell::Cell;
+use std::sync::Arc;
+
+use pbrt::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f};
+use pbrt::core::interaction::{InteractionCommon, SurfaceInteraction};
+use pbrt::core::pbrt::Float;
+use pbrt::core::shape::{CustomShape, Shape};
+use pbrt::core::transform::Transform;
+
+/// An axis-aligned square in the plane z = *height*, facing up.
+struct Square {
+    height: Float,
+    half_size: Float,
+}
+
+impl CustomShape for Square {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new(
+            Point3f {
+                x: -self.half_size,
+                y: -self.half_size,
+                z: self.height,
+            },
+            Point3f {
+                x: self.half_size,
+                y: self.half_size,
+                z: self.height,
+            },
+        )
+    }
+    fn get_object_to_world(&self) -> Transform {
+        Transform::default()
+    }
+    fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        if r.d.z == 0.0 as Float {
+            return false;
+        }
+        let t: Float = (self.height - r.o.z) / r.d.z;
+        if t <= 0.0 as Float || t >= r.t_max.get() {
+            return false;
+        }
+        let p: Point3f = r.position(t);
+        if p.x.abs() > self.half_size || p.y.abs() > self.half_size {
+            return false;
+        }
+        let size: Float = 2.0 as Float * self.half_size;
+        *isect = SurfaceInteraction::new(
+            &p,
+            &Vector3f::default(),
+            Point2f {
+                x: (p.x + self.half_size) / size,
+                y: (p.y + self.half_size) / size,
+            },
+            &-r.d,
+            &Vector3f {
+                x: size,
+                y: 0.0,
+                z: 0.0,
+            },
+            &Vector3f {
+                x: 0.0,
+                y: size,
+                z: 0.0,
+            },
+            &Normal3f::default(),
+            &Normal3f::default(),
+            r.time,
+            None,
+        );
+        *t_hit = t;
+        true
+    }
+    fn area(&self) -> Float {
+        4.0 as Float * self.half_size * self.half_size
+    }
+    fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
+        *pdf = 1.0 as Float / self.area();
+        InteractionCommon {
+            p: Point3f {
+                x: (2.0 as Float * u.x - 1.0 as Float) * self.half_size,
+                y: (2.0 as Float * u.y - 1.0 as Float) * self.half_size,
+                z: self.height,
+            },
+            n: Normal3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+fn main() {
+    let shape: Shape = Shape::Custom(Arc::new(Square {
+        height: 1.0,
+        half_size: 0.5,
+    }));
+    println!("world_bound() = {:?}", shape.world_bound());
+    println!("area() = {:?}", shape.area());
+    let ray: Ray = Ray {
+        o: Point3f {
+            x: 0.25,
+            y: 0.0,
+            z: 3.0,
+        },
+        d: Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        },
+        t_max: Cell::new(Float::INFINITY),
+        ..Default::default()
+    };
+    let mut t_hit: Float = 0.0;
+    let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+    if shape.intersect(&ray, &mut t_hit, &mut isect) {
+        println!("t_hit = {:?}", t_hit);
+        println!("p = {:?}", isect.common.p);
+        println!("uv = {:?}", isect.uv);
+    }
+    println!("intersect_p() = {:?}", shape.intersect_p(&ray));
+}
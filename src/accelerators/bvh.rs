@@ -1,26 +1,45 @@
 // std
+use std::hash::Hasher;
+use std::path::Path;
 use std::sync::Arc;
 // others
+use rayon::prelude::*;
 // use time::PreciseTime;
 use typed_arena::Arena;
 // pbrt
-use crate::core::geometry::{bnd3_union_bnd3f, bnd3_union_pnt3f};
+use crate::accelerators::bvhcache;
+use crate::accelerators::bvhcache::{KeyHasher, Pod};
+use crate::accelerators::widebvh::WideBVH;
+use crate::core::geometry::{bnd3_intersect_bnd3f, bnd3_union_bnd3f, bnd3_union_pnt3f};
 use crate::core::geometry::{Bounds3f, Point3f, Ray, Vector3f, XYZEnum};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::light::Light;
 use crate::core::material::Material;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
-use crate::core::primitive::Primitive;
+use crate::core::primitive::{Primitive, TransformedPrimitive};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::shape::Shape;
+use crate::core::stats;
+use crate::core::transform::AnimatedTransform;
 
 // see bvh.h
 
+/// Maximum number of rays traversing the hierarchy together (see
+/// _BVHAccel::intersect_packet()_).
+pub const PACKET_SIZE: usize = 16;
+
 #[derive(Debug, Clone)]
 pub enum SplitMethod {
     SAH,
     HLBVH,
     Middle,
     EqualCounts,
+    /// SAH with spatial splits, *split_budget* limits the additional
+    /// primitive references (relative to the number of primitives)
+    SBVH {
+        split_budget: Float,
+    },
 }
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -100,7 +119,35 @@ impl Default for BucketInfo {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+/// Nodes with fewer primitives are built on a single thread.
+const PARALLEL_BUILD_THRESHOLD: usize = 64 * 1024;
+/// Number of primitives per task when computing bounds in parallel.
+const PARALLEL_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Upper levels of a parallel build, see _BVHAccel::parallel_build()_.
+enum BVHTopNode {
+    Interior {
+        axis: u8,
+        c0: Box<BVHTopNode>,
+        c1: Box<BVHTopNode>,
+    },
+    Subtree,
+}
+
+struct BVHSubtree<'s> {
+    primitive_info: &'s mut [BVHPrimitiveInfo],
+    prim_offset: usize,
+    ordered_prim_numbers: &'s mut [usize],
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct MortonPrimitive {
+    primitive_index: usize,
+    morton_code: u32,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
 pub struct LinearBVHNode {
     bounds: Bounds3f,
     // in C++ a union { int primitivesOffset;     // leaf
@@ -111,12 +158,67 @@ pub struct LinearBVHNode {
     pad: u8,
 }
 
+// 32 bytes without padding, see _pad_
+unsafe impl Pod for LinearBVHNode {}
+
+impl LinearBVHNode {
+    /// Checks flattened nodes (e.g. read from a cache) before they
+    /// are traversed: every node but the root has exactly one parent
+    /// stored before it, all offsets are in range, the leaves cover
+    /// the *n_primitives* (ordered) primitives and the traversal
+    /// stack can't overflow.
+    pub fn is_valid_tree(nodes: &[LinearBVHNode], n_primitives: usize) -> bool {
+        if nodes.is_empty() {
+            return n_primitives == 0;
+        }
+        let mut n_parents: Vec<u32> = vec![0_u32; nodes.len()];
+        let mut depth: Vec<usize> = vec![0_usize; nodes.len()];
+        let mut n_leaf_primitives: usize = 0;
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 && n_parents[i] != 1 {
+                return false;
+            }
+            if node.n_primitives > 0 {
+                if node.offset < 0
+                    || node.offset as usize + node.n_primitives as usize > n_primitives
+                {
+                    return false;
+                }
+                n_leaf_primitives += node.n_primitives as usize;
+            } else {
+                // the first child follows its parent
+                let second_child: usize = node.offset as usize;
+                if node.axis > 2
+                    || node.offset < 0
+                    || second_child <= i + 1
+                    || second_child >= nodes.len()
+                {
+                    return false;
+                }
+                for child in [i + 1, second_child].iter() {
+                    n_parents[*child] += 1;
+                    depth[*child] = depth[i] + 1;
+                    // see _nodes_to_visit_ in intersect()
+                    if depth[*child] >= 64 {
+                        return false;
+                    }
+                }
+            }
+        }
+        n_leaf_primitives == n_primitives
+    }
+}
+
 // BVHAccel -> Aggregate -> Primitive
 pub struct BVHAccel {
     max_prims_in_node: usize,
     split_method: SplitMethod,
     pub primitives: Vec<Arc<Primitive>>,
+    /// index of each primitive in the vector the BVH was built from
+    pub primitive_numbers: Vec<usize>,
     pub nodes: Vec<LinearBVHNode>,
+    /// replaces the binary _nodes_ for a width of 4 or 8
+    pub wide_nodes: Option<WideBVH>,
 }
 
 impl BVHAccel {
@@ -124,12 +226,15 @@ impl BVHAccel {
         p: Vec<Arc<Primitive>>,
         max_prims_in_node: usize,
         split_method: SplitMethod,
+        width: usize,
     ) -> Self {
         let bvh = Arc::new(BVHAccel {
             max_prims_in_node: std::cmp::min(max_prims_in_node, 255),
             split_method: split_method.clone(),
             primitives: p,
+            primitive_numbers: Vec::new(),
             nodes: Vec::new(),
+            wide_nodes: None,
         });
         let num_prims = bvh.primitives.len();
         if num_prims == 0_usize {
@@ -141,23 +246,61 @@ impl BVHAccel {
             let world_bound = bvh.primitives[i].world_bound();
             *item = BVHPrimitiveInfo::new(i, world_bound);
         }
-        // TODO: if (splitMethod == SplitMethod::HLBVH)
+        // the treelets of an HLBVH (or the subtrees of a parallel
+        // build) are emitted into arenas of their own
+        let mut treelet_arenas: Vec<Arena<BVHBuildNode>> = Vec::new();
         let arena: Arena<BVHBuildNode> = Arena::with_capacity(1024 * 1024);
         let mut total_nodes: usize = 0;
-        let mut ordered_prims: Vec<Arc<Primitive>> = Vec::with_capacity(num_prims);
+        let mut ordered_prim_numbers: Vec<usize> = Vec::with_capacity(num_prims);
         // println!("BVHAccel::recursive_build(..., {}, ...)", num_prims);
         // let start = PreciseTime::now();
-        let root = BVHAccel::recursive_build(
-            bvh, // instead of self
-            &arena,
-            &mut primitive_info,
-            0,
-            num_prims,
-            &mut total_nodes,
-            &mut ordered_prims,
-        );
+        let root = match split_method {
+            SplitMethod::HLBVH => BVHAccel::hlbvh_build(
+                &bvh,
+                &arena,
+                &mut treelet_arenas,
+                &primitive_info,
+                &mut total_nodes,
+                &mut ordered_prim_numbers,
+            ),
+            SplitMethod::SBVH { split_budget } => BVHAccel::sbvh_build(
+                &bvh,
+                &arena,
+                &primitive_info,
+                split_budget,
+                &mut total_nodes,
+                &mut ordered_prim_numbers,
+            ),
+            _ => {
+                ordered_prim_numbers.resize(num_prims, 0);
+                BVHAccel::parallel_build(
+                    &bvh, // instead of self
+                    &arena,
+                    &mut treelet_arenas,
+                    &mut primitive_info,
+                    &mut ordered_prim_numbers,
+                    &mut total_nodes,
+                )
+            }
+        };
+        let ordered_prims: Vec<Arc<Primitive>> = ordered_prim_numbers
+            .iter()
+            .map(|prim_num| bvh.primitives[*prim_num].clone())
+            .collect();
         // let end = PreciseTime::now();
         // println!("{} seconds for building BVH ...", start.to(end));
+        if width > 2 {
+            // collapse binary tree into wide nodes
+            let bvh_ordered_prims = BVHAccel {
+                max_prims_in_node: std::cmp::min(max_prims_in_node, 255),
+                split_method,
+                primitives: ordered_prims,
+                primitive_numbers: ordered_prim_numbers,
+                nodes: Vec::new(),
+                wide_nodes: Some(WideBVH::new(root, width)),
+            };
+            return bvh_ordered_prims;
+        }
         // flatten first
         let mut nodes = vec![LinearBVHNode::default(); total_nodes];
         let mut offset: usize = 0;
@@ -172,216 +315,775 @@ impl BVHAccel {
             max_prims_in_node: std::cmp::min(max_prims_in_node, 255),
             split_method,
             primitives: ordered_prims,
+            primitive_numbers: ordered_prim_numbers,
             nodes,
+            wide_nodes: None,
         });
         let unwrapped = Arc::try_unwrap(bvh_ordered_prims);
         unwrapped.ok().unwrap()
     }
     pub fn create(prims: Vec<Arc<Primitive>>, ps: &ParamSet) -> Primitive {
+        let split_method: SplitMethod = BVHAccel::split_method_from_params(ps);
+        let max_prims_in_node: i32 = ps.find_one_int("maxnodeprims", 4);
+        let width: i32 = BVHAccel::width_from_params(ps);
+        if let Some(cache_directory) = bvhcache::cache_directory(ps) {
+            return Primitive::BVH(Box::new(BVHAccel::new_cached(
+                prims,
+                max_prims_in_node as usize,
+                split_method,
+                width as usize,
+                &cache_directory,
+            )));
+        }
+        Primitive::BVH(Box::new(BVHAccel::new(
+            prims,
+            max_prims_in_node as usize,
+            split_method,
+            width as usize,
+        )))
+    }
+    /// Like _BVHAccel::new()_, but reads the nodes and the primitive
+    /// order from the cache in *cache_directory* if the same
+    /// hierarchy was built before, otherwise the new hierarchy is
+    /// stored there.
+    pub fn new_cached(
+        p: Vec<Arc<Primitive>>,
+        max_prims_in_node: usize,
+        split_method: SplitMethod,
+        width: usize,
+        cache_directory: &Path,
+    ) -> Self {
+        if p.is_empty() {
+            return BVHAccel::new(p, max_prims_in_node, split_method, width);
+        }
+        let max_prims_in_node: usize = std::cmp::min(max_prims_in_node, 255);
+        // the hierarchy depends on the primitive bounds only, except
+        // for spatial splits which clip the triangles themselves
+        let bounds: Vec<Bounds3f> = p.par_iter().map(|prim| prim.world_bound()).collect();
+        let mut hasher: KeyHasher = KeyHasher::default();
+        hasher.write_usize(max_prims_in_node);
+        hasher.write_usize(width);
+        match split_method {
+            SplitMethod::SAH => hasher.write_u32(0),
+            SplitMethod::HLBVH => hasher.write_u32(1),
+            SplitMethod::Middle => hasher.write_u32(2),
+            SplitMethod::EqualCounts => hasher.write_u32(3),
+            SplitMethod::SBVH { split_budget } => {
+                hasher.write_u32(4);
+                hasher.write_float(split_budget);
+            }
+        }
+        hasher.write_usize(p.len());
+        for (prim, bound) in p.iter().zip(bounds.iter()) {
+            hasher.write_bounds(bound);
+            if let SplitMethod::SBVH { .. } = split_method {
+                if let Primitive::Geometric(ref primitive) = **prim {
+                    if let Shape::Trngl(ref triangle) = *primitive.shape {
+                        for v in triangle.vertices().iter() {
+                            hasher.write_float(v.x);
+                            hasher.write_float(v.y);
+                            hasher.write_float(v.z);
+                        }
+                    }
+                }
+            }
+        }
+        let key: u64 = hasher.finish();
+        if let Some(cached) = bvhcache::load_bvh(cache_directory, key, p.len()) {
+            let primitives: Vec<Arc<Primitive>> = cached
+                .primitive_numbers
+                .iter()
+                .map(|prim_num| p[*prim_num].clone())
+                .collect();
+            return BVHAccel {
+                max_prims_in_node,
+                split_method,
+                primitives,
+                primitive_numbers: cached.primitive_numbers,
+                nodes: cached.nodes,
+                wide_nodes: cached.wide_nodes,
+            };
+        }
+        let bvh: BVHAccel = BVHAccel::new(p, max_prims_in_node, split_method, width);
+        bvhcache::store_bvh(
+            cache_directory,
+            key,
+            &bvh.primitive_numbers,
+            &bvh.nodes,
+            bvh.wide_nodes.as_ref(),
+        );
+        bvh
+    }
+    pub fn split_method_from_params(ps: &ParamSet) -> SplitMethod {
         let split_method_name: String = ps.find_one_string("splitmethod", String::from("sah"));
-        let split_method;
         if split_method_name == "sah" {
-            split_method = SplitMethod::SAH;
+            SplitMethod::SAH
         } else if split_method_name == "hlbvh" {
-            split_method = SplitMethod::HLBVH;
+            SplitMethod::HLBVH
         } else if split_method_name == "middle" {
-            split_method = SplitMethod::Middle;
+            SplitMethod::Middle
         } else if split_method_name == "equal" {
-            split_method = SplitMethod::EqualCounts;
+            SplitMethod::EqualCounts
+        } else if split_method_name == "sbvh" {
+            SplitMethod::SBVH {
+                split_budget: ps
+                    .find_one_float("splitbudget", 0.3 as Float)
+                    .max(0.0 as Float),
+            }
         } else {
             println!(
                 "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
                 split_method_name
             );
-            split_method = SplitMethod::SAH;
+            SplitMethod::SAH
         }
-        let max_prims_in_node: i32 = ps.find_one_int("maxnodeprims", 4);
-        Primitive::BVH(Box::new(BVHAccel::new(
-            prims,
-            max_prims_in_node as usize,
-            split_method,
-        )))
     }
+    /// Number of children per node (2, 4 or 8) requested by
+    /// _"integer width"_.
+    pub fn width_from_params(ps: &ParamSet) -> i32 {
+        let width: i32 = ps.find_one_int("width", 2);
+        if width != 2 && width != 4 && width != 8 {
+            println!(
+                "WARNING: BVH width {} not supported.  Using 2 (binary BVH).",
+                width
+            );
+            return 2;
+        }
+        width
+    }
+    /// Builds the BVH with the split method of *bvh* (except
+    /// HLBVH). The primitives referenced by the leaves are stored in
+    /// *ordered_prim_numbers*. Nodes with at least
+    /// PARALLEL_BUILD_THRESHOLD primitives are split in parallel, and
+    /// the subtrees below them are built in parallel into arenas of
+    /// their own. The resulting tree is the same as the one built by
+    /// _recursive_build()_ on a single thread.
+    pub fn parallel_build<'a>(
+        bvh: &BVHAccel,
+        arena: &'a Arena<BVHBuildNode<'a>>,
+        subtree_arenas: &'a mut Vec<Arena<BVHBuildNode<'a>>>,
+        primitive_info: &mut [BVHPrimitiveInfo],
+        ordered_prim_numbers: &mut [usize],
+        total_nodes: &mut usize,
+    ) -> &'a BVHBuildNode<'a> {
+        // split upper levels of the hierarchy in parallel
+        let mut subtrees: Vec<BVHSubtree> = Vec::new();
+        let top: BVHTopNode =
+            BVHAccel::split_top(bvh, primitive_info, 0, ordered_prim_numbers, &mut subtrees);
+        // build subtrees in parallel
+        for subtree in subtrees.iter() {
+            subtree_arenas.push(Arena::with_capacity(2 * subtree.primitive_info.len()));
+        }
+        let subtree_roots: Vec<(&'a BVHBuildNode<'a>, usize)> = subtree_arenas
+            .par_iter_mut()
+            .zip(subtrees.par_iter_mut())
+            .map(|(subtree_arena, subtree)| {
+                let subtree_arena: &'a Arena<BVHBuildNode<'a>> = subtree_arena;
+                let mut nodes_created: usize = 0;
+                let root: &'a BVHBuildNode<'a> = BVHAccel::recursive_build(
+                    bvh,
+                    subtree_arena,
+                    subtree.primitive_info,
+                    subtree.prim_offset,
+                    subtree.ordered_prim_numbers,
+                    &mut nodes_created,
+                );
+                (root, nodes_created)
+            })
+            .collect();
+        let mut roots: Vec<&'a BVHBuildNode<'a>> = Vec::with_capacity(subtree_roots.len());
+        for (root, nodes_created) in subtree_roots {
+            roots.push(root);
+            *total_nodes += nodes_created;
+        }
+        // connect upper levels and subtrees
+        BVHAccel::build_top(arena, &top, &mut roots.into_iter(), total_nodes)
+    }
+    fn split_top<'s>(
+        bvh: &BVHAccel,
+        primitive_info: &'s mut [BVHPrimitiveInfo],
+        prim_offset: usize,
+        ordered_prim_numbers: &'s mut [usize],
+        subtrees: &mut Vec<BVHSubtree<'s>>,
+    ) -> BVHTopNode {
+        let n_primitives: usize = primitive_info.len();
+        if n_primitives >= PARALLEL_BUILD_THRESHOLD {
+            if let (_bounds, Some((dim, mid))) = BVHAccel::find_split(bvh, primitive_info, true) {
+                let (info0, info1) = primitive_info.split_at_mut(mid);
+                let (ordered1, ordered0) = ordered_prim_numbers.split_at_mut(n_primitives - mid);
+                let mut subtrees0: Vec<BVHSubtree<'s>> = Vec::new();
+                let mut subtrees1: Vec<BVHSubtree<'s>> = Vec::new();
+                let (subtrees0_ref, subtrees1_ref) = (&mut subtrees0, &mut subtrees1);
+                let (c0, c1) = rayon::join(
+                    move || {
+                        BVHAccel::split_top(
+                            bvh,
+                            info0,
+                            prim_offset + n_primitives - mid,
+                            ordered0,
+                            subtrees0_ref,
+                        )
+                    },
+                    move || BVHAccel::split_top(bvh, info1, prim_offset, ordered1, subtrees1_ref),
+                );
+                // subtrees are listed in the order _build_top()_ visits them
+                subtrees.append(&mut subtrees0);
+                subtrees.append(&mut subtrees1);
+                return BVHTopNode::Interior {
+                    axis: dim,
+                    c0: Box::new(c0),
+                    c1: Box::new(c1),
+                };
+            }
+        }
+        subtrees.push(BVHSubtree {
+            primitive_info,
+            prim_offset,
+            ordered_prim_numbers,
+        });
+        BVHTopNode::Subtree
+    }
+    fn build_top<'a>(
+        arena: &'a Arena<BVHBuildNode<'a>>,
+        top: &BVHTopNode,
+        roots: &mut dyn Iterator<Item = &'a BVHBuildNode<'a>>,
+        total_nodes: &mut usize,
+    ) -> &'a BVHBuildNode<'a> {
+        match top {
+            BVHTopNode::Interior { axis, c0, c1 } => {
+                let node: &mut BVHBuildNode<'a> = arena.alloc(BVHBuildNode::default());
+                *total_nodes += 1_usize;
+                let c0 = BVHAccel::build_top(arena, c0, roots, total_nodes);
+                let c1 = BVHAccel::build_top(arena, c1, roots, total_nodes);
+                node.init_interior(*axis, c0, c1);
+                node
+            }
+            BVHTopNode::Subtree => roots.next().unwrap(),
+        }
+    }
+    /// Builds the (sub)tree for *primitive_info* on the current
+    /// thread. The leaves refer to the primitives starting at
+    /// *prim_offset*, their numbers are stored in
+    /// *ordered_prim_numbers* (primitives of the second child come
+    /// first).
     pub fn recursive_build<'a>(
-        bvh: Arc<BVHAccel>,
+        bvh: &BVHAccel,
         arena: &'a Arena<BVHBuildNode<'a>>,
-        primitive_info: &mut Vec<BVHPrimitiveInfo>,
-        start: usize,
-        end: usize,
+        primitive_info: &mut [BVHPrimitiveInfo],
+        prim_offset: usize,
+        ordered_prim_numbers: &mut [usize],
         total_nodes: &mut usize,
-        ordered_prims: &mut Vec<Arc<Primitive>>,
     ) -> &'a BVHBuildNode<'a> {
-        assert_ne!(start, end);
+        assert!(!primitive_info.is_empty());
         let node: &mut BVHBuildNode<'a> = arena.alloc(BVHBuildNode::default());
         *total_nodes += 1_usize;
-        // compute bounds of all primitives in BVH node
-        let mut bounds: Bounds3f = Bounds3f::default();
-        for item in primitive_info.iter().take(end).skip(start) {
-            bounds = bnd3_union_bnd3f(&bounds, &item.bounds);
-        }
-        let n_primitives: usize = end - start;
-        if n_primitives == 1 {
-            // create leaf _BVHBuildNode_
-            let first_prim_offset: usize = ordered_prims.len();
-            for item in primitive_info.iter().take(end).skip(start) {
-                let prim_num: usize = item.primitive_number;
-                ordered_prims.push(bvh.primitives[prim_num].clone());
-            }
-            node.init_leaf(first_prim_offset, n_primitives, &bounds);
-            return node;
-        } else {
-            // compute bound of primitive centroids, choose split dimension _dim_
-            let mut centroid_bounds: Bounds3f = Bounds3f::default();
-            for item in primitive_info.iter().take(end).skip(start) {
-                centroid_bounds = bnd3_union_pnt3f(&centroid_bounds, &item.centroid);
-            }
-            let dim: u8 = centroid_bounds.maximum_extent();
-            let dim_i: XYZEnum = match dim {
-                0 => XYZEnum::X,
-                1 => XYZEnum::Y,
-                _ => XYZEnum::Z,
-            };
-            // partition primitives into two sets and build children
-            let mut mid: usize = (start + end) / 2_usize;
-            if centroid_bounds.p_max[dim_i] == centroid_bounds.p_min[dim_i] {
+        let n_primitives: usize = primitive_info.len();
+        match BVHAccel::find_split(bvh, primitive_info, false) {
+            (bounds, None) => {
                 // create leaf _BVHBuildNode_
-                let first_prim_offset: usize = ordered_prims.len();
-                for item in primitive_info.iter().take(end).skip(start) {
-                    let prim_num: usize = item.primitive_number;
-                    ordered_prims.push(bvh.primitives[prim_num].clone());
-                }
-                node.init_leaf(first_prim_offset, n_primitives, &bounds);
-                return node;
-            } else {
-                // partition primitives based on _splitMethod_
-                match bvh.split_method {
-                    SplitMethod::Middle => {
-                        // TODO
-                    }
-                    SplitMethod::EqualCounts => {
-                        // TODO
-                    }
-                    SplitMethod::SAH | SplitMethod::HLBVH => {
-                        if n_primitives <= 2 {
-                            mid = (start + end) / 2;
-                            if start != end - 1
-                                && primitive_info[end - 1].centroid[dim_i]
-                                    < primitive_info[start].centroid[dim_i]
-                            {
-                                primitive_info.swap(start, end - 1);
-                            }
-                        } else {
-                            // allocate _BucketInfo_ for SAH partition buckets
-                            let n_buckets: usize = 12;
-                            let mut buckets: [BucketInfo; 12] = [BucketInfo::default(); 12];
-                            // initialize _BucketInfo_ for SAH partition buckets
-                            for item in primitive_info.iter().take(end).skip(start) {
-                                let mut b: usize = (n_buckets as Float
-                                    * centroid_bounds.offset(&item.centroid)[dim_i])
-                                    as usize;
-                                if b == n_buckets {
-                                    b = n_buckets - 1;
-                                }
-                                // assert!(b >= 0_usize, "b >= 0");
-                                assert!(b < n_buckets, "b < {}", n_buckets);
-                                buckets[b].count += 1;
-                                buckets[b].bounds =
-                                    bnd3_union_bnd3f(&buckets[b].bounds, &item.bounds);
-                            }
-                            // compute costs for splitting after each bucket
-                            let mut cost: [Float; 11] = [0.0; 11];
-                            for (i, cost_item) in cost.iter_mut().enumerate().take(n_buckets - 1) {
-                                let mut b0: Bounds3f = Bounds3f::default();
-                                let mut b1: Bounds3f = Bounds3f::default();
-                                let mut count0: usize = 0;
-                                let mut count1: usize = 0;
-                                for item in buckets.iter().take(i + 1) {
-                                    b0 = bnd3_union_bnd3f(&b0, &item.bounds);
-                                    count0 += item.count;
-                                }
-                                for item in buckets.iter().take(n_buckets).skip(i + 1) {
-                                    b1 = bnd3_union_bnd3f(&b1, &item.bounds);
-                                    count1 += item.count;
-                                }
-                                *cost_item = 1.0
-                                    + (count0 as Float * b0.surface_area()
-                                        + count1 as Float * b1.surface_area())
-                                        / bounds.surface_area();
-                            }
-                            // find bucket to split at that minimizes SAH metric
-                            let mut min_cost: Float = cost[0];
-                            let mut min_cost_split_bucket: usize = 0;
-                            for (i, item) in cost.iter().enumerate().take(n_buckets - 1) {
-                                if item < &min_cost {
-                                    min_cost = *item;
-                                    min_cost_split_bucket = i;
-                                }
-                            }
-                            // either create leaf or split primitives
-                            // at selected SAH bucket
-                            let leaf_cost: Float = n_primitives as Float;
-                            if n_primitives > bvh.max_prims_in_node || min_cost < leaf_cost {
-                                let (mut left, mut right): (
-                                    Vec<BVHPrimitiveInfo>,
-                                    Vec<BVHPrimitiveInfo>,
-                                ) = primitive_info[start..end].iter().partition(|&pi| {
-                                    let mut b: usize = (n_buckets as Float
-                                        * centroid_bounds.offset(&pi.centroid)[dim_i])
-                                        as usize;
-                                    if b == n_buckets {
-                                        b = n_buckets - 1;
-                                    }
-                                    // assert!(b >= 0_usize, "b >= 0");
-                                    assert!(b < n_buckets, "b < {}", n_buckets);
-                                    b <= min_cost_split_bucket
-                                });
-                                mid = start + left.len();
-                                let combined_len = left.len() + right.len();
-                                if combined_len == primitive_info.len() {
-                                    primitive_info.clear();
-                                    primitive_info.append(&mut left);
-                                    primitive_info.append(&mut right);
-                                } else {
-                                    primitive_info.splice(start..mid, left.iter().cloned());
-                                    primitive_info.splice(mid..end, right.iter().cloned());
-                                }
-                            } else {
-                                // create leaf _BVHBuildNode_
-                                let first_prim_offset: usize = ordered_prims.len();
-                                for item in primitive_info.iter().take(end).skip(start) {
-                                    let prim_num: usize = item.primitive_number;
-                                    ordered_prims.push(bvh.primitives[prim_num].clone());
-                                }
-                                node.init_leaf(first_prim_offset, n_primitives, &bounds);
-                                return node;
-                            }
-                        }
-                    }
+                for (prim_num, item) in ordered_prim_numbers.iter_mut().zip(primitive_info.iter()) {
+                    *prim_num = item.primitive_number;
                 }
+                node.init_leaf(prim_offset, n_primitives, &bounds);
+            }
+            (_bounds, Some((dim, mid))) => {
+                let (info0, info1) = primitive_info.split_at_mut(mid);
+                let (ordered1, ordered0) = ordered_prim_numbers.split_at_mut(n_primitives - mid);
                 // make sure we get result for c1 before c0
                 let c1 = BVHAccel::recursive_build(
-                    bvh.clone(),
+                    bvh,
                     arena,
-                    primitive_info,
-                    mid,
-                    end,
+                    info1,
+                    prim_offset,
+                    ordered1,
                     total_nodes,
-                    ordered_prims,
                 );
                 let c0 = BVHAccel::recursive_build(
                     bvh,
                     arena,
-                    primitive_info,
-                    start,
-                    mid,
+                    info0,
+                    prim_offset + n_primitives - mid,
+                    ordered0,
                     total_nodes,
-                    ordered_prims,
                 );
                 node.init_interior(dim, c0, c1);
             }
         }
         node
     }
+    /// Returns the bounds of all primitives in *primitive_info* and,
+    /// unless they should become a leaf, the split dimension and the
+    /// index where the (partitioned) primitives are split. The bounds
+    /// and the SAH buckets are computed in parallel for *parallel*,
+    /// which doesn't change the result.
+    fn find_split(
+        bvh: &BVHAccel,
+        primitive_info: &mut [BVHPrimitiveInfo],
+        parallel: bool,
+    ) -> (Bounds3f, Option<(u8, usize)>) {
+        // compute bounds of all primitives in BVH node
+        let (bounds, centroid_bounds): (Bounds3f, Bounds3f) = if parallel {
+            primitive_info
+                .par_chunks(PARALLEL_CHUNK_SIZE)
+                .map(BVHAccel::primitive_bounds)
+                .reduce(
+                    || (Bounds3f::default(), Bounds3f::default()),
+                    |a, b| (bnd3_union_bnd3f(&a.0, &b.0), bnd3_union_bnd3f(&a.1, &b.1)),
+                )
+        } else {
+            BVHAccel::primitive_bounds(primitive_info)
+        };
+        let n_primitives: usize = primitive_info.len();
+        if n_primitives == 1 {
+            return (bounds, None);
+        }
+        // compute bound of primitive centroids, choose split dimension _dim_
+        let dim: u8 = centroid_bounds.maximum_extent();
+        let dim_i: XYZEnum = match dim {
+            0 => XYZEnum::X,
+            1 => XYZEnum::Y,
+            _ => XYZEnum::Z,
+        };
+        if centroid_bounds.p_max[dim_i] == centroid_bounds.p_min[dim_i] {
+            return (bounds, None);
+        }
+        // partition primitives based on _splitMethod_
+        let mid: usize = match bvh.split_method {
+            SplitMethod::Middle => {
+                // TODO
+                n_primitives / 2
+            }
+            SplitMethod::EqualCounts => {
+                // TODO
+                n_primitives / 2
+            }
+            SplitMethod::SAH | SplitMethod::HLBVH | SplitMethod::SBVH { .. } => {
+                if n_primitives <= 2 {
+                    if primitive_info[n_primitives - 1].centroid[dim_i]
+                        < primitive_info[0].centroid[dim_i]
+                    {
+                        primitive_info.swap(0, n_primitives - 1);
+                    }
+                    n_primitives / 2
+                } else {
+                    // allocate _BucketInfo_ for SAH partition buckets
+                    let n_buckets: usize = 12;
+                    let bucket_index = |pi: &BVHPrimitiveInfo| -> usize {
+                        let b: usize = (n_buckets as Float
+                            * centroid_bounds.offset(&pi.centroid)[dim_i])
+                            as usize;
+                        // assert!(b >= 0_usize, "b >= 0");
+                        assert!(b <= n_buckets, "b <= {}", n_buckets);
+                        b.min(n_buckets - 1)
+                    };
+                    // initialize _BucketInfo_ for SAH partition buckets
+                    let fill_buckets = |chunk: &[BVHPrimitiveInfo]| -> [BucketInfo; 12] {
+                        let mut buckets: [BucketInfo; 12] = [BucketInfo::default(); 12];
+                        for item in chunk {
+                            let b: usize = bucket_index(item);
+                            buckets[b].count += 1;
+                            buckets[b].bounds = bnd3_union_bnd3f(&buckets[b].bounds, &item.bounds);
+                        }
+                        buckets
+                    };
+                    let buckets: [BucketInfo; 12] = if parallel {
+                        primitive_info
+                            .par_chunks(PARALLEL_CHUNK_SIZE)
+                            .map(fill_buckets)
+                            .reduce(
+                                || [BucketInfo::default(); 12],
+                                |mut a, b| {
+                                    for (a, b) in a.iter_mut().zip(b.iter()) {
+                                        a.count += b.count;
+                                        a.bounds = bnd3_union_bnd3f(&a.bounds, &b.bounds);
+                                    }
+                                    a
+                                },
+                            )
+                    } else {
+                        fill_buckets(primitive_info)
+                    };
+                    // compute costs for splitting after each bucket
+                    let mut cost: [Float; 11] = [0.0; 11];
+                    for (i, cost_item) in cost.iter_mut().enumerate().take(n_buckets - 1) {
+                        let mut b0: Bounds3f = Bounds3f::default();
+                        let mut b1: Bounds3f = Bounds3f::default();
+                        let mut count0: usize = 0;
+                        let mut count1: usize = 0;
+                        for item in buckets.iter().take(i + 1) {
+                            b0 = bnd3_union_bnd3f(&b0, &item.bounds);
+                            count0 += item.count;
+                        }
+                        for item in buckets.iter().take(n_buckets).skip(i + 1) {
+                            b1 = bnd3_union_bnd3f(&b1, &item.bounds);
+                            count1 += item.count;
+                        }
+                        *cost_item = 1.0
+                            + (count0 as Float * b0.surface_area()
+                                + count1 as Float * b1.surface_area())
+                                / bounds.surface_area();
+                    }
+                    // find bucket to split at that minimizes SAH metric
+                    let mut min_cost: Float = cost[0];
+                    let mut min_cost_split_bucket: usize = 0;
+                    for (i, item) in cost.iter().enumerate().take(n_buckets - 1) {
+                        if item < &min_cost {
+                            min_cost = *item;
+                            min_cost_split_bucket = i;
+                        }
+                    }
+                    // either create leaf or split primitives
+                    // at selected SAH bucket
+                    let leaf_cost: Float = n_primitives as Float;
+                    if n_primitives > bvh.max_prims_in_node || min_cost < leaf_cost {
+                        let (left, right): (Vec<BVHPrimitiveInfo>, Vec<BVHPrimitiveInfo>) =
+                            primitive_info
+                                .iter()
+                                .partition(|&pi| bucket_index(pi) <= min_cost_split_bucket);
+                        primitive_info[..left.len()].copy_from_slice(&left);
+                        primitive_info[left.len()..].copy_from_slice(&right);
+                        left.len()
+                    } else {
+                        return (bounds, None);
+                    }
+                }
+            }
+        };
+        (bounds, Some((dim, mid)))
+    }
+    fn primitive_bounds(primitive_info: &[BVHPrimitiveInfo]) -> (Bounds3f, Bounds3f) {
+        let mut bounds: Bounds3f = Bounds3f::default();
+        let mut centroid_bounds: Bounds3f = Bounds3f::default();
+        for item in primitive_info {
+            bounds = bnd3_union_bnd3f(&bounds, &item.bounds);
+            centroid_bounds = bnd3_union_pnt3f(&centroid_bounds, &item.centroid);
+        }
+        (bounds, centroid_bounds)
+    }
+    /// Builds a linear BVH (see pbrt's HLBVHBuild()): primitives are
+    /// sorted by the Morton codes of their centroids, clusters with
+    /// the same top 12 bits become treelets which are emitted in
+    /// parallel, and the treelets are combined by an SAH build.
+    pub fn hlbvh_build<'a>(
+        bvh: &BVHAccel,
+        arena: &'a Arena<BVHBuildNode<'a>>,
+        treelet_arenas: &'a mut Vec<Arena<BVHBuildNode<'a>>>,
+        primitive_info: &[BVHPrimitiveInfo],
+        total_nodes: &mut usize,
+        ordered_prim_numbers: &mut Vec<usize>,
+    ) -> &'a BVHBuildNode<'a> {
+        // compute bounding box of all primitive centroids
+        let mut bounds: Bounds3f = Bounds3f::default();
+        for pi in primitive_info {
+            bounds = bnd3_union_pnt3f(&bounds, &pi.centroid);
+        }
+        // compute Morton indices of primitives
+        let morton_bits: u32 = 10;
+        let morton_scale: Float = (1 << morton_bits) as Float;
+        let mut morton_prims: Vec<MortonPrimitive> = primitive_info
+            .par_iter()
+            .enumerate()
+            .map(|(i, pi)| {
+                let centroid_offset: Vector3f = bounds.offset(&pi.centroid);
+                MortonPrimitive {
+                    primitive_index: i,
+                    morton_code: encode_morton_3(&(centroid_offset * morton_scale)),
+                }
+            })
+            .collect();
+        // radix sort primitive Morton indices
+        let treelet_sizes: Vec<usize> = radix_sort(&mut morton_prims);
+        // the leaves refer to the primitives in Morton order
+        ordered_prim_numbers.extend(
+            morton_prims
+                .iter()
+                .map(|mp| primitive_info[mp.primitive_index].primitive_number),
+        );
+        // create LBVHs for treelets in parallel
+        let mut treelets: Vec<(usize, usize)> = Vec::new();
+        let mut start: usize = 0;
+        for n_primitives in treelet_sizes {
+            if n_primitives > 0 {
+                treelets.push((start, n_primitives));
+                treelet_arenas.push(Arena::with_capacity(2 * n_primitives));
+            }
+            start += n_primitives;
+        }
+        let first_bit_index: i32 = 29 - 12;
+        let max_prims_in_node: usize = bvh.max_prims_in_node;
+        let morton_prims: &[MortonPrimitive] = &morton_prims;
+        let treelet_roots: Vec<(&'a BVHBuildNode<'a>, usize)> = treelet_arenas
+            .par_iter_mut()
+            .zip(treelets.par_iter())
+            .map(|(treelet_arena, (start, n_primitives))| {
+                let treelet_arena: &'a Arena<BVHBuildNode<'a>> = treelet_arena;
+                let mut nodes_created: usize = 0;
+                let root: &'a BVHBuildNode<'a> = BVHAccel::emit_lbvh(
+                    treelet_arena,
+                    primitive_info,
+                    &morton_prims[*start..(*start + *n_primitives)],
+                    *start,
+                    first_bit_index,
+                    max_prims_in_node,
+                    &mut nodes_created,
+                );
+                (root, nodes_created)
+            })
+            .collect();
+        let mut finished_treelets: Vec<&'a BVHBuildNode<'a>> = Vec::with_capacity(treelets.len());
+        for (root, nodes_created) in treelet_roots {
+            finished_treelets.push(root);
+            *total_nodes += nodes_created;
+        }
+        // create and return SAH BVH from LBVH treelets
+        BVHAccel::build_upper_sah(arena, &mut finished_treelets, total_nodes)
+    }
+    fn emit_lbvh<'a>(
+        arena: &'a Arena<BVHBuildNode<'a>>,
+        primitive_info: &[BVHPrimitiveInfo],
+        morton_prims: &[MortonPrimitive],
+        first_prim_offset: usize,
+        bit_index: i32,
+        max_prims_in_node: usize,
+        total_nodes: &mut usize,
+    ) -> &'a BVHBuildNode<'a> {
+        let n_primitives: usize = morton_prims.len();
+        if bit_index == -1 || n_primitives < max_prims_in_node {
+            // create and return leaf node of LBVH treelet
+            *total_nodes += 1;
+            let node: &mut BVHBuildNode<'a> = arena.alloc(BVHBuildNode::default());
+            let mut bounds: Bounds3f = Bounds3f::default();
+            for mp in morton_prims {
+                bounds = bnd3_union_bnd3f(&bounds, &primitive_info[mp.primitive_index].bounds);
+            }
+            node.init_leaf(first_prim_offset, n_primitives, &bounds);
+            return node;
+        }
+        let mask: u32 = 1 << bit_index;
+        // advance to next subtree level if there's no LBVH split for this bit
+        if (morton_prims[0].morton_code & mask)
+            == (morton_prims[n_primitives - 1].morton_code & mask)
+        {
+            return BVHAccel::emit_lbvh(
+                arena,
+                primitive_info,
+                morton_prims,
+                first_prim_offset,
+                bit_index - 1,
+                max_prims_in_node,
+                total_nodes,
+            );
+        }
+        // find LBVH split point for this dimension
+        let split_offset: usize = morton_prims.partition_point(|mp| (mp.morton_code & mask) == 0);
+        // create and return interior LBVH node
+        *total_nodes += 1;
+        let node: &mut BVHBuildNode<'a> = arena.alloc(BVHBuildNode::default());
+        let c0 = BVHAccel::emit_lbvh(
+            arena,
+            primitive_info,
+            &morton_prims[..split_offset],
+            first_prim_offset,
+            bit_index - 1,
+            max_prims_in_node,
+            total_nodes,
+        );
+        let c1 = BVHAccel::emit_lbvh(
+            arena,
+            primitive_info,
+            &morton_prims[split_offset..],
+            first_prim_offset + split_offset,
+            bit_index - 1,
+            max_prims_in_node,
+            total_nodes,
+        );
+        let axis: u8 = (bit_index % 3) as u8;
+        node.init_interior(axis, c0, c1);
+        node
+    }
+    fn build_upper_sah<'a>(
+        arena: &'a Arena<BVHBuildNode<'a>>,
+        treelet_roots: &mut [&'a BVHBuildNode<'a>],
+        total_nodes: &mut usize,
+    ) -> &'a BVHBuildNode<'a> {
+        let n_nodes: usize = treelet_roots.len();
+        if n_nodes == 1 {
+            return treelet_roots[0];
+        }
+        *total_nodes += 1;
+        let node: &mut BVHBuildNode<'a> = arena.alloc(BVHBuildNode::default());
+        // compute bounds of all nodes under this HLBVH node
+        let mut bounds: Bounds3f = Bounds3f::default();
+        for root in treelet_roots.iter() {
+            bounds = bnd3_union_bnd3f(&bounds, &root.bounds);
+        }
+        // compute bound of HLBVH node centroids, choose split dimension _dim_
+        let mut centroid_bounds: Bounds3f = Bounds3f::default();
+        for root in treelet_roots.iter() {
+            let centroid: Point3f = (root.bounds.p_min + root.bounds.p_max) * 0.5 as Float;
+            centroid_bounds = bnd3_union_pnt3f(&centroid_bounds, &centroid);
+        }
+        let dim: u8 = centroid_bounds.maximum_extent();
+        let dim_i: XYZEnum = match dim {
+            0 => XYZEnum::X,
+            1 => XYZEnum::Y,
+            _ => XYZEnum::Z,
+        };
+        // allocate _BucketInfo_ for SAH partition buckets
+        let n_buckets: usize = 12;
+        let mut buckets: [BucketInfo; 12] = [BucketInfo::default(); 12];
+        let bucket_index = |root: &BVHBuildNode| -> usize {
+            let centroid: Float =
+                (root.bounds.p_min[dim_i] + root.bounds.p_max[dim_i]) * 0.5 as Float;
+            let extent: Float = centroid_bounds.p_max[dim_i] - centroid_bounds.p_min[dim_i];
+            if extent > 0.0 as Float {
+                let b: usize = (n_buckets as Float
+                    * ((centroid - centroid_bounds.p_min[dim_i]) / extent))
+                    as usize;
+                b.min(n_buckets - 1)
+            } else {
+                0_usize
+            }
+        };
+        // initialize _BucketInfo_ for HLBVH SAH partition buckets
+        for root in treelet_roots.iter() {
+            let b: usize = bucket_index(root);
+            buckets[b].count += 1;
+            buckets[b].bounds = bnd3_union_bnd3f(&buckets[b].bounds, &root.bounds);
+        }
+        // compute costs for splitting after each bucket
+        let mut cost: [Float; 11] = [0.0; 11];
+        for (i, cost_item) in cost.iter_mut().enumerate() {
+            let mut b0: Bounds3f = Bounds3f::default();
+            let mut b1: Bounds3f = Bounds3f::default();
+            let mut count0: usize = 0;
+            let mut count1: usize = 0;
+            for item in buckets.iter().take(i + 1) {
+                b0 = bnd3_union_bnd3f(&b0, &item.bounds);
+                count0 += item.count;
+            }
+            for item in buckets.iter().skip(i + 1) {
+                b1 = bnd3_union_bnd3f(&b1, &item.bounds);
+                count1 += item.count;
+            }
+            *cost_item = 0.125
+                + (count0 as Float * b0.surface_area() + count1 as Float * b1.surface_area())
+                    / bounds.surface_area();
+        }
+        // find bucket to split at that minimizes SAH metric
+        let mut min_cost: Float = cost[0];
+        let mut min_cost_split_bucket: usize = 0;
+        for (i, item) in cost.iter().enumerate() {
+            if *item < min_cost {
+                min_cost = *item;
+                min_cost_split_bucket = i;
+            }
+        }
+        // split nodes and create interior HLBVH SAH node
+        let mut mid: usize = 0;
+        for i in 0..n_nodes {
+            if bucket_index(treelet_roots[i]) <= min_cost_split_bucket {
+                treelet_roots.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid == 0 || mid == n_nodes {
+            // all centroids fell into one bucket
+            mid = n_nodes / 2;
+        }
+        let (left, right) = treelet_roots.split_at_mut(mid);
+        let c0 = BVHAccel::build_upper_sah(arena, left, total_nodes);
+        let c1 = BVHAccel::build_upper_sah(arena, right, total_nodes);
+        node.init_interior(dim, c0, c1);
+        node
+    }
+    /// Builds a BVH with spatial splits (see Stich et al., "Spatial
+    /// Splits in Bounding Volume Hierarchies"). Where the children of
+    /// the best object split overlap, splitting the space of the node
+    /// is considered as well: primitive references straddling the
+    /// split plane are clipped against it and end up in both
+    /// children. At most *split_budget* times the number of
+    /// primitives additional references are created.
+    pub fn sbvh_build<'a>(
+        bvh: &BVHAccel,
+        arena: &'a Arena<BVHBuildNode<'a>>,
+        primitive_info: &[BVHPrimitiveInfo],
+        split_budget: Float,
+        total_nodes: &mut usize,
+        ordered_prim_numbers: &mut Vec<usize>,
+    ) -> &'a BVHBuildNode<'a> {
+        let (bounds, _centroid_bounds) = BVHAccel::primitive_bounds(primitive_info);
+        let mut builder: SBVHBuilder = SBVHBuilder {
+            bvh,
+            min_overlap: bounds.surface_area() * SBVH_MIN_OVERLAP,
+            remaining_references: (split_budget * primitive_info.len() as Float) as usize,
+            ordered_prim_numbers,
+        };
+        builder.build(arena, primitive_info.to_vec(), 0, total_nodes)
+    }
+    /// Updates the bounds of all nodes after primitives changed
+    /// (e.g. instances were moved by _set_instance_transform()_),
+    /// keeping the topology of the hierarchy. This is much faster
+    /// than a rebuild, but the tree gets worse if primitives move
+    /// far.
+    pub fn refit(&mut self) {
+        let primitive_bounds: Vec<Bounds3f> = self
+            .primitives
+            .par_iter()
+            .map(|primitive| primitive.world_bound())
+            .collect();
+        if let Some(ref mut wide_nodes) = self.wide_nodes {
+            wide_nodes.refit(&primitive_bounds);
+            return;
+        }
+        // children are stored after their parent
+        for i in (0..self.nodes.len()).rev() {
+            let node: &LinearBVHNode = &self.nodes[i];
+            let bounds: Bounds3f = if node.n_primitives > 0 {
+                let first: usize = node.offset as usize;
+                primitive_bounds[first..(first + node.n_primitives as usize)]
+                    .iter()
+                    .fold(Bounds3f::default(), |b, pb| bnd3_union_bnd3f(&b, pb))
+            } else {
+                bnd3_union_bnd3f(
+                    &self.nodes[i + 1].bounds,
+                    &self.nodes[node.offset as usize].bounds,
+                )
+            };
+            self.nodes[i].bounds = bounds;
+        }
+    }
+    /// Replaces the transformation of the instance (a
+    /// _TransformedPrimitive_) which was the *primitive_number*-th
+    /// primitive passed to _BVHAccel::new()_. Returns false if it
+    /// isn't an instance. Call _refit()_ after all instances of a
+    /// frame were moved.
+    pub fn set_instance_transform(
+        &mut self,
+        primitive_number: usize,
+        primitive_to_world: &AnimatedTransform,
+    ) -> bool {
+        let mut instance: Option<Arc<Primitive>> = None;
+        for i in 0..self.primitives.len() {
+            if self.primitive_numbers[i] != primitive_number {
+                continue;
+            }
+            if instance.is_none() {
+                if let Primitive::Transformed(ref primitive) = *self.primitives[i] {
+                    instance = Some(Arc::new(Primitive::Transformed(Box::new(
+                        TransformedPrimitive::new(primitive.primitive.clone(), *primitive_to_world),
+                    ))));
+                } else {
+                    return false;
+                }
+            }
+            // spatial splits may reference an instance several times
+            if let Some(ref instance) = instance {
+                self.primitives[i] = instance.clone();
+            }
+        }
+        instance.is_some()
+    }
     pub fn flatten_bvh_tree<'a>(
         node: &BVHBuildNode<'a>,
         nodes: &mut Vec<LinearBVHNode>,
@@ -419,17 +1121,30 @@ impl BVHAccel {
     }
     // Primitive
     pub fn world_bound(&self) -> Bounds3f {
-        if !self.nodes.is_empty() {
+        if let Some(ref wide_nodes) = self.wide_nodes {
+            wide_nodes.bounds()
+        } else if !self.nodes.is_empty() {
             self.nodes[0].bounds
         } else {
             Bounds3f::default()
         }
     }
     pub fn intersect(&self, ray: &Ray, isect: &mut SurfaceInteraction) -> bool {
+        let _p = ProfilePhase::new(Prof::AccelIntersect);
+        if let Some(ref wide_nodes) = self.wide_nodes {
+            return wide_nodes.intersect(ray, false, |first, n_primitives| {
+                let mut hit: bool = false;
+                for primitive in &self.primitives[first..(first + n_primitives)] {
+                    if primitive.intersect(ray, isect) {
+                        hit = true;
+                    }
+                }
+                hit
+            });
+        }
         if self.nodes.is_empty() {
             return false;
         }
-        // TODO: ProfilePhase p(Prof::AccelIntersect);
         let mut hit: bool = false;
         let inv_dir: Vector3f = Vector3f {
             x: 1.0 / ray.d.x,
@@ -445,8 +1160,10 @@ impl BVHAccel {
         let mut to_visit_offset: u32 = 0;
         let mut current_node_index: u32 = 0;
         let mut nodes_to_visit: [u32; 64] = [0_u32; 64];
+        let mut n_visits: u64 = 0;
         loop {
             let node: &LinearBVHNode = &self.nodes[current_node_index as usize];
+            n_visits += 1;
             // check ray against BVH node
             if node.bounds.intersect_p(ray, &inv_dir, &dir_is_neg) {
                 if node.n_primitives > 0 {
@@ -485,13 +1202,21 @@ impl BVHAccel {
                 current_node_index = nodes_to_visit[to_visit_offset as usize];
             }
         }
+        stats::count_node_visits(n_visits);
         hit
     }
     pub fn intersect_p(&self, ray: &Ray) -> bool {
+        let _p = ProfilePhase::new(Prof::AccelIntersectP);
+        if let Some(ref wide_nodes) = self.wide_nodes {
+            return wide_nodes.intersect(ray, true, |first, n_primitives| {
+                self.primitives[first..(first + n_primitives)]
+                    .iter()
+                    .any(|primitive| primitive.intersect_p(ray))
+            });
+        }
         if self.nodes.is_empty() {
             return false;
         }
-        // TODO: ProfilePhase p(Prof::AccelIntersectP);
         let inv_dir: Vector3f = Vector3f {
             x: 1.0 / ray.d.x,
             y: 1.0 / ray.d.y,
@@ -505,13 +1230,16 @@ impl BVHAccel {
         let mut to_visit_offset: u32 = 0;
         let mut current_node_index: u32 = 0;
         let mut nodes_to_visit: [u32; 64] = [0_u32; 64];
+        let mut n_visits: u64 = 0;
         loop {
             let node: &LinearBVHNode = &self.nodes[current_node_index as usize];
+            n_visits += 1;
             if node.bounds.intersect_p(ray, &inv_dir, &dir_is_neg) {
                 // process BVH node _node_ for traversal
                 if node.n_primitives > 0 {
                     for i in 0..node.n_primitives {
                         if self.primitives[node.offset as usize + i as usize].intersect_p(ray) {
+                            stats::count_node_visits(n_visits);
                             return true;
                         }
                     }
@@ -537,8 +1265,136 @@ impl BVHAccel {
                 current_node_index = nodes_to_visit[to_visit_offset as usize];
             }
         }
+        stats::count_node_visits(n_visits);
         false
     }
+    /// Finds the closest intersections of a packet of up to
+    /// _PACKET_SIZE_ rays, which traverse the binary hierarchy
+    /// together. The rays should be coherent (e.g. primary rays of
+    /// neighbouring pixels or shadow rays towards the same light),
+    /// the traversal order follows the direction of the first ray.
+    pub fn intersect_packet(
+        &self,
+        rays: &[Ray],
+        isects: &mut [SurfaceInteraction],
+        hits: &mut [bool],
+    ) {
+        if self.wide_nodes.is_some() {
+            for ((ray, isect), hit) in rays.iter().zip(isects.iter_mut()).zip(hits.iter_mut()) {
+                *hit = self.intersect(ray, isect);
+            }
+            return;
+        }
+        self.traverse_packet(rays, false, |r, first, n_primitives| {
+            for primitive in &self.primitives[first..(first + n_primitives)] {
+                if primitive.intersect(&rays[r], &mut isects[r]) {
+                    hits[r] = true;
+                }
+            }
+            hits[r]
+        });
+    }
+    /// Occlusion test for a packet of up to _PACKET_SIZE_ rays (see
+    /// _intersect_packet()_).
+    pub fn intersect_p_packet(&self, rays: &[Ray], occluded: &mut [bool]) {
+        if self.wide_nodes.is_some() {
+            for (ray, occluded) in rays.iter().zip(occluded.iter_mut()) {
+                *occluded = self.intersect_p(ray);
+            }
+            return;
+        }
+        self.traverse_packet(rays, true, |r, first, n_primitives| {
+            occluded[r] = self.primitives[first..(first + n_primitives)]
+                .iter()
+                .any(|primitive| primitive.intersect_p(&rays[r]));
+            occluded[r]
+        });
+    }
+    /// Calls *intersect_leaf* with the ray index, the first primitive
+    /// and the number of primitives for each leaf hit by a ray of the
+    /// packet. For *any_hit* rays which hit something drop out of
+    /// the packet.
+    fn traverse_packet<F>(&self, rays: &[Ray], any_hit: bool, mut intersect_leaf: F)
+    where
+        F: FnMut(usize, usize, usize) -> bool,
+    {
+        assert!(rays.len() <= PACKET_SIZE);
+        if self.nodes.is_empty() || rays.is_empty() {
+            return;
+        }
+        let mut inv_dirs: [Vector3f; PACKET_SIZE] = [Vector3f::default(); PACKET_SIZE];
+        let mut dirs_are_neg: [[u8; 3]; PACKET_SIZE] = [[0_u8; 3]; PACKET_SIZE];
+        for (r, ray) in rays.iter().enumerate() {
+            inv_dirs[r] = Vector3f {
+                x: 1.0 / ray.d.x,
+                y: 1.0 / ray.d.y,
+                z: 1.0 / ray.d.z,
+            };
+            dirs_are_neg[r] = [
+                (inv_dirs[r].x < 0.0) as u8,
+                (inv_dirs[r].y < 0.0) as u8,
+                (inv_dirs[r].z < 0.0) as u8,
+            ];
+        }
+        // one bit per ray which still traverses the hierarchy, the
+        // stack holds the rays which entered the parent of a node
+        let mut active: u32 = (1_u32 << rays.len()) - 1;
+        let mut to_visit_offset: usize = 0;
+        let mut current_node_index: u32 = 0;
+        let mut current_rays: u32 = active;
+        let mut nodes_to_visit: [(u32, u32); 64] = [(0_u32, 0_u32); 64];
+        let mut n_visits: u64 = 0;
+        loop {
+            let node: &LinearBVHNode = &self.nodes[current_node_index as usize];
+            // check rays against BVH node
+            let mut node_rays: u32 = 0;
+            for r in 0..rays.len() {
+                if current_rays & active & (1_u32 << r) != 0 {
+                    n_visits += 1;
+                    if node
+                        .bounds
+                        .intersect_p(&rays[r], &inv_dirs[r], &dirs_are_neg[r])
+                    {
+                        node_rays |= 1_u32 << r;
+                    }
+                }
+            }
+            if node_rays != 0 && node.n_primitives > 0 {
+                // intersect rays with primitives in leaf BVH node
+                for r in 0..rays.len() {
+                    if node_rays & (1_u32 << r) != 0
+                        && intersect_leaf(r, node.offset as usize, node.n_primitives as usize)
+                        && any_hit
+                    {
+                        active &= !(1_u32 << r);
+                    }
+                }
+                if active == 0 {
+                    break;
+                }
+            } else if node_rays != 0 {
+                // put far BVH node on _nodesToVisit_ stack, advance
+                // to near node (as seen by the first ray)
+                if dirs_are_neg[0][node.axis as usize] == 1_u8 {
+                    nodes_to_visit[to_visit_offset] = (current_node_index + 1_u32, node_rays);
+                    current_node_index = node.offset as u32;
+                } else {
+                    nodes_to_visit[to_visit_offset] = (node.offset as u32, node_rays);
+                    current_node_index += 1_u32;
+                }
+                to_visit_offset += 1;
+                current_rays = node_rays;
+                continue;
+            }
+            if to_visit_offset == 0 {
+                break;
+            }
+            to_visit_offset -= 1;
+            current_node_index = nodes_to_visit[to_visit_offset].0;
+            current_rays = nodes_to_visit[to_visit_offset].1;
+        }
+        stats::count_node_visits(n_visits);
+    }
     pub fn get_material(&self) -> Option<Arc<Material>> {
         None
     }
@@ -546,3 +1402,422 @@ impl BVHAccel {
         None
     }
 }
+
+// BVH Utility Functions
+
+/// Spatial splits are only considered if the children of the best
+/// object split overlap by more than this fraction of the surface
+/// area of the root.
+const SBVH_MIN_OVERLAP: Float = 1.0e-5;
+/// Number of bins along each axis for spatial splits.
+const SBVH_N_SPATIAL_BINS: usize = 32;
+/// Deeper nodes use object splits only (the traversal stack has 64
+/// entries).
+const SBVH_MAX_DEPTH: usize = 48;
+
+enum SBVHSplit {
+    Object { dim: XYZEnum, split_bucket: usize },
+    Spatial { dim: XYZEnum, position: Float },
+}
+
+struct SBVHBuilder<'b> {
+    bvh: &'b BVHAccel,
+    min_overlap: Float,
+    remaining_references: usize,
+    ordered_prim_numbers: &'b mut Vec<usize>,
+}
+
+impl<'b> SBVHBuilder<'b> {
+    fn build<'a>(
+        &mut self,
+        arena: &'a Arena<BVHBuildNode<'a>>,
+        mut references: Vec<BVHPrimitiveInfo>,
+        depth: usize,
+        total_nodes: &mut usize,
+    ) -> &'a BVHBuildNode<'a> {
+        let node: &mut BVHBuildNode<'a> = arena.alloc(BVHBuildNode::default());
+        *total_nodes += 1_usize;
+        let (bounds, centroid_bounds) = BVHAccel::primitive_bounds(&references);
+        let n_primitives: usize = references.len();
+        let mut best: Option<(Float, SBVHSplit)> = None;
+        if n_primitives > 1 {
+            // find best object split and check overlap of its children
+            let mut overlap: Float = Float::INFINITY;
+            if let Some((cost, dim, split_bucket, b0, b1)) =
+                self.find_object_split(&references, &bounds, &centroid_bounds)
+            {
+                overlap = surface_area_of_overlap(&b0, &b1);
+                best = Some((cost, SBVHSplit::Object { dim, split_bucket }));
+            }
+            // find best spatial split
+            if overlap > self.min_overlap && depth < SBVH_MAX_DEPTH {
+                for dim in [XYZEnum::X, XYZEnum::Y, XYZEnum::Z].iter() {
+                    if let Some((cost, position)) =
+                        self.find_spatial_split(&references, &bounds, *dim)
+                    {
+                        if best.is_none() || cost < best.as_ref().unwrap().0 {
+                            best = Some((
+                                cost,
+                                SBVHSplit::Spatial {
+                                    dim: *dim,
+                                    position,
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        let leaf_cost: Float = n_primitives as Float;
+        let split: SBVHSplit = match best {
+            Some((cost, split))
+                if n_primitives > self.bvh.max_prims_in_node || cost < leaf_cost =>
+            {
+                split
+            }
+            _ => {
+                // create leaf _BVHBuildNode_
+                let first_prim_offset: usize = self.ordered_prim_numbers.len();
+                for item in references.iter() {
+                    self.ordered_prim_numbers.push(item.primitive_number);
+                }
+                node.init_leaf(first_prim_offset, n_primitives, &bounds);
+                return node;
+            }
+        };
+        // partition references and build children
+        let (dim, left, right): (XYZEnum, Vec<BVHPrimitiveInfo>, Vec<BVHPrimitiveInfo>) =
+            match split {
+                SBVHSplit::Object { dim, split_bucket } => {
+                    let (left, right): (Vec<BVHPrimitiveInfo>, Vec<BVHPrimitiveInfo>) =
+                        references.drain(..).partition(|r| {
+                            object_split_bucket(r, &centroid_bounds, dim) <= split_bucket
+                        });
+                    (dim, left, right)
+                }
+                SBVHSplit::Spatial { dim, position } => {
+                    let mut left: Vec<BVHPrimitiveInfo> = Vec::new();
+                    let mut right: Vec<BVHPrimitiveInfo> = Vec::new();
+                    for r in references.drain(..) {
+                        if r.bounds.p_max[dim] <= position {
+                            left.push(r);
+                        } else if r.bounds.p_min[dim] >= position {
+                            right.push(r);
+                        } else {
+                            // split reference
+                            let b0: Bounds3f =
+                                self.clip_reference(&r, dim, bounds.p_min[dim], position);
+                            let b1: Bounds3f =
+                                self.clip_reference(&r, dim, position, bounds.p_max[dim]);
+                            let empty0: bool = is_empty(&b0);
+                            let empty1: bool = is_empty(&b1);
+                            if empty0 || empty1 {
+                                if empty0 {
+                                    right.push(r);
+                                } else {
+                                    left.push(r);
+                                }
+                            } else {
+                                left.push(BVHPrimitiveInfo::new(r.primitive_number, b0));
+                                right.push(BVHPrimitiveInfo::new(r.primitive_number, b1));
+                                self.remaining_references =
+                                    self.remaining_references.saturating_sub(1);
+                            }
+                        }
+                    }
+                    (dim, left, right)
+                }
+            };
+        if left.is_empty() || right.is_empty() {
+            // split failed, keep all references in a leaf
+            let first_prim_offset: usize = self.ordered_prim_numbers.len();
+            for item in left.iter().chain(right.iter()) {
+                self.ordered_prim_numbers.push(item.primitive_number);
+            }
+            node.init_leaf(first_prim_offset, left.len() + right.len(), &bounds);
+            return node;
+        }
+        let c0 = self.build(arena, left, depth + 1, total_nodes);
+        let c1 = self.build(arena, right, depth + 1, total_nodes);
+        node.init_interior(dim as u8, c0, c1);
+        node
+    }
+    /// SAH object split along the largest extent of the centroids
+    /// (as in _BVHAccel::find_split()_). Returns the cost, the split
+    /// dimension and bucket, and the bounds of both children.
+    fn find_object_split(
+        &self,
+        references: &[BVHPrimitiveInfo],
+        bounds: &Bounds3f,
+        centroid_bounds: &Bounds3f,
+    ) -> Option<(Float, XYZEnum, usize, Bounds3f, Bounds3f)> {
+        let dim: XYZEnum = match centroid_bounds.maximum_extent() {
+            0 => XYZEnum::X,
+            1 => XYZEnum::Y,
+            _ => XYZEnum::Z,
+        };
+        if centroid_bounds.p_max[dim] == centroid_bounds.p_min[dim] {
+            return None;
+        }
+        let mut buckets: [BucketInfo; 12] = [BucketInfo::default(); 12];
+        for r in references {
+            let b: usize = object_split_bucket(r, centroid_bounds, dim);
+            buckets[b].count += 1;
+            buckets[b].bounds = bnd3_union_bnd3f(&buckets[b].bounds, &r.bounds);
+        }
+        let mut best: Option<(Float, XYZEnum, usize, Bounds3f, Bounds3f)> = None;
+        for i in 0..(buckets.len() - 1) {
+            let mut b0: Bounds3f = Bounds3f::default();
+            let mut b1: Bounds3f = Bounds3f::default();
+            let mut count0: usize = 0;
+            let mut count1: usize = 0;
+            for item in buckets.iter().take(i + 1) {
+                b0 = bnd3_union_bnd3f(&b0, &item.bounds);
+                count0 += item.count;
+            }
+            for item in buckets.iter().skip(i + 1) {
+                b1 = bnd3_union_bnd3f(&b1, &item.bounds);
+                count1 += item.count;
+            }
+            if count0 == 0 || count1 == 0 {
+                continue;
+            }
+            let cost: Float = 1.0
+                + (count0 as Float * b0.surface_area() + count1 as Float * b1.surface_area())
+                    / bounds.surface_area();
+            if best.is_none() || cost < best.as_ref().unwrap().0 {
+                best = Some((cost, dim, i, b0, b1));
+            }
+        }
+        best
+    }
+    /// Bins the (clipped) references along *dim* and returns the cost
+    /// and position of the best spatial split which doesn't exceed
+    /// the remaining reference budget.
+    fn find_spatial_split(
+        &self,
+        references: &[BVHPrimitiveInfo],
+        bounds: &Bounds3f,
+        dim: XYZEnum,
+    ) -> Option<(Float, Float)> {
+        let n_bins: usize = SBVH_N_SPATIAL_BINS;
+        let origin: Float = bounds.p_min[dim];
+        let bin_width: Float = (bounds.p_max[dim] - origin) / n_bins as Float;
+        if bin_width <= 0.0 as Float {
+            return None;
+        }
+        let bin_index = |x: Float| -> usize {
+            (((x - origin) / bin_width) as isize).clamp(0, n_bins as isize - 1) as usize
+        };
+        let mut bin_bounds: Vec<Bounds3f> = vec![Bounds3f::default(); n_bins];
+        let mut entries: Vec<usize> = vec![0_usize; n_bins];
+        let mut exits: Vec<usize> = vec![0_usize; n_bins];
+        for r in references {
+            let first: usize = bin_index(r.bounds.p_min[dim]);
+            let last: usize = bin_index(r.bounds.p_max[dim]).max(first);
+            for (b, bin) in bin_bounds.iter_mut().enumerate().take(last + 1).skip(first) {
+                let clipped: Bounds3f = if first == last {
+                    r.bounds
+                } else {
+                    self.clip_reference(
+                        r,
+                        dim,
+                        origin + b as Float * bin_width,
+                        origin + (b + 1) as Float * bin_width,
+                    )
+                };
+                if !is_empty(&clipped) {
+                    *bin = bnd3_union_bnd3f(bin, &clipped);
+                }
+            }
+            entries[first] += 1;
+            exits[last] += 1;
+        }
+        // sweep from the right to get bounds and counts of right sides
+        let mut right_bounds: Vec<Bounds3f> = vec![Bounds3f::default(); n_bins];
+        let mut right_counts: Vec<usize> = vec![0_usize; n_bins];
+        let mut b1: Bounds3f = Bounds3f::default();
+        let mut count1: usize = 0;
+        for i in (1..n_bins).rev() {
+            b1 = bnd3_union_bnd3f(&b1, &bin_bounds[i]);
+            count1 += exits[i];
+            right_bounds[i] = b1;
+            right_counts[i] = count1;
+        }
+        // sweep from the left and evaluate SAH for each bin boundary
+        let mut best: Option<(Float, Float)> = None;
+        let mut b0: Bounds3f = Bounds3f::default();
+        let mut count0: usize = 0;
+        let n_references: usize = references.len();
+        for i in 0..(n_bins - 1) {
+            b0 = bnd3_union_bnd3f(&b0, &bin_bounds[i]);
+            count0 += entries[i];
+            let count1: usize = right_counts[i + 1];
+            if count0 == 0
+                || count1 == 0
+                || count0 + count1 - n_references > self.remaining_references
+            {
+                continue;
+            }
+            let cost: Float = 1.0
+                + (count0 as Float * b0.surface_area()
+                    + count1 as Float * right_bounds[i + 1].surface_area())
+                    / bounds.surface_area();
+            if best.is_none() || cost < best.unwrap().0 {
+                best = Some((cost, origin + (i + 1) as Float * bin_width));
+            }
+        }
+        best
+    }
+    /// Bounds of the part of a reference between *min* and *max*
+    /// along *dim*, triangles are clipped exactly.
+    fn clip_reference(
+        &self,
+        r: &BVHPrimitiveInfo,
+        dim: XYZEnum,
+        min: Float,
+        max: Float,
+    ) -> Bounds3f {
+        let mut bounds: Bounds3f = r.bounds;
+        bounds.p_min[dim] = bounds.p_min[dim].max(min);
+        bounds.p_max[dim] = bounds.p_max[dim].min(max);
+        if let Primitive::Geometric(primitive) = &*self.bvh.primitives[r.primitive_number] {
+            if let Shape::Trngl(triangle) = &*primitive.shape {
+                bounds =
+                    bnd3_intersect_bnd3f(&bounds, &triangle.clipped_world_bound(dim, min, max));
+            }
+        }
+        bounds
+    }
+}
+
+fn object_split_bucket(r: &BVHPrimitiveInfo, centroid_bounds: &Bounds3f, dim: XYZEnum) -> usize {
+    let n_buckets: usize = 12;
+    let b: usize = (n_buckets as Float * centroid_bounds.offset(&r.centroid)[dim]) as usize;
+    b.min(n_buckets - 1)
+}
+
+fn is_empty(b: &Bounds3f) -> bool {
+    b.p_min.x > b.p_max.x || b.p_min.y > b.p_max.y || b.p_min.z > b.p_max.z
+}
+
+fn surface_area_of_overlap(b0: &Bounds3f, b1: &Bounds3f) -> Float {
+    let overlap: Bounds3f = bnd3_intersect_bnd3f(b0, b1);
+    if is_empty(&overlap) {
+        0.0 as Float
+    } else {
+        overlap.surface_area()
+    }
+}
+
+fn left_shift_3(x: u32) -> u32 {
+    let mut x: u32 = x;
+    if x == (1 << 10) {
+        x -= 1;
+    }
+    x = (x | (x << 16)) & 0b0000_0011_0000_0000_0000_0000_1111_1111;
+    x = (x | (x << 8)) & 0b0000_0011_0000_0000_1111_0000_0000_1111;
+    x = (x | (x << 4)) & 0b0000_0011_0000_1100_0011_0000_1100_0011;
+    x = (x | (x << 2)) & 0b0000_1001_0010_0100_1001_0010_0100_1001;
+    x
+}
+
+fn encode_morton_3(v: &Vector3f) -> u32 {
+    (left_shift_3(v.z as u32) << 2) | (left_shift_3(v.y as u32) << 1) | left_shift_3(v.x as u32)
+}
+
+/// Sorts by Morton code. The primitives are bucketed by the top 12
+/// bits (the HLBVH treelets) first, then the buckets are sorted in
+/// parallel by the remaining bits. Returns the bucket sizes.
+fn radix_sort(v: &mut Vec<MortonPrimitive>) -> Vec<usize> {
+    let low_bits: u32 = 30 - 12;
+    let n_buckets: usize = 1 << 12;
+    // count the primitives per bucket in parallel
+    let bucket_sizes: Vec<usize> = v
+        .par_chunks(64 * 1024)
+        .map(|chunk| {
+            let mut counts: Vec<usize> = vec![0; n_buckets];
+            for mp in chunk {
+                counts[(mp.morton_code >> low_bits) as usize] += 1;
+            }
+            counts
+        })
+        .reduce(
+            || vec![0; n_buckets],
+            |mut a, b| {
+                for (count_a, count_b) in a.iter_mut().zip(b.iter()) {
+                    *count_a += *count_b;
+                }
+                a
+            },
+        );
+    // scatter the primitives to their buckets
+    let mut out_index: Vec<usize> = Vec::with_capacity(n_buckets);
+    let mut offset: usize = 0;
+    for size in &bucket_sizes {
+        out_index.push(offset);
+        offset += size;
+    }
+    let mut sorted: Vec<MortonPrimitive> = vec![MortonPrimitive::default(); v.len()];
+    for mp in v.iter() {
+        let bucket: usize = (mp.morton_code >> low_bits) as usize;
+        sorted[out_index[bucket]] = *mp;
+        out_index[bucket] += 1;
+    }
+    // sort the buckets in parallel
+    let mut buckets: Vec<&mut [MortonPrimitive]> = Vec::new();
+    let mut rest: &mut [MortonPrimitive] = &mut sorted;
+    for size in &bucket_sizes {
+        let (bucket, tail) = std::mem::take(&mut rest).split_at_mut(*size);
+        if bucket.len() > 1 {
+            buckets.push(bucket);
+        }
+        rest = tail;
+    }
+    buckets
+        .par_iter_mut()
+        .for_each(|bucket| radix_sort_low_bits(bucket, low_bits));
+    *v = sorted;
+    bucket_sizes
+}
+
+/// Serial radix sort (see pbrt's RadixSort()) of the lowest *n_bits*
+/// bits of the Morton codes.
+fn radix_sort_low_bits(v: &mut [MortonPrimitive], n_bits: u32) {
+    let bits_per_pass: u32 = 6;
+    let n_passes: u32 = n_bits / bits_per_pass;
+    let n_buckets: usize = 1 << bits_per_pass;
+    let bit_mask: u32 = (1 << bits_per_pass) - 1;
+    let mut temp: Vec<MortonPrimitive> = v.to_vec();
+    for pass in 0..n_passes {
+        // perform one pass of radix sort, sorting _bits_per_pass_ bits
+        let low_bit: u32 = pass * bits_per_pass;
+        // set in and out vector references for radix sort pass
+        let (v_in, v_out): (&[MortonPrimitive], &mut [MortonPrimitive]) = if pass & 1 == 1 {
+            (&temp, v)
+        } else {
+            (v, &mut temp)
+        };
+        // count number of zero bits in array for current radix sort bit
+        let mut bucket_count: Vec<usize> = vec![0; n_buckets];
+        for mp in v_in.iter() {
+            let bucket: usize = ((mp.morton_code >> low_bit) & bit_mask) as usize;
+            bucket_count[bucket] += 1;
+        }
+        // compute starting index in output array for each bucket
+        let mut out_index: Vec<usize> = vec![0; n_buckets];
+        for i in 1..n_buckets {
+            out_index[i] = out_index[i - 1] + bucket_count[i - 1];
+        }
+        // store sorted values in output array
+        for mp in v_in.iter() {
+            let bucket: usize = ((mp.morton_code >> low_bit) & bit_mask) as usize;
+            v_out[out_index[bucket]] = *mp;
+            out_index[bucket] += 1;
+        }
+    }
+    // copy final result from _temp_, if needed
+    if n_passes & 1 == 1 {
+        v.copy_from_slice(&temp);
+    }
+}
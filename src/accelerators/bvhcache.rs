@@ -0,0 +1,445 @@
+//! An optional on-disk cache for acceleration structures and the
+//! mesh data they are built from. It is enabled by the
+//! **Accelerator** parameter _"string cachedir"_ and stores two kinds
+//! of entries in that directory, each named after a 64-bit key which
+//! is hashed from the geometry inputs:
+//!
+//! - `<key>.bvh`: the flattened **LinearBVHNode** array (or the wide
+//!   nodes) together with the primitive order of a **BVHAccel**, keyed
+//!   by the primitive bounds and the build parameters
+//! - `<key>.mesh`: the vertices, normals, uv coordinates and indices
+//!   read from a PLY file, keyed by the file's path, size and
+//!   modification time
+//!
+//! A subsequent render of the same scene memory-maps the entries and
+//! skips parsing the PLY files as well as building the BVH. Entries
+//! are written by the build which produced them and are never
+//! invalidated, a stale entry simply isn't found anymore.
+
+// std
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+// pbrt
+use crate::accelerators::bvh::LinearBVHNode;
+use crate::accelerators::widebvh::WideBVH;
+use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f};
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::Float;
+
+const MAGIC: &[u8; 8] = b"rs_pbrt\0";
+const VERSION: u32 = 1;
+const KIND_BVH: u32 = 1;
+const KIND_MESH: u32 = 2;
+
+/// Types which are written to (and read from) the cache as raw
+/// bytes.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]` (or primitive) types without
+/// padding bytes, for which every bit pattern is a valid value.
+pub unsafe trait Pod: Copy {}
+
+unsafe impl Pod for u32 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for u64 {}
+unsafe impl Pod for Float {}
+
+/// A fast, deterministic 64-bit hash for cache keys (the hashers of
+/// the standard library may change between Rust releases).
+pub struct KeyHasher {
+    hash: u64,
+}
+
+impl Default for KeyHasher {
+    fn default() -> Self {
+        let mut hasher: KeyHasher = KeyHasher { hash: 0_u64 };
+        // entries of other versions are never read back
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.write_u32(VERSION);
+        hasher
+    }
+}
+
+impl KeyHasher {
+    pub fn write_float(&mut self, f: Float) {
+        self.write_u32(f.to_bits());
+    }
+    pub fn write_bounds(&mut self, b: &Bounds3f) {
+        self.write_float(b.p_min.x);
+        self.write_float(b.p_min.y);
+        self.write_float(b.p_min.z);
+        self.write_float(b.p_max.x);
+        self.write_float(b.p_max.y);
+        self.write_float(b.p_max.z);
+    }
+}
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        // final avalanche (see SplitMix64)
+        let mut z: u64 = self.hash;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word: [u8; 8] = [0_u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(word));
+        }
+        self.write_u64(bytes.len() as u64);
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ i).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Returns the cache directory requested by _"string cachedir"_ (if
+/// any), the directory is created if it doesn't exist yet.
+pub fn cache_directory(ps: &ParamSet) -> Option<PathBuf> {
+    let directory: String = ps.find_one_string("cachedir", String::new());
+    if directory.is_empty() {
+        return None;
+    }
+    let path: PathBuf = PathBuf::from(directory);
+    if let Err(e) = fs::create_dir_all(&path) {
+        println!(
+            "WARNING: Unable to create cache directory {:?}: {}",
+            path, e
+        );
+        return None;
+    }
+    Some(path)
+}
+
+/// A BVH read back from the cache.
+pub struct CachedBVH {
+    pub primitive_numbers: Vec<usize>,
+    pub nodes: Vec<LinearBVHNode>,
+    pub wide_nodes: Option<WideBVH>,
+}
+
+pub fn load_bvh(directory: &Path, key: u64, n_primitives: usize) -> Option<CachedBVH> {
+    let file: MappedFile = MappedFile::open(&entry_path(directory, key, "bvh"))?;
+    let mut reader: CacheReader = CacheReader::new(file.bytes(), KIND_BVH, key)?;
+    let width: u32 = reader.read_u32()?;
+    let primitive_numbers: Vec<usize> = reader
+        .read_slice::<u32>()?
+        .into_iter()
+        .map(|n| n as usize)
+        .collect();
+    if primitive_numbers.iter().any(|n| *n >= n_primitives) {
+        return None;
+    }
+    let mut nodes: Vec<LinearBVHNode> = Vec::new();
+    let mut wide_nodes: Option<WideBVH> = None;
+    match width {
+        2 => nodes = reader.read_slice()?,
+        4 => wide_nodes = Some(WideBVH::Four(reader.read_slice()?)),
+        8 => wide_nodes = Some(WideBVH::Eight(reader.read_slice()?)),
+        _ => return None,
+    }
+    // a damaged entry is a cache miss (instead of a crash while
+    // traversing it)
+    let valid: bool = match wide_nodes {
+        Some(ref wide_nodes) => wide_nodes.is_valid(primitive_numbers.len()),
+        None => LinearBVHNode::is_valid_tree(&nodes, primitive_numbers.len()),
+    };
+    if !valid {
+        return None;
+    }
+    Some(CachedBVH {
+        primitive_numbers,
+        nodes,
+        wide_nodes,
+    })
+}
+
+pub fn store_bvh(
+    directory: &Path,
+    key: u64,
+    primitive_numbers: &[usize],
+    nodes: &[LinearBVHNode],
+    wide_nodes: Option<&WideBVH>,
+) {
+    let primitive_numbers: Vec<u32> = primitive_numbers.iter().map(|n| *n as u32).collect();
+    store(directory, key, "bvh", KIND_BVH, |writer| match wide_nodes {
+        Some(WideBVH::Four(wide_nodes)) => {
+            writer.write_u32(4)?;
+            writer.write_slice(&primitive_numbers)?;
+            writer.write_slice(wide_nodes)
+        }
+        Some(WideBVH::Eight(wide_nodes)) => {
+            writer.write_u32(8)?;
+            writer.write_slice(&primitive_numbers)?;
+            writer.write_slice(wide_nodes)
+        }
+        None => {
+            writer.write_u32(2)?;
+            writer.write_slice(&primitive_numbers)?;
+            writer.write_slice(nodes)
+        }
+    });
+}
+
+/// The (object space) mesh data of a PLY file.
+#[derive(Default)]
+pub struct MeshData {
+    pub p: Vec<Point3f>,
+    pub n: Vec<Normal3f>,
+    pub uv: Vec<Point2f>,
+    pub vertex_indices: Vec<u32>,
+    pub face_indices: Vec<i32>,
+}
+
+/// Key of the mesh data read from *filename*, unless the file
+/// doesn't exist.
+pub fn mesh_key(filename: &str) -> Option<u64> {
+    let path: PathBuf = fs::canonicalize(filename).ok()?;
+    let metadata = fs::metadata(&path).ok()?;
+    let mut hasher: KeyHasher = KeyHasher::default();
+    hasher.write(path.to_string_lossy().as_bytes());
+    hasher.write_u64(metadata.len());
+    if let Ok(modified) = metadata.modified() {
+        if let Ok(duration) = modified.duration_since(UNIX_EPOCH) {
+            hasher.write_u64(duration.as_secs());
+            hasher.write_u32(duration.subsec_nanos());
+        }
+    }
+    Some(hasher.finish())
+}
+
+pub fn load_mesh(directory: &Path, key: u64) -> Option<MeshData> {
+    let file: MappedFile = MappedFile::open(&entry_path(directory, key, "mesh"))?;
+    let mut reader: CacheReader = CacheReader::new(file.bytes(), KIND_MESH, key)?;
+    let p: Vec<Float> = reader.read_slice()?;
+    let n: Vec<Float> = reader.read_slice()?;
+    let uv: Vec<Float> = reader.read_slice()?;
+    let vertex_indices: Vec<u32> = reader.read_slice()?;
+    let face_indices: Vec<i32> = reader.read_slice()?;
+    Some(MeshData {
+        p: p.chunks_exact(3)
+            .map(|c| Point3f {
+                x: c[0],
+                y: c[1],
+                z: c[2],
+            })
+            .collect(),
+        n: n.chunks_exact(3)
+            .map(|c| Normal3f {
+                x: c[0],
+                y: c[1],
+                z: c[2],
+            })
+            .collect(),
+        uv: uv
+            .chunks_exact(2)
+            .map(|c| Point2f { x: c[0], y: c[1] })
+            .collect(),
+        vertex_indices,
+        face_indices,
+    })
+}
+
+pub fn store_mesh(directory: &Path, key: u64, mesh: &MeshData) {
+    let mut p: Vec<Float> = Vec::with_capacity(mesh.p.len() * 3);
+    for pnt in &mesh.p {
+        p.extend_from_slice(&[pnt.x, pnt.y, pnt.z]);
+    }
+    let mut n: Vec<Float> = Vec::with_capacity(mesh.n.len() * 3);
+    for nrm in &mesh.n {
+        n.extend_from_slice(&[nrm.x, nrm.y, nrm.z]);
+    }
+    let mut uv: Vec<Float> = Vec::with_capacity(mesh.uv.len() * 2);
+    for pt2 in &mesh.uv {
+        uv.extend_from_slice(&[pt2.x, pt2.y]);
+    }
+    store(directory, key, "mesh", KIND_MESH, |writer| {
+        writer.write_slice(&p)?;
+        writer.write_slice(&n)?;
+        writer.write_slice(&uv)?;
+        writer.write_slice(&mesh.vertex_indices)?;
+        writer.write_slice(&mesh.face_indices)
+    });
+}
+
+fn entry_path(directory: &Path, key: u64, extension: &str) -> PathBuf {
+    directory.join(format!("{:016x}.{}", key, extension))
+}
+
+fn store<F>(directory: &Path, key: u64, extension: &str, kind: u32, write_entry: F)
+where
+    F: FnOnce(&mut CacheWriter) -> std::io::Result<()>,
+{
+    // write to a temporary file first, so concurrent renders never
+    // map a partially written entry
+    let path: PathBuf = entry_path(directory, key, extension);
+    let tmp_path: PathBuf = path.with_extension(format!("{}.{}", extension, std::process::id()));
+    let result = File::create(&tmp_path).and_then(|file| {
+        let mut writer: CacheWriter = CacheWriter {
+            writer: BufWriter::new(file),
+        };
+        writer.writer.write_all(MAGIC)?;
+        writer.write_u32(VERSION)?;
+        writer.write_u32(kind)?;
+        writer.writer.write_all(&key.to_ne_bytes())?;
+        write_entry(&mut writer)?;
+        writer.writer.flush()?;
+        fs::rename(&tmp_path, &path)
+    });
+    if let Err(e) = result {
+        println!("WARNING: Unable to write cache entry {:?}: {}", path, e);
+        let _ = fs::remove_file(&tmp_path);
+    }
+}
+
+struct CacheWriter {
+    writer: BufWriter<File>,
+}
+
+impl CacheWriter {
+    fn write_u32(&mut self, i: u32) -> std::io::Result<()> {
+        self.writer.write_all(&i.to_ne_bytes())
+    }
+    /// Writes the length and element size followed by the raw
+    /// bytes.
+    fn write_slice<T: Pod>(&mut self, items: &[T]) -> std::io::Result<()> {
+        self.writer.write_all(&(items.len() as u64).to_ne_bytes())?;
+        self.write_u32(std::mem::size_of::<T>() as u32)?;
+        // Pod types have no padding, so all bytes are initialized
+        let bytes: &[u8] = unsafe {
+            std::slice::from_raw_parts(items.as_ptr() as *const u8, std::mem::size_of_val(items))
+        };
+        self.writer.write_all(bytes)
+    }
+}
+
+struct CacheReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> CacheReader<'a> {
+    /// Checks the header, returns _None_ for entries of another
+    /// kind, version or key (or another platform).
+    fn new(bytes: &'a [u8], kind: u32, key: u64) -> Option<Self> {
+        let mut reader: CacheReader = CacheReader { bytes, offset: 0 };
+        if reader.read_bytes(MAGIC.len())? != MAGIC
+            || reader.read_u32()? != VERSION
+            || reader.read_u32()? != kind
+            || reader.read_u64()? != key
+        {
+            return None;
+        }
+        Some(reader)
+    }
+    fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end: usize = self.offset.checked_add(len)?;
+        let bytes: &[u8] = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+    fn read_u32(&mut self) -> Option<u32> {
+        let mut word: [u8; 4] = [0_u8; 4];
+        word.copy_from_slice(self.read_bytes(4)?);
+        Some(u32::from_ne_bytes(word))
+    }
+    fn read_u64(&mut self) -> Option<u64> {
+        let mut word: [u8; 8] = [0_u8; 8];
+        word.copy_from_slice(self.read_bytes(8)?);
+        Some(u64::from_ne_bytes(word))
+    }
+    fn read_slice<T: Pod>(&mut self) -> Option<Vec<T>> {
+        let len: usize = self.read_u64()? as usize;
+        if self.read_u32()? as usize != std::mem::size_of::<T>() {
+            return None;
+        }
+        let bytes: &[u8] = self.read_bytes(len.checked_mul(std::mem::size_of::<T>())?)?;
+        let mut items: Vec<T> = Vec::with_capacity(len);
+        // the mapped bytes might not be aligned for T, copy them
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                items.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            );
+            items.set_len(len);
+        }
+        Some(items)
+    }
+}
+
+/// A read-only memory mapping of a whole file.
+#[cfg(unix)]
+struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl MappedFile {
+    fn open(path: &Path) -> Option<Self> {
+        use std::os::unix::io::AsRawFd;
+        let file: File = File::open(path).ok()?;
+        let len: usize = file.metadata().ok()?.len() as usize;
+        if len == 0 {
+            return None;
+        }
+        let ptr: *mut libc::c_void = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        // the mapping stays valid after the file is closed
+        Some(MappedFile { ptr, len })
+    }
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// Without _mmap()_ the file is read into memory instead.
+#[cfg(not(unix))]
+struct MappedFile {
+    bytes: Vec<u8>,
+}
+
+#[cfg(not(unix))]
+impl MappedFile {
+    fn open(path: &Path) -> Option<Self> {
+        Some(MappedFile {
+            bytes: fs::read(path).ok()?,
+        })
+    }
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
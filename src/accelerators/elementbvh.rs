@@ -0,0 +1,228 @@
+//! A small bounding volume hierarchy over the elements of a single
+//! shape (points of a point cloud, triangles of a compact mesh). In
+//! contrast to **BVHAccel** it doesn't know anything about primitives,
+//! the shape owning it stores its elements in the order of the leaves
+//! and intersects them itself.
+
+// pbrt
+use crate::core::geometry::{bnd3_expand, bnd3_union_bnd3f, bnd3_union_pnt3f, pnt3_inside_bnd3};
+use crate::core::geometry::{Bounds3f, Point3f, Ray, Vector3f, XYZEnum};
+use crate::core::pbrt::Float;
+use crate::core::stats;
+
+#[derive(Debug, Default, Copy, Clone)]
+struct ElementBVHNode {
+    bounds: Bounds3f,
+    /// first element (leaf) or second child (interior node)
+    offset: u32,
+    /// zero for interior nodes
+    n_elements: u16,
+    axis: u8,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ElementBVH {
+    nodes: Vec<ElementBVHNode>,
+}
+
+impl ElementBVH {
+    /// Builds the hierarchy by median splits of the element
+    /// centroids. Returns the hierarchy and the order in which the
+    /// caller has to store its elements (the i-th element referenced
+    /// by the leaves is *order\[i\]* of *bounds*).
+    pub fn new(bounds: &[Bounds3f], max_in_leaf: usize) -> (ElementBVH, Vec<u32>) {
+        let mut bvh: ElementBVH = ElementBVH::default();
+        let mut order: Vec<u32> = (0..bounds.len() as u32).collect();
+        if !bounds.is_empty() {
+            let centroids: Vec<Point3f> = bounds
+                .iter()
+                .map(|b| b.p_min * 0.5 as Float + b.p_max * 0.5 as Float)
+                .collect();
+            bvh.nodes.reserve(2 * bounds.len() / max_in_leaf + 1);
+            ElementBVH::recursive_build(
+                &mut bvh.nodes,
+                bounds,
+                &centroids,
+                &mut order,
+                0,
+                max_in_leaf,
+            );
+        }
+        (bvh, order)
+    }
+    fn recursive_build(
+        nodes: &mut Vec<ElementBVHNode>,
+        element_bounds: &[Bounds3f],
+        centroids: &[Point3f],
+        indices: &mut [u32],
+        start: usize,
+        max_in_leaf: usize,
+    ) -> usize {
+        let node_index: usize = nodes.len();
+        let mut bounds: Bounds3f = element_bounds[indices[0] as usize];
+        let mut centroid_bounds: Bounds3f = Bounds3f::new(
+            centroids[indices[0] as usize],
+            centroids[indices[0] as usize],
+        );
+        for i in indices.iter() {
+            bounds = bnd3_union_bnd3f(&bounds, &element_bounds[*i as usize]);
+            centroid_bounds = bnd3_union_pnt3f(&centroid_bounds, &centroids[*i as usize]);
+        }
+        if indices.len() <= max_in_leaf {
+            nodes.push(ElementBVHNode {
+                bounds,
+                offset: start as u32,
+                n_elements: indices.len() as u16,
+                axis: 0,
+            });
+            return node_index;
+        }
+        // split at the median along the axis of largest extent
+        let axis: u8 = centroid_bounds.maximum_extent();
+        let dim: XYZEnum = match axis {
+            0 => XYZEnum::X,
+            1 => XYZEnum::Y,
+            _ => XYZEnum::Z,
+        };
+        let mid: usize = indices.len() / 2;
+        indices.select_nth_unstable_by(mid, |a, b| {
+            centroids[*a as usize][dim]
+                .partial_cmp(&centroids[*b as usize][dim])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        nodes.push(ElementBVHNode {
+            bounds,
+            offset: 0,
+            n_elements: 0,
+            axis,
+        });
+        let (left, right) = indices.split_at_mut(mid);
+        ElementBVH::recursive_build(nodes, element_bounds, centroids, left, start, max_in_leaf);
+        let second_child: usize = ElementBVH::recursive_build(
+            nodes,
+            element_bounds,
+            centroids,
+            right,
+            start + mid,
+            max_in_leaf,
+        );
+        nodes[node_index].offset = second_child as u32;
+        node_index
+    }
+    /// Bounds of all elements.
+    pub fn bounds(&self) -> Bounds3f {
+        if self.nodes.is_empty() {
+            Bounds3f::default()
+        } else {
+            self.nodes[0].bounds
+        }
+    }
+    /// Calls *visit_element* for each element in a leaf whose bounds,
+    /// grown by *delta*, contain the point *p*.
+    pub fn for_each_near<F>(&self, p: &Point3f, delta: Float, mut visit_element: F)
+    where
+        F: FnMut(usize),
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut nodes_to_visit: [usize; 64] = [0_usize; 64];
+        let mut to_visit_offset: usize = 0;
+        let mut current_node_index: usize = 0;
+        loop {
+            let node: &ElementBVHNode = &self.nodes[current_node_index];
+            if pnt3_inside_bnd3(p, &bnd3_expand(&node.bounds, delta)) {
+                if node.n_elements > 0 {
+                    let first: usize = node.offset as usize;
+                    for i in first..(first + node.n_elements as usize) {
+                        visit_element(i);
+                    }
+                } else {
+                    nodes_to_visit[to_visit_offset] = node.offset as usize;
+                    to_visit_offset += 1;
+                    current_node_index += 1;
+                    continue;
+                }
+            }
+            if to_visit_offset == 0 {
+                break;
+            }
+            to_visit_offset -= 1;
+            current_node_index = nodes_to_visit[to_visit_offset];
+        }
+    }
+    /// Traverses the hierarchy and calls *intersect_element* for each
+    /// element in a leaf hit by the ray (whose *t_max* shrinks with
+    /// each hit). Returns the parametric distance and the index of the
+    /// closest element hit (or the first one found for *any_hit*).
+    pub fn intersect<F>(
+        &self,
+        r: &Ray,
+        any_hit: bool,
+        mut intersect_element: F,
+    ) -> Option<(Float, usize)>
+    where
+        F: FnMut(usize, &Ray) -> Option<Float>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let ray: Ray = r.clone();
+        let inv_dir: Vector3f = Vector3f {
+            x: 1.0 / ray.d.x,
+            y: 1.0 / ray.d.y,
+            z: 1.0 / ray.d.z,
+        };
+        let dir_is_neg: [u8; 3] = [
+            (inv_dir.x < 0.0) as u8,
+            (inv_dir.y < 0.0) as u8,
+            (inv_dir.z < 0.0) as u8,
+        ];
+        let mut hit: Option<(Float, usize)> = None;
+        let mut nodes_to_visit: [usize; 64] = [0_usize; 64];
+        let mut to_visit_offset: usize = 0;
+        let mut current_node_index: usize = 0;
+        let mut n_visits: u64 = 0;
+        loop {
+            let node: &ElementBVHNode = &self.nodes[current_node_index];
+            n_visits += 1;
+            if node.bounds.intersect_p(&ray, &inv_dir, &dir_is_neg) {
+                if node.n_elements > 0 {
+                    let first: usize = node.offset as usize;
+                    for i in first..(first + node.n_elements as usize) {
+                        if let Some(t) = intersect_element(i, &ray) {
+                            if any_hit {
+                                stats::count_node_visits(n_visits);
+                                return Some((t, i));
+                            }
+                            ray.t_max.set(t);
+                            hit = Some((t, i));
+                        }
+                    }
+                    if to_visit_offset == 0 {
+                        break;
+                    }
+                    to_visit_offset -= 1;
+                    current_node_index = nodes_to_visit[to_visit_offset];
+                } else if dir_is_neg[node.axis as usize] == 1 {
+                    // visit second child first
+                    nodes_to_visit[to_visit_offset] = current_node_index + 1;
+                    to_visit_offset += 1;
+                    current_node_index = node.offset as usize;
+                } else {
+                    nodes_to_visit[to_visit_offset] = node.offset as usize;
+                    to_visit_offset += 1;
+                    current_node_index += 1;
+                }
+            } else {
+                if to_visit_offset == 0 {
+                    break;
+                }
+                to_visit_offset -= 1;
+                current_node_index = nodes_to_visit[to_visit_offset];
+            }
+        }
+        stats::count_node_visits(n_visits);
+        hit
+    }
+}
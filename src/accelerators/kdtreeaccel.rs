@@ -10,6 +10,8 @@ use crate::core::paramset::ParamSet;
 use crate::core::pbrt::log_2_int_i32;
 use crate::core::pbrt::Float;
 use crate::core::primitive::Primitive;
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::stats;
 
 pub const MAX_TODO: usize = 64;
 
@@ -501,7 +503,7 @@ impl KdTreeAccel {
         self.bounds
     }
     pub fn intersect(&self, ray: &Ray, isect: &mut SurfaceInteraction) -> bool {
-        // TODO: ProfilePhase p(Prof::AccelIntersect);
+        let _p = ProfilePhase::new(Prof::AccelIntersect);
         if self.nodes.is_empty() {
             return false;
         }
@@ -523,11 +525,13 @@ impl KdTreeAccel {
         let mut hit: bool = false;
         let mut node_idx: usize = 0;
         let mut node_opt: Option<&KdAccelNode> = self.nodes.get(node_idx);
+        let mut n_visits: u64 = 0;
         while let Some(node) = node_opt {
             // bail out if we found a hit closer than the current node
             if ray.t_max.get() < t_min {
                 break;
             }
+            n_visits += 1;
             if !node.is_leaf() {
                 // process kd-tree interior node
 
@@ -616,10 +620,11 @@ impl KdTreeAccel {
                 }
             }
         }
+        stats::count_node_visits(n_visits);
         hit
     }
     pub fn intersect_p(&self, ray: &Ray) -> bool {
-        // TODO: ProfilePhase p(Prof::AccelIntersectP);
+        let _p = ProfilePhase::new(Prof::AccelIntersectP);
         if self.nodes.is_empty() {
             return false;
         }
@@ -639,7 +644,9 @@ impl KdTreeAccel {
         let mut todo_pos: usize = 0;
         let mut node_idx: usize = 0;
         let mut node_opt: Option<&KdAccelNode> = self.nodes.get(node_idx);
+        let mut n_visits: u64 = 0;
         while let Some(node) = node_opt {
+            n_visits += 1;
             if node.is_leaf() {
                 // check for shadow ray intersections inside leaf node
                 let n_primitives: i32 = node.n_primitives();
@@ -650,6 +657,7 @@ impl KdTreeAccel {
                     }
                     let p: &Arc<Primitive> = &self.primitives[one_primitive as usize];
                     if p.intersect_p(ray) {
+                        stats::count_node_visits(n_visits);
                         return true;
                     }
                 } else {
@@ -663,6 +671,7 @@ impl KdTreeAccel {
                             as usize;
                         let prim: &Arc<Primitive> = &self.primitives[primitive_index];
                         if prim.intersect_p(ray) {
+                            stats::count_node_visits(n_visits);
                             return true;
                         }
                     }
@@ -726,6 +735,7 @@ impl KdTreeAccel {
                 }
             }
         }
+        stats::count_node_visits(n_visits);
         false
     }
     pub fn get_material(&self) -> Option<Arc<Material>> {
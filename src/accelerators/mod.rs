@@ -6,7 +6,13 @@
 //! primitive in turn to find the closest intersection.
 //!
 //! - BVHAccel
+//! - BVHCache
+//! - ElementBVH
 //! - KdTreeAccel
+//! - WideBVH
 
 pub mod bvh;
+pub mod bvhcache;
+pub mod elementbvh;
 pub mod kdtreeaccel;
+pub mod widebvh;
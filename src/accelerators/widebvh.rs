@@ -0,0 +1,336 @@
+//! A wide bounding volume hierarchy with four or eight children per
+//! node, collapsed from the binary tree built by **BVHAccel**. The
+//! bounds of the children are stored in structure-of-arrays form, so
+//! a ray is tested against all child boxes of a node at once by
+//! simple loops over the lanes, which the compiler can turn into
+//! SIMD instructions.
+
+// pbrt
+use crate::accelerators::bvh::BVHBuildNode;
+use crate::accelerators::bvhcache::Pod;
+use crate::core::geometry::bnd3_union_bnd3f;
+use crate::core::geometry::{Bounds3f, Point3f, Ray};
+use crate::core::pbrt::{gamma, Float};
+use crate::core::stats;
+
+/// Size of the traversal stack, each visited node pushes up to
+/// seven children.
+const STACK_SIZE: usize = 512;
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct WideBVHNode<const N: usize> {
+    /// minima of the child bounds (x, y and z for all lanes)
+    bounds_min: [[Float; N]; 3],
+    /// maxima of the child bounds (x, y and z for all lanes)
+    bounds_max: [[Float; N]; 3],
+    /// child node (interior) or first primitive (leaf)
+    offset: [u32; N],
+    /// number of primitives of a leaf child, zero for interior
+    /// children and empty lanes
+    n_primitives: [u16; N],
+}
+
+impl<const N: usize> Default for WideBVHNode<N> {
+    fn default() -> Self {
+        // empty lanes have inverted bounds which are never hit
+        WideBVHNode {
+            bounds_min: [[Float::INFINITY; N]; 3],
+            bounds_max: [[-Float::INFINITY; N]; 3],
+            offset: [0_u32; N],
+            n_primitives: [0_u16; N],
+        }
+    }
+}
+
+// N is even, so the u16 lanes leave no padding
+unsafe impl<const N: usize> Pod for WideBVHNode<N> {}
+
+#[derive(Debug, Clone)]
+pub enum WideBVH {
+    Four(Vec<WideBVHNode<4>>),
+    Eight(Vec<WideBVHNode<8>>),
+}
+
+impl WideBVH {
+    /// Collapses the binary tree below *root* into nodes with
+    /// *width* (4 or 8) children.
+    pub fn new(root: &BVHBuildNode, width: usize) -> Self {
+        match width {
+            4 => {
+                let mut nodes: Vec<WideBVHNode<4>> = Vec::new();
+                collapse(&mut nodes, root);
+                WideBVH::Four(nodes)
+            }
+            8 => {
+                let mut nodes: Vec<WideBVHNode<8>> = Vec::new();
+                collapse(&mut nodes, root);
+                WideBVH::Eight(nodes)
+            }
+            _ => panic!("WideBVH::new(): unsupported width {}", width),
+        }
+    }
+    pub fn width(&self) -> usize {
+        match self {
+            WideBVH::Four(_) => 4,
+            WideBVH::Eight(_) => 8,
+        }
+    }
+    /// Bounds of all primitives.
+    pub fn bounds(&self) -> Bounds3f {
+        match self {
+            WideBVH::Four(nodes) => nodes.first().map_or(Bounds3f::default(), node_bounds),
+            WideBVH::Eight(nodes) => nodes.first().map_or(Bounds3f::default(), node_bounds),
+        }
+    }
+    /// Updates the child bounds of all nodes, *primitive_bounds*
+    /// holds the bounds of the primitives referenced by the leaves.
+    pub fn refit(&mut self, primitive_bounds: &[Bounds3f]) {
+        match self {
+            WideBVH::Four(nodes) => refit(nodes, primitive_bounds),
+            WideBVH::Eight(nodes) => refit(nodes, primitive_bounds),
+        }
+    }
+    /// Checks the nodes (e.g. read from a cache) before they are
+    /// traversed, see **LinearBVHNode::is_valid_tree()**.
+    pub fn is_valid(&self, n_primitives: usize) -> bool {
+        match self {
+            WideBVH::Four(nodes) => is_valid_tree(nodes, n_primitives),
+            WideBVH::Eight(nodes) => is_valid_tree(nodes, n_primitives),
+        }
+    }
+    /// Traverses the hierarchy front to back and calls
+    /// *intersect_leaf* with the first primitive and the number of
+    /// primitives of each leaf hit by the ray. The closure returns
+    /// whether one of the primitives was hit (and is expected to
+    /// shrink the ray's *t_max*). For *any_hit* the traversal stops
+    /// at the first hit.
+    pub fn intersect<F>(&self, ray: &Ray, any_hit: bool, intersect_leaf: F) -> bool
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        match self {
+            WideBVH::Four(nodes) => traverse(nodes, ray, any_hit, intersect_leaf),
+            WideBVH::Eight(nodes) => traverse(nodes, ray, any_hit, intersect_leaf),
+        }
+    }
+}
+
+fn collapse<const N: usize>(nodes: &mut Vec<WideBVHNode<N>>, node: &BVHBuildNode) -> u32 {
+    // gather up to N children by repeatedly opening the interior
+    // child with the largest surface area
+    let mut children: Vec<&BVHBuildNode> = Vec::with_capacity(N);
+    if let (Some(child1), Some(child2)) = (node.child1, node.child2) {
+        children.push(child1);
+        children.push(child2);
+    } else {
+        children.push(node);
+    }
+    while children.len() < N {
+        let mut largest: Option<(usize, Float)> = None;
+        for (i, child) in children.iter().enumerate() {
+            if child.n_primitives == 0 {
+                let area: Float = child.bounds.surface_area();
+                if largest.is_none() || area > largest.unwrap().1 {
+                    largest = Some((i, area));
+                }
+            }
+        }
+        if let Some((i, _area)) = largest {
+            let child: &BVHBuildNode = children[i];
+            children[i] = child.child1.unwrap();
+            children.insert(i + 1, child.child2.unwrap());
+        } else {
+            break;
+        }
+    }
+    // reserve node before children are appended
+    let node_index: usize = nodes.len();
+    nodes.push(WideBVHNode::default());
+    let mut wide_node: WideBVHNode<N> = WideBVHNode::default();
+    for (lane, child) in children.iter().enumerate() {
+        let p_min: Point3f = child.bounds.p_min;
+        let p_max: Point3f = child.bounds.p_max;
+        wide_node.bounds_min[0][lane] = p_min.x;
+        wide_node.bounds_min[1][lane] = p_min.y;
+        wide_node.bounds_min[2][lane] = p_min.z;
+        wide_node.bounds_max[0][lane] = p_max.x;
+        wide_node.bounds_max[1][lane] = p_max.y;
+        wide_node.bounds_max[2][lane] = p_max.z;
+        if child.n_primitives > 0 {
+            wide_node.offset[lane] = child.first_prim_offset as u32;
+            wide_node.n_primitives[lane] = child.n_primitives as u16;
+        } else {
+            wide_node.offset[lane] = collapse(nodes, child);
+        }
+    }
+    nodes[node_index] = wide_node;
+    node_index as u32
+}
+
+fn node_bounds<const N: usize>(node: &WideBVHNode<N>) -> Bounds3f {
+    let mut bounds: Bounds3f = Bounds3f::default();
+    for lane in 0..N {
+        if node.bounds_min[0][lane] <= node.bounds_max[0][lane] {
+            bounds.p_min.x = bounds.p_min.x.min(node.bounds_min[0][lane]);
+            bounds.p_min.y = bounds.p_min.y.min(node.bounds_min[1][lane]);
+            bounds.p_min.z = bounds.p_min.z.min(node.bounds_min[2][lane]);
+            bounds.p_max.x = bounds.p_max.x.max(node.bounds_max[0][lane]);
+            bounds.p_max.y = bounds.p_max.y.max(node.bounds_max[1][lane]);
+            bounds.p_max.z = bounds.p_max.z.max(node.bounds_max[2][lane]);
+        }
+    }
+    bounds
+}
+
+fn is_valid_tree<const N: usize>(nodes: &[WideBVHNode<N>], n_primitives: usize) -> bool {
+    if nodes.is_empty() {
+        return n_primitives == 0;
+    }
+    let mut n_parents: Vec<u32> = vec![0_u32; nodes.len()];
+    let mut depth: Vec<usize> = vec![0_usize; nodes.len()];
+    let mut n_leaf_primitives: usize = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 && n_parents[i] != 1 {
+            return false;
+        }
+        for lane in 0..N {
+            let offset: usize = node.offset[lane] as usize;
+            let n: usize = node.n_primitives[lane] as usize;
+            if n > 0 {
+                if offset + n > n_primitives {
+                    return false;
+                }
+                n_leaf_primitives += n;
+            } else if offset > i {
+                if offset >= nodes.len() {
+                    return false;
+                }
+                n_parents[offset] += 1;
+                depth[offset] = depth[i] + 1;
+                // each level keeps up to N - 1 children on the stack
+                if (depth[offset] + 1) * N > STACK_SIZE {
+                    return false;
+                }
+            } else if (0..3).any(|axis| {
+                node.bounds_min[axis][lane] != Float::INFINITY
+                    || node.bounds_max[axis][lane] != -Float::INFINITY
+            }) {
+                // lanes which are neither leaves nor interior have
+                // to be empty, so they are never hit
+                return false;
+            }
+        }
+    }
+    n_leaf_primitives == n_primitives
+}
+
+fn refit<const N: usize>(nodes: &mut [WideBVHNode<N>], primitive_bounds: &[Bounds3f]) {
+    // children are stored after their parent
+    for i in (0..nodes.len()).rev() {
+        for lane in 0..N {
+            let n_primitives: usize = nodes[i].n_primitives[lane] as usize;
+            let offset: usize = nodes[i].offset[lane] as usize;
+            let bounds: Bounds3f = if n_primitives > 0 {
+                primitive_bounds[offset..(offset + n_primitives)]
+                    .iter()
+                    .fold(Bounds3f::default(), |b, pb| bnd3_union_bnd3f(&b, pb))
+            } else if offset > i {
+                node_bounds(&nodes[offset])
+            } else {
+                // empty lane
+                continue;
+            };
+            nodes[i].bounds_min[0][lane] = bounds.p_min.x;
+            nodes[i].bounds_min[1][lane] = bounds.p_min.y;
+            nodes[i].bounds_min[2][lane] = bounds.p_min.z;
+            nodes[i].bounds_max[0][lane] = bounds.p_max.x;
+            nodes[i].bounds_max[1][lane] = bounds.p_max.y;
+            nodes[i].bounds_max[2][lane] = bounds.p_max.z;
+        }
+    }
+}
+
+fn traverse<const N: usize, F>(
+    nodes: &[WideBVHNode<N>],
+    ray: &Ray,
+    any_hit: bool,
+    mut intersect_leaf: F,
+) -> bool
+where
+    F: FnMut(usize, usize) -> bool,
+{
+    if nodes.is_empty() {
+        return false;
+    }
+    let inv_dir: [Float; 3] = [1.0 / ray.d.x, 1.0 / ray.d.y, 1.0 / ray.d.z];
+    let dir_is_neg: [bool; 3] = [inv_dir[0] < 0.0, inv_dir[1] < 0.0, inv_dir[2] < 0.0];
+    let origin: [Float; 3] = [ray.o.x, ray.o.y, ray.o.z];
+    // ensure robust bounds intersection (see Bounds3::IntersectP())
+    let far_scale: Float = 1.0 + 2.0 * gamma(3_i32);
+    let mut hit: bool = false;
+    let mut nodes_to_visit: [u32; STACK_SIZE] = [0_u32; STACK_SIZE];
+    let mut to_visit_offset: usize = 0;
+    let mut current_node_index: u32 = 0;
+    let mut n_visits: u64 = 0;
+    loop {
+        let node: &WideBVHNode<N> = &nodes[current_node_index as usize];
+        n_visits += 1;
+        // slab test against all child boxes
+        let mut t_near: [Float; N] = [0.0 as Float; N];
+        let mut t_far: [Float; N] = [ray.t_max.get(); N];
+        for axis in 0..3 {
+            let (near, far): (&[Float; N], &[Float; N]) = if dir_is_neg[axis] {
+                (&node.bounds_max[axis], &node.bounds_min[axis])
+            } else {
+                (&node.bounds_min[axis], &node.bounds_max[axis])
+            };
+            for lane in 0..N {
+                let t0: Float = (near[lane] - origin[axis]) * inv_dir[axis];
+                let t1: Float = (far[lane] - origin[axis]) * inv_dir[axis] * far_scale;
+                t_near[lane] = t_near[lane].max(t0);
+                t_far[lane] = t_far[lane].min(t1);
+            }
+        }
+        // sort hit children front to back
+        let mut lanes: [usize; N] = [0_usize; N];
+        let mut n_hits: usize = 0;
+        for lane in 0..N {
+            if t_near[lane] <= t_far[lane] {
+                let mut i: usize = n_hits;
+                while i > 0 && t_near[lanes[i - 1]] > t_near[lane] {
+                    lanes[i] = lanes[i - 1];
+                    i -= 1;
+                }
+                lanes[i] = lane;
+                n_hits += 1;
+            }
+        }
+        // intersect leaves, push interior children far to near
+        for lane in lanes.iter().take(n_hits) {
+            let n_primitives: u16 = node.n_primitives[*lane];
+            if n_primitives > 0
+                && intersect_leaf(node.offset[*lane] as usize, n_primitives as usize)
+            {
+                if any_hit {
+                    stats::count_node_visits(n_visits);
+                    return true;
+                }
+                hit = true;
+            }
+        }
+        for lane in lanes.iter().take(n_hits).rev() {
+            if node.n_primitives[*lane] == 0 {
+                nodes_to_visit[to_visit_offset] = node.offset[*lane];
+                to_visit_offset += 1;
+            }
+        }
+        if to_visit_offset == 0 {
+            break;
+        }
+        to_visit_offset -= 1;
+        current_node_index = nodes_to_visit[to_visit_offset];
+    }
+    stats::count_node_visits(n_visits);
+    hit
+}
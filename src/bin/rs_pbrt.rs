@@ -24,7 +24,10 @@ use pbrt::core::api::{
     pbrt_world_begin,
 };
 use pbrt::core::api::{ApiState, BsdfState};
+use pbrt::core::checkpoint::CheckpointOptions;
+use pbrt::core::distributed::{DistributedOptions, DistributedRole};
 use pbrt::core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
+use pbrt::core::integrator::ProgressiveOptions;
 use pbrt::core::paramset::ParamSet;
 use pbrt::core::pbrt::{Float, Spectrum};
 use pbrt::core::transform::Transform;
@@ -35,6 +38,7 @@ use std::io::BufReader;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
 /// Parse a PBRT scene file (extension .pbrt) and render it.
 #[derive(StructOpt)]
@@ -42,6 +46,43 @@ struct Cli {
     /// use specified number of threads for rendering
     #[structopt(short = "t", long = "nthreads", default_value = "0")]
     nthreads: u8,
+    /// write the render statistics as JSON to this file
+    #[structopt(long = "stats-json", parse(from_os_str))]
+    stats_json: Option<std::path::PathBuf>,
+    /// periodically write a checkpoint of the render to this file
+    #[structopt(long = "checkpoint", parse(from_os_str))]
+    checkpoint: Option<std::path::PathBuf>,
+    /// continue the render stored in this checkpoint file (and keep
+    /// writing checkpoints to it)
+    #[structopt(long = "resume", parse(from_os_str), conflicts_with = "checkpoint")]
+    resume: Option<std::path::PathBuf>,
+    /// seconds between two checkpoints
+    #[structopt(long = "checkpoint-interval", default_value = "300")]
+    checkpoint_interval: u64,
+    /// render the whole image in passes of increasing samples per pixel
+    #[structopt(long = "progressive")]
+    progressive: bool,
+    /// stop a progressive render after this many seconds
+    #[structopt(long = "time-limit")]
+    time_limit: Option<u64>,
+    /// hand out the image tiles to workers connecting to this
+    /// address (e.g. 0.0.0.0:7878) and write the image
+    #[structopt(
+        long = "coordinator",
+        conflicts_with_all = &["checkpoint", "resume", "progressive", "time_limit"]
+    )]
+    coordinator: Option<String>,
+    /// render image tiles for the coordinator at this address (the
+    /// same scene has to be given)
+    #[structopt(
+        long = "worker",
+        conflicts_with_all = &["coordinator", "checkpoint", "resume", "progressive", "time_limit"]
+    )]
+    worker: Option<String>,
+    /// seconds the coordinator waits for a sign of life of a worker
+    /// before it hands the worker's tile to another one
+    #[structopt(long = "worker-timeout", default_value = "600")]
+    worker_timeout: u64,
     /// The path to the file to read
     #[structopt(parse(from_os_str))]
     path: std::path::PathBuf,
@@ -177,6 +218,34 @@ fn pbrt_string_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, S
     (string1, string2)
 }
 
+fn pbrt_strings_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, Vec<String>) {
+    let mut strings: Vec<String> = Vec::new();
+    // single string or several strings using brackets
+    let ident = pairs.next();
+    let string1: String = String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap();
+    let option = pairs.next();
+    let lbrack = option.clone().unwrap();
+    let mut string = if lbrack.as_str() == "[" {
+        // check for brackets
+        pairs.next()
+    } else {
+        // no brackets
+        option
+    };
+    while string.is_some() {
+        let pair = string.unwrap().clone();
+        if pair.as_str() == "]" {
+            // closing bracket found
+            break;
+        } else {
+            let ident = pair.into_inner().next();
+            strings.push(String::from_str(ident.unwrap().clone().as_span().as_str()).unwrap());
+        }
+        string = pairs.next();
+    }
+    (string1, strings)
+}
+
 fn pbrt_texture_parameter(pairs: &mut pest::iterators::Pairs<Rule>) -> (String, String) {
     // single string with or without brackets
     let ident = pairs.next();
@@ -350,12 +419,16 @@ fn extract_params(key_word: String, pairs: pest::iterators::Pair<Rule>) -> Param
                                 pbrt_float_parameter(&mut parameter_pair.into_inner());
                             let string: String = tuple.0;
                             let floats: Vec<Float> = tuple.1;
-                            params.add_rgb_spectrum(
-                                string,
-                                Spectrum {
-                                    c: [floats[0], floats[1], floats[2]],
-                                },
-                            );
+                            if floats.len() == 3 {
+                                params.add_rgb_spectrum(
+                                    string,
+                                    Spectrum {
+                                        c: [floats[0], floats[1], floats[2]],
+                                    },
+                                );
+                            } else {
+                                params.add_rgb_spectra(string, floats);
+                            }
                         }
                         Rule::spectrum_param => {
                             // TODO: "spectrum Kd" [ 300 .3  400 .6   410 .65  415 .8  500 .2  600 .1 ]
@@ -380,11 +453,15 @@ fn extract_params(key_word: String, pairs: pest::iterators::Pair<Rule>) -> Param
                             params.add_sampled_spectrum_files(string1, strings);
                         }
                         Rule::string_param => {
-                            let tuple: (String, String) =
-                                pbrt_string_parameter(&mut parameter_pair.into_inner());
+                            let tuple: (String, Vec<String>) =
+                                pbrt_strings_parameter(&mut parameter_pair.into_inner());
                             let string1: String = tuple.0;
-                            let string2: String = tuple.1;
-                            params.add_string(string1, string2);
+                            let strings: Vec<String> = tuple.1;
+                            if strings.len() == 1 {
+                                params.add_string(string1, strings[0].clone());
+                            } else {
+                                params.add_strings(string1, strings);
+                            }
                         }
                         Rule::texture_param => {
                             let tuple: (String, String) =
@@ -858,6 +935,36 @@ fn main() {
     println!("Copyright (c) 2016-2020 Jan Douglas Bert Walter.");
     println!("Rust code based on C++ code by Matt Pharr, Greg Humphreys, and Wenzel Jakob.");
     let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+    api_state.stats_json = args.stats_json;
+    let checkpoint_interval: Duration = Duration::from_secs(args.checkpoint_interval);
+    api_state.render_settings.checkpoint = match (args.checkpoint, args.resume) {
+        (Some(filename), _) => Some(CheckpointOptions::new(filename, false)),
+        (None, Some(filename)) => Some(CheckpointOptions::new(filename, true)),
+        (None, None) => None,
+    }
+    .map(|mut options| {
+        options.interval = checkpoint_interval;
+        options
+    });
+    if args.progressive || args.time_limit.is_some() {
+        api_state.render_settings.progressive = Some(ProgressiveOptions {
+            time_limit: args.time_limit.map(Duration::from_secs),
+            ..Default::default()
+        });
+    }
+    api_state.render_settings.distributed = match (args.coordinator, args.worker) {
+        (Some(address), _) => Some(DistributedOptions {
+            role: DistributedRole::Coordinator,
+            address,
+            worker_timeout: Duration::from_secs(args.worker_timeout),
+        }),
+        (None, Some(address)) => Some(DistributedOptions {
+            role: DistributedRole::Worker,
+            address,
+            worker_timeout: Duration::from_secs(args.worker_timeout),
+        }),
+        (None, None) => None,
+    };
     parse_file(
         args.path.into_os_string().into_string().unwrap(),
         &mut api_state,
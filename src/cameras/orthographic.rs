@@ -11,6 +11,7 @@ use crate::core::medium::Medium;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::lerp;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::sampling::concentric_sample_disk;
 use crate::core::transform::{AnimatedTransform, Transform};
 
@@ -148,7 +149,7 @@ impl OrthographicCamera {
     }
     // Camera
     pub fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        // TODO: ProfilePhase prof(Prof::GenerateCameraRay);
+        let _p = ProfilePhase::new(Prof::GenerateCameraRay);
         // compute raster and camera sample positions
         let p_film: Point3f = Point3f {
             x: sample.p_film.x,
@@ -15,6 +15,7 @@ use crate::core::medium::{Medium, MediumInterface};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::lerp;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::sampling::concentric_sample_disk;
 use crate::core::transform::{AnimatedTransform, Transform};
 
@@ -188,7 +189,7 @@ impl PerspectiveCamera {
     }
     // Camera
     pub fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        // TODO: ProfilePhase prof(Prof::GenerateCameraRay);
+        let _p = ProfilePhase::new(Prof::GenerateCameraRay);
         // compute raster and camera sample positions
         let p_film: Point3f = Point3f {
             x: sample.p_film.x,
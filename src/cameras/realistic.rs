@@ -19,6 +19,7 @@ use crate::core::medium::Medium;
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::{lerp, quadratic};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::reflection::refract;
 use crate::core::transform::{AnimatedTransform, Transform};
 
@@ -196,7 +197,7 @@ impl RealisticCamera {
         ))))
     }
     pub fn generate_ray(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        // TODO: ProfilePhase prof(Prof::GenerateCameraRay);
+        let _p = ProfilePhase::new(Prof::GenerateCameraRay);
         // ++totalRays;
         // find point on film, _p_film_, corresponding to _sample.p_film_
         let s: Point2f = Point2f {
@@ -9,6 +9,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 // pbrt
 use crate::accelerators::bvh::{BVHAccel, SplitMethod};
+use crate::accelerators::bvhcache;
 use crate::accelerators::kdtreeaccel::KdTreeAccel;
 use crate::cameras::environment::EnvironmentCamera;
 use crate::cameras::orthographic::OrthographicCamera;
@@ -19,7 +20,7 @@ use crate::core::film::Film;
 use crate::core::filter::Filter;
 use crate::core::geometry::{vec3_coordinate_system, vec3_cross_vec3};
 use crate::core::geometry::{Bounds2i, Normal3f, Point2f, Point2i, Point3f, Vector3f};
-use crate::core::integrator::{Integrator, SamplerIntegrator};
+use crate::core::integrator::{Integrator, RenderSettings, SamplerIntegrator};
 use crate::core::light::Light;
 use crate::core::material::Material;
 use crate::core::medium::get_medium_scattering_properties;
@@ -28,11 +29,15 @@ use crate::core::mipmap::ImageWrap;
 use crate::core::paramset::{ParamSet, TextureParams};
 use crate::core::pbrt::lerp;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::primitive::{CSGOperation, CSGPrimitive};
 use crate::core::primitive::{GeometricPrimitive, Primitive, TransformedPrimitive};
+use crate::core::profiler::{self, Prof, ProfilePhase};
 use crate::core::reflection::FourierBSDFTable;
 use crate::core::sampler::Sampler;
 use crate::core::scene::Scene;
+use crate::core::session::{RenderSession, TileCallback};
 use crate::core::shape::Shape;
+use crate::core::stats::{self, Counter};
 use crate::core::texture::{
     CylindricalMapping2D, IdentityMapping3D, PlanarMapping2D, SphericalMapping2D, Texture,
     TextureMapping2D, TextureMapping3D, UVMapping2D,
@@ -45,6 +50,7 @@ use crate::filters::sinc::LanczosSincFilter;
 use crate::filters::triangle::TriangleFilter;
 use crate::integrators::ao::AOIntegrator;
 use crate::integrators::bdpt::BDPTIntegrator;
+use crate::integrators::bvhcost::{BVHCostIntegrator, TraversalCost};
 use crate::integrators::directlighting::{DirectLightingIntegrator, LightStrategy};
 use crate::integrators::mlt::MLTIntegrator;
 use crate::integrators::path::PathIntegrator;
@@ -62,6 +68,7 @@ use crate::materials::disney::DisneyMaterial;
 use crate::materials::fourier::FourierMaterial;
 use crate::materials::glass::GlassMaterial;
 use crate::materials::hair::HairMaterial;
+use crate::materials::indexed::IndexedMaterial;
 use crate::materials::matte::MatteMaterial;
 use crate::materials::metal::MetalMaterial;
 use crate::materials::mirror::MirrorMaterial;
@@ -79,15 +86,18 @@ use crate::samplers::random::RandomSampler;
 use crate::samplers::sobol::SobolSampler;
 use crate::samplers::stratified::StratifiedSampler;
 use crate::samplers::zerotwosequence::ZeroTwoSequenceSampler;
+use crate::shapes::bilinearpatch::create_bilinear_mesh_shape;
 use crate::shapes::curve::create_curve_shape;
 use crate::shapes::cylinder::Cylinder;
 use crate::shapes::disk::Disk;
 use crate::shapes::loopsubdiv::loop_subdivide;
-use crate::shapes::nurbs::nurbs_evaluate_surface;
 use crate::shapes::nurbs::Homogeneous3;
+use crate::shapes::nurbs::{nurbs_evaluate_surface, nurbs_tessellate_adaptive, NurbsTessellation};
 use crate::shapes::plymesh::create_ply_mesh;
+use crate::shapes::points::create_points_shape;
+use crate::shapes::sdf::create_sdf_shape;
 use crate::shapes::sphere::Sphere;
-use crate::shapes::triangle::{Triangle, TriangleMesh};
+use crate::shapes::triangle::{create_triangle_mesh_shapes, Triangle, TriangleMesh};
 use crate::textures::checkerboard::Checkerboard2DTexture;
 use crate::textures::constant::ConstantTexture;
 use crate::textures::dots::DotsTexture;
@@ -97,11 +107,18 @@ use crate::textures::imagemap::{convert_to_float, convert_to_spectrum};
 use crate::textures::marble::MarbleTexture;
 use crate::textures::mix::MixTexture;
 use crate::textures::scale::ScaleTexture;
+use crate::textures::vertexcolor::VertexColorTexture;
 use crate::textures::windy::WindyTexture;
 use crate::textures::wrinkled::WrinkledTexture;
 
 // see api.cpp
 
+/// Creates shapes for a name registered with
+/// _pbrt_register_shape()_, arguments are like for the built-in
+/// shapes (object to world, world to object, reverse orientation and
+/// the parameters of the _Shape_ statement).
+pub type CustomShapeCreator = fn(&Transform, &Transform, bool, &ParamSet) -> Vec<Arc<Shape>>;
+
 pub struct BsdfState {
     pub loaded_bsdfs: HashMap<String, Arc<FourierBSDFTable>>,
 }
@@ -117,6 +134,10 @@ impl Default for BsdfState {
 pub struct ApiState {
     number_of_threads: u8,
     pub search_directory: Option<Box<PathBuf>>,
+    /// if set, the render statistics are written as JSON to this file
+    pub stats_json: Option<PathBuf>,
+    /// checkpoints and progressive rendering (set from the command line)
+    pub render_settings: RenderSettings,
     cur_transform: TransformSet,
     active_transform_bits: u8,
     named_coordinate_systems: HashMap<&'static str, TransformSet>,
@@ -126,6 +147,7 @@ pub struct ApiState {
     pushed_transforms: Vec<TransformSet>,
     pushed_active_transform_bits: Vec<u8>,
     param_set: ParamSet,
+    shape_creators: HashMap<String, CustomShapeCreator>,
 }
 
 impl Default for ApiState {
@@ -133,6 +155,8 @@ impl Default for ApiState {
         ApiState {
             number_of_threads: 0_u8,
             search_directory: None,
+            stats_json: None,
+            render_settings: RenderSettings::default(),
             cur_transform: TransformSet {
                 t: [Transform {
                     m: Matrix4x4 {
@@ -161,6 +185,7 @@ impl Default for ApiState {
             pushed_transforms: Vec::new(),
             pushed_active_transform_bits: Vec::new(),
             param_set: ParamSet::default(),
+            shape_creators: HashMap::new(),
         }
     }
 }
@@ -403,6 +428,35 @@ impl RenderOptions {
                         AOIntegrator::new(cos_sample, n_samples, camera, sampler, pixel_bounds),
                     )));
                     some_integrator = Some(integrator);
+                } else if self.integrator_name == "bvhcost" {
+                    let cost_name: String = self
+                        .integrator_params
+                        .find_one_string("cost", String::from("nodes"));
+                    let cost: TraversalCost = if cost_name == "nodes" {
+                        TraversalCost::NodeVisits
+                    } else if cost_name == "primitives" {
+                        TraversalCost::PrimitiveTests
+                    } else {
+                        println!(
+                            "WARNING: Traversal cost \"{}\" unknown.  Using \"nodes\".",
+                            cost_name
+                        );
+                        TraversalCost::NodeVisits
+                    };
+                    let max_cost: Float = self
+                        .integrator_params
+                        .find_one_float("maxcost", 0.0 as Float);
+                    let counts_filename: String = self
+                        .integrator_params
+                        .find_one_string("countsfilename", String::from("bvhcost.txt"));
+                    let integrator = Box::new(Integrator::BVHCost(BVHCostIntegrator::new(
+                        camera,
+                        sampler,
+                        cost,
+                        max_cost,
+                        counts_filename,
+                    )));
+                    some_integrator = Some(integrator);
                 } else if self.integrator_name == "sppm" {
                     // CreateSPPMIntegrator
                     let mut n_iterations: i32 =
@@ -442,11 +496,27 @@ impl RenderOptions {
         some_integrator
     }
     pub fn make_scene(&self) -> Scene {
-        let some_accelerator = make_accelerator(
-            &self.accelerator_name,
-            &self.primitives,
-            &self.accelerator_params,
-        );
+        // two levels: the top-level accelerator is built over the
+        // (transformed) instances and an accelerator for all other
+        // primitives, so instances can be moved without a rebuild
+        let (instances, mut primitives): (Vec<Arc<Primitive>>, Vec<Arc<Primitive>>) = self
+            .primitives
+            .iter()
+            .cloned()
+            .partition(|primitive| matches!(**primitive, Primitive::Transformed(_)));
+        if !instances.is_empty() && primitives.len() > 1 {
+            if let Some(accelerator) = make_accelerator(
+                &self.accelerator_name,
+                &primitives,
+                &self.accelerator_params,
+            ) {
+                primitives = vec![accelerator];
+            }
+        }
+        let mut top_level: Vec<Arc<Primitive>> = instances;
+        top_level.append(&mut primitives);
+        let some_accelerator =
+            make_accelerator(&self.accelerator_name, &top_level, &self.accelerator_params);
         if let Some(accelerator) = some_accelerator {
             Scene::new(accelerator, self.lights.clone())
         } else {
@@ -663,6 +733,25 @@ fn create_material(api_state: &ApiState, bsdf_state: &mut BsdfState) -> Option<A
                 }
             }
             return None;
+        } else if api_state.graphics_state.material == "indexed" {
+            let names: Vec<String> = mp.material_params.find_string("namedmaterials");
+            if names.is_empty() {
+                panic!("Must provide \"namedmaterials\" with indexed material.");
+            }
+            let mut materials: Vec<Arc<Material>> = Vec::with_capacity(names.len());
+            for name in names {
+                match api_state.graphics_state.named_materials.get(&name) {
+                    Some(Some(named_material)) => materials.push(named_material.clone()),
+                    Some(None) => return None,
+                    None => {
+                        panic!("Material \"{}\" unknown.", name);
+                    }
+                }
+            }
+            let indexed = Arc::new(Material::Indexed(Box::new(IndexedMaterial::new(
+                materials,
+            ))));
+            return Some(indexed);
         } else if api_state.graphics_state.material == "metal" {
             return Some(MetalMaterial::create(&mut mp));
         } else if api_state.graphics_state.material == "substrate" {
@@ -1267,6 +1356,12 @@ fn make_texture(api_state: &mut ApiState) {
             let st = Arc::new(ScaleTexture::<Spectrum>::new(tex1, tex2));
             Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
                 .insert(api_state.param_set.name.clone(), st);
+        } else if api_state.param_set.tex_name == "vertexcolor" {
+            let vt = Arc::new(VertexColorTexture::new(
+                tp.find_spectrum("default", Spectrum::new(1.0)),
+            ));
+            Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
+                .insert(api_state.param_set.name.clone(), vt);
         } else if api_state.param_set.tex_name == "mix" {
             let mt = Arc::new(MixTexture::<Spectrum>::new(
                 tp.get_spectrum_texture("tex1", Spectrum::new(0.0)),
@@ -1793,6 +1888,30 @@ fn get_shapes_and_materials(
         let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
         shapes.push(disk);
         materials.push(mtl);
+    } else if api_state.param_set.name == "points" {
+        let point_shapes: Vec<Arc<Shape>> = create_points_shape(
+            &obj_to_world,
+            &world_to_obj,
+            api_state.graphics_state.reverse_orientation,
+            &api_state.param_set,
+        );
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state);
+        for shape in point_shapes {
+            shapes.push(shape);
+            materials.push(mtl.clone());
+        }
+    } else if api_state.param_set.name == "sdf" {
+        let sdf_shapes: Vec<Arc<Shape>> = create_sdf_shape(
+            &obj_to_world,
+            &world_to_obj,
+            api_state.graphics_state.reverse_orientation,
+            &api_state.param_set,
+        );
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state);
+        for shape in sdf_shapes {
+            shapes.push(shape);
+            materials.push(mtl.clone());
+        }
     } else if api_state.param_set.name == "cone" {
         println!("TODO: CreateConeShape");
     } else if api_state.param_set.name == "paraboloid" {
@@ -1811,9 +1930,33 @@ fn get_shapes_and_materials(
             shapes.push(shape.clone());
             materials.push(mtl.clone());
         }
+    } else if api_state.param_set.name == "bilinearmesh" {
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state);
+        let bilinear_shapes: Vec<Arc<Shape>> = create_bilinear_mesh_shape(
+            &obj_to_world,
+            &world_to_obj,
+            api_state.graphics_state.reverse_orientation,
+            &api_state.param_set,
+        );
+        for shape in bilinear_shapes {
+            shapes.push(shape.clone());
+            materials.push(mtl.clone());
+        }
     } else if api_state.param_set.name == "trianglemesh" {
         let vi = api_state.param_set.find_int("indices");
-        let p = api_state.param_set.find_point3f("P");
+        let mut p = api_state.param_set.find_point3f("P");
+        // vertex-animated meshes provide one set of "P" (and "N") per time sample
+        let times: Vec<Float> = api_state.param_set.find_float("times");
+        let n_samples: usize = times.len().max(1);
+        if n_samples * (p.len() / n_samples) != p.len() {
+            panic!(
+                "trianglemesh with {} \"times\" needs a multiple of {} \"P\" values ({} given)",
+                times.len(),
+                n_samples,
+                p.len()
+            );
+        }
+        let motion_p: Vec<Point3f> = p.split_off(p.len() / n_samples);
         // try "uv" with Point2f
         let mut uvs = api_state.param_set.find_point2f("uv");
         if uvs.is_empty() {
@@ -1853,7 +1996,11 @@ fn get_shapes_and_materials(
                 s_ws.push(obj_to_world.transform_vector(&item));
             }
         }
-        let n = api_state.param_set.find_normal3f("N");
+        let mut n = api_state.param_set.find_normal3f("N");
+        let mut motion_n: Vec<Normal3f> = Vec::new();
+        if n.len() == n_samples * p.len() {
+            motion_n = n.split_off(p.len());
+        }
         let mut n_ws: Vec<Normal3f> = Vec::new();
         if !n.is_empty() {
             assert!(n.len() == p.len());
@@ -1872,6 +2019,15 @@ fn get_shapes_and_materials(
                 );
             }
         }
+        let mut face_indices: Vec<i32> = api_state.param_set.find_int("faceIndices");
+        if !face_indices.is_empty() && face_indices.len() != vi.len() / 3 {
+            println!(
+                "ERROR: Number of face indices, {}, doesn't match number of faces, {}",
+                face_indices.len(),
+                vi.len() / 3
+            );
+            face_indices = Vec::new();
+        }
         // TODO: alpha
         // CreateTriangleMesh
         // transform mesh vertices to world space
@@ -1885,7 +2041,7 @@ fn get_shapes_and_materials(
         for item in &vi {
             vertex_indices.push(*item as u32);
         }
-        let mesh = Arc::new(TriangleMesh::new(
+        let mut mesh = TriangleMesh::new(
             obj_to_world,
             world_to_obj,
             api_state.graphics_state.reverse_orientation,
@@ -1898,14 +2054,23 @@ fn get_shapes_and_materials(
             uvs,
             None,
             None,
-        ));
+            face_indices,
+        );
+        if n_samples > 1 {
+            // transform remaining time samples to world space
+            let motion_p_ws: Vec<Vec<Point3f>> = motion_p
+                .chunks(n_vertices)
+                .map(|ps| ps.iter().map(|p| obj_to_world.transform_point(p)).collect())
+                .collect();
+            let motion_n_ws: Vec<Vec<Normal3f>> = motion_n
+                .chunks(n_vertices)
+                .map(|ns| ns.iter().map(|n| obj_to_world.transform_normal(n)).collect())
+                .collect();
+            mesh.set_motion_samples(times, motion_p_ws, motion_n_ws);
+        }
         let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
-        for id in 0..mesh.n_triangles {
-            let triangle = Arc::new(Shape::Trngl(Triangle::new(
-                mesh.clone(),
-                id.try_into().unwrap(),
-            )));
-            shapes.push(triangle.clone());
+        for triangle in create_triangle_mesh_shapes(mesh, &api_state.param_set) {
+            shapes.push(triangle);
             materials.push(mtl.clone());
         }
     } else if api_state.param_set.name == "plymesh" {
@@ -1919,6 +2084,7 @@ fn get_shapes_and_materials(
                 api_state.graphics_state.float_textures.clone(),
                 // additional parameters:
                 Some(search_directory),
+                bvhcache::cache_directory(&api_state.render_options.accelerator_params).as_deref(),
             );
             for shape in ply_shapes {
                 shapes.push(shape.clone());
@@ -2034,21 +2200,6 @@ fn get_shapes_and_materials(
                 npts
             );
         }
-        // compute NURBS dicing rates
-        let diceu: usize = 30;
-        let dicev: usize = 30;
-        let mut ueval: Vec<Float> = Vec::with_capacity(diceu);
-        let mut veval: Vec<Float> = Vec::with_capacity(dicev);
-        let mut eval_ps: Vec<Point3f> = Vec::with_capacity(diceu * dicev);
-        let mut eval_ns: Vec<Normal3f> = Vec::with_capacity(diceu * dicev);
-        for i in 0..diceu {
-            ueval.push(lerp(i as Float / (diceu - 1) as Float, u0, u1));
-        }
-        for i in 0..dicev {
-            veval.push(lerp(i as Float / (dicev - 1) as Float, v0, v1));
-        }
-        // evaluate NURBS over grid of points
-        let mut uvs: Vec<Point2f> = Vec::with_capacity(diceu * dicev);
         // turn NURBS into triangles
         let mut hom3: Vec<Homogeneous3> = Vec::with_capacity((nu * nv) as usize);
         if is_homogeneous {
@@ -2070,49 +2221,93 @@ fn get_shapes_and_materials(
                 });
             }
         }
-        for veval_item in veval.iter().take(dicev) {
-            for ueval_item in ueval.iter().take(diceu) {
-                uvs.push(Point2f {
-                    x: *ueval_item,
-                    y: *veval_item,
-                });
-                let mut dpdu: Vector3f = Vector3f::default();
-                let mut dpdv: Vector3f = Vector3f::default();
-                let pt: Point3f = nurbs_evaluate_surface(
-                    uorder,
-                    &uknots,
-                    nu,
-                    *ueval_item,
-                    vorder,
-                    &vknots,
-                    nv,
-                    *veval_item,
-                    &hom3,
-                    Some(&mut dpdu),
-                    Some(&mut dpdv),
-                );
-                eval_ps.push(Point3f {
-                    x: pt.x,
-                    y: pt.y,
-                    z: pt.z,
-                });
-                eval_ns.push(Normal3f::from(vec3_cross_vec3(&dpdu, &dpdv).normalize()));
+        // an optional chordal error (in object space) enables adaptive tessellation
+        let chordal_error: Float = api_state.param_set.find_one_float("chordalerror", 0.0);
+        let max_depth: i32 = api_state.param_set.find_one_int("maxdepth", 10);
+        let mut eval_ps: Vec<Point3f>;
+        let mut eval_ns: Vec<Normal3f>;
+        let mut uvs: Vec<Point2f>;
+        let mut vertices: Vec<u32>;
+        if chordal_error > 0.0 as Float {
+            let tess: NurbsTessellation = nurbs_tessellate_adaptive(
+                uorder,
+                &uknots,
+                nu,
+                u0,
+                u1,
+                vorder,
+                &vknots,
+                nv,
+                v0,
+                v1,
+                &hom3,
+                chordal_error,
+                max_depth,
+            );
+            eval_ps = tess.p;
+            eval_ns = tess.n;
+            uvs = tess.uv;
+            vertices = tess.vertex_indices;
+        } else {
+            // compute NURBS dicing rates
+            let diceu: usize = 30;
+            let dicev: usize = 30;
+            eval_ps = Vec::with_capacity(diceu * dicev);
+            eval_ns = Vec::with_capacity(diceu * dicev);
+            uvs = Vec::with_capacity(diceu * dicev);
+            vertices = Vec::with_capacity(6 * (diceu - 1) * (dicev - 1));
+            let mut ueval: Vec<Float> = Vec::with_capacity(diceu);
+            let mut veval: Vec<Float> = Vec::with_capacity(dicev);
+            for i in 0..diceu {
+                ueval.push(lerp(i as Float / (diceu - 1) as Float, u0, u1));
             }
-        }
-        // generate points-polygons mesh
-        let n_tris: usize = 2 * (diceu - 1) * (dicev - 1);
-        let mut vertices: Vec<u32> = Vec::with_capacity(3 * n_tris);
-        // compute the vertex offset numbers for the triangles
-        for v in 0_usize..(dicev - 1) as usize {
-            for u in 0_usize..(diceu - 1) as usize {
-                vertices.push((v * diceu + u).try_into().unwrap());
-                vertices.push((v * diceu + u + 1).try_into().unwrap());
-                vertices.push(((v + 1) * diceu + u + 1).try_into().unwrap());
-                vertices.push((v * diceu + u).try_into().unwrap());
-                vertices.push(((v + 1) * diceu + u + 1).try_into().unwrap());
-                vertices.push(((v + 1) * diceu + u).try_into().unwrap());
+            for i in 0..dicev {
+                veval.push(lerp(i as Float / (dicev - 1) as Float, v0, v1));
+            }
+            // evaluate NURBS over grid of points
+            for veval_item in veval.iter().take(dicev) {
+                for ueval_item in ueval.iter().take(diceu) {
+                    uvs.push(Point2f {
+                        x: *ueval_item,
+                        y: *veval_item,
+                    });
+                    let mut dpdu: Vector3f = Vector3f::default();
+                    let mut dpdv: Vector3f = Vector3f::default();
+                    let pt: Point3f = nurbs_evaluate_surface(
+                        uorder,
+                        &uknots,
+                        nu,
+                        *ueval_item,
+                        vorder,
+                        &vknots,
+                        nv,
+                        *veval_item,
+                        &hom3,
+                        Some(&mut dpdu),
+                        Some(&mut dpdv),
+                    );
+                    eval_ps.push(Point3f {
+                        x: pt.x,
+                        y: pt.y,
+                        z: pt.z,
+                    });
+                    eval_ns.push(Normal3f::from(vec3_cross_vec3(&dpdu, &dpdv).normalize()));
+                }
+            }
+            // generate points-polygons mesh
+            // compute the vertex offset numbers for the triangles
+            for v in 0_usize..(dicev - 1) as usize {
+                for u in 0_usize..(diceu - 1) as usize {
+                    vertices.push((v * diceu + u).try_into().unwrap());
+                    vertices.push((v * diceu + u + 1).try_into().unwrap());
+                    vertices.push(((v + 1) * diceu + u + 1).try_into().unwrap());
+                    vertices.push((v * diceu + u).try_into().unwrap());
+                    vertices.push(((v + 1) * diceu + u + 1).try_into().unwrap());
+                    vertices.push(((v + 1) * diceu + u).try_into().unwrap());
+                }
             }
         }
+        let n_tris: usize = vertices.len() / 3;
         // transform mesh vertices to world space
         let mut p_ws: Vec<Point3f> = Vec::new();
         let n_vertices: usize = eval_ps.len();
@@ -2138,6 +2333,7 @@ fn get_shapes_and_materials(
             uvs,
             None,
             None,
+            Vec::new(),
         ));
         let mtl: Option<Arc<Material>> = create_material(&api_state, bsdf_state);
         for id in 0..mesh.n_triangles {
@@ -2148,6 +2344,17 @@ fn get_shapes_and_materials(
             shapes.push(triangle.clone());
             materials.push(mtl.clone());
         }
+    } else if let Some(creator) = api_state.shape_creators.get(&api_state.param_set.name) {
+        let mtl: Option<Arc<Material>> = create_material(api_state, bsdf_state);
+        for shape in creator(
+            &obj_to_world,
+            &world_to_obj,
+            api_state.graphics_state.reverse_orientation,
+            &api_state.param_set,
+        ) {
+            shapes.push(shape);
+            materials.push(mtl.clone());
+        }
     } else {
         panic!("Shape \"{}\" unknown.", api_state.param_set.name);
     }
@@ -2264,14 +2471,83 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
     // MakeIntegrator
     let some_integrator: Option<Box<Integrator>> = api_state.render_options.make_integrator();
     if let Some(mut integrator) = some_integrator {
-        let scene = api_state.render_options.make_scene();
+        profiler::init_profiler();
+        let scene = {
+            let _p = ProfilePhase::new(Prof::SceneConstruction);
+            api_state.render_options.make_scene()
+        };
         let num_threads: u8 = api_state.number_of_threads;
-        integrator.render(&scene, num_threads);
+        let settings: RenderSettings = get_render_settings(api_state);
+        integrator.render_with_settings(&scene, num_threads, &settings);
+        profiler::cleanup_profiler();
+        // the worker threads reported theirs when they exited
+        stats::report_thread_stats();
+        stats::print_stats(&mut std::io::stdout()).unwrap();
+        profiler::report_profiler_results(&mut std::io::stdout()).unwrap();
+        profiler::clear_profiler();
+        if let Some(ref filename) = api_state.stats_json {
+            println!("Writing statistics {:?}", filename);
+            if let Err(e) = std::fs::write(filename, stats::stats_json()) {
+                println!("ERROR: Unable to write statistics to {:?}: {}", filename, e);
+            }
+        }
+        stats::clear_stats();
+    } else {
+        panic!("Unable to create integrator.");
+    }
+}
+
+/// Like **pbrt_cleanup()**, but renders the scene on background
+/// threads and returns immediately. The *on_tile* callback is called
+/// for each tile merged into the film. Fails for integrators which
+/// a **RenderSession** doesn't support.
+pub fn pbrt_render_session(
+    api_state: &ApiState,
+    on_tile: Option<TileCallback>,
+) -> Result<RenderSession, String> {
+    assert!(
+        api_state.pushed_graphics_states.is_empty(),
+        "Missing end to pbrtAttributeBegin()"
+    );
+    assert!(
+        api_state.pushed_transforms.is_empty(),
+        "Missing end to pbrtTransformBegin()"
+    );
+    let some_integrator: Option<Box<Integrator>> = api_state.render_options.make_integrator();
+    if let Some(integrator) = some_integrator {
+        let scene: Scene = api_state.render_options.make_scene();
+        RenderSession::new(
+            scene,
+            integrator,
+            api_state.number_of_threads,
+            &get_render_settings(api_state),
+            on_tile,
+        )
     } else {
         panic!("Unable to create integrator.");
     }
 }
 
+fn get_render_settings(api_state: &ApiState) -> RenderSettings {
+    let mut settings: RenderSettings = api_state.render_settings.clone();
+    if let Some(ref mut progressive) = settings.progressive {
+        // like SPPM, write an image every n passes
+        progressive.write_frequency = api_state
+            .render_options
+            .integrator_params
+            .find_one_int("imagewritefrequency", progressive.write_frequency)
+            .max(1);
+    }
+    settings
+}
+
+/// Makes `Shape "name" ...` in the scene description call *creator*,
+/// which usually returns _Shape::Custom_ shapes. Built-in shapes
+/// can't be replaced.
+pub fn pbrt_register_shape(api_state: &mut ApiState, name: &str, creator: CustomShapeCreator) {
+    api_state.shape_creators.insert(String::from(name), creator);
+}
+
 pub fn pbrt_translate(api_state: &mut ApiState, dx: Float, dy: Float, dz: Float) {
     // println!("Translate {} {} {}", dx, dy, dz);
     let translate: Transform = Transform::translate(&Vector3f {
@@ -2696,8 +2972,38 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
     // collect area lights
     let mut prims: Vec<Arc<Primitive>> = Vec::new();
     let mut area_lights: Vec<Arc<Light>> = Vec::new();
-    // possibly create area light for shape (see pbrtShape())
-    if api_state.graphics_state.area_light != String::new() {
+    // signed distance fields can't be sampled (no area either)
+    let area_light_supported: bool = api_state.param_set.name != "sdf";
+    if !area_light_supported && api_state.graphics_state.area_light != String::new() {
+        println!(
+            "ERROR: Area lights not supported with \"{}\" shapes, ignoring the area light",
+            api_state.param_set.name
+        );
+    }
+    // deforming meshes are sampled at the time of the reference
+    // point, but emit rays and report their power at the first time
+    if api_state.graphics_state.area_light != String::new()
+        && (api_state.param_set.find_float("times").len() > 1
+            || !api_state
+                .param_set
+                .find_string("motionfilenames")
+                .is_empty())
+    {
+        println!(
+            "WARNING: Area light on deforming \"{}\" uses the first time sample for light power and emitted rays",
+            api_state.param_set.name
+        );
+    }
+    if api_state.param_set.name == "csg" {
+        // combine previously defined objects (see ObjectBegin)
+        if api_state.graphics_state.area_light != String::new() {
+            println!("WARNING: Area lights not supported with CSG shapes");
+        }
+        if let Some(csg) = create_csg_primitive(api_state) {
+            prims.push(csg);
+        }
+    } else if area_light_supported && api_state.graphics_state.area_light != String::new() {
+        // possibly create area light for shape (see pbrtShape())
         // MakeAreaLight
         if api_state.graphics_state.area_light == "area"
             || api_state.graphics_state.area_light == "diffuse"
@@ -2781,6 +3087,7 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
                     prims.clone(),
                     4,
                     SplitMethod::SAH,
+                    2,
                 ))));
                 prims.clear();
                 prims.push(bvh);
@@ -2819,6 +3126,82 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
     }
 }
 
+/// Creates a CSG primitive from objects defined via ObjectBegin, e.g.:
+///
+/// Shape "csg" "string operation" "difference" "string objects" [ "A" "B" ]
+///
+/// More than two objects are combined from left to right.
+fn create_csg_primitive(api_state: &ApiState) -> Option<Arc<Primitive>> {
+    let operation_name: String = api_state
+        .param_set
+        .find_one_string("operation", String::from("union"));
+    let operation: CSGOperation = if operation_name == "union" {
+        CSGOperation::Union
+    } else if operation_name == "intersection" {
+        CSGOperation::Intersection
+    } else if operation_name == "difference" {
+        CSGOperation::Difference
+    } else {
+        println!(
+            "ERROR: CSG operation \"{}\" unknown. Ignoring shape.",
+            operation_name
+        );
+        return None;
+    };
+    let names: Vec<String> = api_state.param_set.find_string("objects");
+    if names.len() < 2 {
+        println!("ERROR: CSG shape needs at least two \"objects\". Ignoring shape.");
+        return None;
+    }
+    let mut operands: Vec<Arc<Primitive>> = Vec::with_capacity(names.len());
+    for name in &names {
+        if let Some(instance_vec) = api_state.render_options.instances.get(name) {
+            if instance_vec.is_empty() {
+                println!("ERROR: CSG object {:?} is empty. Ignoring shape.", name);
+                return None;
+            } else if instance_vec.len() == 1 {
+                operands.push(instance_vec[0].clone());
+            } else {
+                operands.push(Arc::new(Primitive::BVH(Box::new(BVHAccel::new(
+                    instance_vec.clone(),
+                    4,
+                    SplitMethod::SAH,
+                    2,
+                )))));
+            }
+        } else {
+            println!(
+                "ERROR: Unable to find instance named {:?} for CSG shape",
+                name
+            );
+            return None;
+        }
+    }
+    let mut operands = operands.into_iter();
+    let mut csg: Arc<Primitive> = operands.next().unwrap();
+    for operand in operands {
+        csg = Arc::new(Primitive::CSG(Box::new(CSGPrimitive::new(
+            csg, operand, operation,
+        ))));
+    }
+    // place the result like an object instance
+    let t0: &Transform = &api_state.cur_transform.t[0];
+    let t1: &Transform = &api_state.cur_transform.t[1];
+    if !t0.is_identity() || !t1.is_identity() {
+        let animated_instance_to_world: AnimatedTransform = AnimatedTransform::new(
+            t0,
+            api_state.render_options.transform_start_time,
+            t1,
+            api_state.render_options.transform_end_time,
+        );
+        csg = Arc::new(Primitive::Transformed(Box::new(TransformedPrimitive::new(
+            csg,
+            animated_instance_to_world,
+        ))));
+    }
+    Some(csg)
+}
+
 // Attempt to determine if the ParamSet for a shape may provide a value for
 // its material's parameters. Unfortunately, materials don't provide an
 // explicit representation of their parameters that we can query and
@@ -2937,40 +3320,26 @@ pub fn pbrt_object_instance(api_state: &mut ApiState, params: ParamSet) {
         if instance_vec.is_empty() {
             return;
         }
-        // TODO: ++nObjectInstancesUsed;
+        stats::add_counter(Counter::ObjectInstancesUsed, 1);
         if instance_vec.len() > 1_usize {
             // create aggregate for instance _Primitive_s
             if api_state.render_options.accelerator_name == "bvh" {
                 //  CreateBVHAccelerator
-                let split_method_name: String = api_state
-                    .render_options
-                    .accelerator_params
-                    .find_one_string("splitmethod", String::from("sah"));
-                let split_method;
-                if split_method_name == "sah" {
-                    split_method = SplitMethod::SAH;
-                } else if split_method_name == "hlbvh" {
-                    split_method = SplitMethod::HLBVH;
-                } else if split_method_name == "middle" {
-                    split_method = SplitMethod::Middle;
-                } else if split_method_name == "equal" {
-                    split_method = SplitMethod::EqualCounts;
-                } else {
-                    println!(
-                        "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
-                        split_method_name
-                    );
-                    split_method = SplitMethod::SAH;
-                }
+                let split_method: SplitMethod = BVHAccel::split_method_from_params(
+                    &api_state.render_options.accelerator_params,
+                );
                 let max_prims_in_node: i32 = api_state
                     .render_options
                     .accelerator_params
                     .find_one_int("maxnodeprims", 4);
+                let width: i32 =
+                    BVHAccel::width_from_params(&api_state.render_options.accelerator_params);
                 let accelerator: Arc<Primitive> =
                     Arc::new(Primitive::BVH(Box::new(BVHAccel::new(
                         instance_vec.clone(),
                         max_prims_in_node as usize,
                         split_method,
+                        width as usize,
                     ))));
                 instance_vec.clear();
                 instance_vec.push(accelerator);
@@ -2978,7 +3347,7 @@ pub fn pbrt_object_instance(api_state: &mut ApiState, params: ParamSet) {
                 // println!("TODO: CreateKdTreeAccelerator");
                 // WARNING: Use BVHAccel for now !!!
                 let accelerator: Arc<Primitive> = Arc::new(Primitive::BVH(Box::new(
-                    BVHAccel::new(instance_vec.clone(), 4, SplitMethod::SAH),
+                    BVHAccel::new(instance_vec.clone(), 4, SplitMethod::SAH, 2),
                 )));
                 instance_vec.clear();
                 instance_vec.push(accelerator);
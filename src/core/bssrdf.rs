@@ -23,6 +23,7 @@ use crate::core::medium::phase_hg;
 use crate::core::pbrt::clamp_t;
 use crate::core::pbrt::INV_4_PI;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::reflection::{cos_theta, fr_dielectric};
 use crate::core::reflection::{Bsdf, Bxdf, BxdfType};
 use crate::core::scene::Scene;
@@ -145,7 +146,7 @@ impl TabulatedBssrdf {
         pi: &mut SurfaceInteraction,
         pdf: &mut Float,
     ) -> Spectrum {
-        // ProfilePhase pp(Prof::BSSRDFEvaluation);
+        let _p = ProfilePhase::new(Prof::BSSRDFEvaluation);
         let mut u1: Float = u1; // shadowing input parameter
 
         // choose projection axis for BSSRDF sampling
@@ -404,7 +405,7 @@ impl TabulatedBssrdf {
     }
     // Bssrdf
     pub fn s(&self, pi: &SurfaceInteraction, wi: &Vector3f) -> Spectrum {
-        // ProfilePhase pp(Prof::BSSRDFEvaluation);
+        let _p = ProfilePhase::new(Prof::BSSRDFEvaluation);
         let ft: Float = fr_dielectric(cos_theta(&self.po_wo), 1.0 as Float, self.eta);
         self.sp(pi) * self.sw(wi) * (1.0 as Float - ft)
     }
@@ -420,7 +421,7 @@ impl TabulatedBssrdf {
         u2: Point2f,
         pdf: &mut Float,
     ) -> (Spectrum, Option<SurfaceInteraction>) {
-        // ProfilePhase pp(Prof::BSSRDFSampling);
+        let _p = ProfilePhase::new(Prof::BSSRDFSampling);
         let mut si: SurfaceInteraction = SurfaceInteraction::default();
         let sp: Spectrum = self.sample_sp(scene, u1, u2, &mut si, pdf);
         if !sp.is_black() {
@@ -0,0 +1,199 @@
+//! Checkpoints allow long renders to be continued after the process
+//! was interrupted. While rendering, the film's pixel sums (including
+//! splats) are written to a file periodically, together with the
+//! number of samples per pixel each image tile has completed. A
+//! render which resumes from such a file restores the film and only
+//! renders the samples which are missing.
+//!
+//! Tiles are always rendered completely, so a tile either has all its
+//! samples or none. Resuming with a larger _"integer pixelsamples"_
+//! adds the missing samples to all tiles, though. The sampler of a
+//! resumed tile is seeded like in an uninterrupted render and skips
+//! the sample indices which were used already, so the resumed render
+//! gives the same image.
+
+// std
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+// pbrt
+use crate::core::film::Film;
+use crate::core::geometry::Bounds2i;
+use crate::core::pbrt::Float;
+
+const MAGIC: &[u8; 8] = b"rs_pbrtC";
+const VERSION: u32 = 1;
+
+/// Where and how often checkpoints are written.
+#[derive(Debug, Clone)]
+pub struct CheckpointOptions {
+    pub filename: PathBuf,
+    /// continue the render stored in *filename* (if it exists)
+    pub resume: bool,
+    /// minimum time between two checkpoints
+    pub interval: Duration,
+}
+
+impl CheckpointOptions {
+    pub fn new(filename: PathBuf, resume: bool) -> Self {
+        CheckpointOptions {
+            filename,
+            resume,
+            interval: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Keeps track of the completed tiles of a render and writes
+/// checkpoints. Only the thread merging the tiles into the **Film**
+/// should use it, so the film and the tile counts stay consistent.
+pub struct Checkpointer<'a> {
+    options: &'a CheckpointOptions,
+    film: &'a Film,
+    /// samples per pixel rendered so far, per tile
+    tile_samples: Vec<i64>,
+    last_write: Instant,
+}
+
+impl<'a> Checkpointer<'a> {
+    /// Restores the film from the checkpoint file when resuming.
+    pub fn new(options: &'a CheckpointOptions, film: &'a Film, n_tiles: usize) -> Self {
+        let mut tile_samples: Vec<i64> = vec![0_i64; n_tiles];
+        if options.resume {
+            match read_checkpoint(&options.filename, film, n_tiles) {
+                Ok(samples) => {
+                    println!("Resuming from checkpoint {:?} ...", options.filename);
+                    tile_samples = samples;
+                }
+                Err(e) => println!(
+                    "WARNING: Unable to resume from checkpoint {:?}: {}",
+                    options.filename, e
+                ),
+            }
+        }
+        Checkpointer {
+            options,
+            film,
+            tile_samples,
+            last_write: Instant::now(),
+        }
+    }
+    /// Samples per pixel each tile has completed.
+    pub fn tile_samples(&self) -> &[i64] {
+        &self.tile_samples
+    }
+    /// Records that the tile *tile_index* has *samples_per_pixel*
+    /// samples now (after it was merged into the film) and writes a
+    /// checkpoint if the last one is old enough.
+    pub fn tile_done(&mut self, tile_index: usize, samples_per_pixel: i64) {
+        self.tile_samples[tile_index] = samples_per_pixel;
+        if self.last_write.elapsed() >= self.options.interval {
+            self.write();
+        }
+    }
+    pub fn write(&mut self) {
+        if let Err(e) = write_checkpoint(&self.options.filename, self.film, &self.tile_samples) {
+            println!(
+                "WARNING: Unable to write checkpoint {:?}: {}",
+                self.options.filename, e
+            );
+        }
+        self.last_write = Instant::now();
+    }
+}
+
+fn bounds_values(bounds: &Bounds2i) -> [i32; 4] {
+    [
+        bounds.p_min.x,
+        bounds.p_min.y,
+        bounds.p_max.x,
+        bounds.p_max.y,
+    ]
+}
+
+fn write_checkpoint(path: &Path, film: &Film, tile_samples: &[i64]) -> std::io::Result<()> {
+    // write to a temporary file first, an interrupted write must not
+    // destroy the previous checkpoint
+    let tmp_path: PathBuf = path.with_extension("tmp");
+    let data: Vec<Float> = film.get_pixel_data();
+    let result = File::create(&tmp_path).and_then(|file| {
+        let mut writer: BufWriter<File> = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        for v in &bounds_values(&film.cropped_pixel_bounds) {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        writer.write_all(&(tile_samples.len() as u64).to_le_bytes())?;
+        for samples in tile_samples {
+            writer.write_all(&samples.to_le_bytes())?;
+        }
+        writer.write_all(&(data.len() as u64).to_le_bytes())?;
+        for v in &data {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp_path, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn read_checkpoint(path: &Path, film: &Film, n_tiles: usize) -> Result<Vec<i64>, String> {
+    let bytes: Vec<u8> = fs::read(path).map_err(|e| e.to_string())?;
+    let mut reader: CheckpointReader = CheckpointReader {
+        bytes: &bytes,
+        offset: 0,
+    };
+    if reader.read_bytes(MAGIC.len())? != MAGIC {
+        return Err(String::from("not a checkpoint file"));
+    }
+    if u32::from_le_bytes(reader.read_array()?) != VERSION {
+        return Err(String::from("unsupported checkpoint version"));
+    }
+    for v in &bounds_values(&film.cropped_pixel_bounds) {
+        if i32::from_le_bytes(reader.read_array()?) != *v {
+            return Err(String::from("the image size or crop window changed"));
+        }
+    }
+    if u64::from_le_bytes(reader.read_array()?) != n_tiles as u64 {
+        return Err(String::from("the number of tiles changed"));
+    }
+    let mut tile_samples: Vec<i64> = Vec::with_capacity(n_tiles);
+    for _ in 0..n_tiles {
+        tile_samples.push(i64::from_le_bytes(reader.read_array()?));
+    }
+    let n_values: usize = u64::from_le_bytes(reader.read_array()?) as usize;
+    let mut data: Vec<Float> = Vec::with_capacity(n_values.min(bytes.len()));
+    for _ in 0..n_values {
+        data.push(Float::from_le_bytes(reader.read_array()?));
+    }
+    if !film.set_pixel_data(&data) {
+        return Err(String::from("the number of pixels changed"));
+    }
+    Ok(tile_samples)
+}
+
+struct CheckpointReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> CheckpointReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes: &[u8] = self
+            .bytes
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| String::from("checkpoint file is truncated"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array: [u8; N] = [0_u8; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+}
@@ -0,0 +1,525 @@
+//! Distributes the image tiles of a render over several processes,
+//! possibly on other machines. A coordinator hands out the tiles of a
+//! **BlockQueue** to worker processes, which load the same scene,
+//! render the tiles and send the resulting **FilmTile** pixels back.
+//! The coordinator merges them into its **Film** and writes the
+//! image once all tiles are done.
+//!
+//! Each worker opens one TCP connection per thread and the messages
+//! on a connection are (all numbers little-endian):
+//!
+//! - worker: `HELLO` (magic, version, sample bounds and samples per
+//!   pixel, so a worker with another scene is rejected)
+//! - coordinator: `TILE x y` (or `DONE` if there is nothing left)
+//! - worker: `HEARTBEAT` every **HEARTBEAT_INTERVAL** while it
+//!   renders the tile
+//! - worker: `RESULT x y n` followed by _n_ floats (see
+//!   **FilmTile::get_pixel_data()**), then it waits for the next
+//!   `TILE`
+//!
+//! Tiles of workers which disconnect (or don't send anything within
+//! **DistributedOptions::worker_timeout**) are handed out again, so
+//! slow tiles are fine as long as the worker is alive. A tile which
+//! gets lost more than **MAX_TILE_REISSUES** times aborts the render.
+//! Tiles are seeded like in a local render, so the image doesn't
+//! depend on which worker rendered which tile.
+
+// std
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+// pbrt
+use crate::blockqueue::BlockQueue;
+use crate::core::film::{Film, FilmTile};
+use crate::core::geometry::{Bounds2i, Point2i, Vector2i};
+use crate::core::integrator::{get_tile_bounds, SamplerIntegrator, TILE_SIZE};
+use crate::core::pbrt::Float;
+use crate::core::progressreporter::ProgressReporter;
+use crate::core::sampler::Sampler;
+use crate::core::scene::Scene;
+
+const MAGIC: &[u8; 8] = b"rs_pbrtD";
+const VERSION: u32 = 2;
+const MSG_TILE: u32 = 1;
+const MSG_DONE: u32 = 2;
+const MSG_HEARTBEAT: u32 = 3;
+const MSG_RESULT: u32 = 4;
+/// How often workers tell the coordinator they are still rendering a
+/// tile (well below the smallest worker timeout of a second).
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+/// How often a tile is handed out again before the coordinator gives
+/// up (e.g. because every worker crashes on it).
+const MAX_TILE_REISSUES: u32 = 5;
+/// How long workers try to reach a coordinator which is still
+/// loading the scene.
+const CONNECT_ATTEMPTS: u32 = 600;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The part a process plays in a distributed render.
+#[derive(Debug, Clone)]
+pub enum DistributedRole {
+    /// listens on the address and merges the tiles of the workers
+    Coordinator,
+    /// connects to the coordinator at the address
+    Worker,
+}
+
+#[derive(Debug, Clone)]
+pub struct DistributedOptions {
+    pub role: DistributedRole,
+    /// e.g. "0.0.0.0:7878" (coordinator) or "render01:7878" (worker)
+    pub address: String,
+    /// how long the coordinator waits for a message of a worker (see
+    /// **HEARTBEAT_INTERVAL**) before it drops the worker and hands
+    /// its tile to another one
+    pub worker_timeout: Duration,
+}
+
+/// The tile layout and sample count, which coordinator and workers
+/// have to agree on.
+struct TileGrid {
+    sample_bounds: Bounds2i,
+    n_tiles: Point2i,
+    samples_per_pixel: i64,
+}
+
+impl TileGrid {
+    fn new(integrator: &SamplerIntegrator, film: &Film) -> Self {
+        let sample_bounds: Bounds2i = film.get_sample_bounds();
+        let sample_extent: Vector2i = sample_bounds.diagonal();
+        TileGrid {
+            sample_bounds,
+            n_tiles: Point2i {
+                x: (sample_extent.x + TILE_SIZE - 1) / TILE_SIZE,
+                y: (sample_extent.y + TILE_SIZE - 1) / TILE_SIZE,
+            },
+            samples_per_pixel: integrator.get_sampler().get_samples_per_pixel(),
+        }
+    }
+    fn n_tiles_total(&self) -> usize {
+        (self.n_tiles.x * self.n_tiles.y) as usize
+    }
+    fn tile(&self, x: u32, y: u32) -> Option<Point2i> {
+        if (x as i32) < self.n_tiles.x && (y as i32) < self.n_tiles.y {
+            Some(Point2i {
+                x: x as i32,
+                y: y as i32,
+            })
+        } else {
+            None
+        }
+    }
+    fn tile_bounds(&self, tile: Point2i) -> Bounds2i {
+        get_tile_bounds(&self.sample_bounds, tile, TILE_SIZE)
+    }
+    fn bounds_values(&self) -> [i32; 4] {
+        [
+            self.sample_bounds.p_min.x,
+            self.sample_bounds.p_min.y,
+            self.sample_bounds.p_max.x,
+            self.sample_bounds.p_max.y,
+        ]
+    }
+    fn write_hello(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let mut msg: Vec<u8> = Vec::new();
+        msg.extend_from_slice(MAGIC);
+        msg.extend_from_slice(&VERSION.to_le_bytes());
+        for v in &self.bounds_values() {
+            msg.extend_from_slice(&v.to_le_bytes());
+        }
+        msg.extend_from_slice(&self.samples_per_pixel.to_le_bytes());
+        stream.write_all(&msg)
+    }
+    /// Returns false if the worker renders another scene (or speaks
+    /// another protocol).
+    fn read_hello(&self, stream: &mut TcpStream) -> std::io::Result<bool> {
+        let mut magic: [u8; 8] = [0_u8; 8];
+        stream.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(stream)? != VERSION {
+            return Ok(false);
+        }
+        for v in &self.bounds_values() {
+            if read_i32(stream)? != *v {
+                return Ok(false);
+            }
+        }
+        Ok(read_i64(stream)? == self.samples_per_pixel)
+    }
+}
+
+/// Waits for workers on *address* and merges the tiles they render
+/// into the film, returns once all tiles are done (or false if
+/// *address* can't be used or a tile got lost too often).
+pub fn render_coordinator(
+    integrator: &SamplerIntegrator,
+    address: &str,
+    worker_timeout: Duration,
+) -> bool {
+    let film = integrator.get_camera().get_film();
+    let grid: TileGrid = TileGrid::new(integrator, &film);
+    let listener: TcpListener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("ERROR: Unable to listen on {:?}: {}", address, e);
+            return false;
+        }
+    };
+    // poll, so we can stop accepting workers once all tiles are done
+    listener
+        .set_nonblocking(true)
+        .expect("Failed to configure listener");
+    match listener.local_addr() {
+        Ok(local_addr) => println!("Waiting for workers on {} ...", local_addr),
+        Err(_) => println!("Waiting for workers on {:?} ...", address),
+    }
+    let block_queue = BlockQueue::new(
+        (
+            (grid.n_tiles.x * TILE_SIZE) as u32,
+            (grid.n_tiles.y * TILE_SIZE) as u32,
+        ),
+        (TILE_SIZE as u32, TILE_SIZE as u32),
+        (0, 0),
+    );
+    let reporter: ProgressReporter =
+        ProgressReporter::new(grid.n_tiles_total() as u64, "Rendering");
+    let coordinator: Coordinator = Coordinator {
+        grid: &grid,
+        film: &film,
+        block_queue: &block_queue,
+        lost_tiles: Mutex::new(Vec::new()),
+        n_reissues: Mutex::new(vec![0_u32; grid.n_tiles_total()]),
+        failed: AtomicBool::new(false),
+        worker_timeout,
+        n_merged: AtomicUsize::new(0),
+        done: AtomicBool::new(false),
+        reporter: &reporter,
+    };
+    let coordinator = &coordinator;
+    crossbeam::scope(|scope| {
+        while !coordinator.done.load(Ordering::Acquire) {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    scope.spawn(move |_| {
+                        if let Err(e) = coordinator.serve(stream) {
+                            println!("WARNING: Lost worker {}: {}", peer, e);
+                        }
+                    });
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                Err(e) => println!("WARNING: Unable to accept worker: {}", e),
+            }
+        }
+    })
+    .unwrap();
+    reporter.done();
+    !coordinator.failed.load(Ordering::Acquire)
+}
+
+struct Coordinator<'a> {
+    grid: &'a TileGrid,
+    film: &'a Film,
+    block_queue: &'a BlockQueue,
+    /// tiles of workers which disconnected, handed out first
+    lost_tiles: Mutex<Vec<(u32, u32)>>,
+    /// how often each tile was lost
+    n_reissues: Mutex<Vec<u32>>,
+    /// set (together with _done_) if a tile got lost too often
+    failed: AtomicBool,
+    worker_timeout: Duration,
+    n_merged: AtomicUsize,
+    done: AtomicBool,
+    reporter: &'a ProgressReporter,
+}
+
+impl<'a> Coordinator<'a> {
+    fn serve(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        // a stalled worker would keep its tile forever, a busy one
+        // sends heartbeats
+        stream.set_read_timeout(Some(self.worker_timeout))?;
+        if !self.grid.read_hello(&mut stream)? {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "worker renders another scene",
+            ));
+        }
+        while let Some((x, y)) = self.next_tile() {
+            if let Err(e) = self.render_tile(&mut stream, x, y) {
+                self.lose_tile(x, y);
+                return Err(match e.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut => std::io::Error::new(
+                        ErrorKind::TimedOut,
+                        format!("no result for tile ({}, {}) in time", x, y),
+                    ),
+                    _ => e,
+                });
+            }
+        }
+        stream.write_all(&MSG_DONE.to_le_bytes())
+    }
+    /// Hands the tile out again, unless it got lost too often.
+    fn lose_tile(&self, x: u32, y: u32) {
+        let tile_index: usize = (y as i32 * self.grid.n_tiles.x + x as i32) as usize;
+        let mut n_reissues = self.n_reissues.lock().unwrap();
+        n_reissues[tile_index] += 1;
+        if n_reissues[tile_index] > MAX_TILE_REISSUES {
+            println!(
+                "ERROR: Tile ({}, {}) got lost {} times, giving up",
+                x, y, n_reissues[tile_index]
+            );
+            self.failed.store(true, Ordering::Release);
+            self.done.store(true, Ordering::Release);
+        } else {
+            self.lost_tiles.lock().unwrap().push((x, y));
+        }
+    }
+    /// Waits while other workers might still lose tiles, returns
+    /// _None_ once all tiles are merged.
+    fn next_tile(&self) -> Option<(u32, u32)> {
+        loop {
+            if let Some(tile) = self.lost_tiles.lock().unwrap().pop() {
+                return Some(tile);
+            }
+            if let Some(tile) = self.block_queue.next() {
+                return Some(tile);
+            }
+            if self.done.load(Ordering::Acquire) {
+                return None;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+    fn render_tile(&self, stream: &mut TcpStream, x: u32, y: u32) -> std::io::Result<()> {
+        let mut msg: Vec<u8> = Vec::with_capacity(12);
+        msg.extend_from_slice(&MSG_TILE.to_le_bytes());
+        msg.extend_from_slice(&x.to_le_bytes());
+        msg.extend_from_slice(&y.to_le_bytes());
+        stream.write_all(&msg)?;
+        // the worker renders the tile ...
+        loop {
+            match read_u32(stream)? {
+                MSG_HEARTBEAT => {}
+                MSG_RESULT => break,
+                _ => return Err(invalid_data("unknown message")),
+            }
+        }
+        if read_u32(stream)? != x || read_u32(stream)? != y {
+            return Err(invalid_data("result for another tile"));
+        }
+        let tile: Point2i = self.grid.tile(x, y).unwrap();
+        let mut film_tile: FilmTile = self.film.get_film_tile(&self.grid.tile_bounds(tile));
+        let n: usize = read_u64(stream)? as usize;
+        let expected: usize = film_tile.get_pixel_data().len();
+        if n != expected {
+            return Err(invalid_data("wrong number of pixels"));
+        }
+        let mut bytes: Vec<u8> = vec![0_u8; n * std::mem::size_of::<Float>()];
+        stream.read_exact(&mut bytes)?;
+        let data: Vec<Float> = bytes
+            .chunks_exact(std::mem::size_of::<Float>())
+            .map(|chunk| {
+                let mut word: [u8; std::mem::size_of::<Float>()] =
+                    [0_u8; std::mem::size_of::<Float>()];
+                word.copy_from_slice(chunk);
+                Float::from_le_bytes(word)
+            })
+            .collect();
+        film_tile.set_pixel_data(&data);
+        // merge image tile into _Film_
+        self.film.merge_film_tile(&film_tile);
+        self.reporter.update(1);
+        if self.n_merged.fetch_add(1, Ordering::AcqRel) + 1 == self.grid.n_tiles_total() {
+            self.done.store(true, Ordering::Release);
+        }
+        Ok(())
+    }
+}
+
+/// Connects to the coordinator at *address* with one connection per
+/// thread and renders the tiles it hands out, returns once the
+/// coordinator has no tiles left.
+pub fn render_worker(
+    integrator: &SamplerIntegrator,
+    scene: &Scene,
+    address: &str,
+    num_threads: u8,
+) {
+    let film = integrator.get_camera().get_film();
+    let grid: TileGrid = TileGrid::new(integrator, &film);
+    let num_cores = if num_threads == 0_u8 {
+        num_cpus::get()
+    } else {
+        num_threads as usize
+    };
+    println!(
+        "Rendering tiles for {} with {:?} thread(s) ...",
+        address, num_cores
+    );
+    let worker: Worker = Worker {
+        integrator,
+        scene,
+        film: &film,
+        grid: &grid,
+        n_rendered: AtomicUsize::new(0),
+    };
+    let worker = &worker;
+    crossbeam::scope(|scope| {
+        for _ in 0..num_cores {
+            scope.spawn(move |_| {
+                if let Err(e) = connect(address).and_then(|stream| worker.work(stream)) {
+                    println!("WARNING: Connection to {} failed: {}", address, e);
+                }
+            });
+        }
+    })
+    .unwrap();
+    println!(
+        "Rendered {} tile(s)",
+        worker.n_rendered.load(Ordering::Acquire)
+    );
+}
+
+fn connect(address: &str) -> std::io::Result<TcpStream> {
+    let mut attempt: u32 = 0;
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => {
+                attempt += 1;
+                if attempt >= CONNECT_ATTEMPTS {
+                    return Err(e);
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+struct Worker<'a> {
+    integrator: &'a SamplerIntegrator,
+    scene: &'a Scene,
+    film: &'a Film,
+    grid: &'a TileGrid,
+    n_rendered: AtomicUsize,
+}
+
+impl<'a> Worker<'a> {
+    fn work(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(true)?;
+        self.grid.write_hello(&mut stream)?;
+        // heartbeats and results are written from two threads
+        let writer: Mutex<TcpStream> = Mutex::new(stream.try_clone()?);
+        let rendering: AtomicBool = AtomicBool::new(false);
+        let finished: AtomicBool = AtomicBool::new(false);
+        crossbeam::scope(|scope| {
+            scope.spawn(|_| send_heartbeats(&writer, &rendering, &finished));
+            let result = self.render_tiles(&mut stream, &writer, &rendering);
+            finished.store(true, Ordering::Release);
+            result
+        })
+        .unwrap()
+    }
+    /// Renders the tiles the coordinator hands out, *rendering* is set
+    /// while a tile is being rendered.
+    fn render_tiles(
+        &self,
+        stream: &mut TcpStream,
+        writer: &Mutex<TcpStream>,
+        rendering: &AtomicBool,
+    ) -> std::io::Result<()> {
+        let grid: &TileGrid = self.grid;
+        let mut tile_sampler: Box<Sampler> = self.integrator.get_sampler().clone_with_seed(0_u64);
+        loop {
+            match read_u32(stream) {
+                Ok(MSG_TILE) => {}
+                Ok(MSG_DONE) => return Ok(()),
+                Ok(_) => return Err(invalid_data("unknown message")),
+                // the coordinator closes connections once it's done
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            }
+            let x: u32 = read_u32(stream)?;
+            let y: u32 = read_u32(stream)?;
+            let tile: Point2i = grid
+                .tile(x, y)
+                .ok_or_else(|| invalid_data("unknown tile"))?;
+            let tile_index: usize = (tile.y * grid.n_tiles.x + tile.x) as usize;
+            tile_sampler.reseed(tile_index as u64);
+            rendering.store(true, Ordering::Release);
+            let film_tile: FilmTile = self.integrator.render_tile(
+                self.scene,
+                self.film,
+                &mut tile_sampler,
+                &grid.tile_bounds(tile),
+                0,
+                grid.samples_per_pixel,
+            );
+            rendering.store(false, Ordering::Release);
+            let data: Vec<Float> = film_tile.get_pixel_data();
+            let mut msg: Vec<u8> =
+                Vec::with_capacity(20 + data.len() * std::mem::size_of::<Float>());
+            msg.extend_from_slice(&MSG_RESULT.to_le_bytes());
+            msg.extend_from_slice(&x.to_le_bytes());
+            msg.extend_from_slice(&y.to_le_bytes());
+            msg.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            for v in &data {
+                msg.extend_from_slice(&v.to_le_bytes());
+            }
+            writer.lock().unwrap().write_all(&msg)?;
+            self.n_rendered.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+}
+
+/// Sends a heartbeat every **HEARTBEAT_INTERVAL** while *rendering* is
+/// set, returns once *finished* is set or the connection is closed.
+fn send_heartbeats(writer: &Mutex<TcpStream>, rendering: &AtomicBool, finished: &AtomicBool) {
+    let mut last_heartbeat: Instant = Instant::now();
+    while !finished.load(Ordering::Acquire) {
+        std::thread::sleep(POLL_INTERVAL);
+        if rendering.load(Ordering::Acquire) && last_heartbeat.elapsed() >= HEARTBEAT_INTERVAL {
+            if writer
+                .lock()
+                .unwrap()
+                .write_all(&MSG_HEARTBEAT.to_le_bytes())
+                .is_err()
+            {
+                return;
+            }
+            last_heartbeat = Instant::now();
+        }
+    }
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(stream: &mut TcpStream) -> std::io::Result<u32> {
+    let mut word: [u8; 4] = [0_u8; 4];
+    stream.read_exact(&mut word)?;
+    Ok(u32::from_le_bytes(word))
+}
+
+fn read_i32(stream: &mut TcpStream) -> std::io::Result<i32> {
+    let mut word: [u8; 4] = [0_u8; 4];
+    stream.read_exact(&mut word)?;
+    Ok(i32::from_le_bytes(word))
+}
+
+fn read_u64(stream: &mut TcpStream) -> std::io::Result<u64> {
+    let mut word: [u8; 8] = [0_u8; 8];
+    stream.read_exact(&mut word)?;
+    Ok(u64::from_le_bytes(word))
+}
+
+fn read_i64(stream: &mut TcpStream) -> std::io::Result<i64> {
+    let mut word: [u8; 8] = [0_u8; 8];
+    stream.read_exact(&mut word)?;
+    Ok(i64::from_le_bytes(word))
+}
@@ -27,11 +27,16 @@ use crate::core::geometry::{Bounds2f, Bounds2i, Point2f, Point2i, Vector2f};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::{clamp_t, gamma_correct};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::spectrum::xyz_to_rgb;
 
 // see film.h
 
 const FILTER_TABLE_WIDTH: usize = 16;
+/// Number of values per pixel in **Film::get_pixel_data()**.
+pub const PIXEL_DATA_SIZE: usize = 7;
+/// Number of values per pixel in **FilmTile::get_pixel_data()**.
+pub const TILE_PIXEL_DATA_SIZE: usize = 4;
 
 #[derive(Debug, Clone)]
 pub struct Pixel {
@@ -91,7 +96,7 @@ impl<'a> FilmTile<'a> {
         }
     }
     pub fn add_sample(&mut self, p_film: Point2f, l: &mut Spectrum, sample_weight: Float) {
-        // TODO: ProfilePhase _(Prof::AddFilmSample);
+        let _p = ProfilePhase::new(Prof::AddFilmSample);
         if l.y() > self.max_sample_luminance {
             *l *= Spectrum::new(self.max_sample_luminance / l.y());
         }
@@ -146,6 +151,33 @@ impl<'a> FilmTile<'a> {
             }
         }
     }
+    /// Returns the sums stored for all pixels of the tile,
+    /// *TILE_PIXEL_DATA_SIZE* values per pixel (*contrib_sum* as RGB
+    /// and *filter_weight_sum*).
+    pub fn get_pixel_data(&self) -> Vec<Float> {
+        let mut data: Vec<Float> = Vec::with_capacity(self.pixels.len() * TILE_PIXEL_DATA_SIZE);
+        for pixel in self.pixels.iter() {
+            data.extend_from_slice(&pixel.contrib_sum.c);
+            data.push(pixel.filter_weight_sum);
+        }
+        data
+    }
+    /// Replaces the sums of all pixels by the ones returned by
+    /// **get_pixel_data()**, returns false if the size doesn't match.
+    pub fn set_pixel_data(&mut self, data: &[Float]) -> bool {
+        if data.len() != self.pixels.len() * TILE_PIXEL_DATA_SIZE {
+            return false;
+        }
+        for (pixel, values) in self
+            .pixels
+            .iter_mut()
+            .zip(data.chunks_exact(TILE_PIXEL_DATA_SIZE))
+        {
+            pixel.contrib_sum.c.copy_from_slice(&values[0..3]);
+            pixel.filter_weight_sum = values[3];
+        }
+        true
+    }
     fn get_pixel_index(&self, x: i32, y: i32) -> usize {
         let width: i32 = self.pixel_bounds.p_max.x - self.pixel_bounds.p_min.x;
         let pidx = (y - self.pixel_bounds.p_min.y) * width + (x - self.pixel_bounds.p_min.x);
@@ -343,7 +375,7 @@ impl Film {
         )
     }
     pub fn merge_film_tile(&self, tile: &FilmTile) {
-        // TODO: ProfilePhase p(Prof::MergeFilmTile);
+        let _p = ProfilePhase::new(Prof::MergeFilmTile);
         // println!("Merging film tile {:?}", tile.pixel_bounds);
         // TODO: std::lock_guard<std::mutex> lock(mutex);
         for pixel in &tile.pixel_bounds {
@@ -384,9 +416,38 @@ impl Film {
             merge_pixel.splat_xyz[2] = 0.0;
         }
     }
+    /// Returns the sums stored for all pixels, *PIXEL_DATA_SIZE*
+    /// values per pixel (*xyz*, *filter_weight_sum* and *splat_xyz*).
+    pub fn get_pixel_data(&self) -> Vec<Float> {
+        let pixels_read = self.pixels.read().unwrap();
+        let mut data: Vec<Float> = Vec::with_capacity(pixels_read.len() * PIXEL_DATA_SIZE);
+        for pixel in pixels_read.iter() {
+            data.extend_from_slice(&pixel.xyz);
+            data.push(pixel.filter_weight_sum);
+            data.extend_from_slice(&pixel.splat_xyz);
+        }
+        data
+    }
+    /// Replaces the sums of all pixels by the ones returned by
+    /// **get_pixel_data()**, returns false if the size doesn't match.
+    pub fn set_pixel_data(&self, data: &[Float]) -> bool {
+        let mut pixels_write = self.pixels.write().unwrap();
+        if data.len() != pixels_write.len() * PIXEL_DATA_SIZE {
+            return false;
+        }
+        for (pixel, values) in pixels_write
+            .iter_mut()
+            .zip(data.chunks_exact(PIXEL_DATA_SIZE))
+        {
+            pixel.xyz.copy_from_slice(&values[0..3]);
+            pixel.filter_weight_sum = values[3];
+            pixel.splat_xyz.copy_from_slice(&values[4..7]);
+        }
+        true
+    }
     pub fn add_splat(&self, p: Point2f, v: &Spectrum) {
         let mut v: Spectrum = *v;
-        // TODO: ProfilePhase pp(Prof::SplatFilm);
+        let _p = ProfilePhase::new(Prof::SplatFilm);
         if v.has_nans() {
             println!(
                 "ERROR: Ignoring splatted spectrum with NaN values at ({:?}, {:?})",
@@ -433,10 +494,13 @@ impl Film {
         splat_xyz[1] += xyz[1];
         splat_xyz[2] += xyz[2];
     }
-    #[cfg(not(feature = "openexr"))]
-    pub fn write_image(&self, splat_scale: Float) {
+    /// The linear RGB values of all pixels in the cropped image
+    /// region (row by row, three values per pixel), like they would
+    /// be written by **write_image()**.
+    pub fn get_rgb(&self, splat_scale: Float) -> Vec<Float> {
         let mut rgb: Vec<Float> =
             vec![0.0 as Float; (3 * self.cropped_pixel_bounds.area()) as usize];
+        let pixels = self.pixels.read().unwrap();
         let mut offset;
         for p in &self.cropped_pixel_bounds {
             // convert pixel XYZ color to RGB
@@ -444,7 +508,7 @@ impl Film {
             let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
             offset = ((p.x - self.cropped_pixel_bounds.p_min.x)
                 + (p.y - self.cropped_pixel_bounds.p_min.y) * width) as usize;
-            let pixel: &Pixel = &self.pixels.read().unwrap()[offset];
+            let pixel: &Pixel = &pixels[offset];
 
             let start: usize = 3 * offset;
             let mut rgb_array: [Float; 3] = [0.0 as Float; 3];
@@ -477,6 +541,11 @@ impl Film {
             rgb[start + 1] *= self.scale;
             rgb[start + 2] *= self.scale;
         }
+        rgb
+    }
+    #[cfg(not(feature = "openexr"))]
+    pub fn write_image(&self, splat_scale: Float) {
+        let rgb: Vec<Float> = self.get_rgb(splat_scale);
         let filename = "pbrt.png";
         println!(
             "Writing image {:?} with bounds {:?}",
@@ -527,53 +596,12 @@ impl Film {
     }
     #[cfg(feature = "openexr")]
     pub fn write_image(&self, splat_scale: Float) {
-        let mut rgb: Vec<Float> =
-            vec![0.0 as Float; (3 * self.cropped_pixel_bounds.area()) as usize];
-        let mut exr: Vec<(Float, Float, Float)> = // copy data for OpenEXR image
-            vec![(0.0_f32, 0.0_f32, 0.0_f32); self.cropped_pixel_bounds.area() as usize];
-        let mut offset;
-        for p in &self.cropped_pixel_bounds {
-            // convert pixel XYZ color to RGB
-            assert!(pnt2_inside_exclusivei(p, &self.cropped_pixel_bounds));
-            let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
-            offset = ((p.x - self.cropped_pixel_bounds.p_min.x)
-                + (p.y - self.cropped_pixel_bounds.p_min.y) * width) as usize;
-            let pixel: &Pixel = &self.pixels.read().unwrap()[offset];
-            let start = 3 * offset;
-            let mut rgb_array: [Float; 3] = [0.0 as Float; 3];
-            xyz_to_rgb(&pixel.xyz, &mut rgb_array); // TODO: Use 'rgb' directly.
-            rgb[start] = rgb_array[0];
-            rgb[start + 1] = rgb_array[1];
-            rgb[start + 2] = rgb_array[2];
-            // normalize pixel with weight sum
-            let filter_weight_sum: Float = pixel.filter_weight_sum;
-            if filter_weight_sum != 0.0 as Float {
-                let inv_wt: Float = 1.0 as Float / filter_weight_sum;
-                rgb[start] = (rgb[start] * inv_wt).max(0.0 as Float);
-                rgb[start + 1] = (rgb[start + 1] * inv_wt).max(0.0 as Float);
-                rgb[start + 2] = (rgb[start + 2] * inv_wt).max(0.0 as Float);
-            }
-            // add splat value at pixel
-            let mut splat_rgb: [Float; 3] = [0.0 as Float; 3];
-            let pixel_splat_xyz: &[Float; 3] = &pixel.splat_xyz;
-            let splat_xyz: [Float; 3] = [
-                *pixel_splat_xyz.index(0),
-                *pixel_splat_xyz.index(1),
-                *pixel_splat_xyz.index(2),
-            ];
-            xyz_to_rgb(&splat_xyz, &mut splat_rgb);
-            rgb[start] += splat_scale * splat_rgb[0];
-            rgb[start + 1] += splat_scale * splat_rgb[1];
-            rgb[start + 2] += splat_scale * splat_rgb[2];
-            // scale pixel value by _scale_
-            rgb[start] *= self.scale;
-            rgb[start + 1] *= self.scale;
-            rgb[start + 2] *= self.scale;
-            // copy data for OpenEXR image
-            exr[offset].0 = rgb[start];
-            exr[offset].1 = rgb[start + 1];
-            exr[offset].2 = rgb[start + 2];
-        }
+        let rgb: Vec<Float> = self.get_rgb(splat_scale);
+        // copy data for OpenEXR image
+        let exr: Vec<(Float, Float, Float)> = rgb
+            .chunks_exact(3)
+            .map(|values| (values[0], values[1], values[2]))
+            .collect();
         let filename = "pbrt.png";
         println!(
             "Writing image {:?} with bounds {:?}",
@@ -2342,6 +2342,22 @@ pub fn bnd3_union_bnd3f(b1: &Bounds3f, b2: &Bounds3f) -> Bounds3f {
     Bounds3f { p_min, p_max }
 }
 
+/// Construct a new box that bounds the space shared by two other
+/// bounding boxes (the result is degenerate if they don't overlap).
+pub fn bnd3_intersect_bnd3f(b1: &Bounds3f, b2: &Bounds3f) -> Bounds3f {
+    let p_min: Point3f = Point3f {
+        x: b1.p_min.x.max(b2.p_min.x),
+        y: b1.p_min.y.max(b2.p_min.y),
+        z: b1.p_min.z.max(b2.p_min.z),
+    };
+    let p_max: Point3f = Point3f {
+        x: b1.p_max.x.min(b2.p_max.x),
+        y: b1.p_max.y.min(b2.p_max.y),
+        z: b1.p_max.z.min(b2.p_max.z),
+    };
+    Bounds3f { p_min, p_max }
+}
+
 /// Determine if a given point is inside the bounding box.
 pub fn pnt3_inside_bnd3(p: &Point3f, b: &Bounds3f) -> bool {
     p.x >= b.p_min.x
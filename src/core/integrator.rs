@@ -3,22 +3,31 @@
 
 // std
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 // pbrt
 use crate::blockqueue::BlockQueue;
 use crate::core::camera::{Camera, CameraSample};
+use crate::core::checkpoint::{CheckpointOptions, Checkpointer};
+use crate::core::distributed::{self, DistributedOptions, DistributedRole};
+use crate::core::film::{Film, FilmTile};
 use crate::core::geometry::{pnt2_inside_exclusivei, vec3_abs_dot_nrmf};
 use crate::core::geometry::{Bounds2i, Point2f, Point2i, Ray, Vector2i, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::light::is_delta_light;
 use crate::core::light::{Light, VisibilityTester};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::progressreporter::ProgressReporter;
 use crate::core::reflection::BxdfType;
 use crate::core::sampler::Sampler;
 use crate::core::sampling::power_heuristic;
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::session::RenderControl;
+use crate::core::stats::{self, Counter};
 use crate::integrators::ao::AOIntegrator;
 use crate::integrators::bdpt::BDPTIntegrator;
+use crate::integrators::bvhcost::BVHCostIntegrator;
 use crate::integrators::directlighting::DirectLightingIntegrator;
 use crate::integrators::mlt::MLTIntegrator;
 use crate::integrators::path::PathIntegrator;
@@ -30,18 +39,119 @@ use crate::integrators::whitted::WhittedIntegrator;
 
 pub enum Integrator {
     BDPT(BDPTIntegrator),
+    BVHCost(BVHCostIntegrator),
     MLT(MLTIntegrator),
     SPPM(SPPMIntegrator),
     Sampler(SamplerIntegrator),
 }
 
+/// Optional features of a render, see
+/// **Integrator::render_with_settings()**.
+#[derive(Debug, Clone, Default)]
+pub struct RenderSettings {
+    /// periodically write a checkpoint (or resume from one)
+    pub checkpoint: Option<CheckpointOptions>,
+    /// render the whole image in passes of increasing sample counts
+    pub progressive: Option<ProgressiveOptions>,
+    /// share the tiles with other processes
+    pub distributed: Option<DistributedOptions>,
+    /// lets a host application pause or cancel the render and get
+    /// notified about merged tiles, see **RenderSession**
+    pub control: Option<Arc<RenderControl>>,
+}
+
+/// How a progressive render writes images and when it stops. The
+/// passes double the number of samples per pixel until the sampler's
+/// _"integer pixelsamples"_ are reached.
+#[derive(Debug, Clone)]
+pub struct ProgressiveOptions {
+    /// stop after this time (tiles which were started are finished)
+    pub time_limit: Option<Duration>,
+    /// write an intermediate image after every n-th pass
+    pub write_frequency: i32,
+}
+
+impl Default for ProgressiveOptions {
+    fn default() -> Self {
+        ProgressiveOptions {
+            time_limit: None,
+            write_frequency: 1,
+        }
+    }
+}
+
+/// The size of the (square) image tiles which are rendered by one
+/// thread at a time.
+pub const TILE_SIZE: i32 = 16;
+
+/// The sample bounds of the image tile *tile*.
+pub fn get_tile_bounds(sample_bounds: &Bounds2i, tile: Point2i, tile_size: i32) -> Bounds2i {
+    let x0: i32 = sample_bounds.p_min.x + tile.x * tile_size;
+    let x1: i32 = std::cmp::min(x0 + tile_size, sample_bounds.p_max.x);
+    let y0: i32 = sample_bounds.p_min.y + tile.y * tile_size;
+    let y1: i32 = std::cmp::min(y0 + tile_size, sample_bounds.p_max.y);
+    Bounds2i::new(Point2i { x: x0, y: y0 }, Point2i { x: x1, y: y1 })
+}
+
+/// The samples per pixel reached after each pass of a progressive
+/// render: 1, 2, 4, ... up to *samples_per_pixel*.
+pub fn progressive_pass_samples(samples_per_pixel: i64) -> Vec<i64> {
+    let mut passes: Vec<i64> = Vec::new();
+    let mut spp: i64 = 1;
+    while spp < samples_per_pixel {
+        passes.push(spp);
+        spp *= 2;
+    }
+    passes.push(samples_per_pixel.max(1));
+    passes
+}
+
 impl Integrator {
     pub fn render(&mut self, scene: &Scene, num_threads: u8) {
+        self.render_with_settings(scene, num_threads, &RenderSettings::default())
+    }
+    /// Like **render()**, but with checkpoints, progressive passes,
+    /// distributed over several processes or controlled by a host
+    /// application. Only the
+    /// [SamplerIntegrators](enum.SamplerIntegrator.html) support all
+    /// of them, the **BDPTIntegrator** supports checkpoints.
+    pub fn render_with_settings(
+        &mut self,
+        scene: &Scene,
+        num_threads: u8,
+        settings: &RenderSettings,
+    ) {
+        if settings.checkpoint.is_some()
+            && !matches!(self, Integrator::BDPT(_) | Integrator::Sampler(_))
+        {
+            println!("WARNING: Integrator doesn't support checkpoints, ignoring them");
+        }
+        if settings.progressive.is_some() && !matches!(self, Integrator::Sampler(_)) {
+            println!("WARNING: Integrator doesn't support progressive rendering, ignoring it");
+        }
+        if settings.distributed.is_some() && !matches!(self, Integrator::Sampler(_)) {
+            println!("WARNING: Integrator doesn't support distributed rendering, ignoring it");
+        }
+        if settings.control.is_some() && !matches!(self, Integrator::Sampler(_)) {
+            println!("WARNING: Integrator doesn't support render control, ignoring it");
+        }
         match self {
-            Integrator::BDPT(integrator) => integrator.render(scene, num_threads),
+            Integrator::BDPT(integrator) => {
+                integrator.render(scene, num_threads, settings.checkpoint.as_ref())
+            }
+            Integrator::BVHCost(integrator) => integrator.render(scene, num_threads),
             Integrator::MLT(integrator) => integrator.render(scene, num_threads),
             Integrator::SPPM(integrator) => integrator.render(scene, num_threads),
-            Integrator::Sampler(integrator) => integrator.render(scene, num_threads),
+            Integrator::Sampler(integrator) => integrator.render(scene, num_threads, settings),
+        }
+    }
+    pub fn get_camera(&self) -> Arc<Camera> {
+        match self {
+            Integrator::BDPT(integrator) => integrator.get_camera(),
+            Integrator::BVHCost(integrator) => integrator.get_camera(),
+            Integrator::MLT(integrator) => integrator.get_camera(),
+            Integrator::SPPM(integrator) => integrator.get_camera(),
+            Integrator::Sampler(integrator) => integrator.get_camera(),
         }
     }
 }
@@ -67,155 +177,289 @@ impl SamplerIntegrator {
     /// All [SamplerIntegrators](enum.SamplerIntegrator.html) use the
     /// same render loop, but call an individual
     /// [li()](enum.SamplerIntegrator.html#method.li) method.
-    pub fn render(&mut self, scene: &Scene, num_threads: u8) {
+    pub fn render(&mut self, scene: &Scene, num_threads: u8, settings: &RenderSettings) {
         let film = self.get_camera().get_film();
         let sample_bounds: Bounds2i = film.get_sample_bounds();
         self.preprocess(scene);
+        if let Some(ref options) = settings.distributed {
+            if settings.checkpoint.is_some() || settings.progressive.is_some() {
+                println!(
+                    "WARNING: Distributed renders don't support checkpoints or progressive passes, ignoring them"
+                );
+            }
+            match options.role {
+                DistributedRole::Coordinator => {
+                    if distributed::render_coordinator(
+                        self,
+                        &options.address,
+                        options.worker_timeout,
+                    ) {
+                        film.write_image(1.0 as Float);
+                    }
+                }
+                DistributedRole::Worker => {
+                    distributed::render_worker(self, scene, &options.address, num_threads)
+                }
+            }
+            return;
+        }
+        let start_time: Instant = Instant::now();
         let sample_extent: Vector2i = sample_bounds.diagonal();
-        let tile_size: i32 = 16;
+        let tile_size: i32 = TILE_SIZE;
         let x: i32 = (sample_extent.x + tile_size - 1) / tile_size;
         let y: i32 = (sample_extent.y + tile_size - 1) / tile_size;
         let n_tiles: Point2i = Point2i { x, y };
-        // TODO: ProgressReporter reporter(nTiles.x * nTiles.y, "Rendering");
+        let n_tiles_total: usize = (n_tiles.x * n_tiles.y) as usize;
+        let samples_per_pixel: i64 = self.get_sampler().get_samples_per_pixel();
+        let mut checkpointer: Option<Checkpointer> = settings
+            .checkpoint
+            .as_ref()
+            .map(|options| Checkpointer::new(options, &film, n_tiles_total));
+        // samples per pixel each tile has completed
+        let mut tile_samples: Vec<i64> = match checkpointer {
+            Some(ref checkpointer) => checkpointer.tile_samples().to_vec(),
+            None => vec![0_i64; n_tiles_total],
+        };
+        let passes: Vec<i64> = match settings.progressive {
+            Some(_) => progressive_pass_samples(samples_per_pixel),
+            None => vec![samples_per_pixel],
+        };
+        let deadline: Option<Instant> = settings
+            .progressive
+            .as_ref()
+            .and_then(|progressive| progressive.time_limit)
+            .map(|time_limit| start_time + time_limit);
         let num_cores = if num_threads == 0_u8 {
             num_cpus::get()
         } else {
             num_threads as usize
         };
+        let control: Option<&RenderControl> = settings.control.as_deref();
         println!("Rendering with {:?} thread(s) ...", num_cores);
-        {
-            let block_queue = BlockQueue::new(
-                (
-                    (n_tiles.x * tile_size) as u32,
-                    (n_tiles.y * tile_size) as u32,
-                ),
-                (tile_size as u32, tile_size as u32),
-                (0, 0),
-            );
-            let integrator = &self;
-            let bq = &block_queue;
-            let sampler = &self.get_sampler();
-            let camera = &self.get_camera();
-            let film = &film;
-            let pixel_bounds = &self.get_pixel_bounds();
-            crossbeam::scope(|scope| {
-                let (pixel_tx, pixel_rx) = crossbeam_channel::bounded(num_cores);
-                // spawn worker threads
-                for _ in 0..num_cores {
-                    let pixel_tx = pixel_tx.clone();
-                    let mut tile_sampler: Box<Sampler> = sampler.clone_with_seed(0_u64);
-                    scope.spawn(move |_| {
-                        while let Some((x, y)) = bq.next() {
-                            let tile: Point2i = Point2i {
-                                x: x as i32,
-                                y: y as i32,
-                            };
-                            let seed: i32 = tile.y * n_tiles.x + tile.x;
-                            tile_sampler.reseed(seed as u64);
-                            let x0: i32 = sample_bounds.p_min.x + tile.x * tile_size;
-                            let x1: i32 = std::cmp::min(x0 + tile_size, sample_bounds.p_max.x);
-                            let y0: i32 = sample_bounds.p_min.y + tile.y * tile_size;
-                            let y1: i32 = std::cmp::min(y0 + tile_size, sample_bounds.p_max.y);
-                            let tile_bounds: Bounds2i =
-                                Bounds2i::new(Point2i { x: x0, y: y0 }, Point2i { x: x1, y: y1 });
-                            // println!("Starting image tile {:?}", tile_bounds);
-                            let mut film_tile = film.get_film_tile(&tile_bounds);
-                            for pixel in &tile_bounds {
-                                tile_sampler.start_pixel(pixel);
-                                if !pnt2_inside_exclusivei(pixel, &pixel_bounds) {
-                                    continue;
+        let mut image_written: bool = false;
+        for (pass, pass_spp) in passes.iter().enumerate() {
+            let pass_spp: i64 = *pass_spp;
+            let n_tiles_done: usize = tile_samples
+                .iter()
+                .filter(|samples| **samples >= pass_spp)
+                .count();
+            if n_tiles_done == n_tiles_total {
+                continue;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                println!("Time limit reached, stopping ...");
+                break;
+            }
+            if control.is_some_and(|control| control.is_cancelled()) {
+                break;
+            }
+            let title: String = if passes.len() > 1 {
+                format!("Rendering pass {} ({} spp)", pass + 1, pass_spp)
+            } else {
+                String::from("Rendering")
+            };
+            let reporter: ProgressReporter = ProgressReporter::new(n_tiles_total as u64, &title);
+            reporter.update(n_tiles_done as u64);
+            // the workers read the counts of the previous pass
+            let pass_tile_samples: Vec<i64> = tile_samples.clone();
+            {
+                let block_queue = BlockQueue::new(
+                    (
+                        (n_tiles.x * tile_size) as u32,
+                        (n_tiles.y * tile_size) as u32,
+                    ),
+                    (tile_size as u32, tile_size as u32),
+                    (0, 0),
+                );
+                let integrator = &self;
+                let bq = &block_queue;
+                let reporter = &reporter;
+                let sampler = &self.get_sampler();
+                let film = &film;
+                let pass_tile_samples = &pass_tile_samples;
+                let tile_samples = &mut tile_samples;
+                let checkpointer = &mut checkpointer;
+                crossbeam::scope(|scope| {
+                    let (pixel_tx, pixel_rx) = crossbeam_channel::bounded(num_cores);
+                    // spawn worker threads
+                    for _ in 0..num_cores {
+                        let pixel_tx = pixel_tx.clone();
+                        let mut tile_sampler: Box<Sampler> = sampler.clone_with_seed(0_u64);
+                        scope.spawn(move |_| {
+                            let _p = ProfilePhase::new(Prof::IntegratorRender);
+                            while let Some((x, y)) = bq.next() {
+                                // tiles which were started are finished
+                                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                                    break;
                                 }
-                                let mut done: bool = false;
-                                while !done {
-                                    // let's use the copy_arena crate instead of pbrt's MemoryArena
-                                    // let mut arena: Arena = Arena::with_capacity(262144); // 256kB
-
-                                    // initialize _CameraSample_ for current sample
-                                    let camera_sample: CameraSample =
-                                        tile_sampler.get_camera_sample(pixel);
-                                    // generate camera ray for current sample
-                                    let mut ray: Ray = Ray::default();
-                                    let ray_weight: Float =
-                                        camera.generate_ray_differential(&camera_sample, &mut ray);
-                                    ray.scale_differentials(
-                                        1.0 as Float
-                                            / (tile_sampler.get_samples_per_pixel() as Float)
-                                                .sqrt(),
-                                    );
-                                    // TODO: ++nCameraRays;
-                                    // evaluate radiance along camera ray
-                                    let mut l: Spectrum = Spectrum::new(0.0 as Float);
-                                    let y: Float = l.y();
-                                    if ray_weight > 0.0 {
-                                        // ADDED
-                                        let clipping_start: Float = camera.get_clipping_start();
-                                        if clipping_start > 0.0 as Float {
-                                            // adjust ray origin for near clipping
-                                            ray.o = ray.position(clipping_start);
-                                        }
-                                        // ADDED
-                                        l = integrator.li(
-                                            &mut ray,
-                                            scene,
-                                            &mut tile_sampler, // &mut arena,
-                                            0_i32,
-                                        );
-                                    }
-                                    if l.has_nans() {
-                                        println!(
-                                            "Not-a-number radiance value returned for pixel \
-                                                     ({:?}, {:?}), sample {:?}. Setting to black.",
-                                            pixel.x,
-                                            pixel.y,
-                                            tile_sampler.get_current_sample_number()
-                                        );
-                                        l = Spectrum::new(0.0);
-                                    } else if y < -10.0e-5 as Float {
-                                        println!(
-                                            "Negative luminance value, {:?}, returned for pixel \
-                                                 ({:?}, {:?}), sample {:?}. Setting to black.",
-                                            y,
-                                            pixel.x,
-                                            pixel.y,
-                                            tile_sampler.get_current_sample_number()
-                                        );
-                                        l = Spectrum::new(0.0);
-                                    } else if y.is_infinite() {
-                                        println!(
-                                            "Infinite luminance value returned for pixel ({:?}, \
-                                                 {:?}), sample {:?}. Setting to black.",
-                                            pixel.x,
-                                            pixel.y,
-                                            tile_sampler.get_current_sample_number()
-                                        );
-                                        l = Spectrum::new(0.0);
+                                if let Some(control) = control {
+                                    control.wait_while_paused();
+                                    if control.is_cancelled() {
+                                        break;
                                     }
-                                    // println!("Camera sample: {:?} -> ray: {:?} -> L = {:?}",
-                                    //          camera_sample, ray, l);
-                                    // add camera ray's contribution to image
-                                    film_tile.add_sample(camera_sample.p_film, &mut l, ray_weight);
-                                    done = !tile_sampler.start_next_sample();
-                                } // arena is dropped here !
+                                }
+                                let tile: Point2i = Point2i {
+                                    x: x as i32,
+                                    y: y as i32,
+                                };
+                                let tile_index: usize = (tile.y * n_tiles.x + tile.x) as usize;
+                                let samples_done: i64 = pass_tile_samples[tile_index];
+                                if samples_done >= pass_spp {
+                                    continue;
+                                }
+                                // the same seed in every pass, the samples of
+                                // previous passes are skipped in render_tile()
+                                tile_sampler.reseed(tile_index as u64);
+                                let tile_bounds: Bounds2i =
+                                    get_tile_bounds(&sample_bounds, tile, tile_size);
+                                let film_tile = integrator.render_tile(
+                                    scene,
+                                    film,
+                                    &mut tile_sampler,
+                                    &tile_bounds,
+                                    samples_done,
+                                    pass_spp,
+                                );
+                                // send the tile through the channel to main thread
+                                pixel_tx
+                                    .send((tile_index, film_tile))
+                                    .unwrap_or_else(|_| panic!("Failed to send tile"));
+                            }
+                        });
+                    }
+                    // the channel closes when all workers are done
+                    drop(pixel_tx);
+                    // spawn thread to collect pixels and render image to file
+                    scope.spawn(move |_| {
+                        for (tile_index, film_tile) in pixel_rx.iter() {
+                            // merge image tile into _Film_
+                            film.merge_film_tile(&film_tile);
+                            if let Some(control) = control {
+                                control.tile_done(&film_tile.pixel_bounds);
+                            }
+                            tile_samples[tile_index] = pass_spp;
+                            if let Some(checkpointer) = checkpointer {
+                                checkpointer.tile_done(tile_index, pass_spp);
                             }
-                            // send the tile through the channel to main thread
-                            pixel_tx
-                                .send(film_tile)
-                                .unwrap_or_else(|_| panic!("Failed to send tile"));
+                            reporter.update(1);
                         }
                     });
+                })
+                .unwrap();
+            }
+            reporter.done();
+            image_written = false;
+            if let Some(ref progressive) = settings.progressive {
+                if (pass as i32 + 1) % progressive.write_frequency == 0 {
+                    film.write_image(1.0 as Float);
+                    image_written = true;
                 }
-                // spawn thread to collect pixels and render image to file
-                scope.spawn(move |_| {
-                    for _ in pbr::PbIter::new(0..bq.len()) {
-                        let film_tile = pixel_rx.recv().unwrap();
-                        // merge image tile into _Film_
-                        film.merge_film_tile(&film_tile);
+            }
+        }
+        if control.is_some_and(|control| control.is_cancelled()) {
+            println!("Render cancelled, not writing the image ...");
+        } else if !image_written {
+            film.write_image(1.0 as Float);
+        }
+        if let Some(ref mut checkpointer) = checkpointer {
+            checkpointer.write();
+        }
+    }
+    /// Renders the samples *samples_done* up to *pass_spp* (per
+    /// pixel) of all pixels in *tile_bounds* into a new **FilmTile**.
+    /// The sampler has to be seeded for the tile already.
+    pub fn render_tile<'a>(
+        &self,
+        scene: &Scene,
+        film: &'a Film,
+        tile_sampler: &mut Sampler,
+        tile_bounds: &Bounds2i,
+        samples_done: i64,
+        pass_spp: i64,
+    ) -> FilmTile<'a> {
+        let camera: Arc<Camera> = self.get_camera();
+        let pixel_bounds: Bounds2i = self.get_pixel_bounds();
+        // println!("Starting image tile {:?}", tile_bounds);
+        let mut film_tile = film.get_film_tile(tile_bounds);
+        for pixel in tile_bounds {
+            tile_sampler.start_pixel(pixel);
+            if !pnt2_inside_exclusivei(pixel, &pixel_bounds) {
+                continue;
+            }
+            // skip the samples of previous passes (or renders)
+            for _ in 0..samples_done {
+                tile_sampler.start_next_sample();
+            }
+            let mut done: bool = false;
+            while !done {
+                // let's use the copy_arena crate instead of pbrt's MemoryArena
+                // let mut arena: Arena = Arena::with_capacity(262144); // 256kB
+
+                // initialize _CameraSample_ for current sample
+                let camera_sample: CameraSample = tile_sampler.get_camera_sample(pixel);
+                // generate camera ray for current sample
+                let mut ray: Ray = Ray::default();
+                let ray_weight: Float = camera.generate_ray_differential(&camera_sample, &mut ray);
+                ray.scale_differentials(
+                    1.0 as Float / (tile_sampler.get_samples_per_pixel() as Float).sqrt(),
+                );
+                stats::add_counter(Counter::CameraRays, 1);
+                // evaluate radiance along camera ray
+                let mut l: Spectrum = Spectrum::new(0.0 as Float);
+                let y: Float = l.y();
+                if ray_weight > 0.0 {
+                    // ADDED
+                    let clipping_start: Float = camera.get_clipping_start();
+                    if clipping_start > 0.0 as Float {
+                        // adjust ray origin for near clipping
+                        ray.o = ray.position(clipping_start);
                     }
-                });
-            })
-            .unwrap();
+                    // ADDED
+                    l = self.li(
+                        &mut ray,
+                        scene,
+                        tile_sampler, // &mut arena,
+                        0_i32,
+                    );
+                }
+                if l.has_nans() {
+                    println!(
+                        "Not-a-number radiance value returned for pixel \
+                                 ({:?}, {:?}), sample {:?}. Setting to black.",
+                        pixel.x,
+                        pixel.y,
+                        tile_sampler.get_current_sample_number()
+                    );
+                    l = Spectrum::new(0.0);
+                } else if y < -10.0e-5 as Float {
+                    println!(
+                        "Negative luminance value, {:?}, returned for pixel \
+                             ({:?}, {:?}), sample {:?}. Setting to black.",
+                        y,
+                        pixel.x,
+                        pixel.y,
+                        tile_sampler.get_current_sample_number()
+                    );
+                    l = Spectrum::new(0.0);
+                } else if y.is_infinite() {
+                    println!(
+                        "Infinite luminance value returned for pixel ({:?}, \
+                             {:?}), sample {:?}. Setting to black.",
+                        pixel.x,
+                        pixel.y,
+                        tile_sampler.get_current_sample_number()
+                    );
+                    l = Spectrum::new(0.0);
+                }
+                // println!("Camera sample: {:?} -> ray: {:?} -> L = {:?}",
+                //          camera_sample, ray, l);
+                // add camera ray's contribution to image
+                film_tile.add_sample(camera_sample.p_film, &mut l, ray_weight);
+                done = !tile_sampler.start_next_sample()
+                    || tile_sampler.get_current_sample_number() >= pass_spp;
+            } // arena is dropped here !
         }
-        film.write_image(1.0 as Float);
+        film_tile
     }
     pub fn li(&self, ray: &mut Ray, scene: &Scene, sampler: &mut Sampler, depth: i32) -> Spectrum {
         match self {
@@ -303,7 +547,7 @@ pub fn uniform_sample_all_lights(
     n_light_samples: &[i32],
     handle_media: bool,
 ) -> Spectrum {
-    // TODO: ProfilePhase p(Prof::DirectLighting);
+    let _p = ProfilePhase::new(Prof::DirectLighting);
     let mut l: Spectrum = Spectrum::new(0.0);
     for (j, n_samples) in n_light_samples.iter().enumerate().take(scene.lights.len()) {
         // accumulate contribution of _j_th light to _L_
@@ -362,7 +606,7 @@ pub fn uniform_sample_one_light(
     handle_media: bool,
     light_distrib: Option<&Distribution1D>,
 ) -> Spectrum {
-    // TODO: ProfilePhase p(Prof::DirectLighting);
+    let _p = ProfilePhase::new(Prof::DirectLighting);
 
     // randomly choose a single light to sample, _light_
     let n_lights: usize = scene.lights.len();
@@ -21,6 +21,7 @@ use crate::core::medium::{HenyeyGreenstein, Medium, MediumInterface};
 use crate::core::pbrt::SHADOW_EPSILON;
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::primitive::Primitive;
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::reflection::Bsdf;
 use crate::core::shape::Shape;
 use crate::core::transform::solve_linear_system_2x2;
@@ -265,6 +266,12 @@ pub struct SurfaceInteraction<'a> {
     pub bsdf: Option<Bsdf>,
     pub bssrdf: Option<TabulatedBssrdf>,
     pub shape: Option<&'a Shape>,
+    /// index of the mesh face that was hit (if the shape provides
+    /// per-face indices, zero otherwise)
+    pub face_index: i32,
+    /// color of the vertex (or point) that was hit, if the shape
+    /// provides per-vertex colors
+    pub vertex_color: Option<Spectrum>,
 }
 
 impl<'a> SurfaceInteraction<'a> {
@@ -327,6 +334,8 @@ impl<'a> SurfaceInteraction<'a> {
                 bsdf: None,
                 bssrdf: None,
                 shape: Some(shape),
+                face_index: 0,
+                vertex_color: None,
             }
         } else {
             SurfaceInteraction {
@@ -347,6 +356,8 @@ impl<'a> SurfaceInteraction<'a> {
                 bsdf: None,
                 bssrdf: None,
                 shape: None,
+                face_index: 0,
+                vertex_color: None,
             }
         }
     }
@@ -404,6 +415,7 @@ impl<'a> SurfaceInteraction<'a> {
         allow_multiple_lobes: bool,
         mode: TransportMode,
     ) {
+        let _p = ProfilePhase::new(Prof::ComputeScatteringFuncs);
         self.compute_differentials(ray);
         if let Some(primitive_raw) = self.primitive {
             let primitive = unsafe { &*primitive_raw };
@@ -15,8 +15,10 @@ use crate::core::light::VisibilityTester;
 use crate::core::lowdiscrepancy::radical_inverse;
 use crate::core::pbrt::clamp_t;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::{self, IntDistribution, Ratio};
 
 // see lightdistrib.h
 
@@ -169,6 +171,7 @@ impl SpatialLightDistribution {
     /// Compute the sampling distribution for the voxel with integer
     /// coordiantes given by "pi".
     pub fn compute_distribution(&self, pi: &Point3i) -> Distribution1D {
+        stats::add_ratio(Ratio::LookupsPerDistribution, 0, 1);
         // Compute the world-space bounding box of the voxel
         // corresponding to |pi|.
         let p0: Point3f = Point3f {
@@ -276,8 +279,8 @@ impl SpatialLightDistribution {
     /// effective) sampling distribution for light sources at that
     /// point.
     pub fn lookup(&self, p: &Point3f) -> Arc<Distribution1D> {
-        // TODO: ProfilePhase _(Prof::LightDistribLookup);
-        // TODO: ++nLookups;
+        let _p = ProfilePhase::new(Prof::LightDistribLookup);
+        stats::add_ratio(Ratio::LookupsPerDistribution, 1, 0);
 
         // first, compute integer voxel coordinates for the given
         // point |p| with respect to the overall voxel grid.
@@ -318,9 +321,9 @@ impl SpatialLightDistribution {
         // entry is already used for another value; step stores the
         // square root of the probe step.
         let mut step: u64 = 1;
-        // TODO: int nProbes = 0;
+        let mut n_probes: i64 = 0;
         loop {
-            // TODO: ++nProbes;
+            n_probes += 1;
             let entry: &HashEntry = &self.hash_table[hash as usize];
             // does the hash table entry at offset |hash| match the current point?
             let entry_packed_pos: u64 = entry.packed_pos.load(Ordering::Acquire);
@@ -342,12 +345,14 @@ impl SpatialLightDistribution {
                         if option2.is_some() {
                             if let Some(ref dist) = *option2 {
                                 // We have a valid sampling distribution.
+                                stats::report_value(IntDistribution::ProbesPerLookup, n_probes);
                                 return dist.clone();
                             }
                         }
                     }
                 } else {
                     // We have a valid sampling distribution.
+                    stats::report_value(IntDistribution::ProbesPerLookup, n_probes);
                     return option.as_ref().unwrap().clone();
                 }
             } else if entry_packed_pos != INVALID_PACKED_POS {
@@ -376,6 +381,7 @@ impl SpatialLightDistribution {
                     let dist: Distribution1D = self.compute_distribution(&pi);
                     let arc_dist: Arc<Distribution1D> = Arc::new(dist);
                     entry.distribution.set_if_none(arc_dist.clone());
+                    stats::report_value(IntDistribution::ProbesPerLookup, n_probes);
                     return arc_dist;
                 }
             }
@@ -14,6 +14,7 @@ use crate::materials::disney::DisneyMaterial;
 use crate::materials::fourier::FourierMaterial;
 use crate::materials::glass::GlassMaterial;
 use crate::materials::hair::HairMaterial;
+use crate::materials::indexed::IndexedMaterial;
 use crate::materials::matte::MatteMaterial;
 use crate::materials::metal::MetalMaterial;
 use crate::materials::mirror::MirrorMaterial;
@@ -40,6 +41,7 @@ pub enum Material {
     Fourier(Box<FourierMaterial>),
     Glass(Box<GlassMaterial>),
     Hair(Box<HairMaterial>),
+    Indexed(Box<IndexedMaterial>),
     Matte(Box<MatteMaterial>),
     Metal(Box<MetalMaterial>),
     Mirror(Box<MirrorMaterial>),
@@ -81,6 +83,9 @@ impl Material {
             Material::Hair(material) => {
                 material.compute_scattering_functions(si, mode, allow_multiple_lobes, mat, scale)
             }
+            Material::Indexed(material) => {
+                material.compute_scattering_functions(si, mode, allow_multiple_lobes, mat, scale)
+            }
             Material::Matte(material) => {
                 material.compute_scattering_functions(si, mode, allow_multiple_lobes, mat, scale)
             }
@@ -164,6 +169,8 @@ impl Material {
         } else {
             si_eval.shape = None
         }
+        si_eval.face_index = si.face_index;
+        si_eval.vertex_color = si.vertex_color;
         // shift _si_eval_ _du_ in the $u$ direction
         let mut du: Float = 0.5 as Float * (si.dudx.get().abs() + si.dudy.get().abs());
         // The most common reason for du to be zero is for ray that start from
@@ -10,6 +10,8 @@ use crate::core::geometry::{Point2f, Point2i, Vector2f};
 use crate::core::memory::BlockedArray;
 use crate::core::pbrt::{clamp_t, is_power_of_2, lerp, mod_t, round_up_pow2_32};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::stats::{self, Counter, Memory};
 use crate::core::texture::lanczos;
 
 // see mipmap.h
@@ -191,7 +193,11 @@ where
                 mipmap.weight_lut[i] = (-alpha * r2).exp() - (-alpha).exp();
             }
         }
-        // TODO: mipMapMemory += (4 * resolution[0] * resolution[1] * sizeof(T)) / 3;
+        stats::add_memory(
+            Memory::MipMaps,
+            (4 * resolution.x as usize * resolution.y as usize * std::mem::size_of::<T>() / 3)
+                as u64,
+        );
         mipmap
     }
     pub fn width(&self) -> i32 {
@@ -231,8 +237,8 @@ where
         &l[(ss, tt)]
     }
     pub fn lookup_pnt_flt(&self, st: Point2f, width: Float) -> T {
-        // TODO: ++nTrilerpLookups;
-        // TODO: ProfilePhase p(Prof::TexFiltTrilerp);
+        stats::add_counter(Counter::TrilinearLookups, 1);
+        let _p = ProfilePhase::new(Prof::TexFiltTrilerp);
         // compute MIPMap level for trilinear filtering
         let level: Float = self.levels() as Float - 1.0 as Float + width.max(1e-8 as Float).log2();
         // perform trilinear interpolation at appropriate MIPMap level
@@ -259,8 +265,8 @@ where
                 .max(dst1.x.abs().max(dst1.y.abs()));
             return self.lookup_pnt_flt(st, width);
         }
-        // TODO: ++nEWALookups;
-        // TODO: ProfilePhase p(Prof::TexFiltEWA);
+        stats::add_counter(Counter::EWALookups, 1);
+        let _p = ProfilePhase::new(Prof::TexFiltEWA);
         // compute ellipse minor and major axes
         if dst0.length_squared() < dst1.length_squared() {
             // std::swap(dst0, dst1);
@@ -3,6 +3,8 @@
 pub mod api;
 pub mod bssrdf;
 pub mod camera;
+pub mod checkpoint;
+pub mod distributed;
 pub mod efloat;
 pub mod film;
 pub mod filter;
@@ -23,14 +25,18 @@ pub mod parallel;
 pub mod paramset;
 pub mod pbrt;
 pub mod primitive;
+pub mod profiler;
+pub mod progressreporter;
 pub mod quaternion;
 pub mod reflection;
 pub mod rng;
 pub mod sampler;
 pub mod sampling;
 pub mod scene;
+pub mod session;
 pub mod shape;
 pub mod sobolmatrices;
 pub mod spectrum;
+pub mod stats;
 pub mod texture;
 pub mod transform;
@@ -221,6 +221,15 @@ impl ParamSet {
             looked_up: false,
         });
     }
+    pub fn add_strings(&mut self, name: String, values: Vec<String>) {
+        let n_values: usize = values.len();
+        self.strings.push(ParamSetItem::<String> {
+            name,
+            values,
+            n_values,
+            looked_up: false,
+        });
+    }
     pub fn add_texture(&mut self, name: String, value: String) {
         self.textures.push(ParamSetItem::<String> {
             name,
@@ -289,6 +298,22 @@ impl ParamSet {
             looked_up: false,
         });
     }
+    pub fn add_rgb_spectra(&mut self, name: String, values: Vec<Float>) {
+        assert!(3 * (values.len() / 3) == values.len());
+        let n_values: usize = values.len() / 3_usize;
+        let mut s: Vec<Spectrum> = Vec::with_capacity(n_values);
+        for i in 0..n_values {
+            s.push(Spectrum {
+                c: [values[3 * i], values[3 * i + 1], values[3 * i + 2]],
+            });
+        }
+        self.spectra.push(ParamSetItem::<Spectrum> {
+            name,
+            values: s,
+            n_values,
+            looked_up: false,
+        });
+    }
     pub fn add_blackbody_spectrum(&mut self, name: String, values: Vec<Float>) {
         assert!(values.len() % 2 == 0);
         // temperature (K), scale, ...
@@ -517,6 +542,19 @@ impl ParamSet {
         }
         values
     }
+    pub fn find_string(&self, name: &str) -> Vec<String> {
+        let mut values: Vec<String> = Vec::new();
+        for v in &self.strings {
+            if v.name == name {
+                let n_values = v.n_values;
+                // v.looked_up = true;
+                for i in 0..n_values {
+                    values.push(v.values[i].clone());
+                }
+            }
+        }
+        values
+    }
     pub fn find_point2f(&self, name: &str) -> Vec<Point2f> {
         let mut values: Vec<Point2f> = Vec::new();
         for v in &self.point2fs {
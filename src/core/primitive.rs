@@ -2,11 +2,12 @@
 //! geometry processing and shading subsystems of pbrt.
 
 // std
+use std::cell::Cell;
 use std::sync::Arc;
 // pbrt
 use crate::accelerators::bvh::BVHAccel;
 use crate::accelerators::kdtreeaccel::KdTreeAccel;
-use crate::core::geometry::nrm_dot_nrmf;
+use crate::core::geometry::{bnd3_union_bnd3f, nrm_dot_nrmf, nrm_dot_vec3f, vec3_dot_vec3f};
 use crate::core::geometry::{Bounds3f, Ray};
 use crate::core::interaction::SurfaceInteraction;
 use crate::core::light::Light;
@@ -23,6 +24,7 @@ pub enum Primitive {
     Transformed(Box<TransformedPrimitive>),
     BVH(Box<BVHAccel>),
     KdTree(Box<KdTreeAccel>),
+    CSG(Box<CSGPrimitive>),
 }
 
 impl Primitive {
@@ -32,6 +34,7 @@ impl Primitive {
             Primitive::Transformed(primitive) => primitive.world_bound(),
             Primitive::BVH(primitive) => primitive.world_bound(),
             Primitive::KdTree(primitive) => primitive.world_bound(),
+            Primitive::CSG(primitive) => primitive.world_bound(),
         }
     }
     pub fn intersect(&self, ray: &Ray, isect: &mut SurfaceInteraction) -> bool {
@@ -46,6 +49,7 @@ impl Primitive {
             Primitive::Transformed(primitive) => primitive.intersect(ray, isect),
             Primitive::BVH(primitive) => primitive.intersect(ray, isect),
             Primitive::KdTree(primitive) => primitive.intersect(ray, isect),
+            Primitive::CSG(primitive) => primitive.intersect(ray, isect),
         }
     }
     pub fn intersect_p(&self, ray: &Ray) -> bool {
@@ -54,6 +58,7 @@ impl Primitive {
             Primitive::Transformed(primitive) => primitive.intersect_p(ray),
             Primitive::BVH(primitive) => primitive.intersect_p(ray),
             Primitive::KdTree(primitive) => primitive.intersect_p(ray),
+            Primitive::CSG(primitive) => primitive.intersect_p(ray),
         }
     }
     pub fn get_area_light(&self) -> Option<Arc<Light>> {
@@ -62,6 +67,7 @@ impl Primitive {
             Primitive::Transformed(primitive) => primitive.get_area_light(),
             Primitive::BVH(primitive) => primitive.get_area_light(),
             Primitive::KdTree(primitive) => primitive.get_area_light(),
+            Primitive::CSG(primitive) => primitive.get_area_light(),
         }
     }
     pub fn get_material(&self) -> Option<Arc<Material>> {
@@ -70,6 +76,7 @@ impl Primitive {
             Primitive::Transformed(primitive) => primitive.get_material(),
             Primitive::BVH(primitive) => primitive.get_material(),
             Primitive::KdTree(primitive) => primitive.get_material(),
+            Primitive::CSG(primitive) => primitive.get_material(),
         }
     }
     pub fn compute_scattering_functions(
@@ -256,9 +263,8 @@ impl TransformedPrimitive {
                 // is.shading.dpdv = new_isect.shading.dpdv;
                 // is.shading.dndu = new_isect.shading.dndu;
                 // is.shading.dndv = new_isect.shading.dndv;
-                return true;
             }
-            false
+            true
         } else {
             false
         }
@@ -278,3 +284,154 @@ impl TransformedPrimitive {
         None
     }
 }
+
+/// Boolean operations supported by _CSGPrimitive_.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CSGOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CSGOperation {
+    fn inside(self, inside_a: bool, inside_b: bool) -> bool {
+        match self {
+            CSGOperation::Union => inside_a || inside_b,
+            CSGOperation::Intersection => inside_a && inside_b,
+            CSGOperation::Difference => inside_a && !inside_b,
+        }
+    }
+}
+
+/// Upper limit of surface crossings followed along a single ray.
+const CSG_MAX_CROSSINGS: usize = 256;
+
+/// A single surface crossing of one of the two operands of a
+/// _CSGPrimitive_ along a ray.
+struct CSGCrossing<'a> {
+    /// parametric distance along the original ray
+    t: Float,
+    /// true if the ray enters the (closed) operand
+    entering: bool,
+    isect: SurfaceInteraction<'a>,
+}
+
+/// Constructive solid geometry: combines two closed primitives (with
+/// outward facing normals) by a boolean operation. Rays are followed
+/// from crossing to crossing through both operands, keeping track of
+/// being inside or outside of each of them, until the result of the
+/// boolean operation changes. The surface interaction (and therefore
+/// the material) of the operand crossed there is reported.
+pub struct CSGPrimitive {
+    pub a: Arc<Primitive>,
+    pub b: Arc<Primitive>,
+    pub operation: CSGOperation,
+}
+
+impl CSGPrimitive {
+    pub fn new(a: Arc<Primitive>, b: Arc<Primitive>, operation: CSGOperation) -> Self {
+        CSGPrimitive { a, b, operation }
+    }
+    /// Finds the next crossing of *primitive* along *r*, either from
+    /// the ray origin or (if given) behind the previous crossing.
+    fn next_crossing<'a>(
+        primitive: &Primitive,
+        r: &Ray,
+        previous: Option<&CSGCrossing>,
+    ) -> Option<CSGCrossing<'a>> {
+        let mut ray: Ray = Ray {
+            o: r.o,
+            d: r.d,
+            t_max: Cell::new(Float::INFINITY),
+            time: r.time,
+            medium: r.medium.clone(),
+            differential: None,
+        };
+        let mut t_base: Float = 0.0 as Float;
+        if let Some(previous) = previous {
+            // continue behind the previous crossing
+            ray.o = previous.isect.common.spawn_ray(&r.d).o;
+            t_base = vec3_dot_vec3f(&(ray.o - r.o), &r.d) / vec3_dot_vec3f(&r.d, &r.d);
+        }
+        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+        if primitive.intersect(&ray, &mut isect) {
+            Some(CSGCrossing {
+                t: t_base + ray.t_max.get(),
+                entering: nrm_dot_vec3f(&isect.common.n, &r.d) < 0.0 as Float,
+                isect,
+            })
+        } else {
+            None
+        }
+    }
+    // Primitive
+    pub fn world_bound(&self) -> Bounds3f {
+        match self.operation {
+            CSGOperation::Union => {
+                bnd3_union_bnd3f(&self.a.world_bound(), &self.b.world_bound())
+            }
+            // the result can't be larger than the first operand
+            CSGOperation::Intersection | CSGOperation::Difference => self.a.world_bound(),
+        }
+    }
+    pub fn intersect(&self, r: &Ray, isect: &mut SurfaceInteraction) -> bool {
+        let mut crossing_a: Option<CSGCrossing> = CSGPrimitive::next_crossing(&self.a, r, None);
+        let mut crossing_b: Option<CSGCrossing> = CSGPrimitive::next_crossing(&self.b, r, None);
+        // the first crossing being an exit means the ray starts inside
+        let mut inside_a: bool = matches!(crossing_a, Some(ref c) if !c.entering);
+        let mut inside_b: bool = matches!(crossing_b, Some(ref c) if !c.entering);
+        let mut inside: bool = self.operation.inside(inside_a, inside_b);
+        for _ in 0..CSG_MAX_CROSSINGS {
+            // pick the closer crossing of both operands
+            let from_a: bool = match (&crossing_a, &crossing_b) {
+                (Some(ca), Some(cb)) => ca.t <= cb.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return false,
+            };
+            let crossing: CSGCrossing = if from_a {
+                crossing_a.take().unwrap()
+            } else {
+                crossing_b.take().unwrap()
+            };
+            if crossing.t >= r.t_max.get() {
+                return false;
+            }
+            if from_a {
+                inside_a = crossing.entering;
+            } else {
+                inside_b = crossing.entering;
+            }
+            let inside_now: bool = self.operation.inside(inside_a, inside_b);
+            if inside_now != inside {
+                // the result of the boolean operation changes
+                let t: Float = crossing.t;
+                *isect = crossing.isect;
+                if !from_a && self.operation == CSGOperation::Difference {
+                    // surfaces of the subtracted operand face inwards
+                    isect.common.n = -isect.common.n;
+                    isect.shading.n = -isect.shading.n;
+                }
+                r.t_max.set(t);
+                return true;
+            }
+            inside = inside_now;
+            if from_a {
+                crossing_a = CSGPrimitive::next_crossing(&self.a, r, Some(&crossing));
+            } else {
+                crossing_b = CSGPrimitive::next_crossing(&self.b, r, Some(&crossing));
+            }
+        }
+        false
+    }
+    pub fn intersect_p(&self, r: &Ray) -> bool {
+        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+        self.intersect(&r.clone(), &mut isect)
+    }
+    pub fn get_material(&self) -> Option<Arc<Material>> {
+        None
+    }
+    pub fn get_area_light(&self) -> Option<Arc<Light>> {
+        None
+    }
+}
@@ -0,0 +1,360 @@
+//! A low-overhead profiler which attributes rendering time to phases
+//! like ray intersection, BSDF evaluation or light sampling.
+//!
+//! Each thread keeps the set of phases it is currently in as a bit
+//! mask, which a **ProfilePhase** sets when it's created and resets
+//! when it's dropped. While the profiler runs (between
+//! **init_profiler()** and **cleanup_profiler()**) a background thread
+//! periodically samples the masks of all threads, and
+//! **report_profiler_results()** prints how often each combination
+//! of phases was seen. Threads which are in no phase (e.g. waiting
+//! for others) don't contribute samples.
+
+// std
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// see stats.h
+
+/// The phases the profiler distinguishes, roughly ordered from outer
+/// to inner ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prof {
+    SceneConstruction,
+    AccelConstruction,
+    TextureLoading,
+    MIPMapCreation,
+    IntegratorRender,
+    SamplerIntegratorLi,
+    SPPMCameraPass,
+    SPPMGridConstruction,
+    SPPMPhotonPass,
+    SPPMStatsUpdate,
+    BDPTGenerateSubpath,
+    BDPTConnectSubpaths,
+    LightDistribLookup,
+    LightDistribSpinWait,
+    LightDistribCreation,
+    DirectLighting,
+    BSDFEvaluation,
+    BSDFSampling,
+    BSDFPdf,
+    BSSRDFEvaluation,
+    BSSRDFSampling,
+    PhaseFuncEvaluation,
+    PhaseFuncSampling,
+    AccelIntersect,
+    AccelIntersectP,
+    LightSample,
+    LightPdf,
+    MediumSample,
+    MediumTr,
+    TriIntersect,
+    TriIntersectP,
+    CurveIntersect,
+    CurveIntersectP,
+    ShapeIntersect,
+    ShapeIntersectP,
+    ComputeScatteringFuncs,
+    GenerateCameraRay,
+    MergeFilmTile,
+    SplatFilm,
+    AddFilmSample,
+    StartPixel,
+    GetSample,
+    TexFiltTrilerp,
+    TexFiltEWA,
+}
+
+const PROF_NAMES: [&str; 44] = [
+    "Scene parsing and creation",
+    "Acceleration structure creation",
+    "Texture loading",
+    "MIP map generation",
+    "Integrator::Render()",
+    "SamplerIntegrator::Li()",
+    "SPPM camera pass",
+    "SPPM grid construction",
+    "SPPM photon pass",
+    "SPPM stats update",
+    "BDPT subpath generation",
+    "BDPT subpath connections",
+    "SpatialLightDistribution lookup",
+    "SpatialLightDistribution spin wait",
+    "SpatialLightDistribution creation",
+    "Direct lighting",
+    "BSDF::f()",
+    "BSDF::Sample_f()",
+    "BSDF::PDF()",
+    "BSSRDF::f()",
+    "BSSRDF::Sample_f()",
+    "PhaseFunction::p()",
+    "PhaseFunction::Sample_p()",
+    "Accelerator::Intersect()",
+    "Accelerator::IntersectP()",
+    "Light::Sample_*()",
+    "Light::Pdf()",
+    "Medium::Sample()",
+    "Medium::Tr()",
+    "Triangle::Intersect()",
+    "Triangle::IntersectP()",
+    "Curve::Intersect()",
+    "Curve::IntersectP()",
+    "Other Shape::Intersect()",
+    "Other Shape::IntersectP()",
+    "Material::ComputeScatteringFunctions()",
+    "Camera::GenerateRay[Differential]()",
+    "Film::MergeTile()",
+    "Film::AddSplat()",
+    "Film::AddSample()",
+    "Sampler::StartPixelSample()",
+    "Sampler::GetSample[12]D()",
+    "MIPMap::Lookup() (trilinear)",
+    "MIPMap::Lookup() (EWA)",
+];
+
+impl Prof {
+    #[inline]
+    fn to_bits(self) -> u64 {
+        1_u64 << (self as u64)
+    }
+}
+
+/// Phases spent finding intersections with the scene geometry.
+const GEOMETRY_BITS: u64 = (1_u64 << Prof::AccelIntersect as u64)
+    | (1_u64 << Prof::AccelIntersectP as u64)
+    | (1_u64 << Prof::TriIntersect as u64)
+    | (1_u64 << Prof::TriIntersectP as u64)
+    | (1_u64 << Prof::CurveIntersect as u64)
+    | (1_u64 << Prof::CurveIntersectP as u64)
+    | (1_u64 << Prof::ShapeIntersect as u64)
+    | (1_u64 << Prof::ShapeIntersectP as u64);
+
+/// Phases spent evaluating materials, textures, lights and media.
+const SHADING_BITS: u64 = (1_u64 << Prof::DirectLighting as u64)
+    | (1_u64 << Prof::BSDFEvaluation as u64)
+    | (1_u64 << Prof::BSDFSampling as u64)
+    | (1_u64 << Prof::BSDFPdf as u64)
+    | (1_u64 << Prof::BSSRDFEvaluation as u64)
+    | (1_u64 << Prof::BSSRDFSampling as u64)
+    | (1_u64 << Prof::PhaseFuncEvaluation as u64)
+    | (1_u64 << Prof::PhaseFuncSampling as u64)
+    | (1_u64 << Prof::LightSample as u64)
+    | (1_u64 << Prof::LightPdf as u64)
+    | (1_u64 << Prof::MediumSample as u64)
+    | (1_u64 << Prof::MediumTr as u64)
+    | (1_u64 << Prof::ComputeScatteringFuncs as u64)
+    | (1_u64 << Prof::TexFiltTrilerp as u64)
+    | (1_u64 << Prof::TexFiltEWA as u64);
+
+/// How often the profiler thread samples the phases of all threads.
+const SAMPLING_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Default)]
+struct ProfilerData {
+    /// the phase masks of all threads which ever entered a phase
+    thread_states: Vec<Weak<AtomicU64>>,
+    /// how often each phase mask was seen
+    samples: HashMap<u64, u64>,
+    sampler: Option<JoinHandle<()>>,
+}
+
+lazy_static::lazy_static! {
+    static ref PROFILER_DATA: Mutex<ProfilerData> = Mutex::new(ProfilerData::default());
+}
+
+static PROFILER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static PROFILER_STATE: Arc<AtomicU64> = {
+        let state: Arc<AtomicU64> = Arc::new(AtomicU64::new(0_u64));
+        PROFILER_DATA
+            .lock()
+            .unwrap()
+            .thread_states
+            .push(Arc::downgrade(&state));
+        state
+    };
+}
+
+/// Marks the calling thread as being in phase _p_ until it's
+/// dropped. Nested phases of the same kind are counted once.
+pub struct ProfilePhase {
+    category_bit: u64,
+    reset: bool,
+}
+
+impl ProfilePhase {
+    #[inline]
+    pub fn new(p: Prof) -> Self {
+        let category_bit: u64 = p.to_bits();
+        // only the owning thread writes its state
+        let reset: bool = PROFILER_STATE.with(|state| {
+            let bits: u64 = state.load(Ordering::Relaxed);
+            if bits & category_bit == 0 {
+                state.store(bits | category_bit, Ordering::Relaxed);
+                true
+            } else {
+                false
+            }
+        });
+        ProfilePhase {
+            category_bit,
+            reset,
+        }
+    }
+}
+
+impl Drop for ProfilePhase {
+    #[inline]
+    fn drop(&mut self) {
+        if self.reset {
+            PROFILER_STATE.with(|state| {
+                state.store(
+                    state.load(Ordering::Relaxed) & !self.category_bit,
+                    Ordering::Relaxed,
+                )
+            });
+        }
+    }
+}
+
+fn sample_thread_states() {
+    while PROFILER_RUNNING.load(Ordering::Acquire) {
+        std::thread::sleep(SAMPLING_INTERVAL);
+        let mut guard = PROFILER_DATA.lock().unwrap();
+        let data: &mut ProfilerData = &mut guard;
+        // forget threads which exited
+        data.thread_states.retain(|state| state.strong_count() > 0);
+        for state in data.thread_states.iter() {
+            if let Some(state) = state.upgrade() {
+                let bits: u64 = state.load(Ordering::Relaxed);
+                if bits != 0 {
+                    *data.samples.entry(bits).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Starts sampling the phases of all threads (if not running
+/// already).
+pub fn init_profiler() {
+    let mut data = PROFILER_DATA.lock().unwrap();
+    if !PROFILER_RUNNING.swap(true, Ordering::AcqRel) {
+        data.sampler = Some(std::thread::spawn(sample_thread_states));
+    }
+}
+
+/// Stops sampling, the samples taken so far are kept for
+/// **report_profiler_results()**.
+pub fn cleanup_profiler() {
+    PROFILER_RUNNING.store(false, Ordering::Release);
+    let sampler: Option<JoinHandle<()>> = PROFILER_DATA.lock().unwrap().sampler.take();
+    if let Some(sampler) = sampler {
+        sampler.join().unwrap();
+    }
+}
+
+/// Throws away all samples taken so far.
+pub fn clear_profiler() {
+    PROFILER_DATA.lock().unwrap().samples.clear();
+}
+
+fn phase_names(bits: u64) -> String {
+    let names: Vec<&str> = PROF_NAMES
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| bits & (1_u64 << i) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    names.join(" / ")
+}
+
+/// Writes how the samples are distributed over the combinations of
+/// phases, over the single phases, and whether the time was spent
+/// intersecting the geometry or shading.
+pub fn report_profiler_results<W: Write>(dest: &mut W) -> std::io::Result<()> {
+    let data = PROFILER_DATA.lock().unwrap();
+    let overall_count: u64 = data.samples.values().sum();
+    if overall_count == 0 {
+        return Ok(());
+    }
+    let percent = |count: u64| 100.0 * count as f64 / overall_count as f64;
+    writeln!(dest, "  Profile")?;
+    let mut states: Vec<(u64, u64)> = data
+        .samples
+        .iter()
+        .map(|(bits, count)| (*bits, *count))
+        .collect();
+    states.sort_by_key(|(_, count)| Reverse(*count));
+    for (bits, count) in states.iter() {
+        writeln!(
+            dest,
+            "    {:<80} {:>5.2} % ({})",
+            phase_names(*bits),
+            percent(*count),
+            count
+        )?;
+    }
+    writeln!(dest, "  Profile (aggregate)")?;
+    let mut categories: Vec<(&str, u64)> = PROF_NAMES
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let count: u64 = states
+                .iter()
+                .filter(|(bits, _)| bits & (1_u64 << i) != 0)
+                .map(|(_, count)| count)
+                .sum();
+            (*name, count)
+        })
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    categories.sort_by_key(|(_, count)| Reverse(*count));
+    for (name, count) in categories.iter() {
+        writeln!(
+            dest,
+            "    {:<80} {:>5.2} % ({})",
+            name,
+            percent(*count),
+            count
+        )?;
+    }
+    // intersection phases win over shading phases they are nested in
+    // (e.g. shadow rays during direct lighting)
+    let mut geometry: u64 = 0;
+    let mut shading: u64 = 0;
+    for (bits, count) in states.iter() {
+        if bits & GEOMETRY_BITS != 0 {
+            geometry += count;
+        } else if bits & SHADING_BITS != 0 {
+            shading += count;
+        }
+    }
+    writeln!(dest, "  Profile (summary)")?;
+    writeln!(
+        dest,
+        "    {:<80} {:>5.2} %",
+        "Geometry (intersection)",
+        percent(geometry)
+    )?;
+    writeln!(
+        dest,
+        "    {:<80} {:>5.2} %",
+        "Shading (materials, textures, lights, media)",
+        percent(shading)
+    )?;
+    writeln!(
+        dest,
+        "    {:<80} {:>5.2} %",
+        "Other",
+        percent(overall_count - geometry - shading)
+    )?;
+    Ok(())
+}
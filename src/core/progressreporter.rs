@@ -0,0 +1,129 @@
+//! Reports the progress of long running tasks (usually rendering)
+//! on the terminal, together with the elapsed time and an estimate
+//! of the time left. Library users can register a callback via
+//! **set_progress_callback()** to receive the same information.
+
+// std
+use std::io::Stdout;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+// others
+use pbr::ProgressBar;
+
+// see progressreporter.h
+
+/// The state of a task, passed to the progress callback.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub title: String,
+    pub work_done: u64,
+    pub total_work: u64,
+    pub elapsed: Duration,
+    /// unknown until some work is done
+    pub eta: Option<Duration>,
+}
+
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+lazy_static::lazy_static! {
+    static ref PROGRESS_CALLBACK: RwLock<Option<ProgressCallback>> = RwLock::new(None);
+}
+
+/// Registers a callback which gets called whenever a
+/// **ProgressReporter** made progress (or is done). Pass _None_ to
+/// remove it again.
+pub fn set_progress_callback(callback: Option<ProgressCallback>) {
+    *PROGRESS_CALLBACK.write().unwrap() = callback;
+}
+
+pub struct ProgressReporter {
+    pub title: String,
+    pub total_work: u64,
+    start_time: Instant,
+    state: Mutex<(u64, ProgressBar<Stdout>)>,
+    callback: Option<ProgressCallback>,
+}
+
+impl ProgressReporter {
+    pub fn new(total_work: u64, title: &str) -> Self {
+        let mut bar: ProgressBar<Stdout> = ProgressBar::new(total_work);
+        // we show our own estimate as part of the message
+        bar.show_speed = false;
+        bar.show_time_left = false;
+        bar.message(&format!("{} ", title));
+        ProgressReporter {
+            title: String::from(title),
+            total_work,
+            start_time: Instant::now(),
+            state: Mutex::new((0_u64, bar)),
+            callback: PROGRESS_CALLBACK.read().unwrap().clone(),
+        }
+    }
+    /// Adds *num* units of work done, can be called from several
+    /// threads.
+    pub fn update(&self, num: u64) {
+        let progress: Progress = {
+            let mut state = self.state.lock().unwrap();
+            state.0 = (state.0 + num).min(self.total_work);
+            let progress: Progress = self.progress(state.0);
+            let message: String = match progress.eta {
+                Some(eta) => format!(
+                    "{} ({:.1}s|{:.1}s) ",
+                    self.title,
+                    progress.elapsed.as_secs_f32(),
+                    eta.as_secs_f32()
+                ),
+                None => format!(
+                    "{} ({:.1}s|?s) ",
+                    self.title,
+                    progress.elapsed.as_secs_f32()
+                ),
+            };
+            state.1.message(&message);
+            state.1.set(progress.work_done);
+            progress
+        };
+        if let Some(callback) = &self.callback {
+            callback(&progress);
+        }
+    }
+    /// Marks all the work as done and finishes the progress bar.
+    pub fn done(&self) {
+        let progress: Progress = {
+            let mut state = self.state.lock().unwrap();
+            state.0 = self.total_work;
+            let progress: Progress = self.progress(state.0);
+            state.1.message(&format!(
+                "{} ({:.1}s) ",
+                self.title,
+                progress.elapsed.as_secs_f32()
+            ));
+            state.1.set(progress.work_done);
+            state.1.finish_println("");
+            progress
+        };
+        if let Some(callback) = &self.callback {
+            callback(&progress);
+        }
+    }
+    /// Time since the reporter was created.
+    pub fn elapsed(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+    fn progress(&self, work_done: u64) -> Progress {
+        let elapsed: Duration = self.elapsed();
+        let eta: Option<Duration> = if work_done > 0 {
+            let left: u64 = self.total_work - work_done;
+            Some(elapsed.mul_f64(left as f64 / work_done as f64))
+        } else {
+            None
+        };
+        Progress {
+            title: self.title.clone(),
+            work_done,
+            total_work: self.total_work,
+            elapsed,
+            eta,
+        }
+    }
+}
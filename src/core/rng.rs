@@ -39,6 +39,24 @@ impl Rng {
         self.state = add;
         self.uniform_uint32();
     }
+    /// Skips *idelta* numbers of the sequence (in logarithmic time).
+    pub fn advance(&mut self, idelta: i64) {
+        let mut cur_mult: u64 = PCG32_MULT;
+        let mut cur_plus: u64 = self.inc;
+        let mut acc_mult: u64 = 1_u64;
+        let mut acc_plus: u64 = 0_u64;
+        let mut delta: u64 = idelta as u64;
+        while delta > 0 {
+            if delta & 1 != 0 {
+                acc_mult = acc_mult.wrapping_mul(cur_mult);
+                acc_plus = acc_plus.wrapping_mul(cur_mult).wrapping_add(cur_plus);
+            }
+            cur_plus = cur_mult.wrapping_add(1).wrapping_mul(cur_plus);
+            cur_mult = cur_mult.wrapping_mul(cur_mult);
+            delta /= 2;
+        }
+        self.state = acc_mult.wrapping_mul(self.state).wrapping_add(acc_plus);
+    }
     pub fn uniform_uint32(&mut self) -> u32 {
         let oldstate: u64 = self.state;
         // C++: state = oldstate * PCG32_MULT + inc;
@@ -6,6 +6,7 @@
 use crate::core::camera::CameraSample;
 use crate::core::geometry::{Point2f, Point2i};
 use crate::core::pbrt::Float;
+use crate::core::rng::Rng;
 use crate::integrators::mlt::MLTSampler;
 use crate::samplers::halton::HaltonSampler;
 use crate::samplers::maxmin::MaxMinDistSampler;
@@ -200,3 +201,38 @@ impl Sampler {
         }
     }
 }
+
+/// Random numbers per pixel sample, used by the pixel samplers for
+/// the dimensions they don't precompute. The numbers of a sample only
+/// depend on the pixel and the sample index, so rendering can
+/// continue at any sample of a pixel (progressive passes or a
+/// resumed checkpoint) and get the same samples as an uninterrupted
+/// render. This changes the noise compared to pbrt-v3, see the
+/// [samplers](../../samplers/index.html) module.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PixelSampleRng {
+    pixel_rng: Rng,
+    sample_rng: Rng,
+}
+
+/// Random numbers reserved for each pixel sample.
+const PIXEL_SAMPLE_RNG_STRIDE: i64 = 65536;
+
+impl PixelSampleRng {
+    /// Starts a new pixel, the sequence is chosen by *rng* (which
+    /// generates the precomputed samples).
+    pub fn start_pixel(&mut self, rng: &mut Rng) {
+        let sequence: u64 =
+            (u64::from(rng.uniform_uint32()) << 32) | u64::from(rng.uniform_uint32());
+        self.pixel_rng.set_sequence(sequence);
+        self.sample_rng = self.pixel_rng;
+    }
+    pub fn start_sample(&mut self, sample_index: i64) {
+        self.sample_rng = self.pixel_rng;
+        self.sample_rng
+            .advance(sample_index * PIXEL_SAMPLE_RNG_STRIDE);
+    }
+    pub fn uniform_float(&mut self) -> Float {
+        self.sample_rng.uniform_float()
+    }
+}
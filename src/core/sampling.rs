@@ -392,3 +392,53 @@ pub fn uniform_sample_cone(u: Point2f, cos_theta_max: Float) -> Vector3f {
 //         y: u[XYEnum::Y] * su0,
 //     }
 // }
+
+/// Sample a linear function on $[0,1]$ with values _a_ at 0 and _b_
+/// at 1.
+pub fn sample_linear(u: Float, a: Float, b: Float) -> Float {
+    if u == 0.0 as Float && a == 0.0 as Float {
+        return 0.0 as Float;
+    }
+    let x: Float = u * (a + b) / (a + (a * a * (1.0 as Float - u) + b * b * u).sqrt());
+    x.min(FLOAT_ONE_MINUS_EPSILON)
+}
+
+/// Probability density function (PDF) of a linear function on $[0,1]$.
+pub fn linear_pdf(x: Float, a: Float, b: Float) -> Float {
+    if !(0.0 as Float..=1.0 as Float).contains(&x) {
+        return 0.0 as Float;
+    }
+    2.0 as Float * (a * (1.0 as Float - x) + b * x) / (a + b)
+}
+
+/// Sample a bilinear function on $[0,1]^2$ given by its four corner
+/// values _w_ at (0,0), (1,0), (0,1) and (1,1).
+pub fn sample_bilinear(u: Point2f, w: &[Float; 4]) -> Point2f {
+    // sample $y$ for bilinear marginal distribution
+    let y: Float = sample_linear(u[XYEnum::Y], w[0] + w[1], w[2] + w[3]);
+    // sample $x$ for bilinear conditional distribution
+    let x: Float = sample_linear(
+        u[XYEnum::X],
+        w[0] * (1.0 as Float - y) + w[2] * y,
+        w[1] * (1.0 as Float - y) + w[3] * y,
+    );
+    Point2f { x, y }
+}
+
+/// Probability density function (PDF) of a bilinear function on
+/// $[0,1]^2$.
+pub fn bilinear_pdf(p: Point2f, w: &[Float; 4]) -> Float {
+    if p.x < 0.0 as Float || p.x > 1.0 as Float || p.y < 0.0 as Float || p.y > 1.0 as Float {
+        return 0.0 as Float;
+    }
+    let sum: Float = w[0] + w[1] + w[2] + w[3];
+    if sum == 0.0 as Float {
+        return 1.0 as Float;
+    }
+    4.0 as Float
+        * ((1.0 as Float - p.x) * (1.0 as Float - p.y) * w[0]
+            + p.x * (1.0 as Float - p.y) * w[1]
+            + (1.0 as Float - p.x) * p.y * w[2]
+            + p.x * p.y * w[3])
+        / sum
+}
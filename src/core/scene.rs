@@ -6,12 +6,15 @@
 // std
 use std::sync::Arc;
 // pbrt
+use crate::accelerators::bvh::{BVHAccel, PACKET_SIZE};
 use crate::core::geometry::{Bounds3f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, SurfaceInteraction};
 use crate::core::light::{Light, LightFlags};
 use crate::core::pbrt::Spectrum;
 use crate::core::primitive::Primitive;
 use crate::core::sampler::Sampler;
+use crate::core::stats::{self, Counter};
+use crate::core::transform::AnimatedTransform;
 
 // see scene.h
 
@@ -52,8 +55,42 @@ impl Scene {
     pub fn world_bound(&self) -> &Bounds3f {
         &self.world_bound
     }
+    /// Moves an instance of the top-level BVH to render another frame
+    /// of an animation (see _BVHAccel::set_instance_transform()_).
+    /// The scene description numbers the instances (and shapes with
+    /// animated transformations) in the order they were created.
+    /// Call _refit()_ after all instances of a frame were moved.
+    pub fn set_instance_transform(
+        &mut self,
+        instance: usize,
+        primitive_to_world: &AnimatedTransform,
+    ) -> bool {
+        if let Some(bvh) = self.get_bvh_mut() {
+            bvh.set_instance_transform(instance, primitive_to_world)
+        } else {
+            println!("WARNING: Scene::set_instance_transform() needs an unshared BVH aggregate");
+            false
+        }
+    }
+    /// Updates the bounds of the top-level BVH, the scene and the
+    /// lights after instances were moved.
+    pub fn refit(&mut self) {
+        if let Some(bvh) = self.get_bvh_mut() {
+            bvh.refit();
+        }
+        self.world_bound = self.aggregate.world_bound();
+        for light in self.lights.iter() {
+            light.preprocess(self);
+        }
+    }
+    fn get_bvh_mut(&mut self) -> Option<&mut BVHAccel> {
+        match Arc::get_mut(&mut self.aggregate) {
+            Some(Primitive::BVH(bvh)) => Some(bvh),
+            _ => None,
+        }
+    }
     pub fn intersect(&self, ray: &Ray, isect: &mut SurfaceInteraction) -> bool {
-        // TODO: ++nIntersectionTests;
+        stats::add_counter(Counter::IntersectionTests, 1);
         assert_ne!(
             ray.d,
             Vector3f {
@@ -65,7 +102,7 @@ impl Scene {
         self.aggregate.intersect(ray, isect)
     }
     pub fn intersect_p(&self, ray: &mut Ray) -> bool {
-        // TODO: ++nShadowTests;
+        stats::add_counter(Counter::ShadowTests, 1);
         assert_ne!(
             ray.d,
             Vector3f {
@@ -76,6 +113,47 @@ impl Scene {
         );
         self.aggregate.intersect_p(ray)
     }
+    /// Finds the closest intersection of each ray. Consecutive rays
+    /// are traced as packets through a binary BVH, so neighbouring
+    /// rays should be coherent (see _BVHAccel::intersect_packet()_).
+    pub fn intersect_many(&self, rays: &[Ray]) -> Vec<Option<SurfaceInteraction<'_>>> {
+        let mut result: Vec<Option<SurfaceInteraction>> = Vec::with_capacity(rays.len());
+        for packet in rays.chunks(PACKET_SIZE) {
+            let mut isects: Vec<SurfaceInteraction> = packet
+                .iter()
+                .map(|_ray| SurfaceInteraction::default())
+                .collect();
+            let mut hits: [bool; PACKET_SIZE] = [false; PACKET_SIZE];
+            if let Primitive::BVH(ref bvh) = *self.aggregate {
+                bvh.intersect_packet(packet, &mut isects, &mut hits);
+            } else {
+                for (r, ray) in packet.iter().enumerate() {
+                    hits[r] = self.intersect(ray, &mut isects[r]);
+                }
+            }
+            for (isect, hit) in isects.into_iter().zip(hits.iter()) {
+                result.push(if *hit { Some(isect) } else { None });
+            }
+        }
+        result
+    }
+    /// Occlusion tests for a batch of (shadow) rays, see
+    /// _intersect_many()_.
+    pub fn intersect_p_many(&self, rays: &[Ray]) -> Vec<bool> {
+        let mut result: Vec<bool> = Vec::with_capacity(rays.len());
+        for packet in rays.chunks(PACKET_SIZE) {
+            let mut occluded: [bool; PACKET_SIZE] = [false; PACKET_SIZE];
+            if let Primitive::BVH(ref bvh) = *self.aggregate {
+                bvh.intersect_p_packet(packet, &mut occluded);
+            } else {
+                for (r, ray) in packet.iter().enumerate() {
+                    occluded[r] = self.aggregate.intersect_p(ray);
+                }
+            }
+            result.extend_from_slice(&occluded[..packet.len()]);
+        }
+        result
+    }
     pub fn intersect_tr(
         &self,
         ray: &mut Ray,
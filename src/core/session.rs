@@ -0,0 +1,186 @@
+//! A **RenderSession** renders a scene on background threads, so an
+//! application embedding the renderer stays responsive. The
+//! application gets notified whenever an image tile was merged into
+//! the **Film**, can read the current image as linear RGB values at
+//! any time, and can pause, resume or cancel the render.
+//!
+//! Pausing and cancelling take effect between tiles: tiles which are
+//! being rendered are finished (and merged) first. A cancelled render
+//! doesn't write the final image.
+
+// std
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+// pbrt
+use crate::core::film::Film;
+use crate::core::geometry::{Bounds2i, Vector2i};
+use crate::core::integrator::{Integrator, RenderSettings};
+use crate::core::pbrt::Float;
+use crate::core::scene::Scene;
+
+/// Gets called with the pixel bounds of each tile after it was
+/// merged into the **Film**.
+pub type TileCallback = Arc<dyn Fn(&Bounds2i) + Send + Sync>;
+
+/// The state shared between a **RenderSession** and the render loop
+/// of the integrator (see **RenderSettings**).
+pub struct RenderControl {
+    cancelled: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    on_tile: Option<TileCallback>,
+}
+
+impl RenderControl {
+    pub fn new(on_tile: Option<TileCallback>) -> Self {
+        RenderControl {
+            cancelled: AtomicBool::new(false),
+            paused: Mutex::new(false),
+            resumed: Condvar::new(),
+            on_tile,
+        }
+    }
+    pub fn cancel(&self) {
+        // hold the lock, so a waiting thread can't miss the wake up
+        let _paused = self.paused.lock().unwrap();
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.resumed.notify_all();
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+    pub fn set_paused(&self, paused: bool) {
+        *self.paused.lock().unwrap() = paused;
+        self.resumed.notify_all();
+    }
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+    /// Blocks the calling thread while the render is paused (and not
+    /// cancelled).
+    pub fn wait_while_paused(&self) {
+        let mut paused = self.paused.lock().unwrap();
+        while *paused && !self.is_cancelled() {
+            paused = self.resumed.wait(paused).unwrap();
+        }
+    }
+    /// Calls the tile callback (if any) for a tile which was merged
+    /// into the **Film**.
+    pub fn tile_done(&self, pixel_bounds: &Bounds2i) {
+        if let Some(ref on_tile) = self.on_tile {
+            on_tile(pixel_bounds);
+        }
+    }
+}
+
+impl fmt::Debug for RenderControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RenderControl")
+            .field("cancelled", &self.is_cancelled())
+            .field("paused", &self.is_paused())
+            .field("on_tile", &self.on_tile.is_some())
+            .finish()
+    }
+}
+
+/// Owns the scene and the integrator while they render on background
+/// threads. Dropping the session cancels the render and waits for
+/// it. Only the [SamplerIntegrators](../integrator/enum.SamplerIntegrator.html)
+/// are supported: the other integrators can't be paused or cancelled,
+/// don't report their tiles and scale their splats when they write
+/// the final image (e.g. MLT only knows the scale after its
+/// bootstrap phase), so **get_rgb()** couldn't return their image.
+pub struct RenderSession {
+    film: Arc<Film>,
+    control: Arc<RenderControl>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RenderSession {
+    /// Starts rendering *scene* with *integrator* and returns
+    /// immediately. The *settings* work like for
+    /// **Integrator::render_with_settings()**. Fails for integrators
+    /// other than **Integrator::Sampler**.
+    pub fn new(
+        scene: Scene,
+        mut integrator: Box<Integrator>,
+        num_threads: u8,
+        settings: &RenderSettings,
+        on_tile: Option<TileCallback>,
+    ) -> Result<Self, String> {
+        if !matches!(*integrator, Integrator::Sampler(_)) {
+            return Err(String::from(
+                "Render sessions only support sampler integrators (e.g. \"path\")",
+            ));
+        }
+        let film: Arc<Film> = integrator.get_camera().get_film();
+        let control: Arc<RenderControl> = Arc::new(RenderControl::new(on_tile));
+        let mut settings: RenderSettings = settings.clone();
+        settings.control = Some(control.clone());
+        let thread: JoinHandle<()> = thread::Builder::new()
+            .name(String::from("render session"))
+            .spawn(move || integrator.render_with_settings(&scene, num_threads, &settings))
+            .unwrap_or_else(|e| panic!("Failed to spawn render thread: {}", e));
+        Ok(RenderSession {
+            film,
+            control,
+            thread: Some(thread),
+        })
+    }
+    /// Stops the render threads before they start their next tile.
+    pub fn pause(&self) {
+        self.control.set_paused(true);
+    }
+    pub fn resume(&self) {
+        self.control.set_paused(false);
+    }
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+    /// Stops the render after the current tiles, use **wait()** to
+    /// wait for them.
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+    /// Whether the render is complete (or was cancelled and has
+    /// stopped).
+    pub fn is_finished(&self) -> bool {
+        match self.thread {
+            Some(ref thread) => thread.is_finished(),
+            None => true,
+        }
+    }
+    /// Blocks until the render is complete (or was cancelled and has
+    /// stopped).
+    pub fn wait(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                println!("ERROR: The render thread panicked");
+            }
+        }
+    }
+    pub fn get_film(&self) -> Arc<Film> {
+        self.film.clone()
+    }
+    /// Width and height of the image returned by **get_rgb()**.
+    pub fn get_resolution(&self) -> Vector2i {
+        self.film.cropped_pixel_bounds.diagonal()
+    }
+    /// The current image as linear RGB values, row by row and three
+    /// values per pixel. Pixels without samples are black.
+    pub fn get_rgb(&self) -> Vec<Float> {
+        self.film.get_rgb(1.0 as Float)
+    }
+}
+
+impl Drop for RenderSession {
+    fn drop(&mut self) {
+        self.cancel();
+        self.wait();
+    }
+}
@@ -5,112 +5,263 @@
 //! renderer can use this interface without needing any details about
 //! the underlying shape. This makes it possible to separate the
 //! geometric and the shading subsystem of pbrt.
+//!
+//! Library users can add their own geometry by implementing the
+//! **CustomShape** trait (see `examples/shapes_custom_shape.rs`) and
+//! wrapping it in `Shape::Custom`.
 
+// std
+use std::sync::Arc;
 // pbrt
+use crate::core::geometry::{nrm_abs_dot_vec3f, pnt3_distance_squaredf};
 use crate::core::geometry::{Bounds3f, Point2f, Ray, Vector3f};
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::pbrt::Float;
+use crate::core::stats;
 use crate::core::transform::Transform;
+use crate::shapes::bilinearpatch::BilinearPatch;
 use crate::shapes::curve::Curve;
 use crate::shapes::cylinder::Cylinder;
 use crate::shapes::disk::Disk;
+use crate::shapes::points::PointCloud;
+use crate::shapes::sdf::SignedDistanceField;
 use crate::shapes::sphere::Sphere;
-use crate::shapes::triangle::Triangle;
+use crate::shapes::triangle::{CompactTriangleMesh, Triangle};
 
 // see shape.h
 
+/// Geometry implemented outside of this crate. Implementations are
+/// wrapped in _Shape::Custom_ and can be used like any built-in
+/// shape, e.g. by _pbrt_register_shape()_ for the scene description.
+/// Everything is expected in world space, like for the built-in
+/// shapes; the default methods derive the remaining functionality
+/// from the required ones.
+pub trait CustomShape: Send + Sync {
+    fn object_bound(&self) -> Bounds3f;
+    fn get_object_to_world(&self) -> Transform;
+    fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool;
+    fn area(&self) -> Float;
+    /// Samples a point on the surface, *pdf* is with respect to area.
+    fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon;
+    fn world_bound(&self) -> Bounds3f {
+        self.get_object_to_world()
+            .transform_bounds(&self.object_bound())
+    }
+    fn intersect_p(&self, r: &Ray) -> bool {
+        let mut t_hit: Float = 0.0;
+        let mut isect: SurfaceInteraction = SurfaceInteraction::default();
+        self.intersect(r, &mut t_hit, &mut isect)
+    }
+    fn get_reverse_orientation(&self) -> bool {
+        false
+    }
+    fn get_transform_swaps_handedness(&self) -> bool {
+        self.get_object_to_world().swaps_handedness()
+    }
+    /// Area density of _sample()_ returning the point *iref* on the
+    /// surface (e.g. the origin of a ray leaving an area light, which
+    /// may be offset slightly from the surface). Override it if the
+    /// points aren't distributed uniformly by area.
+    fn pdf(&self, _iref: &InteractionCommon) -> Float {
+        1.0 as Float / self.area()
+    }
+    fn sample_with_ref_point(
+        &self,
+        iref: &InteractionCommon,
+        u: Point2f,
+        pdf: &mut Float,
+    ) -> InteractionCommon {
+        let intr: InteractionCommon = self.sample(u, pdf);
+        let mut wi: Vector3f = intr.p - iref.p;
+        if wi.length_squared() == 0.0 as Float {
+            *pdf = 0.0 as Float;
+        } else {
+            wi = wi.normalize();
+            // convert from area measure, as returned by the Sample()
+            // call above, to solid angle measure.
+            *pdf *= pnt3_distance_squaredf(&iref.p, &intr.p) / nrm_abs_dot_vec3f(&intr.n, &-wi);
+            if (*pdf).is_infinite() {
+                *pdf = 0.0 as Float;
+            }
+        }
+        intr
+    }
+    fn pdf_with_ref_point(&self, iref: &dyn Interaction, wi: &Vector3f) -> Float {
+        // intersect sample ray with area light geometry
+        let ray: Ray = iref.spawn_ray(wi);
+        let mut t_hit: Float = 0.0;
+        let mut isect_light: SurfaceInteraction = SurfaceInteraction::default();
+        if self.intersect(&ray, &mut t_hit, &mut isect_light) {
+            // convert light sample weight to solid angle measure
+            let mut pdf: Float = pnt3_distance_squaredf(iref.get_p(), &isect_light.common.p)
+                / nrm_abs_dot_vec3f(&isect_light.common.n, &-(*wi))
+                * self.pdf(&isect_light.common);
+            if pdf.is_infinite() {
+                pdf = 0.0 as Float;
+            }
+            pdf
+        } else {
+            0.0 as Float
+        }
+    }
+}
+
 pub enum Shape {
+    Blnr(BilinearPatch),
     Crv(Curve),
     Clndr(Cylinder),
     Dsk(Disk),
+    Pnts(PointCloud),
+    Sdf(SignedDistanceField),
     Sphr(Sphere),
     Trngl(Triangle),
+    TrnglMsh(CompactTriangleMesh),
+    Custom(Arc<dyn CustomShape>),
 }
 
 impl Shape {
     pub fn object_bound(&self) -> Bounds3f {
         match self {
+            Shape::Blnr(shape) => shape.object_bound(),
             Shape::Crv(shape) => shape.object_bound(),
             Shape::Clndr(shape) => shape.object_bound(),
             Shape::Dsk(shape) => shape.object_bound(),
+            Shape::Custom(shape) => shape.object_bound(),
+            Shape::TrnglMsh(shape) => shape.object_bound(),
+            Shape::Pnts(shape) => shape.object_bound(),
+            Shape::Sdf(shape) => shape.object_bound(),
             Shape::Sphr(shape) => shape.object_bound(),
             Shape::Trngl(shape) => shape.object_bound(),
         }
     }
     pub fn world_bound(&self) -> Bounds3f {
         match self {
+            Shape::Blnr(shape) => shape.world_bound(),
             Shape::Crv(shape) => shape.world_bound(),
             Shape::Clndr(shape) => shape.world_bound(),
             Shape::Dsk(shape) => shape.world_bound(),
+            Shape::Custom(shape) => shape.world_bound(),
+            Shape::TrnglMsh(shape) => shape.world_bound(),
+            Shape::Pnts(shape) => shape.world_bound(),
+            Shape::Sdf(shape) => shape.world_bound(),
             Shape::Sphr(shape) => shape.world_bound(),
             Shape::Trngl(shape) => shape.world_bound(),
         }
     }
     pub fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        self.count_test();
         match self {
+            Shape::Blnr(shape) => shape.intersect(r, t_hit, isect),
             Shape::Crv(shape) => shape.intersect(r, t_hit, isect),
             Shape::Clndr(shape) => shape.intersect(r, t_hit, isect),
             Shape::Dsk(shape) => shape.intersect(r, t_hit, isect),
+            Shape::Custom(shape) => shape.intersect(r, t_hit, isect),
+            Shape::TrnglMsh(shape) => shape.intersect(r, t_hit, isect),
+            Shape::Pnts(shape) => shape.intersect(r, t_hit, isect),
+            Shape::Sdf(shape) => shape.intersect(r, t_hit, isect),
             Shape::Sphr(shape) => shape.intersect(r, t_hit, isect),
             Shape::Trngl(shape) => shape.intersect(r, t_hit, isect),
         }
     }
     pub fn intersect_p(&self, r: &Ray) -> bool {
+        self.count_test();
         match self {
+            Shape::Blnr(shape) => shape.intersect_p(r),
             Shape::Crv(shape) => shape.intersect_p(r),
             Shape::Clndr(shape) => shape.intersect_p(r),
             Shape::Dsk(shape) => shape.intersect_p(r),
+            Shape::Custom(shape) => shape.intersect_p(r),
+            Shape::TrnglMsh(shape) => shape.intersect_p(r),
+            Shape::Pnts(shape) => shape.intersect_p(r),
+            Shape::Sdf(shape) => shape.intersect_p(r),
             Shape::Sphr(shape) => shape.intersect_p(r),
             Shape::Trngl(shape) => shape.intersect_p(r),
         }
     }
+    fn count_test(&self) {
+        match self {
+            // curves and triangles (of meshes) count their own tests
+            Shape::Crv(_) | Shape::TrnglMsh(_) | Shape::Trngl(_) => {}
+            _ => stats::count_primitive_test(),
+        }
+    }
     pub fn get_reverse_orientation(&self) -> bool {
         match self {
+            Shape::Blnr(shape) => shape.get_reverse_orientation(),
             Shape::Crv(shape) => shape.get_reverse_orientation(),
             Shape::Clndr(shape) => shape.get_reverse_orientation(),
             Shape::Dsk(shape) => shape.get_reverse_orientation(),
+            Shape::Custom(shape) => shape.get_reverse_orientation(),
+            Shape::TrnglMsh(shape) => shape.get_reverse_orientation(),
+            Shape::Pnts(shape) => shape.get_reverse_orientation(),
+            Shape::Sdf(shape) => shape.get_reverse_orientation(),
             Shape::Sphr(shape) => shape.get_reverse_orientation(),
             Shape::Trngl(shape) => shape.get_reverse_orientation(),
         }
     }
     pub fn get_transform_swaps_handedness(&self) -> bool {
         match self {
+            Shape::Blnr(shape) => shape.get_transform_swaps_handedness(),
             Shape::Crv(shape) => shape.get_transform_swaps_handedness(),
             Shape::Clndr(shape) => shape.get_transform_swaps_handedness(),
             Shape::Dsk(shape) => shape.get_transform_swaps_handedness(),
+            Shape::Custom(shape) => shape.get_transform_swaps_handedness(),
+            Shape::TrnglMsh(shape) => shape.get_transform_swaps_handedness(),
+            Shape::Pnts(shape) => shape.get_transform_swaps_handedness(),
+            Shape::Sdf(shape) => shape.get_transform_swaps_handedness(),
             Shape::Sphr(shape) => shape.get_transform_swaps_handedness(),
             Shape::Trngl(shape) => shape.get_transform_swaps_handedness(),
         }
     }
     pub fn get_object_to_world(&self) -> Transform {
         match self {
+            Shape::Blnr(shape) => shape.get_object_to_world(),
             Shape::Crv(shape) => shape.get_object_to_world(),
             Shape::Clndr(shape) => shape.get_object_to_world(),
             Shape::Dsk(shape) => shape.get_object_to_world(),
+            Shape::Custom(shape) => shape.get_object_to_world(),
+            Shape::TrnglMsh(shape) => shape.get_object_to_world(),
+            Shape::Pnts(shape) => shape.get_object_to_world(),
+            Shape::Sdf(shape) => shape.get_object_to_world(),
             Shape::Sphr(shape) => shape.get_object_to_world(),
             Shape::Trngl(shape) => shape.get_object_to_world(),
         }
     }
     pub fn area(&self) -> Float {
         match self {
+            Shape::Blnr(shape) => shape.area(),
             Shape::Crv(shape) => shape.area(),
             Shape::Clndr(shape) => shape.area(),
             Shape::Dsk(shape) => shape.area(),
+            Shape::Custom(shape) => shape.area(),
+            Shape::TrnglMsh(shape) => shape.area(),
+            Shape::Pnts(shape) => shape.area(),
+            Shape::Sdf(shape) => shape.area(),
             Shape::Sphr(shape) => shape.area(),
             Shape::Trngl(shape) => shape.area(),
         }
     }
     pub fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
         match self {
+            Shape::Blnr(shape) => shape.sample(u, pdf),
             Shape::Crv(shape) => shape.sample(u, pdf),
             Shape::Clndr(shape) => shape.sample(u, pdf),
             Shape::Dsk(shape) => shape.sample(u, pdf),
+            Shape::Custom(shape) => shape.sample(u, pdf),
+            Shape::TrnglMsh(shape) => shape.sample(u, pdf),
+            Shape::Pnts(shape) => shape.sample(u, pdf),
+            Shape::Sdf(shape) => shape.sample(u, pdf),
             Shape::Sphr(shape) => shape.sample(u, pdf),
             Shape::Trngl(shape) => shape.sample(u, pdf),
         }
     }
-    pub fn pdf(&self, _iref: &InteractionCommon) -> Float {
-        1.0 as Float / self.area()
+    pub fn pdf(&self, iref: &InteractionCommon) -> Float {
+        match self {
+            Shape::Blnr(shape) => shape.pdf(iref),
+            Shape::Custom(shape) => shape.pdf(iref),
+            Shape::Pnts(shape) => shape.pdf(iref),
+            _ => 1.0 as Float / self.area(),
+        }
     }
     pub fn sample_with_ref_point(
         &self,
@@ -119,18 +270,28 @@ impl Shape {
         pdf: &mut Float,
     ) -> InteractionCommon {
         match self {
+            Shape::Blnr(shape) => shape.sample_with_ref_point(iref, u, pdf),
             Shape::Crv(shape) => shape.sample_with_ref_point(iref, u, pdf),
             Shape::Clndr(shape) => shape.sample_with_ref_point(iref, u, pdf),
             Shape::Dsk(shape) => shape.sample_with_ref_point(iref, u, pdf),
+            Shape::Custom(shape) => shape.sample_with_ref_point(iref, u, pdf),
+            Shape::TrnglMsh(shape) => shape.sample_with_ref_point(iref, u, pdf),
+            Shape::Pnts(shape) => shape.sample_with_ref_point(iref, u, pdf),
+            Shape::Sdf(shape) => shape.sample_with_ref_point(iref, u, pdf),
             Shape::Sphr(shape) => shape.sample_with_ref_point(iref, u, pdf),
             Shape::Trngl(shape) => shape.sample_with_ref_point(iref, u, pdf),
         }
     }
     pub fn pdf_with_ref_point(&self, iref: &dyn Interaction, wi: &Vector3f) -> Float {
         match self {
+            Shape::Blnr(shape) => shape.pdf_with_ref_point(iref, wi),
             Shape::Crv(shape) => shape.pdf_with_ref_point(iref, wi),
             Shape::Clndr(shape) => shape.pdf_with_ref_point(iref, wi),
             Shape::Dsk(shape) => shape.pdf_with_ref_point(iref, wi),
+            Shape::Custom(shape) => shape.pdf_with_ref_point(iref, wi),
+            Shape::TrnglMsh(shape) => shape.pdf_with_ref_point(iref, wi),
+            Shape::Pnts(shape) => shape.pdf_with_ref_point(iref, wi),
+            Shape::Sdf(shape) => shape.pdf_with_ref_point(iref, wi),
             Shape::Sphr(shape) => shape.pdf_with_ref_point(iref, wi),
             Shape::Trngl(shape) => shape.pdf_with_ref_point(iref, wi),
         }
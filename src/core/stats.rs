@@ -0,0 +1,456 @@
+//! Statistics gathered while rendering. The counters are kept per
+//! thread, so updating them in the inner loops of the ray
+//! intersection code needs no synchronization. The values of a
+//! thread are merged into a global accumulator when the thread exits
+//! (or calls **report_thread_stats()**), and **print_stats()** writes
+//! them as a report similar to the one of the C++ version.
+
+// std
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::Mutex;
+
+// see stats.h
+
+macro_rules! stats_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident => $title:expr,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $($variant,)*
+        }
+
+        impl $name {
+            const ALL: &'static [$name] = &[$($name::$variant,)*];
+            /// The "category/title" used in the statistics report.
+            pub fn title(self) -> &'static str {
+                match self {
+                    $($name::$variant => $title,)*
+                }
+            }
+        }
+    };
+}
+
+stats_enum! {
+    /// Counts events, like `STAT_COUNTER` in the C++ version.
+    Counter {
+        CameraRays => "Integrator/Camera rays traced",
+        IntersectionTests => "Intersections/Regular ray intersection tests",
+        ShadowTests => "Intersections/Shadow ray intersection tests",
+        ObjectInstancesUsed => "Scene/Object instances used",
+        SplitCurves => "Scene/Split curves",
+        TrilinearLookups => "Texture/Trilinear MIPMap lookups",
+        EWALookups => "Texture/EWA lookups",
+        VolumeInteractions => "Integrator/Volume interactions",
+        SurfaceInteractions => "Integrator/Surface interactions",
+        PhotonSurfaceInteractions => "Stochastic Progressive Photon Mapping/Total Photon Surface Interactions",
+    }
+}
+
+stats_enum! {
+    /// Counts bytes, like `STAT_MEMORY_COUNTER` in the C++ version.
+    Memory {
+        Curves => "Memory/Curves",
+        MipMaps => "Memory/Texture MIP maps",
+        SPPMPixels => "Memory/SPPM Pixels",
+    }
+}
+
+stats_enum! {
+    /// A numerator and a denominator reported as a percentage, like
+    /// `STAT_PERCENT` in the C++ version.
+    Percent {
+        TriangleHits => "Intersections/Ray-triangle intersection tests",
+        CurveHits => "Intersections/Ray-curve intersection tests",
+        AcceptedMutations => "Integrator/Acceptance rate",
+        ZeroRadiancePaths => "Integrator/Zero-radiance paths",
+    }
+}
+
+stats_enum! {
+    /// A numerator and a denominator reported as their quotient, like
+    /// `STAT_RATIO` in the C++ version.
+    Ratio {
+        GridStepsPerTrCall => "Media/Grid steps per Tr() call",
+        LookupsPerDistribution => "SpatialLightDistribution/Lookups per distribution",
+        VisiblePointsChecked => "Stochastic Progressive Photon Mapping/Visible points checked per photon intersection",
+    }
+}
+
+stats_enum! {
+    /// Integer values reported with their average, minimum and
+    /// maximum, like `STAT_INT_DISTRIBUTION` in the C++ version.
+    IntDistribution {
+        PathLength => "Integrator/Path length",
+        ProbesPerLookup => "SpatialLightDistribution/Hash probes per lookup",
+    }
+}
+
+const N_COUNTERS: usize = Counter::ALL.len();
+const N_MEMORY: usize = Memory::ALL.len();
+const N_PERCENTS: usize = Percent::ALL.len();
+const N_RATIOS: usize = Ratio::ALL.len();
+const N_DISTRIBUTIONS: usize = IntDistribution::ALL.len();
+
+#[derive(Debug, Clone, Copy)]
+struct DistributionSummary {
+    count: u64,
+    sum: i64,
+    min: i64,
+    max: i64,
+}
+
+impl Default for DistributionSummary {
+    fn default() -> Self {
+        DistributionSummary {
+            count: 0_u64,
+            sum: 0_i64,
+            min: i64::MAX,
+            max: i64::MIN,
+        }
+    }
+}
+
+impl DistributionSummary {
+    fn add(&mut self, value: i64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+    fn merge(&mut self, other: &DistributionSummary) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// The statistics of all threads which already reported theirs.
+#[derive(Debug, Default)]
+struct StatsAccumulator {
+    counters: [u64; N_COUNTERS],
+    memory: [u64; N_MEMORY],
+    percents: [(u64, u64); N_PERCENTS],
+    ratios: [(u64, u64); N_RATIOS],
+    distributions: [DistributionSummary; N_DISTRIBUTIONS],
+}
+
+lazy_static::lazy_static! {
+    static ref STATS_ACCUMULATOR: Mutex<StatsAccumulator> = Mutex::new(StatsAccumulator::default());
+}
+
+/// The statistics of one thread, merged into the accumulator when
+/// the thread exits.
+struct ThreadStats {
+    counters: [Cell<u64>; N_COUNTERS],
+    memory: [Cell<u64>; N_MEMORY],
+    percents: [(Cell<u64>, Cell<u64>); N_PERCENTS],
+    ratios: [(Cell<u64>, Cell<u64>); N_RATIOS],
+    distributions: [Cell<DistributionSummary>; N_DISTRIBUTIONS],
+}
+
+impl ThreadStats {
+    fn new() -> Self {
+        ThreadStats {
+            counters: Default::default(),
+            memory: Default::default(),
+            percents: Default::default(),
+            ratios: Default::default(),
+            distributions: Default::default(),
+        }
+    }
+    fn report(&self) {
+        let mut guard = STATS_ACCUMULATOR.lock().unwrap();
+        let accum: &mut StatsAccumulator = &mut guard;
+        for (sum, value) in accum.counters.iter_mut().zip(self.counters.iter()) {
+            *sum += value.replace(0_u64);
+        }
+        for (sum, value) in accum.memory.iter_mut().zip(self.memory.iter()) {
+            *sum += value.replace(0_u64);
+        }
+        for (sum, value) in accum
+            .percents
+            .iter_mut()
+            .chain(accum.ratios.iter_mut())
+            .zip(self.percents.iter().chain(self.ratios.iter()))
+        {
+            sum.0 += value.0.replace(0_u64);
+            sum.1 += value.1.replace(0_u64);
+        }
+        for (sum, value) in accum
+            .distributions
+            .iter_mut()
+            .zip(self.distributions.iter())
+        {
+            sum.merge(&value.take());
+        }
+    }
+}
+
+impl Drop for ThreadStats {
+    fn drop(&mut self) {
+        self.report();
+    }
+}
+
+thread_local! {
+    static THREAD_STATS: ThreadStats = ThreadStats::new();
+    static NODE_VISITS: Cell<u64> = const { Cell::new(0_u64) };
+    static PRIMITIVE_TESTS: Cell<u64> = const { Cell::new(0_u64) };
+}
+
+/// Adds *n* to a counter.
+#[inline]
+pub fn add_counter(counter: Counter, n: u64) {
+    THREAD_STATS.with(|s| {
+        let c: &Cell<u64> = &s.counters[counter as usize];
+        c.set(c.get() + n)
+    });
+}
+
+/// Adds *bytes* to a memory counter.
+#[inline]
+pub fn add_memory(memory: Memory, bytes: u64) {
+    THREAD_STATS.with(|s| {
+        let c: &Cell<u64> = &s.memory[memory as usize];
+        c.set(c.get() + bytes)
+    });
+}
+
+/// Adds to the numerator and the denominator of a percentage,
+/// usually one of them is zero.
+#[inline]
+pub fn add_percent(percent: Percent, numerator: u64, denominator: u64) {
+    THREAD_STATS.with(|s| {
+        let (num, denom) = &s.percents[percent as usize];
+        num.set(num.get() + numerator);
+        denom.set(denom.get() + denominator);
+    });
+}
+
+/// Adds to the numerator and the denominator of a ratio, usually one
+/// of them is zero.
+#[inline]
+pub fn add_ratio(ratio: Ratio, numerator: u64, denominator: u64) {
+    THREAD_STATS.with(|s| {
+        let (num, denom) = &s.ratios[ratio as usize];
+        num.set(num.get() + numerator);
+        denom.set(denom.get() + denominator);
+    });
+}
+
+/// Adds one value to a distribution.
+#[inline]
+pub fn report_value(distribution: IntDistribution, value: i64) {
+    THREAD_STATS.with(|s| {
+        let d: &Cell<DistributionSummary> = &s.distributions[distribution as usize];
+        let mut summary: DistributionSummary = d.get();
+        summary.add(value);
+        d.set(summary);
+    });
+}
+
+/// Merges the statistics of the calling thread into the global
+/// accumulator. Threads do that when they exit, but threads of a
+/// pool (e.g. rayon's) never exit and have to call this explicitly.
+pub fn report_thread_stats() {
+    THREAD_STATS.with(|s| s.report());
+}
+
+/// Resets all statistics reported so far.
+pub fn clear_stats() {
+    *STATS_ACCUMULATOR.lock().unwrap() = StatsAccumulator::default();
+}
+
+fn split_title(title: &str) -> (&str, &str) {
+    match title.find('/') {
+        Some(pos) => (&title[..pos], &title[pos + 1..]),
+        None => ("", title),
+    }
+}
+
+fn format_memory(bytes: u64) -> String {
+    let kb: f64 = bytes as f64 / 1024.0;
+    if kb < 1024.0 {
+        format!("{:.2} kB", kb)
+    } else if kb < 1024.0 * 1024.0 {
+        format!("{:.2} MiB", kb / 1024.0)
+    } else {
+        format!("{:.2} GiB", kb / (1024.0 * 1024.0))
+    }
+}
+
+/// Writes the statistics of all threads which reported theirs,
+/// grouped by category. Statistics which were never touched are
+/// omitted.
+pub fn print_stats<W: Write>(dest: &mut W) -> std::io::Result<()> {
+    let accum = STATS_ACCUMULATOR.lock().unwrap();
+    let mut to_print: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut add = |title: &'static str, line: String| {
+        let (category, title) = split_title(title);
+        to_print
+            .entry(category)
+            .or_default()
+            .push(format!("{:<42}{}", title, line));
+    };
+    for (counter, value) in Counter::ALL.iter().zip(accum.counters.iter()) {
+        if *value > 0 {
+            add(counter.title(), format!("{:>12}", value));
+        }
+    }
+    for (memory, value) in Memory::ALL.iter().zip(accum.memory.iter()) {
+        if *value > 0 {
+            add(memory.title(), format!("{:>12}", format_memory(*value)));
+        }
+    }
+    for (percent, (num, denom)) in Percent::ALL.iter().zip(accum.percents.iter()) {
+        if *denom > 0 {
+            add(
+                percent.title(),
+                format!(
+                    "{:>12} / {:>12} ({:.2}%)",
+                    num,
+                    denom,
+                    100.0 * *num as f64 / *denom as f64
+                ),
+            );
+        }
+    }
+    for (ratio, (num, denom)) in Ratio::ALL.iter().zip(accum.ratios.iter()) {
+        if *denom > 0 {
+            add(
+                ratio.title(),
+                format!(
+                    "{:>12} / {:>12} ({:.2}x)",
+                    num,
+                    denom,
+                    *num as f64 / *denom as f64
+                ),
+            );
+        }
+    }
+    for (distribution, summary) in IntDistribution::ALL.iter().zip(accum.distributions.iter()) {
+        if summary.count > 0 {
+            add(
+                distribution.title(),
+                format!(
+                    "{:>12.3} avg [range {} - {}]",
+                    summary.sum as f64 / summary.count as f64,
+                    summary.min,
+                    summary.max
+                ),
+            );
+        }
+    }
+    writeln!(dest, "Statistics:")?;
+    for (category, lines) in to_print.iter() {
+        writeln!(dest, "  {}", category)?;
+        for line in lines {
+            writeln!(dest, "    {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns the same statistics as **print_stats()** as a JSON
+/// object with one member per kind of statistic, keyed by the
+/// "category/title" of each value. Memory is given in bytes.
+pub fn stats_json() -> String {
+    let accum = STATS_ACCUMULATOR.lock().unwrap();
+    let counters: Vec<String> = Counter::ALL
+        .iter()
+        .zip(accum.counters.iter())
+        .filter(|(_, value)| **value > 0)
+        .map(|(counter, value)| format!("{}: {}", json_string(counter.title()), value))
+        .collect();
+    let memory: Vec<String> = Memory::ALL
+        .iter()
+        .zip(accum.memory.iter())
+        .filter(|(_, value)| **value > 0)
+        .map(|(memory, value)| format!("{}: {}", json_string(memory.title()), value))
+        .collect();
+    let fraction = |(title, (num, denom)): (&'static str, &(u64, u64))| {
+        format!(
+            "{}: {{\"numerator\": {}, \"denominator\": {}}}",
+            json_string(title),
+            num,
+            denom
+        )
+    };
+    let percents: Vec<String> = Percent::ALL
+        .iter()
+        .map(|percent| percent.title())
+        .zip(accum.percents.iter())
+        .filter(|(_, (_, denom))| *denom > 0)
+        .map(fraction)
+        .collect();
+    let ratios: Vec<String> = Ratio::ALL
+        .iter()
+        .map(|ratio| ratio.title())
+        .zip(accum.ratios.iter())
+        .filter(|(_, (_, denom))| *denom > 0)
+        .map(fraction)
+        .collect();
+    let distributions: Vec<String> = IntDistribution::ALL
+        .iter()
+        .zip(accum.distributions.iter())
+        .filter(|(_, summary)| summary.count > 0)
+        .map(|(distribution, summary)| {
+            format!(
+                "{}: {{\"count\": {}, \"sum\": {}, \"min\": {}, \"max\": {}}}",
+                json_string(distribution.title()),
+                summary.count,
+                summary.sum,
+                summary.min,
+                summary.max
+            )
+        })
+        .collect();
+    let members: Vec<String> = [
+        ("counters", counters),
+        ("memory", memory),
+        ("percentages", percents),
+        ("ratios", ratios),
+        ("distributions", distributions),
+    ]
+    .iter()
+    .map(|(name, values)| {
+        if values.is_empty() {
+            format!("  \"{}\": {{}}", name)
+        } else {
+            format!("  \"{}\": {{\n    {}\n  }}", name, values.join(",\n    "))
+        }
+    })
+    .collect();
+    format!("{{\n{}\n}}\n", members.join(",\n"))
+}
+
+/// Counts visits of acceleration structure nodes (BVH or kd-tree
+/// nodes) by rays.
+#[inline]
+pub fn count_node_visits(n: u64) {
+    NODE_VISITS.with(|c| c.set(c.get() + n));
+}
+
+/// Counts a ray-primitive (e.g. ray-triangle) intersection test.
+#[inline]
+pub fn count_primitive_test() {
+    PRIMITIVE_TESTS.with(|c| c.set(c.get() + 1));
+}
+
+/// Returns the node visits and primitive intersection tests of the
+/// calling thread so far. The difference of two calls gives the
+/// traversal cost of the rays traced in between.
+pub fn traversal_counts() -> (u64, u64) {
+    (
+        NODE_VISITS.with(|c| c.get()),
+        PRIMITIVE_TESTS.with(|c| c.get()),
+    )
+}
@@ -862,9 +862,11 @@ impl Transform {
         //     }
         // }
         // ret.bssrdf = si.bssrdf.clone();
-        ret.primitive = None; // TODO? si.primitive;
+        // keep the primitive (and therefore its material)
+        ret.primitive = si.primitive;
         ret.shading.n = nrm_faceforward_nrm(&ret.shading.n, &ret.common.n);
-        // TODO: ret.faceIndex = si.faceIndex;
+        ret.face_index = si.face_index;
+        ret.vertex_color = si.vertex_color;
         *si = ret;
     }
 }
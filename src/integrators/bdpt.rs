@@ -5,6 +5,7 @@ use std::sync::Arc;
 // pbrt
 use crate::blockqueue::BlockQueue;
 use crate::core::camera::{Camera, CameraSample};
+use crate::core::checkpoint::{CheckpointOptions, Checkpointer};
 use crate::core::geometry::{
     nrm_abs_dot_vec3f, pnt2_inside_exclusivei, pnt3_offset_ray_origin, vec3_abs_dot_nrmf,
     vec3_dot_nrmf,
@@ -21,6 +22,8 @@ use crate::core::lightdistrib::create_light_sample_distribution;
 use crate::core::material::TransportMode;
 use crate::core::medium::{HenyeyGreenstein, Medium, MediumInterface};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::progressreporter::ProgressReporter;
 use crate::core::reflection::Bsdf;
 use crate::core::reflection::BxdfType;
 use crate::core::sampler::Sampler;
@@ -840,7 +843,7 @@ impl BDPTIntegrator {
     pub fn get_light_sample_strategy(&self) -> String {
         self.light_sample_strategy.clone()
     }
-    pub fn render(&self, scene: &Scene, num_threads: u8) {
+    pub fn render(&self, scene: &Scene, num_threads: u8, checkpoint: Option<&CheckpointOptions>) {
         // TODO
         // Compute a reverse mapping from light pointers to offsets into
         // the scene lights vector (and, equivalently, offsets into
@@ -858,12 +861,24 @@ impl BDPTIntegrator {
         let tile_size: i32 = 16;
         let n_x_tiles: i32 = (sample_extent.x + tile_size - 1) / tile_size;
         let n_y_tiles: i32 = (sample_extent.y + tile_size - 1) / tile_size;
-        // TODO: ProgressReporter reporter(nXTiles * nYTiles, "Rendering");
+        let n_tiles_total: usize = (n_x_tiles * n_y_tiles) as usize;
+        let reporter: ProgressReporter = ProgressReporter::new(n_tiles_total as u64, "Rendering");
         // TODO: Allocate buffers for debug visualization
         // ...
         // render and write the output image to disk
         if !scene.lights.is_empty() {
             let samples_per_pixel: i64 = self.sampler.get_samples_per_pixel();
+            let mut checkpointer: Option<Checkpointer> =
+                checkpoint.map(|options| Checkpointer::new(options, &film, n_tiles_total));
+            let tile_samples: Vec<i64> = match checkpointer {
+                Some(ref checkpointer) => checkpointer.tile_samples().to_vec(),
+                None => vec![0_i64; n_tiles_total],
+            };
+            let n_tiles_done: usize = tile_samples
+                .iter()
+                .filter(|samples| **samples >= samples_per_pixel)
+                .count();
+            reporter.update(n_tiles_done as u64);
             let num_cores = if num_threads == 0_u8 {
                 num_cpus::get()
             } else {
@@ -880,25 +895,35 @@ impl BDPTIntegrator {
                     (0, 0),
                 );
                 let bq = &block_queue;
+                let reporter = &reporter;
                 let integrator = &self;
                 let sampler = &self.get_sampler();
                 let camera = &self.get_camera();
                 let film = &film;
                 // let pixel_bounds = integrator.get_pixel_bounds().clone();
+                let tile_samples = &tile_samples;
+                let checkpointer = &mut checkpointer;
                 crossbeam::scope(|scope| {
                     let (pixel_tx, pixel_rx) = crossbeam_channel::bounded(num_cores);
                     // spawn worker threads
                     for _ in 0..num_cores {
                         let pixel_tx = pixel_tx.clone();
                         scope.spawn(move |_| {
+                            let _p = ProfilePhase::new(Prof::IntegratorRender);
                             while let Some((x, y)) = bq.next() {
                                 let tile: Point2i = Point2i {
                                     x: x as i32,
                                     y: y as i32,
                                 };
-                                let seed: i32 = tile.y * n_x_tiles + tile.x;
+                                let tile_index: usize = (tile.y * n_x_tiles + tile.x) as usize;
+                                let samples_done: i64 = tile_samples[tile_index];
+                                if samples_done >= samples_per_pixel {
+                                    continue;
+                                }
+                                // the same seed as an uninterrupted render, the
+                                // samples it took already are skipped below
                                 let mut tile_sampler: Box<Sampler> =
-                                    sampler.clone_with_seed(seed as u64);
+                                    sampler.clone_with_seed(tile_index as u64);
                                 let x0: i32 = sample_bounds.p_min.x + tile.x * tile_size;
                                 let x1: i32 = std::cmp::min(x0 + tile_size, sample_bounds.p_max.x);
                                 let y0: i32 = sample_bounds.p_min.y + tile.y * tile_size;
@@ -909,11 +934,18 @@ impl BDPTIntegrator {
                                 );
                                 // println!("Starting image tile {:?}", tile_bounds);
                                 let mut film_tile = film.get_film_tile(&tile_bounds);
+                                // splats are added together with the
+                                // tile, this keeps checkpoints consistent
+                                let mut splats: Vec<(Point2f, Spectrum)> = Vec::new();
                                 for p_pixel in &tile_bounds {
                                     tile_sampler.start_pixel(p_pixel);
                                     if !pnt2_inside_exclusivei(p_pixel, &integrator.pixel_bounds) {
                                         continue;
                                     }
+                                    // skip the samples a previous render took
+                                    for _ in 0..samples_done {
+                                        tile_sampler.start_next_sample();
+                                    }
                                     let mut done: bool = false;
                                     while !done {
                                         // Get a distribution for sampling
@@ -1029,7 +1061,7 @@ impl BDPTIntegrator {
                                                     if t != 1 {
                                                         l += lpath;
                                                     } else if !lpath.is_black() {
-                                                        film.add_splat(p_film_new, &lpath);
+                                                        splats.push((p_film_new, lpath));
                                                     }
                                                 }
                                             }
@@ -1046,22 +1078,33 @@ impl BDPTIntegrator {
                                 }
                                 // send the tile through the channel to main thread
                                 pixel_tx
-                                    .send(film_tile)
+                                    .send((tile_index, film_tile, splats))
                                     .unwrap_or_else(|_| panic!("Failed to send tile"));
                             }
                         });
                     }
                     // spawn thread to collect pixels and render image to file
                     scope.spawn(move |_| {
-                        for _ in pbr::PbIter::new(0..bq.len()) {
-                            let film_tile = pixel_rx.recv().unwrap();
+                        for _ in n_tiles_done..bq.len() {
+                            let (tile_index, film_tile, splats) = pixel_rx.recv().unwrap();
                             // merge image tile into _Film_
                             film.merge_film_tile(&film_tile);
+                            for (p_film, lpath) in splats.iter() {
+                                film.add_splat(*p_film, lpath);
+                            }
+                            if let Some(checkpointer) = checkpointer {
+                                checkpointer.tile_done(tile_index, samples_per_pixel);
+                            }
+                            reporter.update(1);
                         }
                     });
                 })
                 .unwrap();
             }
+            reporter.done();
+            if let Some(ref mut checkpointer) = checkpointer {
+                checkpointer.write();
+            }
             film.write_image(1.0 as Float / samples_per_pixel as Float);
             // TODO: Write buffers for debug visualization
         }
@@ -1110,7 +1153,7 @@ pub fn generate_camera_subpath<'a>(
     if max_depth == 0 {
         return (0_usize, Point3f::default(), Float::default());
     }
-    // TODO: ProfilePhase _(Prof::BDPTGenerateSubpath);
+    let _p = ProfilePhase::new(Prof::BDPTGenerateSubpath);
     // sample initial ray for camera subpath
     let mut camera_sample: CameraSample = CameraSample::default();
     camera_sample.p_film = p_film;
@@ -1157,7 +1200,7 @@ pub fn generate_light_subpath<'a>(
     if max_depth == 0_u32 {
         return 0_usize;
     }
-    // TODO: ProfilePhase _(Prof::BDPTGenerateSubpath);
+    let _p = ProfilePhase::new(Prof::BDPTGenerateSubpath);
     // sample initial ray for light subpath
     let mut light_pdf: Option<Float> = Some(0.0 as Float);
     let light_num: usize = light_distr.sample_discrete(sampler.get_1d(), light_pdf.as_mut());
@@ -2200,7 +2243,7 @@ pub fn connect_bdpt<'a>(
     p_raster: &mut Point2f,
     mis_weight_opt: Option<&mut Float>,
 ) -> Spectrum {
-    // TODO: ProfilePhase _(Prof::BDPTConnectSubpaths);
+    let _p = ProfilePhase::new(Prof::BDPTConnectSubpaths);
     let mut l: Spectrum = Spectrum::default();
     // ignore invalid connections related to infinite area lights
     if t > 1 && s != 0 && camera_vertices[t - 1].vertex_type == VertexType::Light {
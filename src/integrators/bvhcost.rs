@@ -0,0 +1,261 @@
+// std
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+// pbrt
+use crate::blockqueue::BlockQueue;
+use crate::core::camera::{Camera, CameraSample};
+use crate::core::geometry::{Bounds2i, Point2i, Ray, Vector2i};
+use crate::core::interaction::SurfaceInteraction;
+use crate::core::pbrt::{clamp_t, Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::progressreporter::ProgressReporter;
+use crate::core::sampler::Sampler;
+use crate::core::scene::Scene;
+use crate::core::stats;
+
+/// The count shown by the heatmap of a **BVHCostIntegrator**.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraversalCost {
+    NodeVisits,
+    PrimitiveTests,
+}
+
+/// Visualizes why a scene renders slowly: counts the acceleration
+/// structure nodes visited and the primitive intersection tests of
+/// the camera rays of each pixel. The image shows the chosen count as
+/// a false-color heatmap (blue is cheap, red is expensive), and the
+/// raw counts (averaged over the pixel samples) are written to a text
+/// file.
+pub struct BVHCostIntegrator {
+    pub camera: Arc<Camera>,
+    pub sampler: Box<Sampler>,
+    pub cost: TraversalCost,
+    /// count mapped to red, if zero the maximum count of all pixels
+    pub max_cost: Float,
+    pub counts_filename: String,
+}
+
+impl BVHCostIntegrator {
+    pub fn new(
+        camera: Arc<Camera>,
+        sampler: Box<Sampler>,
+        cost: TraversalCost,
+        max_cost: Float,
+        counts_filename: String,
+    ) -> Self {
+        BVHCostIntegrator {
+            camera,
+            sampler,
+            cost,
+            max_cost,
+            counts_filename,
+        }
+    }
+    pub fn render(&self, scene: &Scene, num_threads: u8) {
+        let film = self.camera.get_film();
+        let pixel_bounds: Bounds2i = film.get_cropped_pixel_bounds();
+        let pixel_extent: Vector2i = pixel_bounds.diagonal();
+        let tile_size: i32 = 16;
+        let n_x_tiles: i32 = (pixel_extent.x + tile_size - 1) / tile_size;
+        let n_y_tiles: i32 = (pixel_extent.y + tile_size - 1) / tile_size;
+        let num_cores = if num_threads == 0_u8 {
+            num_cpus::get()
+        } else {
+            num_threads as usize
+        };
+        println!("Rendering with {:?} thread(s) ...", num_cores);
+        // node visits and primitive tests per camera ray
+        let n_pixels: usize = pixel_bounds.area() as usize;
+        let mut counts: Vec<(Float, Float)> = vec![(0.0 as Float, 0.0 as Float); n_pixels];
+        let reporter: ProgressReporter =
+            ProgressReporter::new((n_x_tiles * n_y_tiles) as u64, "Rendering");
+        {
+            let block_queue = BlockQueue::new(
+                (
+                    (n_x_tiles * tile_size) as u32,
+                    (n_y_tiles * tile_size) as u32,
+                ),
+                (tile_size as u32, tile_size as u32),
+                (0, 0),
+            );
+            let bq = &block_queue;
+            let reporter = &reporter;
+            let sampler = &self.sampler;
+            let camera = &self.camera;
+            let counts = &mut counts;
+            crossbeam::scope(|scope| {
+                let (pixel_tx, pixel_rx) = crossbeam_channel::bounded(num_cores);
+                // spawn worker threads
+                for _ in 0..num_cores {
+                    let pixel_tx = pixel_tx.clone();
+                    scope.spawn(move |_| {
+                        let _p = ProfilePhase::new(Prof::IntegratorRender);
+                        while let Some((x, y)) = bq.next() {
+                            let tile: Point2i = Point2i {
+                                x: x as i32,
+                                y: y as i32,
+                            };
+                            let seed: i32 = tile.y * n_x_tiles + tile.x;
+                            let mut tile_sampler: Box<Sampler> =
+                                sampler.clone_with_seed(seed as u64);
+                            let x0: i32 = pixel_bounds.p_min.x + tile.x * tile_size;
+                            let x1: i32 = std::cmp::min(x0 + tile_size, pixel_bounds.p_max.x);
+                            let y0: i32 = pixel_bounds.p_min.y + tile.y * tile_size;
+                            let y1: i32 = std::cmp::min(y0 + tile_size, pixel_bounds.p_max.y);
+                            let tile_bounds: Bounds2i =
+                                Bounds2i::new(Point2i { x: x0, y: y0 }, Point2i { x: x1, y: y1 });
+                            let mut tile_counts: Vec<(Point2i, Float, Float)> = Vec::new();
+                            for pixel in &tile_bounds {
+                                tile_sampler.start_pixel(pixel);
+                                let mut n_rays: u64 = 0;
+                                let mut node_visits: u64 = 0;
+                                let mut primitive_tests: u64 = 0;
+                                let mut done: bool = false;
+                                while !done {
+                                    let camera_sample: CameraSample =
+                                        tile_sampler.get_camera_sample(pixel);
+                                    let mut ray: Ray = Ray::default();
+                                    let ray_weight: Float =
+                                        camera.generate_ray_differential(&camera_sample, &mut ray);
+                                    if ray_weight > 0.0 {
+                                        let clipping_start: Float = camera.get_clipping_start();
+                                        if clipping_start > 0.0 as Float {
+                                            // adjust ray origin for near clipping
+                                            ray.o = ray.position(clipping_start);
+                                        }
+                                        let (nodes_before, tests_before) =
+                                            stats::traversal_counts();
+                                        let mut isect: SurfaceInteraction =
+                                            SurfaceInteraction::default();
+                                        scene.intersect(&ray, &mut isect);
+                                        let (nodes_after, tests_after) = stats::traversal_counts();
+                                        node_visits += nodes_after - nodes_before;
+                                        primitive_tests += tests_after - tests_before;
+                                        n_rays += 1;
+                                    }
+                                    done = !tile_sampler.start_next_sample();
+                                }
+                                let inv_n_rays: Float = if n_rays > 0 {
+                                    1.0 as Float / n_rays as Float
+                                } else {
+                                    0.0 as Float
+                                };
+                                tile_counts.push((
+                                    pixel,
+                                    node_visits as Float * inv_n_rays,
+                                    primitive_tests as Float * inv_n_rays,
+                                ));
+                            }
+                            // send the tile through the channel to main thread
+                            pixel_tx
+                                .send(tile_counts)
+                                .unwrap_or_else(|_| panic!("Failed to send tile"));
+                        }
+                    });
+                }
+                // spawn thread to collect the counts of all tiles
+                scope.spawn(move |_| {
+                    let width: i32 = pixel_bounds.p_max.x - pixel_bounds.p_min.x;
+                    for _ in 0..bq.len() {
+                        let tile_counts: Vec<(Point2i, Float, Float)> = pixel_rx.recv().unwrap();
+                        for (pixel, node_visits, primitive_tests) in tile_counts {
+                            let offset: i32 = (pixel.x - pixel_bounds.p_min.x)
+                                + (pixel.y - pixel_bounds.p_min.y) * width;
+                            counts[offset as usize] = (node_visits, primitive_tests);
+                        }
+                        reporter.update(1);
+                    }
+                });
+            })
+            .unwrap();
+        }
+        reporter.done();
+        // report averages and maxima
+        let mut sum: (Float, Float) = (0.0 as Float, 0.0 as Float);
+        let mut max: (Float, Float) = (0.0 as Float, 0.0 as Float);
+        for (node_visits, primitive_tests) in counts.iter() {
+            sum.0 += node_visits;
+            sum.1 += primitive_tests;
+            max.0 = max.0.max(*node_visits);
+            max.1 = max.1.max(*primitive_tests);
+        }
+        let n: Float = n_pixels.max(1) as Float;
+        println!(
+            "Node visits per camera ray: {:.2} average, {:.2} maximum",
+            sum.0 / n,
+            max.0
+        );
+        println!(
+            "Primitive tests per camera ray: {:.2} average, {:.2} maximum",
+            sum.1 / n,
+            max.1
+        );
+        self.write_counts(&pixel_bounds, &counts);
+        // map the chosen count to false colors
+        let max_cost: Float = if self.max_cost > 0.0 as Float {
+            self.max_cost
+        } else {
+            match self.cost {
+                TraversalCost::NodeVisits => max.0,
+                TraversalCost::PrimitiveTests => max.1,
+            }
+        };
+        let image: Vec<Spectrum> = counts
+            .iter()
+            .map(|(node_visits, primitive_tests)| {
+                let cost: Float = match self.cost {
+                    TraversalCost::NodeVisits => *node_visits,
+                    TraversalCost::PrimitiveTests => *primitive_tests,
+                };
+                if max_cost > 0.0 as Float {
+                    heat_color(cost / max_cost)
+                } else {
+                    heat_color(0.0 as Float)
+                }
+            })
+            .collect();
+        film.set_image(&image);
+        film.write_image(1.0 as Float);
+    }
+    /// Writes one line per pixel with its coordinates, the node
+    /// visits and the primitive tests per camera ray.
+    fn write_counts(&self, pixel_bounds: &Bounds2i, counts: &[(Float, Float)]) {
+        println!("Writing counts {:?}", self.counts_filename);
+        let result = File::create(&self.counts_filename).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            writeln!(writer, "# x y node_visits primitive_tests")?;
+            for (pixel, (node_visits, primitive_tests)) in pixel_bounds.into_iter().zip(counts) {
+                writeln!(
+                    writer,
+                    "{} {} {} {}",
+                    pixel.x, pixel.y, node_visits, primitive_tests
+                )?;
+            }
+            writer.flush()
+        });
+        if let Err(e) = result {
+            println!(
+                "ERROR: Unable to write counts to {:?}: {}",
+                self.counts_filename, e
+            );
+        }
+    }
+    pub fn get_camera(&self) -> Arc<Camera> {
+        self.camera.clone()
+    }
+}
+
+/// Maps *x* in [0, 1] to blue, cyan, green, yellow and red.
+fn heat_color(x: Float) -> Spectrum {
+    let x: Float = 4.0 as Float * clamp_t(x, 0.0 as Float, 1.0 as Float);
+    if x < 1.0 as Float {
+        Spectrum::rgb(0.0, x, 1.0)
+    } else if x < 2.0 as Float {
+        Spectrum::rgb(0.0, 1.0, 2.0 - x)
+    } else if x < 3.0 as Float {
+        Spectrum::rgb(x - 2.0, 1.0, 0.0)
+    } else {
+        Spectrum::rgb(1.0, 4.0 - x, 0.0)
+    }
+}
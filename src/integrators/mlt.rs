@@ -1,7 +1,6 @@
 // std
 use std::ops::DerefMut;
 use std::sync::Arc;
-use std::thread;
 // pbrt
 use crate::core::camera::Camera;
 use crate::core::film::Film;
@@ -10,10 +9,13 @@ use crate::core::integrator::compute_light_power_distribution;
 use crate::core::pbrt::erf_inv;
 use crate::core::pbrt::SQRT_2;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::progressreporter::ProgressReporter;
 use crate::core::rng::Rng;
 use crate::core::sampler::Sampler;
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::{self, Percent};
 use crate::integrators::bdpt::Vertex;
 use crate::integrators::bdpt::{connect_bdpt, generate_camera_subpath, generate_light_subpath};
 // others
@@ -199,7 +201,7 @@ impl MLTSampler {
         self.array_2d_offset = 0_usize;
     }
     pub fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         let index: i32 = self.get_next_index();
         self.ensure_ready(index);
         self.x[index as usize].value
@@ -420,7 +422,8 @@ impl MLTIntegrator {
             let mut bootstrap_weights: Vec<Float> =
                 vec![0.0 as Float; n_bootstrap_samples as usize];
             if !scene.lights.is_empty() {
-                // TODO: ProgressReporter progress(nBootstrap / 256, "Generating bootstrap paths");
+                let progress: ProgressReporter =
+                    ProgressReporter::new(num_cores as u64, "Generating bootstrap paths");
                 // let chunk_size: u32 = clamp_t(integrator.n_bootstrap / 128, 1, 8192);
                 let chunk_size: usize = (n_bootstrap_samples / num_cores as u32) as usize;
                 {
@@ -428,12 +431,14 @@ impl MLTIntegrator {
                         bootstrap_weights.chunks_mut(chunk_size).collect();
                     let integrator = &self;
                     let light_distr = &light_distr;
+                    let progress = &progress;
                     crossbeam::scope(|scope| {
                         let (band_tx, band_rx) = crossbeam_channel::bounded(num_cores);
                         // spawn worker threads
                         for (b, band) in bands.into_iter().enumerate() {
                             let band_tx = band_tx.clone();
                             scope.spawn(move |_| {
+                                let _p = ProfilePhase::new(Prof::IntegratorRender);
                                 for (w, weight) in band.iter_mut().enumerate() {
                                     let rng_index: u64 = ((b * chunk_size) + w) as u64;
                                     let depth: u32 =
@@ -465,13 +470,15 @@ impl MLTIntegrator {
                         }
                         // spawn thread to report progress
                         scope.spawn(move |_| {
-                            for _ in pbr::PbIter::new(0..num_cores) {
+                            for _ in 0..num_cores {
                                 band_rx.recv().unwrap();
+                                progress.update(1);
                             }
                         });
                     })
                     .unwrap();
                 }
+                progress.done();
             }
             let bootstrap: Distribution1D = Distribution1D::new(bootstrap_weights);
             let b: Float = bootstrap.func_int * (self.max_depth + 1) as Float;
@@ -481,95 +488,102 @@ impl MLTIntegrator {
             let n_total_mutations: u64 =
                 self.mutations_per_pixel as u64 * film.get_sample_bounds().area() as u64;
             if !scene.lights.is_empty() {
-                // TODO: let progress_frequency = 32768;
-                // TODO: ProgressReporter progress(nTotalMutations / progressFrequency,
-                //                           "Rendering");
-                // use parallel iterator (par_iter_with) from rayon crate
-                let (sender, receiver) = crossbeam_channel::bounded(num_cores);
+                let progress_frequency: u64 = 32768;
+                let progress: ProgressReporter = ProgressReporter::new(
+                    n_total_mutations.div_ceil(progress_frequency),
+                    "Rendering",
+                );
                 let n_chains = self.n_chains;
-                // spawn thread to report progress
-                let finish = thread::spawn(move || {
-                    for _ in pbr::PbIter::new(0..n_chains) {
-                        receiver.recv().unwrap();
-                    }
-                });
                 // for i in 0..n_chains {
                 let ivec: Vec<u32> = (0..n_chains).collect();
-                ivec.par_iter().for_each_with(sender, |s, &i| {
-                    s.send(i).unwrap_or_else(|_| panic!("Failed to send chain"));
-                    let n_chain_mutations: u64 = ((i as u64 + 1) * n_total_mutations
-                        / n_chains as u64)
-                        .min(n_total_mutations)
-                        - i as u64 * n_total_mutations / n_chains as u64;
-                    // select initial state from the set of bootstrap samples
-                    let mut rng: Rng = Rng::default();
-                    rng.set_sequence(i as u64);
-                    let bootstrap_index: usize =
-                        bootstrap.sample_discrete(rng.uniform_float(), None);
-                    let depth: u32 = bootstrap_index as u32 % (self.max_depth as u32 + 1);
-                    // initialize local variables for selected state
-                    let mut sampler: Box<Sampler> = Box::new(Sampler::MLT(MLTSampler::new(
-                        self.mutations_per_pixel as i64,
-                        bootstrap_index as u64,
-                        self.sigma,
-                        self.large_step_probability,
-                        N_SAMPLE_STREAMS as i32,
-                    )));
-                    let mut p_current: Point2f = Point2f::default();
-                    let mut l_current: Spectrum = self.l(
-                        scene,
-                        light_distr.clone(),
-                        &mut sampler,
-                        depth,
-                        &mut p_current,
-                    );
-                    // run the Markov chain for _n_chain_mutations_ steps
-                    for _j in 0..n_chain_mutations {
-                        match sampler.deref_mut() {
-                            Sampler::MLT(mlt_sampler) => mlt_sampler.start_iteration(),
-                            _ => panic!("MLTSampler needed."),
-                        }
-                        let mut p_proposed: Point2f = Point2f::default();
-                        let l_proposed: Spectrum = self.l(
+                // run the chains on a dedicated pool (instead of rayon's
+                // global one), so _num_threads_ limits them like the
+                // tiles of the other integrators
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(num_cores)
+                    .build()
+                    .unwrap_or_else(|e| panic!("Failed to create thread pool: {}", e));
+                pool.install(|| {
+                    ivec.par_iter().for_each(|&i| {
+                        let _p = ProfilePhase::new(Prof::IntegratorRender);
+                        let n_chain_mutations: u64 = ((i as u64 + 1) * n_total_mutations
+                            / n_chains as u64)
+                            .min(n_total_mutations)
+                            - i as u64 * n_total_mutations / n_chains as u64;
+                        // select initial state from the set of bootstrap samples
+                        let mut rng: Rng = Rng::default();
+                        rng.set_sequence(i as u64);
+                        let bootstrap_index: usize =
+                            bootstrap.sample_discrete(rng.uniform_float(), None);
+                        let depth: u32 = bootstrap_index as u32 % (self.max_depth as u32 + 1);
+                        // initialize local variables for selected state
+                        let mut sampler: Box<Sampler> = Box::new(Sampler::MLT(MLTSampler::new(
+                            self.mutations_per_pixel as i64,
+                            bootstrap_index as u64,
+                            self.sigma,
+                            self.large_step_probability,
+                            N_SAMPLE_STREAMS as i32,
+                        )));
+                        let mut p_current: Point2f = Point2f::default();
+                        let mut l_current: Spectrum = self.l(
                             scene,
                             light_distr.clone(),
                             &mut sampler,
                             depth,
-                            &mut p_proposed,
-                        );
-                        // compute acceptance probability for proposed sample
-                        let accept: Float = (1.0 as Float).min(l_proposed.y() / l_current.y());
-                        // splat both current and proposed samples to _film_
-                        if accept > 0.0 as Float {
-                            film.add_splat(p_proposed, &(l_proposed * accept / l_proposed.y()));
-                        }
-                        film.add_splat(
-                            p_current,
-                            &(l_current * (1.0 as Float - accept) / l_current.y()),
+                            &mut p_current,
                         );
-                        // accept or reject the proposal
-                        if rng.uniform_float() < accept {
-                            p_current = p_proposed;
-                            l_current = l_proposed;
+                        // run the Markov chain for _n_chain_mutations_ steps
+                        for j in 0..n_chain_mutations {
                             match sampler.deref_mut() {
-                                Sampler::MLT(mlt_sampler) => mlt_sampler.accept(),
+                                Sampler::MLT(mlt_sampler) => mlt_sampler.start_iteration(),
                                 _ => panic!("MLTSampler needed."),
                             }
-                        // TODO: ++acceptedMutations;
-                        } else {
-                            match sampler.deref_mut() {
-                                Sampler::MLT(mlt_sampler) => mlt_sampler.reject(),
-                                _ => panic!("MLTSampler needed."),
+                            let mut p_proposed: Point2f = Point2f::default();
+                            let l_proposed: Spectrum = self.l(
+                                scene,
+                                light_distr.clone(),
+                                &mut sampler,
+                                depth,
+                                &mut p_proposed,
+                            );
+                            // compute acceptance probability for proposed sample
+                            let accept: Float = (1.0 as Float).min(l_proposed.y() / l_current.y());
+                            // splat both current and proposed samples to _film_
+                            if accept > 0.0 as Float {
+                                film.add_splat(p_proposed, &(l_proposed * accept / l_proposed.y()));
                             }
+                            film.add_splat(
+                                p_current,
+                                &(l_current * (1.0 as Float - accept) / l_current.y()),
+                            );
+                            // accept or reject the proposal
+                            if rng.uniform_float() < accept {
+                                p_current = p_proposed;
+                                l_current = l_proposed;
+                                match sampler.deref_mut() {
+                                    Sampler::MLT(mlt_sampler) => mlt_sampler.accept(),
+                                    _ => panic!("MLTSampler needed."),
+                                }
+                                stats::add_percent(Percent::AcceptedMutations, 1, 0);
+                            } else {
+                                match sampler.deref_mut() {
+                                    Sampler::MLT(mlt_sampler) => mlt_sampler.reject(),
+                                    _ => panic!("MLTSampler needed."),
+                                }
+                            }
+                            stats::add_percent(Percent::AcceptedMutations, 0, 1);
+                            if (i as u64 * n_total_mutations / n_chains as u64 + j)
+                                .is_multiple_of(progress_frequency)
+                            {
+                                progress.update(1);
+                            }
+                            // TODO: arena.Reset();
                         }
-                        // TODO: ++totalMutations;
-                        // if (i * n_total_mutations / n_chains + j) % progress_frequency == 0 {
-                        //     progress.update();
-                        // }
-                        // TODO: arena.Reset();
-                    }
+                        // rayon's threads don't exit, report explicitly
+                        stats::report_thread_stats();
+                    })
                 });
-                finish.join().unwrap();
+                progress.done();
             }
             // Store final image computed with MLT
             film.write_image(b / self.mutations_per_pixel as Float);
@@ -3,6 +3,7 @@
 //!
 //! - AOIntegrator
 //! - BDPTIntegrator
+//! - BVHCostIntegrator
 //! - DirectLightingIntegrator
 //! - MLTIntegrator
 //! - PathIntegrator
@@ -57,9 +58,18 @@
 //! particles close to but not quite at the point being shaded.
 //!
 //! ![Stochastic Progressive Photon Mapping](/doc/img/caustic_glass_pbrt_rust_sppm.png)
+//!
+//! ## Traversal Cost (bvhcost)
+//!
+//! Instead of computing radiance, the **BVHCostIntegrator** counts
+//! the nodes of the acceleration structure visited and the primitives
+//! tested by the camera rays of each pixel and renders the counts as
+//! a false-color heatmap, which shows where a scene is expensive to
+//! trace.
 
 pub mod ao;
 pub mod bdpt;
+pub mod bvhcost;
 pub mod directlighting;
 pub mod mlt;
 pub mod path;
@@ -13,10 +13,12 @@ use crate::core::lightdistrib::create_light_sample_distribution;
 use crate::core::lightdistrib::LightDistribution;
 use crate::core::material::TransportMode;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::reflection::BxdfType;
 use crate::core::sampler::Sampler;
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::{self, IntDistribution, Percent};
 
 // see path.h
 
@@ -65,7 +67,7 @@ impl PathIntegrator {
         // arena: &mut Arena,
         _depth: i32,
     ) -> Spectrum {
-        // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
+        let _p = ProfilePhase::new(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::default();
         let mut beta: Spectrum = Spectrum::new(1.0 as Float);
         let mut ray: Ray = Ray {
@@ -122,7 +124,7 @@ impl PathIntegrator {
                     let bsdf_flags: u8 = BxdfType::BsdfAll as u8 & !(BxdfType::BsdfSpecular as u8);
                     if let Some(ref bsdf) = isect.bsdf {
                         if bsdf.num_components(bsdf_flags) > 0 {
-                            // TODO: ++total_paths;
+                            stats::add_percent(Percent::ZeroRadiancePaths, 0, 1);
                             let it: &SurfaceInteraction = isect.borrow();
                             let ld: Spectrum = beta
                                 * uniform_sample_one_light(
@@ -133,9 +135,9 @@ impl PathIntegrator {
                                     Some(&distrib),
                                 );
                             // TODO: println!("Sampled direct lighting Ld = {:?}", ld);
-                            // TODO: if ld.is_black() {
-                            //     ++zero_radiance_paths;
-                            // }
+                            if ld.is_black() {
+                                stats::add_percent(Percent::ZeroRadiancePaths, 1, 0);
+                            }
                             assert!(ld.y() >= 0.0 as Float, "ld = {:?}", ld);
                             l += ld;
                         }
@@ -279,6 +281,7 @@ impl PathIntegrator {
             }
             bounces += 1_u32;
         }
+        stats::report_value(IntDistribution::PathLength, bounces as i64);
         l
     }
     pub fn get_camera(&self) -> Arc<Camera> {
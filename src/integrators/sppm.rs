@@ -25,9 +25,12 @@ use crate::core::material::TransportMode;
 use crate::core::parallel::AtomicFloat;
 use crate::core::pbrt::{clamp_t, lerp};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::progressreporter::ProgressReporter;
 use crate::core::reflection::{Bsdf, BxdfType};
 use crate::core::scene::Scene;
 use crate::core::spectrum::RGBEnum;
+use crate::core::stats::{self, Counter, Memory, Ratio};
 use crate::samplers::halton::HaltonSampler;
 
 /// Stochastic Progressive Photon Mapping
@@ -71,8 +74,6 @@ impl SPPMIntegrator {
             num_threads as usize
         };
         println!("Rendering with {:?} thread(s) ...", num_cores);
-        // TODO: ProfilePhase p(Prof::IntegratorRender);
-
         // initialize _pixel_bounds_ and _pixels_ array for SPPM
         let film: Arc<Film> = self.get_camera().get_film();
         let pixel_bounds: Bounds2i = film.cropped_pixel_bounds;
@@ -84,7 +85,10 @@ impl SPPMIntegrator {
             pixels.push(pixel);
         }
         let inv_sqrt_spp: Float = 1.0 as Float / (self.n_iterations as Float).sqrt();
-        // TODO: let pixel_memory_bytes: usize = n_pixels as usize * std::mem::size_of::<SPPMPixel>();
+        stats::add_memory(
+            Memory::SPPMPixels,
+            (n_pixels as usize * std::mem::size_of::<SPPMPixel>()) as u64,
+        );
 
         // compute _light_distr_ for sampling lights proportional to power
         if let Some(light_distr) = compute_light_power_distribution(scene) {
@@ -101,11 +105,11 @@ impl SPPMIntegrator {
                 x: (pixel_extent.x + tile_size - 1) / tile_size,
                 y: (pixel_extent.y + tile_size - 1) / tile_size,
             };
-            // TODO: ProgressReporter progress(2 * nIterations, "Rendering");
-            for iteration in pbr::PbIter::new(0..self.n_iterations) {
+            let progress: ProgressReporter =
+                ProgressReporter::new(2 * self.n_iterations as u64, "Rendering");
+            for iteration in 0..self.n_iterations {
                 // generate SPPM visible points
                 {
-                    // TODO: ProfilePhase _(Prof::SPPMCameraPass);
                     // println!("Generate SPPM visible points ...");
                     {
                         let block_queue = BlockQueue::new(
@@ -126,6 +130,7 @@ impl SPPMIntegrator {
                             for _ in 0..num_cores {
                                 let pixel_tx = pixel_tx.clone();
                                 scope.spawn(move |_| {
+                                    let _p = ProfilePhase::new(Prof::SPPMCameraPass);
                                     while let Some((x, y)) = bq.next() {
                                         let tile: Point2i = Point2i {
                                             x: x as i32,
@@ -184,7 +189,7 @@ impl SPPMIntegrator {
                                             );
                                             let mut specular_bounce: bool = false;
                                             for depth in 0..integrator.max_depth {
-                                                // TODO: ++totalPhotonSurfaceInteractions;
+                                                stats::add_counter(Counter::PhotonSurfaceInteractions, 1);
                                                 let mut isect: SurfaceInteraction =
                                                     SurfaceInteraction::default();
                                                 if scene.intersect(&mut ray, &mut isect) {
@@ -316,6 +321,7 @@ impl SPPMIntegrator {
                         .unwrap();
                     }
                 }
+                progress.update(1);
                 // create grid of all SPPM visible points
                 let mut grid_res: [i32; 3] = [0; 3];
                 let mut grid_bounds: Bounds3f = Bounds3f::default();
@@ -329,7 +335,7 @@ impl SPPMIntegrator {
                     grid_once.push(AtomSetOnce::empty());
                 }
                 {
-                    // TODO: ProfilePhase _(Prof::SPPMGridConstruction);
+                    let _p = ProfilePhase::new(Prof::SPPMGridConstruction);
 
                     // compute grid bounds for SPPM visible points
                     let mut max_radius: Float = 0.0 as Float;
@@ -447,7 +453,6 @@ impl SPPMIntegrator {
                 }
                 std::mem::drop(grid);
                 {
-                    // TODO: ProfilePhase _(Prof::SPPMPhotonPass);
                     // println!("Trace photons and accumulate contributions ...");
                     let chunk_size: usize =
                         (self.photons_per_iteration / num_cores as i32) as usize;
@@ -463,6 +468,7 @@ impl SPPMIntegrator {
                         for (b, band) in bands.into_iter().enumerate() {
                             let band_tx = band_tx.clone();
                             scope.spawn(move |_| {
+                                let _p = ProfilePhase::new(Prof::SPPMPhotonPass);
                                 for photon_index in band.iter() {
                                     // for photon_index in 0..integrator.photons_per_iteration as usize {
                                     // MemoryArena &arena = photonShootArenas[ThreadIndex];
@@ -543,7 +549,8 @@ impl SPPMIntegrator {
                                         for depth in 0..integrator.max_depth {
 					    let mut isect: SurfaceInteraction = SurfaceInteraction::default();
 					    if scene.intersect(&mut photon_ray, &mut isect) {
-                                                // TODO: ++totalPhotonSurfaceInteractions;
+                                                stats::add_counter(Counter::PhotonSurfaceInteractions, 1);
+                                                stats::add_ratio(Ratio::VisiblePointsChecked, 0, 1);
                                                 if depth > 0 {
                                                     // add photon contribution to nearby visible points
                                                     let mut photon_grid_index: Point3i =
@@ -568,6 +575,11 @@ impl SPPMIntegrator {
                                                         if !grid_once[h].is_none() {
                                                             let mut opt = grid_once[h].get();
                                                             while let Some(node) = opt {
+                                                                stats::add_ratio(
+                                                                    Ratio::VisiblePointsChecked,
+                                                                    1,
+                                                                    0,
+                                                                );
                                                                 // deal with linked list
                                                                 let pixel = node.pixel;
                                                                 let radius: Float = pixel.radius;
@@ -705,9 +717,9 @@ impl SPPMIntegrator {
                     .unwrap();
                     }
                 }
+                progress.update(1);
                 // update pixel values from this pass's photons
                 {
-                    // TODO: ProfilePhase _(Prof::SPPMStatsUpdate);
                     // println!("Update pixel values from this pass's photons ...");
                     let chunk_size: usize = (n_pixels / num_cores as i32) as usize;
                     {
@@ -718,6 +730,7 @@ impl SPPMIntegrator {
                             for (b, band) in bands.into_iter().enumerate() {
                                 let band_tx = band_tx.clone();
                                 scope.spawn(move |_| {
+                                    let _p = ProfilePhase::new(Prof::SPPMStatsUpdate);
                                     for p in band.iter_mut() {
                                         // let mut p = &mut pixels[i];
                                         let p_m = p.m.load(atomic::Ordering::Relaxed);
@@ -827,7 +840,7 @@ impl SPPMIntegrator {
                     // }
                 }
             }
-            // TODO: progress.Done();
+            progress.done();
         }
     }
     pub fn get_camera(&self) -> Arc<Camera> {
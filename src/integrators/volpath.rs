@@ -13,10 +13,12 @@ use crate::core::lightdistrib::create_light_sample_distribution;
 use crate::core::lightdistrib::LightDistribution;
 use crate::core::material::TransportMode;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::reflection::BxdfType;
 use crate::core::sampler::Sampler;
 use crate::core::sampling::Distribution1D;
 use crate::core::scene::Scene;
+use crate::core::stats::{self, Counter};
 
 // see volpath.h
 
@@ -66,7 +68,7 @@ impl VolPathIntegrator {
         // arena: &mut Arena,
         _depth: i32,
     ) -> Spectrum {
-        // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
+        let _p = ProfilePhase::new(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::default();
         let mut beta: Spectrum = Spectrum::new(1.0 as Float);
         let mut ray: Ray = Ray {
@@ -113,7 +115,7 @@ impl VolPathIntegrator {
                     let mi_p = mi.common.p;
                     // if mi.is_valid() {...}
                     if let Some(phase) = mi.clone().phase {
-                        // TODO: ++volumeInteractions;
+                        stats::add_counter(Counter::VolumeInteractions, 1);
                         // handle scattering at point in medium for volumetric path tracer
                         if let Some(ref light_distribution) = self.light_distribution {
                             let distrib: Arc<Distribution1D> = light_distribution.lookup(&mi_p);
@@ -132,7 +134,7 @@ impl VolPathIntegrator {
                         }
                     }
                 } else {
-                    // TODO: ++surfaceInteractions;
+                    stats::add_counter(Counter::SurfaceInteractions, 1);
                     // possibly add emitted light at intersection
                     if bounces == 0 || specular_bounce {
                         // add emitted light at path vertex
@@ -314,7 +316,7 @@ impl VolPathIntegrator {
                     let mi_p = mi.common.p;
                     // if mi.is_valid() {...}
                     if let Some(phase) = mi.clone().phase {
-                        // TODO: ++volumeInteractions;
+                        stats::add_counter(Counter::VolumeInteractions, 1);
                         // handle scattering at point in medium for volumetric path tracer
                         if let Some(ref light_distribution) = self.light_distribution {
                             let distrib: Arc<Distribution1D> = light_distribution.lookup(&mi_p);
@@ -17,6 +17,7 @@ use crate::core::medium::MediumInterface;
 use crate::core::mipmap::{ImageWrap, MipMap};
 use crate::core::pbrt::{Float, Spectrum};
 use crate::core::pbrt::{INV_2_PI, INV_PI};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::sampling::concentric_sample_disk;
 use crate::core::sampling::Distribution2D;
 use crate::core::scene::Scene;
@@ -304,7 +305,7 @@ impl InfiniteAreaLight {
         pdf: &mut Float,
         vis: &mut VisibilityTester<'a, 'b>,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
         // find $(u,v)$ sample coordinates in infinite light texture
         let mut map_pdf: Float = 0.0 as Float;
         let uv: Point2f = self.distribution.sample_continuous(u, &mut map_pdf);
@@ -376,7 +377,7 @@ impl InfiniteAreaLight {
         self.lmap.lookup_pnt_flt(st, 0.0 as Float)
     }
     pub fn pdf_li(&self, _iref: &dyn Interaction, w: &Vector3f) -> Float {
-        // TODO: ProfilePhase _(Prof::LightPdf);
+        let _p = ProfilePhase::new(Prof::LightPdf);
         let wi: Vector3f = self.world_to_light.transform_vector(&w);
         let theta: Float = spherical_theta(&wi);
         let phi: Float = spherical_phi(&wi);
@@ -400,7 +401,7 @@ impl InfiniteAreaLight {
         pdf_pos: &mut Float,
         pdf_dir: &mut Float,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
 
         // find $(u,v)$ sample coordinates in infinite light texture
         let mut map_pdf: Float = 0.0 as Float;
@@ -0,0 +1,47 @@
+//std
+use std::sync::Arc;
+// pbrt
+use crate::core::interaction::SurfaceInteraction;
+use crate::core::material::{Material, TransportMode};
+use crate::core::pbrt::Spectrum;
+
+/// The indexed material takes a list of other materials and uses the
+/// face index of the surface being shaded (e.g. read from a PLY
+/// file's `face_indices` or `material_id` property) to pick one of
+/// them. Faces with an out-of-range index use the first material.
+pub struct IndexedMaterial {
+    pub materials: Vec<Arc<Material>>,
+}
+
+impl IndexedMaterial {
+    pub fn new(materials: Vec<Arc<Material>>) -> Self {
+        assert!(!materials.is_empty());
+        IndexedMaterial { materials }
+    }
+    /// Returns the material used for faces with the given index.
+    pub fn get_material(&self, face_index: i32) -> &Arc<Material> {
+        if face_index >= 0 && (face_index as usize) < self.materials.len() {
+            &self.materials[face_index as usize]
+        } else {
+            &self.materials[0]
+        }
+    }
+    // Material
+    pub fn compute_scattering_functions(
+        &self,
+        si: &mut SurfaceInteraction,
+        // arena: &mut Arena,
+        mode: TransportMode,
+        allow_multiple_lobes: bool,
+        material: Option<Arc<Material>>,
+        scale: Option<Spectrum>,
+    ) {
+        self.get_material(si.face_index).compute_scattering_functions(
+            si,
+            mode,
+            allow_multiple_lobes,
+            material,
+            scale,
+        )
+    }
+}
@@ -67,6 +67,8 @@ impl MixMaterial {
             si.common.time,
             si.shape,
         );
+        si2.face_index = si.face_index;
+        si2.vertex_color = si.vertex_color;
         self.m1
             .compute_scattering_functions(si, mode, allow_multiple_lobes, None, Some(s1));
         self.m2
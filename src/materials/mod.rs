@@ -5,6 +5,7 @@
 //! - FourierMaterial
 //! - GlassMaterial
 //! - HairMaterial
+//! - IndexedMaterial
 //! - KdSubsurfaceMaterial
 //! - MatteMaterial
 //! - MetalMaterial
@@ -28,6 +29,7 @@ pub mod disney;
 pub mod fourier;
 pub mod glass;
 pub mod hair;
+pub mod indexed;
 pub mod matte;
 pub mod metal;
 pub mod mirror;
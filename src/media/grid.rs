@@ -7,8 +7,10 @@ use crate::core::interaction::MediumInteraction;
 use crate::core::medium::{HenyeyGreenstein, Medium};
 use crate::core::pbrt::lerp;
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::sampler::Sampler;
 use crate::core::spectrum::RGBEnum;
+use crate::core::stats::{self, Ratio};
 use crate::core::transform::Transform;
 
 // see grid.h
@@ -153,8 +155,8 @@ impl GridDensityMedium {
     }
     // Medium
     pub fn tr(&self, r_world: &Ray, sampler: &mut Sampler) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::MediumTr);
-        // TODO: ++nTrCalls;
+        let _p = ProfilePhase::new(Prof::MediumTr);
+        stats::add_ratio(Ratio::GridStepsPerTrCall, 0, 1);
         let mut in_ray: Ray = Ray::default();
         in_ray.o = r_world.o;
         in_ray.d = r_world.d.normalize();
@@ -183,7 +185,7 @@ impl GridDensityMedium {
         let mut tr: Float = 1.0;
         let mut t: Float = t_min;
         loop {
-            // TODO: ++nTrSteps;
+            stats::add_ratio(Ratio::GridStepsPerTrCall, 1, 0);
             t -= (1.0 as Float - sampler.get_1d()).ln() * self.inv_max_density / self.sigma_t;
             if t >= t_max {
                 break;
@@ -209,7 +211,7 @@ impl GridDensityMedium {
         r_world: &Ray,
         sampler: &mut Sampler,
     ) -> (Spectrum, Option<MediumInteraction>) {
-        // TODO: ProfilePhase _(Prof::MediumSample);
+        let _p = ProfilePhase::new(Prof::MediumSample);
         let mut in_ray: Ray = Ray::default();
         in_ray.o = r_world.o;
         in_ray.d = r_world.d.normalize();
@@ -8,6 +8,7 @@ use crate::core::geometry::Ray;
 use crate::core::interaction::MediumInteraction;
 use crate::core::medium::{HenyeyGreenstein, Medium};
 use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::sampler::Sampler;
 use crate::core::spectrum::RGBEnum;
 
@@ -31,7 +32,7 @@ impl HomogeneousMedium {
     }
     // Medium
     pub fn tr(&self, ray: &Ray, _sampler: &mut Sampler) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::MediumTr);
+        let _p = ProfilePhase::new(Prof::MediumTr);
         (-self.sigma_t * (ray.t_max.get() * ray.d.length()).min(f32::MAX)).exp()
     }
     pub fn sample(
@@ -39,7 +40,7 @@ impl HomogeneousMedium {
         ray: &Ray,
         sampler: &mut Sampler,
     ) -> (Spectrum, Option<MediumInteraction>) {
-        // TODO: ProfilePhase _(Prof::MediumSample);
+        let _p = ProfilePhase::new(Prof::MediumSample);
         // sample a channel and distance along the ray
         let channel: usize = ((sampler.get_1d() * 3.0 as Float) as usize).min(2_usize);
         let channel_rgb: RGBEnum = match channel {
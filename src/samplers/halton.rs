@@ -12,6 +12,7 @@ use crate::core::lowdiscrepancy::{PRIME_SUMS, PRIME_TABLE_SIZE};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::mod_t;
 use crate::core::pbrt::Float;
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::rng::Rng;
 use crate::core::sampler::Sampler;
 
@@ -235,7 +236,7 @@ impl HaltonSampler {
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         // Sampler::StartPixel(p);
         self.current_pixel = p;
         self.current_pixel_sample_index = 0_i64;
@@ -273,7 +274,7 @@ impl HaltonSampler {
         assert!(self.array_end_dim == dim);
     }
     pub fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         if self.dimension >= self.array_start_dim && self.dimension < self.array_end_dim {
             self.dimension = self.array_end_dim;
         }
@@ -284,7 +285,7 @@ impl HaltonSampler {
         ret
     }
     pub fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         if self.dimension + 1 >= self.array_start_dim && self.dimension < self.array_end_dim {
             self.dimension = self.array_end_dim;
         }
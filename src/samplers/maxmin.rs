@@ -5,8 +5,9 @@ use crate::core::lowdiscrepancy::{sample_generator_matrix, sobol_2d, van_der_cor
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{is_power_of_2, log_2_int_i64, round_up_pow2_32, round_up_pow2_64};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{PixelSampleRng, Sampler};
 use crate::core::sampling::shuffle;
 
 pub struct MaxMinDistSampler {
@@ -18,6 +19,7 @@ pub struct MaxMinDistSampler {
     current_1d_dimension: i32,
     current_2d_dimension: i32,
     rng: Rng,
+    sample_rng: PixelSampleRng,
     // inherited from class Sampler (see sampler.h)
     current_pixel: Point2i,
     current_pixel_sample_index: i64,
@@ -63,6 +65,7 @@ impl MaxMinDistSampler {
             current_1d_dimension: 0_i32,
             current_2d_dimension: 0_i32,
             rng: Rng::default(),
+            sample_rng: PixelSampleRng::default(),
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
             samples_1d_array_sizes: Vec::new(),
@@ -90,6 +93,7 @@ impl MaxMinDistSampler {
             current_1d_dimension: self.current_1d_dimension,
             current_2d_dimension: self.current_2d_dimension,
             rng: self.rng,
+            sample_rng: self.sample_rng,
             current_pixel: self.current_pixel,
             current_pixel_sample_index: self.current_pixel_sample_index,
             samples_1d_array_sizes: self.samples_1d_array_sizes.to_vec(),
@@ -114,7 +118,7 @@ impl MaxMinDistSampler {
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         let inv_spp: Float = 1.0 as Float / self.samples_per_pixel as Float;
         for i in 0..self.samples_per_pixel as usize {
             self.samples_2d[0_usize][i] = Point2f {
@@ -153,13 +157,14 @@ impl MaxMinDistSampler {
         }
         // PixelSampler::StartPixel(p);
         self.current_pixel = p;
+        self.sample_rng.start_pixel(&mut self.rng);
         self.current_pixel_sample_index = 0_i64;
         // reset array offsets for next pixel sample
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
     }
     pub fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         if self.current_1d_dimension < self.samples_1d.len() as i32 {
             let sample: Float = self.samples_1d[self.current_1d_dimension as usize]
@@ -167,11 +172,11 @@ impl MaxMinDistSampler {
             self.current_1d_dimension += 1;
             sample
         } else {
-            self.rng.uniform_float()
+            self.sample_rng.uniform_float()
         }
     }
     pub fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         if self.current_2d_dimension < self.samples_2d.len() as i32 {
             let sample: Point2f = self.samples_2d[self.current_2d_dimension as usize]
@@ -180,8 +185,8 @@ impl MaxMinDistSampler {
             sample
         } else {
             // C++ call order for Point2f(rng.UniformFloat(), rng.UniformFloat());
-            let y = self.rng.uniform_float();
-            let x = self.rng.uniform_float();
+            let y = self.sample_rng.uniform_float();
+            let x = self.sample_rng.uniform_float();
             Point2f { x, y }
         }
     }
@@ -228,6 +233,8 @@ impl MaxMinDistSampler {
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
         self.current_pixel_sample_index += 1_i64;
+        self.sample_rng
+            .start_sample(self.current_pixel_sample_index);
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn reseed(&mut self, seed: u64) {
@@ -48,6 +48,19 @@
 //!
 //! ![lowdiscrepancy](/doc/img/cornell_box_pbrt_rust_lowdiscrepancy.png)
 //!
+//! ## Differences to pbrt-v3
+//!
+//! The pixel samplers (MaxMinDistSampler, RandomSampler,
+//! StratifiedSampler and ZeroTwoSequenceSampler) don't take the
+//! samples of dimensions they don't precompute from the random
+//! number generator of the pixel, like pbrt-v3 does. These samples
+//! come from a **PixelSampleRng** instead, which gives each pixel
+//! sample its own part of a sequence. A render can continue at any
+//! sample of a pixel (progressive passes, resumed checkpoints) and
+//! still get the samples of an uninterrupted render. The price is
+//! that images of these samplers differ (in their noise) from the
+//! images of pbrt-v3 and of older versions for the same seed.
+//!
 
 pub mod halton;
 pub mod maxmin;
@@ -2,14 +2,16 @@
 use crate::core::geometry::{Point2f, Point2i};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{PixelSampleRng, Sampler};
 
 // see random.h
 
 pub struct RandomSampler {
     pub samples_per_pixel: i64,
     rng: Rng,
+    sample_rng: PixelSampleRng,
     // inherited from class Sampler (see sampler.h)
     current_pixel: Point2i,
     current_pixel_sample_index: i64,
@@ -26,6 +28,7 @@ impl RandomSampler {
         RandomSampler {
             samples_per_pixel,
             rng: Rng::default(),
+            sample_rng: PixelSampleRng::default(),
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
             samples_1d_array_sizes: Vec::new(),
@@ -58,7 +61,7 @@ impl RandomSampler {
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         for i in 0..self.sample_array_1d.len() {
             for j in 0..self.sample_array_1d[i].len() {
                 self.sample_array_1d[i][j] = self.rng.uniform_float();
@@ -74,21 +77,22 @@ impl RandomSampler {
         }
         // Sampler::StartPixel(p);
         self.current_pixel = p;
+        self.sample_rng.start_pixel(&mut self.rng);
         self.current_pixel_sample_index = 0_i64;
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
     }
     pub fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
-        self.rng.uniform_float()
+        self.sample_rng.uniform_float()
     }
     pub fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         // C++: call x first
-        let x = self.rng.uniform_float();
-        let y = self.rng.uniform_float();
+        let x = self.sample_rng.uniform_float();
+        let y = self.sample_rng.uniform_float();
         Point2f { x, y }
     }
     pub fn get_2d_sample(&self, array_idx: usize, idx: usize) -> Point2f {
@@ -134,6 +138,8 @@ impl RandomSampler {
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
         self.current_pixel_sample_index += 1_i64;
+        self.sample_rng
+            .start_sample(self.current_pixel_sample_index);
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn get_current_pixel(&self) -> Point2i {
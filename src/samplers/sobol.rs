@@ -6,6 +6,7 @@ use crate::core::pbrt::Float;
 use crate::core::pbrt::{
     clamp_t, is_power_of_2, log_2_int_u32, round_up_pow2_32, round_up_pow2_64,
 };
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::rng::FLOAT_ONE_MINUS_EPSILON;
 use crate::core::sampler::Sampler;
 use crate::core::sobolmatrices::NUM_SOBOL_DIMENSIONS;
@@ -140,7 +141,7 @@ impl SobolSampler {
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         // Sampler::StartPixel(p);
         self.current_pixel = p;
         self.current_pixel_sample_index = 0_i64;
@@ -178,7 +179,7 @@ impl SobolSampler {
         assert!(self.array_end_dim == dim);
     }
     pub fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         if self.dimension >= self.array_start_dim && self.dimension < self.array_end_dim {
             self.dimension = self.array_end_dim;
         }
@@ -189,7 +190,7 @@ impl SobolSampler {
         ret
     }
     pub fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         if self.dimension + 1 >= self.array_start_dim && self.dimension < self.array_end_dim {
             self.dimension = self.array_end_dim;
         }
@@ -2,8 +2,9 @@
 use crate::core::geometry::{Point2f, Point2i};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{PixelSampleRng, Sampler};
 use crate::core::sampling::{latin_hypercube, shuffle, stratified_sample_1d, stratified_sample_2d};
 
 pub struct StratifiedSampler {
@@ -17,6 +18,7 @@ pub struct StratifiedSampler {
     current_1d_dimension: i32,
     current_2d_dimension: i32,
     rng: Rng,
+    sample_rng: PixelSampleRng,
     // inherited from class Sampler (see sampler.h)
     current_pixel: Point2i,
     current_pixel_sample_index: i64,
@@ -45,6 +47,7 @@ impl StratifiedSampler {
             current_1d_dimension: 0_i32,
             current_2d_dimension: 0_i32,
             rng: Rng::default(),
+            sample_rng: PixelSampleRng::default(),
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
             samples_1d_array_sizes: Vec::new(),
@@ -74,6 +77,7 @@ impl StratifiedSampler {
             current_1d_dimension: self.current_1d_dimension,
             current_2d_dimension: self.current_2d_dimension,
             rng: self.rng,
+            sample_rng: self.sample_rng,
             current_pixel: self.current_pixel,
             current_pixel_sample_index: self.current_pixel_sample_index,
             samples_1d_array_sizes: self.samples_1d_array_sizes.to_vec(),
@@ -99,7 +103,7 @@ impl StratifiedSampler {
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         // generate single stratified samples for the pixel
         for i in 0..self.samples_1d.len() {
             let samples: &mut [Float] = self.samples_1d[i].as_mut_slice();
@@ -155,13 +159,14 @@ impl StratifiedSampler {
         }
         // PixelSampler::StartPixel(p);
         self.current_pixel = p;
+        self.sample_rng.start_pixel(&mut self.rng);
         self.current_pixel_sample_index = 0_i64;
         // reset array offsets for next pixel sample
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
     }
     pub fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         if self.current_1d_dimension < self.samples_1d.len() as i32 {
             let sample: Float = self.samples_1d[self.current_1d_dimension as usize]
@@ -169,11 +174,11 @@ impl StratifiedSampler {
             self.current_1d_dimension += 1;
             sample
         } else {
-            self.rng.uniform_float()
+            self.sample_rng.uniform_float()
         }
     }
     pub fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         if self.current_2d_dimension < self.samples_2d.len() as i32 {
             let sample: Point2f = self.samples_2d[self.current_2d_dimension as usize]
@@ -182,8 +187,8 @@ impl StratifiedSampler {
             sample
         } else {
             // C++ call order for Point2f(rng.UniformFloat(), rng.UniformFloat());
-            let y = self.rng.uniform_float();
-            let x = self.rng.uniform_float();
+            let y = self.sample_rng.uniform_float();
+            let x = self.sample_rng.uniform_float();
             Point2f { x, y }
         }
     }
@@ -230,6 +235,8 @@ impl StratifiedSampler {
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
         self.current_pixel_sample_index += 1_i64;
+        self.sample_rng
+            .start_sample(self.current_pixel_sample_index);
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn reseed(&mut self, seed: u64) {
@@ -4,8 +4,9 @@ use crate::core::lowdiscrepancy::{sobol_2d, van_der_corput};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::round_up_pow2_32;
 use crate::core::pbrt::Float;
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::rng::Rng;
-use crate::core::sampler::Sampler;
+use crate::core::sampler::{PixelSampleRng, Sampler};
 
 // see zerotwosequence.h
 
@@ -18,6 +19,7 @@ pub struct ZeroTwoSequenceSampler {
     current_1d_dimension: i32,
     current_2d_dimension: i32,
     rng: Rng,
+    sample_rng: PixelSampleRng,
     // inherited from class Sampler (see sampler.h)
     current_pixel: Point2i,
     current_pixel_sample_index: i64,
@@ -39,6 +41,7 @@ impl Default for ZeroTwoSequenceSampler {
             current_1d_dimension: 0_i32,
             current_2d_dimension: 0_i32,
             rng: Rng::default(),
+            sample_rng: PixelSampleRng::default(),
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
             samples_1d_array_sizes: Vec::new(),
@@ -69,6 +72,7 @@ impl ZeroTwoSequenceSampler {
             current_1d_dimension: 0_i32,
             current_2d_dimension: 0_i32,
             rng: Rng::default(),
+            sample_rng: PixelSampleRng::default(),
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
             samples_1d_array_sizes: Vec::new(),
@@ -96,6 +100,7 @@ impl ZeroTwoSequenceSampler {
             current_1d_dimension: self.current_1d_dimension,
             current_2d_dimension: self.current_2d_dimension,
             rng: self.rng,
+            sample_rng: self.sample_rng,
             current_pixel: self.current_pixel,
             current_pixel_sample_index: self.current_pixel_sample_index,
             samples_1d_array_sizes: self.samples_1d_array_sizes.to_vec(),
@@ -120,7 +125,7 @@ impl ZeroTwoSequenceSampler {
     }
     // Sampler
     pub fn start_pixel(&mut self, p: Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         // generate 1D and 2D pixel sample components using $(0,2)$-sequence
         for samples in &mut self.samples_1d {
             van_der_corput(1, self.samples_per_pixel as i32, samples, &mut self.rng);
@@ -149,13 +154,14 @@ impl ZeroTwoSequenceSampler {
         }
         // PixelSampler::StartPixel(p);
         self.current_pixel = p;
+        self.sample_rng.start_pixel(&mut self.rng);
         self.current_pixel_sample_index = 0_i64;
         // reset array offsets for next pixel sample
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
     }
     pub fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         if self.current_1d_dimension < self.samples_1d.len() as i32 {
             let sample: Float = self.samples_1d[self.current_1d_dimension as usize]
@@ -163,11 +169,11 @@ impl ZeroTwoSequenceSampler {
             self.current_1d_dimension += 1;
             sample
         } else {
-            self.rng.uniform_float()
+            self.sample_rng.uniform_float()
         }
     }
     pub fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         if self.current_2d_dimension < self.samples_2d.len() as i32 {
             let sample: Point2f = self.samples_2d[self.current_2d_dimension as usize]
@@ -176,8 +182,8 @@ impl ZeroTwoSequenceSampler {
             sample
         } else {
             // C++ call order for Point2f(rng.UniformFloat(), rng.UniformFloat());
-            let y = self.rng.uniform_float();
-            let x = self.rng.uniform_float();
+            let y = self.sample_rng.uniform_float();
+            let x = self.sample_rng.uniform_float();
             Point2f { x, y }
         }
     }
@@ -224,6 +230,8 @@ impl ZeroTwoSequenceSampler {
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
         self.current_pixel_sample_index += 1_i64;
+        self.sample_rng
+            .start_sample(self.current_pixel_sample_index);
         self.current_pixel_sample_index < self.samples_per_pixel
     }
     pub fn reseed(&mut self, seed: u64) {
@@ -0,0 +1,681 @@
+// std
+use std::cell::Cell;
+use std::sync::Arc;
+// pbrt
+use crate::core::geometry::{
+    bnd3_union_pnt3f, nrm_abs_dot_vec3f, nrm_faceforward_nrm, pnt3_abs, pnt3_distance_squaredf,
+    vec3_coordinate_system, vec3_cross_nrm, vec3_cross_vec3, vec3_dot_nrmf, vec3_dot_vec3f,
+    vec3_max_componentf,
+};
+use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector2f, Vector3f};
+use crate::core::interaction::{Interaction, InteractionCommon, Shading, SurfaceInteraction};
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::{clamp_t, gamma, lerp, quadratic, Float};
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::sampling::{bilinear_pdf, sample_bilinear};
+use crate::core::shape::Shape;
+use crate::core::transform::Transform;
+
+// see shapes.h (pbrt-v4)
+
+/// A mesh of bilinear patches. Each patch is given by four vertex
+/// indices in the order $p_{00}$, $p_{10}$, $p_{01}$, and $p_{11}$.
+#[derive(Clone)]
+pub struct BilinearPatchMesh {
+    /// the total number of patches in the mesh
+    pub n_patches: u32,
+    /// vector of vertex indices (four per patch)
+    pub vertex_indices: Vec<u32>,
+    /// the total number of vertices in the mesh
+    pub n_vertices: u32,
+    /// vector of *n_vertices* vertex positions
+    pub p: Vec<Point3f>,
+    /// an optional vector of normal vectors (can be empty)
+    pub n: Vec<Normal3f>,
+    /// an optional vector of paramtric (u, v) values (texture coordinates)
+    pub uv: Vec<Point2f>,
+    // inherited from class Shape (see shape.h)
+    pub object_to_world: Transform,
+    pub world_to_object: Transform,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+}
+
+impl BilinearPatchMesh {
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        n_patches: u32,
+        vertex_indices: Vec<u32>,
+        n_vertices: u32,
+        p: Vec<Point3f>,
+        n: Vec<Normal3f>,
+        uv: Vec<Point2f>,
+    ) -> Self {
+        BilinearPatchMesh {
+            // Shape
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+            // BilinearPatchMesh
+            n_patches,
+            vertex_indices,
+            n_vertices,
+            p,
+            n,
+            uv,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct BilinearPatch {
+    mesh: Arc<BilinearPatchMesh>,
+    pub id: u32,
+    area: Float,
+}
+
+impl BilinearPatch {
+    pub fn new(mesh: Arc<BilinearPatchMesh>, blp_number: u32) -> Self {
+        let mut patch = BilinearPatch {
+            mesh,
+            id: blp_number,
+            area: 0.0 as Float,
+        };
+        patch.area = patch.compute_area();
+        patch
+    }
+    fn get_indices(&self) -> [usize; 4] {
+        let idx1: usize = (self.id * 4) as usize;
+        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 4)];
+        [
+            idx[0] as usize,
+            idx[1] as usize,
+            idx[2] as usize,
+            idx[3] as usize,
+        ]
+    }
+    /// Returns the vertices $p_{00}$, $p_{10}$, $p_{01}$, and $p_{11}$
+    /// of the patch (in world space).
+    pub fn get_vertices(&self) -> [Point3f; 4] {
+        let idx = self.get_indices();
+        [
+            self.mesh.p[idx[0]],
+            self.mesh.p[idx[1]],
+            self.mesh.p[idx[2]],
+            self.mesh.p[idx[3]],
+        ]
+    }
+    pub fn get_uvs(&self) -> [Point2f; 4] {
+        if self.mesh.uv.is_empty() {
+            [
+                Point2f { x: 0.0, y: 0.0 },
+                Point2f { x: 1.0, y: 0.0 },
+                Point2f { x: 0.0, y: 1.0 },
+                Point2f { x: 1.0, y: 1.0 },
+            ]
+        } else {
+            let idx = self.get_indices();
+            [
+                self.mesh.uv[idx[0]],
+                self.mesh.uv[idx[1]],
+                self.mesh.uv[idx[2]],
+                self.mesh.uv[idx[3]],
+            ]
+        }
+    }
+    /// Are all four vertices in a plane and form a parallelogram?
+    fn is_rectangle(&self) -> bool {
+        let p = self.get_vertices();
+        if (p[1] - p[0]).length_squared() == 0.0 as Float
+            || (p[3] - p[1]).length_squared() == 0.0 as Float
+            || (p[2] - p[3]).length_squared() == 0.0 as Float
+            || (p[0] - p[2]).length_squared() == 0.0 as Float
+        {
+            return false;
+        }
+        // check if bilinear patch vertices are coplanar
+        let n: Vector3f = vec3_cross_vec3(&(p[1] - p[0]), &(p[2] - p[0])).normalize();
+        if vec3_dot_vec3f(&(p[3] - p[0]).normalize(), &n).abs() > 1e-5 as Float {
+            return false;
+        }
+        // check if planar vertices form a rectangle
+        let p_center: Point3f = (p[0] + p[1] + p[2] + p[3]) * 0.25 as Float;
+        let d2: [Float; 4] = [
+            pnt3_distance_squaredf(&p[0], &p_center),
+            pnt3_distance_squaredf(&p[1], &p_center),
+            pnt3_distance_squaredf(&p[3], &p_center),
+            pnt3_distance_squaredf(&p[2], &p_center),
+        ];
+        for item in d2.iter().skip(1) {
+            if (item - d2[0]).abs() / d2[0] > 1e-4 as Float {
+                return false;
+            }
+        }
+        true
+    }
+    fn compute_area(&self) -> Float {
+        let p = self.get_vertices();
+        if self.is_rectangle() {
+            return (p[1] - p[0]).length() * (p[2] - p[0]).length();
+        }
+        // approximate the area of a non-planar bilinear patch by
+        // summing the areas of a grid of quads
+        let na: usize = 3;
+        let mut pts: Vec<Point3f> = Vec::with_capacity((na + 1) * (na + 1));
+        for i in 0..=na {
+            let u: Float = i as Float / na as Float;
+            for j in 0..=na {
+                let v: Float = j as Float / na as Float;
+                pts.push(lerp(u, lerp(v, p[0], p[2]), lerp(v, p[1], p[3])));
+            }
+        }
+        let mut area: Float = 0.0 as Float;
+        for i in 0..na {
+            for j in 0..na {
+                let p00: Point3f = pts[i * (na + 1) + j];
+                let p01: Point3f = pts[i * (na + 1) + j + 1];
+                let p10: Point3f = pts[(i + 1) * (na + 1) + j];
+                let p11: Point3f = pts[(i + 1) * (na + 1) + j + 1];
+                area += 0.5 as Float * vec3_cross_vec3(&(p11 - p00), &(p10 - p01)).length();
+            }
+        }
+        area
+    }
+    /// Compute $(u,v)$ and $t$ of the closest ray-patch intersection
+    /// (if any) in $(0, t_{max})$.
+    fn intersect_bilinear_patch(&self, ray: &Ray, t_max: Float) -> Option<(Point2f, Float)> {
+        let p = self.get_vertices();
+        let (p00, p10, p01, p11) = (p[0], p[1], p[2], p[3]);
+        let o: Vector3f = Vector3f::from(ray.o);
+        // find quadratic coefficients for distance from ray to _u_ iso-lines
+        let a: Float = vec3_dot_vec3f(&vec3_cross_vec3(&(p10 - p00), &(p01 - p11)), &ray.d);
+        let c: Float = vec3_dot_vec3f(&vec3_cross_vec3(&(p00 - ray.o), &ray.d), &(p01 - p00));
+        let b: Float =
+            vec3_dot_vec3f(&vec3_cross_vec3(&(p10 - ray.o), &ray.d), &(p11 - p10)) - (a + c);
+        // solve quadratic for bilinear patch _u_ intersection
+        let mut u1: Float = 0.0;
+        let mut u2: Float = 0.0;
+        if a == 0.0 as Float {
+            // linear equation
+            if b == 0.0 as Float {
+                return None;
+            }
+            u1 = -c / b;
+            u2 = u1;
+        } else if !quadratic(a, b, c, &mut u1, &mut u2) {
+            return None;
+        }
+        // find epsilon _eps_ to ensure that candidate _t_ is greater than zero
+        let eps: Float = gamma(10)
+            * (vec3_max_componentf(&o.abs())
+                + vec3_max_componentf(&ray.d.abs())
+                + vec3_max_componentf(&Vector3f::from(pnt3_abs(&p00)))
+                + vec3_max_componentf(&Vector3f::from(pnt3_abs(&p10)))
+                + vec3_max_componentf(&Vector3f::from(pnt3_abs(&p01)))
+                + vec3_max_componentf(&Vector3f::from(pnt3_abs(&p11))));
+        // compute _v_ and _t_ for both _u_ intersections
+        let mut t: Float = t_max;
+        let mut uv: Point2f = Point2f::default();
+        for (i, ui) in [u1, u2].iter().enumerate() {
+            let ui: Float = *ui;
+            if !(0.0 as Float..=1.0 as Float).contains(&ui) || (i == 1 && ui == u1) {
+                continue;
+            }
+            // precompute common terms for _v_ and _t_ computation
+            let uo: Point3f = lerp(ui, p00, p10);
+            let ud: Vector3f = lerp(ui, p01, p11) - uo;
+            let deltao: Vector3f = uo - ray.o;
+            let perp: Vector3f = vec3_cross_vec3(&ray.d, &ud);
+            let p2: Float = perp.length_squared();
+            // compute matrix determinants for _v_ and _t_ numerators
+            let vi: Float = vec3_dot_vec3f(&deltao, &vec3_cross_vec3(&ray.d, &perp));
+            let ti: Float = vec3_dot_vec3f(&deltao, &vec3_cross_vec3(&ud, &perp));
+            // set _u_, _v_, and _t_ if intersection is valid
+            if ti > p2 * eps && vi >= 0.0 as Float && vi <= p2 && t > ti / p2 {
+                uv = Point2f {
+                    x: ui,
+                    y: vi / p2,
+                };
+                t = ti / p2;
+            }
+        }
+        // check intersection _t_ against _t_max_
+        if t >= t_max {
+            return None;
+        }
+        Some((uv, t))
+    }
+    // Shape
+    pub fn object_bound(&self) -> Bounds3f {
+        let p = self.get_vertices();
+        let w2o: &Transform = &self.mesh.world_to_object;
+        bnd3_union_pnt3f(
+            &bnd3_union_pnt3f(
+                &Bounds3f::new(w2o.transform_point(&p[0]), w2o.transform_point(&p[1])),
+                &w2o.transform_point(&p[2]),
+            ),
+            &w2o.transform_point(&p[3]),
+        )
+    }
+    pub fn world_bound(&self) -> Bounds3f {
+        let p = self.get_vertices();
+        bnd3_union_pnt3f(
+            &bnd3_union_pnt3f(&Bounds3f::new(p[0], p[1]), &p[2]),
+            &p[3],
+        )
+    }
+    pub fn intersect(&self, ray: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        let _p = ProfilePhase::new(Prof::ShapeIntersect);
+        let (uv, t) = match self.intersect_bilinear_patch(ray, ray.t_max.get()) {
+            Some(hit) => hit,
+            None => return false,
+        };
+        let p = self.get_vertices();
+        let (p00, p10, p01, p11) = (p[0], p[1], p[2], p[3]);
+        // compute bilinear patch point $\VEC{p}\,$, $\dpdu$, and $\dpdv$ for $(u,v)$
+        let p_hit: Point3f = lerp(uv.x, lerp(uv.y, p00, p01), lerp(uv.y, p10, p11));
+        let mut dpdu: Vector3f = lerp(uv.y, p10, p11) - lerp(uv.y, p00, p01);
+        let mut dpdv: Vector3f = lerp(uv.x, p01, p11) - lerp(uv.x, p00, p10);
+        if vec3_cross_vec3(&dpdu, &dpdv).length_squared() == 0.0 as Float {
+            // degenerate patch; the intersection is bogus
+            return false;
+        }
+        // compute $(s,t)$ texture coordinates at bilinear patch $(u,v)$
+        let mut st: Point2f = uv;
+        let mut duds: Float = 1.0;
+        let mut dudt: Float = 0.0;
+        let mut dvds: Float = 0.0;
+        let mut dvdt: Float = 1.0;
+        if !self.mesh.uv.is_empty() {
+            // compute texture coordinates for bilinear patch intersection point
+            let uvs: [Point2f; 4] = self.get_uvs();
+            st = lerp(uv.x, lerp(uv.y, uvs[0], uvs[2]), lerp(uv.y, uvs[1], uvs[3]));
+            // update bilinear patch $\dpdu$ and $\dpdv$ accounting for $(s,t)$
+            let dstdu: Vector2f = lerp(uv.y, uvs[1], uvs[3]) - lerp(uv.y, uvs[0], uvs[2]);
+            let dstdv: Vector2f = lerp(uv.x, uvs[2], uvs[3]) - lerp(uv.x, uvs[0], uvs[1]);
+            duds = if dstdu.x.abs() < 1e-8 as Float {
+                0.0
+            } else {
+                1.0 / dstdu.x
+            };
+            dvds = if dstdv.x.abs() < 1e-8 as Float {
+                0.0
+            } else {
+                1.0 / dstdv.x
+            };
+            dudt = if dstdu.y.abs() < 1e-8 as Float {
+                0.0
+            } else {
+                1.0 / dstdu.y
+            };
+            dvdt = if dstdv.y.abs() < 1e-8 as Float {
+                0.0
+            } else {
+                1.0 / dstdv.y
+            };
+            // compute partial derivatives of $\VEC{p}$ with respect to $(s,t)$
+            let dpds: Vector3f = dpdu * duds + dpdv * dvds;
+            let mut dpdt: Vector3f = dpdu * dudt + dpdv * dvdt;
+            // set _dpdu_ and _dpdv_ to updated partial derivatives
+            let cross_st: Vector3f = vec3_cross_vec3(&dpds, &dpdt);
+            if cross_st.length_squared() != 0.0 as Float {
+                if vec3_dot_vec3f(&vec3_cross_vec3(&dpdu, &dpdv), &cross_st) < 0.0 as Float {
+                    dpdt = -dpdt;
+                }
+                dpdu = dpds;
+                dpdv = dpdt;
+            }
+        }
+        // find partial derivatives $\dndu$ and $\dndv$ for bilinear patch
+        let d2pduu: Vector3f = Vector3f::default();
+        let d2pdvv: Vector3f = Vector3f::default();
+        let d2pduv: Vector3f = (p00 - p01) + (p11 - p10);
+        // compute coefficients for fundamental forms
+        let e1: Float = vec3_dot_vec3f(&dpdu, &dpdu);
+        let f1: Float = vec3_dot_vec3f(&dpdu, &dpdv);
+        let g1: Float = vec3_dot_vec3f(&dpdv, &dpdv);
+        let n: Vector3f = vec3_cross_vec3(&dpdu, &dpdv).normalize();
+        let e2: Float = vec3_dot_vec3f(&n, &d2pduu);
+        let f2: Float = vec3_dot_vec3f(&n, &d2pduv);
+        let g2: Float = vec3_dot_vec3f(&n, &d2pdvv);
+        // compute $\dndu$ and $\dndv$ from fundamental form coefficients
+        let egf2: Float = e1 * g1 - f1 * f1;
+        let inv_egf2: Float = if egf2 == 0.0 as Float {
+            0.0 as Float
+        } else {
+            1.0 as Float / egf2
+        };
+        let dndu: Normal3f = Normal3f::from(
+            dpdu * ((f2 * f1 - e2 * g1) * inv_egf2) + dpdv * ((e2 * f1 - f2 * e1) * inv_egf2),
+        );
+        let dndv: Normal3f = Normal3f::from(
+            dpdu * ((g2 * f1 - f2 * g1) * inv_egf2) + dpdv * ((f2 * f1 - g2 * e1) * inv_egf2),
+        );
+        // compute error bounds for bilinear patch intersection
+        let p_abs_sum: Point3f = pnt3_abs(&p00) + pnt3_abs(&p01) + pnt3_abs(&p10) + pnt3_abs(&p11);
+        let p_error: Vector3f = Vector3f::from(p_abs_sum) * gamma(6);
+        // initialize _SurfaceInteraction_ for bilinear patch intersection
+        let wo: Vector3f = -ray.d;
+        let mut surface_normal: Normal3f = Normal3f::from(n);
+        if self.mesh.reverse_orientation ^ self.mesh.transform_swaps_handedness {
+            surface_normal = -surface_normal;
+        }
+        let mut shading: Shading = Shading {
+            n: surface_normal,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+        };
+        if !self.mesh.n.is_empty() {
+            // compute shading normals for bilinear patch intersection point
+            let idx = self.get_indices();
+            let n00: Normal3f = self.mesh.n[idx[0]];
+            let n10: Normal3f = self.mesh.n[idx[1]];
+            let n01: Normal3f = self.mesh.n[idx[2]];
+            let n11: Normal3f = self.mesh.n[idx[3]];
+            let ns: Normal3f = lerp(uv.x, lerp(uv.y, n00, n01), lerp(uv.y, n10, n11));
+            if ns.length_squared() > 0.0 as Float {
+                let ns: Normal3f = ns.normalize();
+                // set shading geometry for bilinear patch intersection
+                let dndu_uv: Normal3f = lerp(uv.y, n10, n11) - lerp(uv.y, n00, n01);
+                let dndv_uv: Normal3f = lerp(uv.x, n01, n11) - lerp(uv.x, n00, n10);
+                // update $\dndu$ and $\dndv$ to account for $(s,t)$ parameterization
+                let dndu_st: Normal3f = dndu_uv * duds + dndv_uv * dvds;
+                let dndv_st: Normal3f = dndu_uv * dudt + dndv_uv * dvdt;
+                // compute shading tangents from _ns_ and _dpdu_
+                let mut ss: Vector3f = vec3_cross_nrm(&vec3_cross_nrm(&dpdu, &ns), &ns);
+                let mut ts: Vector3f;
+                if ss.length_squared() > 0.0 as Float {
+                    ss = -ss.normalize();
+                    ts = vec3_cross_nrm(&ss, &ns);
+                    if vec3_dot_vec3f(&ts, &dpdv) < 0.0 as Float {
+                        ts = -ts;
+                    }
+                } else {
+                    ts = Vector3f::default();
+                    vec3_coordinate_system(&Vector3f::from(ns), &mut ss, &mut ts);
+                }
+                shading.n = ns;
+                shading.dpdu = ss;
+                shading.dpdv = ts;
+                shading.dndu = dndu_st;
+                shading.dndv = dndv_st;
+                surface_normal = nrm_faceforward_nrm(&surface_normal, &shading.n);
+            }
+        }
+        {
+            isect.common.p = p_hit;
+            isect.common.time = ray.time;
+            isect.common.p_error = p_error;
+            isect.common.wo = wo;
+            isect.common.n = surface_normal;
+            isect.common.medium_interface = None;
+        }
+        isect.uv = st;
+        isect.dpdu = dpdu;
+        isect.dpdv = dpdv;
+        isect.dndu = dndu;
+        isect.dndv = dndv;
+        isect.dpdx = Cell::new(Vector3f::default());
+        isect.dpdy = Cell::new(Vector3f::default());
+        isect.dudx = Cell::new(0.0 as Float);
+        isect.dvdx = Cell::new(0.0 as Float);
+        isect.dudy = Cell::new(0.0 as Float);
+        isect.dvdy = Cell::new(0.0 as Float);
+        isect.primitive = None;
+        isect.shading = shading;
+        isect.bsdf = None;
+        isect.shape = None;
+        isect.face_index = 0;
+        *t_hit = t;
+        true
+    }
+    pub fn intersect_p(&self, ray: &Ray) -> bool {
+        let _p = ProfilePhase::new(Prof::ShapeIntersectP);
+        self.intersect_bilinear_patch(ray, ray.t_max.get()).is_some()
+    }
+    pub fn get_reverse_orientation(&self) -> bool {
+        self.mesh.reverse_orientation
+    }
+    pub fn get_transform_swaps_handedness(&self) -> bool {
+        self.mesh.transform_swaps_handedness
+    }
+    pub fn get_object_to_world(&self) -> Transform {
+        self.mesh.object_to_world
+    }
+    pub fn area(&self) -> Float {
+        self.area
+    }
+    /// Returns the four corner weights used for (approximately)
+    /// area-uniform sampling of the patch.
+    fn sampling_weights(&self) -> [Float; 4] {
+        let p = self.get_vertices();
+        let (p00, p10, p01, p11) = (p[0], p[1], p[2], p[3]);
+        [
+            vec3_cross_vec3(&(p10 - p00), &(p01 - p00)).length(),
+            vec3_cross_vec3(&(p10 - p00), &(p11 - p10)).length(),
+            vec3_cross_vec3(&(p01 - p00), &(p11 - p01)).length(),
+            vec3_cross_vec3(&(p11 - p10), &(p11 - p01)).length(),
+        ]
+    }
+    /// Probability density (with respect to surface area) of sampling
+    /// the patch at $(u,v)$.
+    fn pdf_uv(&self, uv: Point2f) -> Float {
+        let p = self.get_vertices();
+        let (p00, p10, p01, p11) = (p[0], p[1], p[2], p[3]);
+        let dpdu: Vector3f = lerp(uv.y, p10, p11) - lerp(uv.y, p00, p01);
+        let dpdv: Vector3f = lerp(uv.x, p01, p11) - lerp(uv.x, p00, p10);
+        let length: Float = vec3_cross_vec3(&dpdu, &dpdv).length();
+        if length == 0.0 as Float {
+            return 0.0 as Float;
+        }
+        if self.is_rectangle() {
+            1.0 as Float / length
+        } else {
+            bilinear_pdf(uv, &self.sampling_weights()) / length
+        }
+    }
+    /// The parametric $(u,v)$ of the point *p* on the patch, found by
+    /// Gauss-Newton iterations starting at the center.
+    fn invert_uv(&self, p: &Point3f) -> Point2f {
+        let v = self.get_vertices();
+        let (p00, p10, p01, p11) = (v[0], v[1], v[2], v[3]);
+        let mut uv: Point2f = Point2f { x: 0.5, y: 0.5 };
+        for _ in 0..16 {
+            let dpdu: Vector3f = lerp(uv.y, p10, p11) - lerp(uv.y, p00, p01);
+            let dpdv: Vector3f = lerp(uv.x, p01, p11) - lerp(uv.x, p00, p10);
+            let r: Vector3f = lerp(uv.x, lerp(uv.y, p00, p01), lerp(uv.y, p10, p11)) - *p;
+            // solve the normal equations for the step
+            let a: Float = vec3_dot_vec3f(&dpdu, &dpdu);
+            let b: Float = vec3_dot_vec3f(&dpdu, &dpdv);
+            let c: Float = vec3_dot_vec3f(&dpdv, &dpdv);
+            let det: Float = a * c - b * b;
+            if det == 0.0 as Float {
+                break;
+            }
+            let ru: Float = vec3_dot_vec3f(&dpdu, &r);
+            let rv: Float = vec3_dot_vec3f(&dpdv, &r);
+            let du: Float = (c * ru - b * rv) / det;
+            let dv: Float = (a * rv - b * ru) / det;
+            uv.x = clamp_t(uv.x - du, 0.0 as Float, 1.0 as Float);
+            uv.y = clamp_t(uv.y - dv, 0.0 as Float, 1.0 as Float);
+            if du.abs() < 1e-6 as Float && dv.abs() < 1e-6 as Float {
+                break;
+            }
+        }
+        uv
+    }
+    /// Probability density (with respect to surface area) of sampling
+    /// the point *iref* on the patch with **sample()**.
+    pub fn pdf(&self, iref: &InteractionCommon) -> Float {
+        if self.is_rectangle() {
+            1.0 as Float / self.area
+        } else {
+            self.pdf_uv(self.invert_uv(&iref.p))
+        }
+    }
+    pub fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
+        let p = self.get_vertices();
+        let (p00, p10, p01, p11) = (p[0], p[1], p[2], p[3]);
+        // sample bilinear patch parametric $(u,v)$ coordinates
+        let uv: Point2f = if self.is_rectangle() {
+            u
+        } else {
+            sample_bilinear(u, &self.sampling_weights())
+        };
+        // compute bilinear patch geometric quantities at sampled $(u,v)$
+        let it_p: Point3f = lerp(uv.x, lerp(uv.y, p00, p01), lerp(uv.y, p10, p11));
+        let dpdu: Vector3f = lerp(uv.y, p10, p11) - lerp(uv.y, p00, p01);
+        let dpdv: Vector3f = lerp(uv.x, p01, p11) - lerp(uv.x, p00, p10);
+        let mut it_n: Normal3f = Normal3f::from(vec3_cross_vec3(&dpdu, &dpdv)).normalize();
+        // flip normal at sampled $(u,v)$ if necessary
+        if !self.mesh.n.is_empty() {
+            let idx = self.get_indices();
+            let ns: Normal3f = lerp(
+                uv.x,
+                lerp(uv.y, self.mesh.n[idx[0]], self.mesh.n[idx[2]]),
+                lerp(uv.y, self.mesh.n[idx[1]], self.mesh.n[idx[3]]),
+            );
+            it_n = nrm_faceforward_nrm(&it_n, &ns);
+        } else if self.mesh.reverse_orientation ^ self.mesh.transform_swaps_handedness {
+            it_n *= -1.0 as Float;
+        }
+        // compute error bounds for sampled point on bilinear patch
+        let p_abs_sum: Point3f = pnt3_abs(&p00) + pnt3_abs(&p01) + pnt3_abs(&p10) + pnt3_abs(&p11);
+        let it_p_error: Vector3f = Vector3f::from(p_abs_sum) * gamma(6);
+        *pdf = self.pdf_uv(uv);
+        InteractionCommon {
+            p: it_p,
+            time: 0.0 as Float,
+            p_error: it_p_error,
+            wo: Vector3f::default(),
+            n: it_n,
+            medium_interface: None,
+        }
+    }
+    pub fn sample_with_ref_point(
+        &self,
+        iref: &InteractionCommon,
+        u: Point2f,
+        pdf: &mut Float,
+    ) -> InteractionCommon {
+        let intr: InteractionCommon = self.sample(u, pdf);
+        let mut wi: Vector3f = intr.p - iref.p;
+        if wi.length_squared() == 0.0 as Float {
+            *pdf = 0.0 as Float;
+        } else {
+            wi = wi.normalize();
+            // convert from area measure, as returned by the Sample()
+            // call above, to solid angle measure.
+            *pdf *= pnt3_distance_squaredf(&iref.p, &intr.p) / nrm_abs_dot_vec3f(&intr.n, &-wi);
+            if (*pdf).is_infinite() {
+                *pdf = 0.0 as Float;
+            }
+        }
+        intr
+    }
+    pub fn pdf_with_ref_point(&self, iref: &dyn Interaction, wi: &Vector3f) -> Float {
+        // intersect sample ray with area light geometry
+        let ray: Ray = iref.spawn_ray(wi);
+        let (uv, t) = match self.intersect_bilinear_patch(&ray, ray.t_max.get()) {
+            Some(hit) => hit,
+            None => return 0.0 as Float,
+        };
+        let p = self.get_vertices();
+        let (p00, p10, p01, p11) = (p[0], p[1], p[2], p[3]);
+        let dpdu: Vector3f = lerp(uv.y, p10, p11) - lerp(uv.y, p00, p01);
+        let dpdv: Vector3f = lerp(uv.x, p01, p11) - lerp(uv.x, p00, p10);
+        let n: Normal3f = Normal3f::from(vec3_cross_vec3(&dpdu, &dpdv)).normalize();
+        // convert light sample weight to solid angle measure
+        let p_hit: Point3f = ray.position(t);
+        let cos_theta: Float = vec3_dot_nrmf(&-(*wi), &n).abs();
+        let mut pdf: Float =
+            self.pdf_uv(uv) * pnt3_distance_squaredf(iref.get_p(), &p_hit) / cos_theta;
+        if pdf.is_infinite() {
+            pdf = 0.0 as Float;
+        }
+        pdf
+    }
+}
+
+pub fn create_bilinear_mesh_shape(
+    o2w: &Transform,
+    w2o: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Shape>> {
+    let mut vi: Vec<i32> = params.find_int("indices");
+    let p: Vec<Point3f> = params.find_point3f("P");
+    if p.is_empty() {
+        panic!("Vertex positions \"P\" not provided for bilinearmesh shape.");
+    }
+    if vi.is_empty() {
+        if p.len() == 4_usize {
+            // a single patch doesn't need any indices
+            vi = vec![0, 1, 2, 3];
+        } else {
+            panic!("Vertex indices \"indices\" not provided for bilinearmesh shape.");
+        }
+    } else if 4 * (vi.len() / 4) != vi.len() {
+        panic!(
+            "Number of vertex indices {} not a multiple of 4 for bilinearmesh shape.",
+            vi.len()
+        );
+    }
+    for item in &vi {
+        if *item < 0 || *item as usize >= p.len() {
+            panic!(
+                "bilinearmesh has out of-bounds vertex index {} ({} \"P\" values were given)",
+                item,
+                p.len()
+            );
+        }
+    }
+    let mut uvs: Vec<Point2f> = params.find_point2f("uv");
+    if !uvs.is_empty() && uvs.len() != p.len() {
+        println!(
+            "WARNING: Number of \"uv\"s for bilinear patch mesh must match \"P\"s. Discarding uvs."
+        );
+        uvs = Vec::new();
+    }
+    let n: Vec<Normal3f> = params.find_normal3f("N");
+    let mut n_ws: Vec<Normal3f> = Vec::new();
+    if !n.is_empty() {
+        if n.len() != p.len() {
+            println!(
+                "WARNING: Number of \"N\"s for bilinear patch mesh must match \"P\"s. Discarding normals."
+            );
+        } else {
+            // transform normals to world space
+            for item in &n {
+                n_ws.push(o2w.transform_normal(item));
+            }
+        }
+    }
+    // transform mesh vertices to world space
+    let mut p_ws: Vec<Point3f> = Vec::with_capacity(p.len());
+    for item in &p {
+        p_ws.push(o2w.transform_point(item));
+    }
+    let vertex_indices: Vec<u32> = vi.iter().map(|i| *i as u32).collect();
+    let mesh = Arc::new(BilinearPatchMesh::new(
+        *o2w,
+        *w2o,
+        reverse_orientation,
+        (vi.len() / 4) as u32,
+        vertex_indices,
+        p.len() as u32,
+        p_ws, // in world space
+        n_ws, // in world space
+        uvs,
+    ));
+    let mut shapes: Vec<Arc<Shape>> = Vec::with_capacity(mesh.n_patches as usize);
+    for id in 0..mesh.n_patches {
+        shapes.push(Arc::new(Shape::Blnr(BilinearPatch::new(mesh.clone(), id))));
+    }
+    shapes
+}
@@ -1,4 +1,5 @@
 // std
+use std::ops::{Add, Mul};
 use std::sync::Arc;
 // pbrt
 use crate::core::geometry::{
@@ -9,9 +10,11 @@ use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector2f,
 use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use crate::core::material::Material;
 use crate::core::paramset::ParamSet;
-use crate::core::pbrt::Float;
 use crate::core::pbrt::{clamp_t, float_to_bits, lerp};
+use crate::core::pbrt::{Float, Spectrum};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::shape::Shape;
+use crate::core::stats::{self, Counter, Memory, Percent};
 use crate::core::transform::Transform;
 
 // see curve.h
@@ -23,6 +26,19 @@ pub enum CurveType {
     Ribbon,
 }
 
+/// How the control points of a curve are interpreted. Everything is
+/// converted to cubic Bézier segments for intersection.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CurveBasis {
+    /// 3n+1 control points for n segments
+    Bezier,
+    /// uniform cubic B-spline, n+3 control points for n segments
+    BSpline,
+    /// interpolates all but the first and the last control point, n+3
+    /// control points for n segments
+    CatmullRom,
+}
+
 #[derive(Clone)]
 pub struct CurveCommon {
     pub curve_type: CurveType,
@@ -31,6 +47,11 @@ pub struct CurveCommon {
     pub n: [Normal3f; 2],
     pub normal_angle: Float,
     pub inv_sin_normal_angle: Float,
+    /// parametric range of the whole curve covered by this segment
+    /// (used for the u coordinate of intersections)
+    pub u_curve: [Float; 2],
+    /// optional per-curve color (see _VertexColorTexture_)
+    pub color: Option<Spectrum>,
 }
 
 impl CurveCommon {
@@ -54,6 +75,8 @@ impl CurveCommon {
                 n: [n0, n1],
                 normal_angle,
                 inv_sin_normal_angle,
+                u_curve: [0.0 as Float, 1.0 as Float],
+                color: None,
             }
         } else {
             CurveCommon {
@@ -63,6 +86,8 @@ impl CurveCommon {
                 n: [Normal3f::default(); 2],
                 normal_angle: 0.0 as Float,
                 inv_sin_normal_angle: 0.0 as Float,
+                u_curve: [0.0 as Float, 1.0 as Float],
+                color: None,
             }
         }
     }
@@ -115,6 +140,17 @@ impl Curve {
         split_depth: i32,
     ) -> Vec<Arc<Shape>> {
         let common: Arc<CurveCommon> = Arc::new(CurveCommon::new(c, w0, w1, curve_type, norm));
+        Curve::create_segments(o2w, w2o, reverse_orientation, common, split_depth)
+    }
+    /// Splits the Bézier segment described by *common* into
+    /// 2^*split_depth* shapes.
+    pub fn create_segments(
+        o2w: Transform,
+        w2o: Transform,
+        reverse_orientation: bool,
+        common: Arc<CurveCommon>,
+        split_depth: i32,
+    ) -> Vec<Arc<Shape>> {
         let n_segments: usize = 1_usize << split_depth;
         let mut segments: Vec<Arc<Shape>> = Vec::with_capacity(n_segments);
         for i in 0..n_segments {
@@ -131,9 +167,12 @@ impl Curve {
                 u_max,
             )));
             segments.push(curve.clone());
-            // TODO: ++nSplitCurves;
+            stats::add_counter(Counter::SplitCurves, 1);
         }
-        // TODO: curveBytes += sizeof(CurveCommon) + n_segments * sizeof(Curve);
+        stats::add_memory(
+            Memory::Curves,
+            (std::mem::size_of::<CurveCommon>() + n_segments * std::mem::size_of::<Curve>()) as u64,
+        );
         segments
     }
     pub fn recursive_intersect(
@@ -320,24 +359,28 @@ impl Curve {
                 }
                 dpdv = ray_to_object.transform_vector(&dpdv_plane);
             }
+            // report _u_ with respect to the whole curve
+            let du_curve: Float = self.common.u_curve[1] - self.common.u_curve[0];
             let mut si: SurfaceInteraction = SurfaceInteraction::new(
                 &ray.position(pc.z),
                 &p_error,
-                Point2f { x: u, y: v },
+                Point2f {
+                    x: lerp(u, self.common.u_curve[0], self.common.u_curve[1]),
+                    y: v,
+                },
                 &-ray.d,
-                &dpdu,
+                &(dpdu / du_curve),
                 &dpdv,
                 &Normal3f::default(),
                 &Normal3f::default(),
                 ray.time,
                 None,
             );
+            si.vertex_color = self.common.color;
             self.object_to_world.transform_surface_interaction(&mut si);
-            // if let Some(ref shape) = si.shape {
-            //     isect.shape = Some(shape.clone());
-            // }
+            *isect = si;
             // }
-            // TODO: ++n_hits;
+            stats::add_percent(Percent::CurveHits, 1, 0);
             true
         }
     }
@@ -364,8 +407,9 @@ impl Curve {
         self.object_to_world.transform_bounds(&self.object_bound())
     }
     pub fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
-        // TODO: ProfilePhase p(isect ? Prof::CurveIntersect : Prof::CurveIntersectP);
-        // TODO: ++nTests;
+        let _p = ProfilePhase::new(Prof::CurveIntersect);
+        stats::count_primitive_test();
+        stats::add_percent(Percent::CurveHits, 0, 1);
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -475,6 +519,7 @@ impl Curve {
         )
     }
     pub fn intersect_p(&self, r: &Ray) -> bool {
+        let _p = ProfilePhase::new(Prof::CurveIntersectP);
         let mut t_hit: Float = 0.0;
         let mut isect_light: SurfaceInteraction = SurfaceInteraction::default();
         self.intersect(r, &mut t_hit, &mut isect_light)
@@ -557,16 +602,67 @@ pub fn create_curve_shape(
     reverse_orientation: bool,
     params: &ParamSet,
 ) -> Vec<Arc<Shape>> {
-    let width: Float = params.find_one_float("width", 1.0 as Float);
-    let width0: Float = params.find_one_float("width0", width);
-    let width1: Float = params.find_one_float("width1", width);
     let cp = params.find_point3f("P");
-    if cp.len() != 4_usize {
+    let basis_string: String = params.find_one_string("basis", String::from("bezier"));
+    let basis: CurveBasis = if basis_string == "bezier" {
+        CurveBasis::Bezier
+    } else if basis_string == "bspline" {
+        CurveBasis::BSpline
+    } else if basis_string == "catmullrom" {
+        CurveBasis::CatmullRom
+    } else {
+        println!(
+            "ERROR: Unknown curve basis \"{:?}\". Using \"bezier\".",
+            basis_string
+        );
+        CurveBasis::Bezier
+    };
+    // several curves can be given at once, "nvertices" control points each
+    let mut n_vertices: Vec<i32> = params.find_int("nvertices");
+    if n_vertices.is_empty() {
+        n_vertices.push(cp.len() as i32);
+    }
+    if n_vertices.iter().map(|nv| *nv as usize).sum::<usize>() != cp.len() {
         panic!(
-            "Must provide 4 control points for \"curve\" primitive. ((Provided {:?}).",
+            "\"nvertices\" of \"curve\" primitive don't add up to the {:?} control points.",
             cp.len()
         );
     }
+    let mut n_segments: Vec<usize> = Vec::with_capacity(n_vertices.len());
+    for nv in &n_vertices {
+        let nv: usize = *nv as usize;
+        let valid: bool = match basis {
+            CurveBasis::Bezier => nv >= 4 && 3 * ((nv - 1) / 3) == nv - 1,
+            _ => nv >= 4,
+        };
+        if !valid {
+            panic!(
+                "Invalid number of control points {:?} for \"{}\" basis of \"curve\" primitive.",
+                nv, basis_string
+            );
+        }
+        n_segments.push(match basis {
+            CurveBasis::Bezier => (nv - 1) / 3,
+            _ => nv - 3,
+        });
+    }
+    // constant, endpoint or per control point widths
+    let mut widths: Vec<Float> = params.find_float("width");
+    if widths.len() > 1 && widths.len() != cp.len() {
+        println!(
+            "ERROR: {:?} \"width\" values given for {:?} control points. Using the first one.",
+            widths.len(),
+            cp.len()
+        );
+        widths.truncate(1);
+    }
+    let width: Float = if widths.len() == 1 {
+        widths[0]
+    } else {
+        1.0 as Float
+    };
+    let width0: Float = params.find_one_float("width0", width);
+    let width1: Float = params.find_one_float("width1", width);
     let curve_type_string: String = params.find_one_string("type", String::from("flat"));
     let mut curve_type: CurveType = CurveType::Flat;
     if curve_type_string == "flat" {
@@ -581,14 +677,17 @@ pub fn create_curve_shape(
             curve_type_string
         );
     }
+    // ribbons need a normal at both ends of each segment
+    let n_normals: usize = n_segments.iter().map(|ns| ns + 1).sum();
     let mut n: Vec<Normal3f> = params.find_normal3f("N");
     if !n.is_empty() {
         if curve_type_string != "ribbon" {
             println!("WARNING: Curve normals are only used with \"ribbon\" type curves.");
             n = Vec::new();
-        } else if n.len() != 2_usize {
+        } else if n.len() != n_normals {
             panic!(
-                "Must provide two normals with \"N\" parameter for ribbon curves. (Provided {:?}).",
+                "Must provide {:?} normals with \"N\" parameter for ribbon curves. (Provided {:?}).",
+                n_normals,
                 n.len()
             );
         }
@@ -597,35 +696,103 @@ pub fn create_curve_shape(
     if curve_type == CurveType::Ribbon && n.is_empty() {
         panic!("Must provide normals \"N\" at curve endpoints with ribbon curves.");
     }
-    if n.is_empty() {
-        Curve::create(
-            *o2w,
-            *w2o,
-            reverse_orientation,
-            &[cp[0], cp[1], cp[2], cp[3]],
-            width0,
-            width1,
-            curve_type,
-            None,
-            sd,
-        )
-    } else {
-        Curve::create(
-            *o2w,
-            *w2o,
-            reverse_orientation,
-            &[cp[0], cp[1], cp[2], cp[3]],
-            width0,
-            width1,
-            curve_type,
-            Some([n[0], n[1]]),
-            sd,
-        )
+    // one color for all curves or one per curve
+    let mut colors: Vec<Spectrum> = params.find_spectrum("Cs");
+    if colors.len() > 1 && colors.len() != n_vertices.len() {
+        println!(
+            "ERROR: {:?} \"Cs\" colors given for {:?} curves. Ignoring them.",
+            colors.len(),
+            n_vertices.len()
+        );
+        colors = Vec::new();
+    }
+    let mut shapes: Vec<Arc<Shape>> = Vec::new();
+    let mut cp_offset: usize = 0;
+    let mut n_offset: usize = 0;
+    for (c, nv) in n_vertices.iter().enumerate() {
+        let nv: usize = *nv as usize;
+        let color: Option<Spectrum> = match colors.len() {
+            0 => None,
+            1 => Some(colors[0]),
+            _ => Some(colors[c]),
+        };
+        let n_seg: usize = n_segments[c];
+        for seg in 0..n_seg {
+            let first: usize = cp_offset
+                + match basis {
+                    CurveBasis::Bezier => 3 * seg,
+                    _ => seg,
+                };
+            let seg_cp: [Point3f; 4] = curve_basis_to_bezier(
+                basis,
+                &[cp[first], cp[first + 1], cp[first + 2], cp[first + 3]],
+            );
+            let u0: Float = seg as Float / n_seg as Float;
+            let u1: Float = (seg + 1) as Float / n_seg as Float;
+            let (w0, w1): (Float, Float) = if widths.len() > 1 {
+                let seg_widths: [Float; 4] = curve_basis_to_bezier(
+                    basis,
+                    &[
+                        widths[first],
+                        widths[first + 1],
+                        widths[first + 2],
+                        widths[first + 3],
+                    ],
+                );
+                (seg_widths[0], seg_widths[3])
+            } else {
+                (lerp(u0, width0, width1), lerp(u1, width0, width1))
+            };
+            let norm: Option<[Normal3f; 2]> = if n.is_empty() {
+                None
+            } else {
+                Some([n[n_offset + seg], n[n_offset + seg + 1]])
+            };
+            let mut common: CurveCommon =
+                CurveCommon::new(&seg_cp, w0, w1, curve_type.clone(), norm);
+            common.u_curve = [u0, u1];
+            common.color = color;
+            shapes.extend(Curve::create_segments(
+                *o2w,
+                *w2o,
+                reverse_orientation,
+                Arc::new(common),
+                sd,
+            ));
+        }
+        cp_offset += nv;
+        n_offset += n_seg + 1;
     }
+    shapes
 }
 
 // Curve Utility Functions
 
+/// Converts the four control points of a curve segment (or any other
+/// per control point value) from *basis* to the cubic Bézier basis.
+fn curve_basis_to_bezier<T>(basis: CurveBasis, p: &[T; 4]) -> [T; 4]
+where
+    T: Copy + Add<Output = T> + Mul<Float, Output = T>,
+{
+    let sixth: Float = 1.0 as Float / 6.0 as Float;
+    let third: Float = 1.0 as Float / 3.0 as Float;
+    match basis {
+        CurveBasis::Bezier => *p,
+        CurveBasis::BSpline => [
+            p[0] * sixth + p[1] * (4.0 as Float * sixth) + p[2] * sixth,
+            p[1] * (2.0 as Float * third) + p[2] * third,
+            p[1] * third + p[2] * (2.0 as Float * third),
+            p[1] * sixth + p[2] * (4.0 as Float * sixth) + p[3] * sixth,
+        ],
+        CurveBasis::CatmullRom => [
+            p[1],
+            p[0] * -sixth + p[1] + p[2] * sixth,
+            p[1] * sixth + p[2] + p[3] * -sixth,
+            p[2],
+        ],
+    }
+}
+
 fn blossom_bezier(p: &[Point3f; 4], u0: Float, u1: Float, u2: Float) -> Point3f {
     let a: [Point3f; 3] = [
         pnt3_lerp(u0, &p[0], &p[1]),
@@ -12,6 +12,7 @@ use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteractio
 use crate::core::material::Material;
 use crate::core::pbrt::Float;
 use crate::core::pbrt::{clamp_t, gamma, lerp, radians};
+use crate::core::profiler::{Prof, ProfilePhase};
 use crate::core::transform::Transform;
 
 // see cylinder.h
@@ -93,7 +94,7 @@ impl Cylinder {
         self.object_to_world.transform_bounds(&self.object_bound())
     }
     pub fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
-        // TODO: ProfilePhase p(Prof::ShapeIntersect);
+        let _p = ProfilePhase::new(Prof::ShapeIntersect);
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -244,7 +245,7 @@ impl Cylinder {
         true
     }
     pub fn intersect_p(&self, r: &Ray) -> bool {
-        // TODO: ProfilePhase p(Prof::ShapeIntersect);
+        let _p = ProfilePhase::new(Prof::ShapeIntersectP);
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
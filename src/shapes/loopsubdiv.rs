@@ -666,6 +666,7 @@ pub fn loop_subdivide(
         Vec::new(),
         None,
         None,
+        Vec::new(),
     ))
 }
 
@@ -6,22 +6,36 @@
 //! the underlying shape. This makes it possible to separate the
 //! geometric and the shading subsystem of pbrt.
 //!
+//! - Bilinear Patch
 //! - Cone
 //! - Curve
 //! - Cylinder
 //! - Disk
 //! - Hyperboloid
 //! - Paraboloid
+//! - Points
+//! - Signed Distance Field
 //! - Sphere
 //! - Triangle
 //!
+//! ## Bilinear Patches
+//!
+//! A bilinear patch is defined by four vertices and interpolates
+//! bilinearly between them. Quads don't have to be split into two
+//! triangles (which creates shading seams for non-planar quads) and
+//! rays are intersected with the patch directly.
+//!
 //! ## Cones
 //!
 //! TODO
 //!
 //! ## Curves
 //!
-//! TODO
+//! Curves are thin ribbons (e.g. hair or grass) intersected without
+//! tessellation. Control points can be given in Bézier, B-spline or
+//! Catmull-Rom basis (`"string basis"`), several curves at once with
+//! `"integer nvertices"`, widths per control point and colors per
+//! curve (`"rgb Cs"`, see the `vertexcolor` texture).
 //!
 //! ## Spheres
 //!
@@ -39,6 +53,12 @@
 //! positions where each individual triangle just stores three offsets
 //! into this array for its three vertices.
 //!
+//! For very large meshes `"bool compact" "true"` (on `trianglemesh`
+//! and `plymesh`) stores the whole mesh as a single shape with its
+//! own bounding volume hierarchy, triangles are just indices into the
+//! mesh. Normals are octahedral encoded and (u, v) values quantized
+//! to 16 bits.
+//!
 //! ## Disks
 //!
 //! The disk is an interesting quadric since it has a particularly
@@ -50,6 +70,19 @@
 //! Another useful quadric is the cylinder. Cylinder shapes are
 //! centered around the z axis.
 //!
+//! ## Points
+//!
+//! A point cloud stores many small spheres (e.g. particles of a
+//! simulation) as one shape with positions, radii and optional colors
+//! in flat arrays and its own bounding volume hierarchy.
+//!
+//! ## Signed Distance Fields
+//!
+//! Implicit surfaces are given by a signed distance function, built
+//! from a few primitives (sphere, box, torus) and combinators (union,
+//! smooth union, translation, repetition) or supplied as a closure.
+//! They are rendered by sphere tracing without meshing them first.
+//!
 //! ## Hyperboloids
 //!
 //! TODO
@@ -59,11 +92,14 @@
 //! TODO
 //!
 
+pub mod bilinearpatch;
 pub mod curve;
 pub mod cylinder;
 pub mod disk;
 pub mod loopsubdiv;
 pub mod nurbs;
 pub mod plymesh;
+pub mod points;
+pub mod sdf;
 pub mod sphere;
 pub mod triangle;
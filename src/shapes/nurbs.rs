@@ -1,8 +1,11 @@
+// std
+use std::collections::{HashMap, HashSet};
 // others
 use smallvec::SmallVec;
 // pbrt
-use crate::core::geometry::{Point3f, Vector3f};
-use crate::core::pbrt::Float;
+use crate::core::geometry::{pnt3_distancef, vec3_cross_vec3};
+use crate::core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
+use crate::core::pbrt::{lerp, Float};
 
 // see nurbs.cpp
 
@@ -29,7 +32,7 @@ pub struct Homogeneous3 {
 pub fn nurbs_evaluate(
     order: i32,
     knot: &[Float],
-    cp: &SmallVec<[Homogeneous3; 128]>,
+    cp: &[Homogeneous3],
     cp_start: i32,
     np: i32,
     cp_stride: i32,
@@ -107,7 +110,7 @@ pub fn nurbs_evaluate_surface(
         iso.push(nurbs_evaluate(
             v_order,
             v_knot,
-            &SmallVec::from_vec(cp.to_vec()),
+            cp,
             u_first_cp as i32 + i,
             vcp,
             ucp,
@@ -133,7 +136,7 @@ pub fn nurbs_evaluate_surface(
             iso[i as usize] = nurbs_evaluate(
                 u_order,
                 u_knot,
-                &SmallVec::from_vec(cp.to_vec()),
+                cp,
                 (v_first_cp as i32 + i) * ucp,
                 ucp,
                 1,
@@ -158,3 +161,207 @@ pub fn nurbs_evaluate_surface(
         z: p.z / p.w,
     }
 }
+
+/// The result of tessellating a NURBS surface: vertex positions,
+/// normals, and $(u,v)$ coordinates (all in object space) plus three
+/// vertex indices per triangle.
+pub struct NurbsTessellation {
+    pub p: Vec<Point3f>,
+    pub n: Vec<Normal3f>,
+    pub uv: Vec<Point2f>,
+    pub vertex_indices: Vec<u32>,
+}
+
+/// Tessellates a NURBS surface by adaptively subdividing the
+/// parametric domain $[u_0,u_1] \times [v_0,v_1]$ (as a quadtree)
+/// until the chordal error, the distance between the surface and the
+/// bilinear interpolation of a cell's corners, is below
+/// *chordal_error* or *max_depth* is reached. Cells bordering finer
+/// cells are triangulated as a fan around their center, so the
+/// resulting mesh is free of T-junction cracks. Normals are computed
+/// from the exact surface derivatives.
+pub fn nurbs_tessellate_adaptive(
+    u_order: i32,
+    u_knot: &[Float],
+    ucp: i32,
+    u0: Float,
+    u1: Float,
+    v_order: i32,
+    v_knot: &[Float],
+    vcp: i32,
+    v0: Float,
+    v1: Float,
+    cp: &[Homogeneous3],
+    chordal_error: Float,
+    max_depth: i32,
+) -> NurbsTessellation {
+    let max_depth: u32 = max_depth.clamp(1, 16) as u32;
+    // the finest level of the quadtree defines an integer lattice
+    let n: u32 = 1_u32 << max_depth;
+    let param = |i: u32, j: u32| -> (Float, Float) {
+        (
+            lerp(i as Float / n as Float, u0, u1),
+            lerp(j as Float / n as Float, v0, v1),
+        )
+    };
+    let position = |u: Float, v: Float| -> Point3f {
+        nurbs_evaluate_surface(
+            u_order, u_knot, ucp, u, v_order, v_knot, vcp, v, cp, None, None,
+        )
+    };
+    // make sure every knot span gets sampled at least once
+    let u_spans: i32 = ucp - u_order + 1;
+    let v_spans: i32 = vcp - v_order + 1;
+    let mut min_depth: u32 = 0;
+    while (1_i32 << min_depth) < u_spans.max(v_spans) {
+        min_depth += 1;
+    }
+    let min_depth: u32 = (min_depth + 1).min(max_depth);
+    // subdivide the parametric domain into leaf cells
+    let mut leaves: Vec<(u32, u32, u32)> = Vec::new();
+    let mut todo: Vec<(u32, u32, u32, u32)> = vec![(0, 0, n, 0)];
+    while let Some((i, j, size, depth)) = todo.pop() {
+        let mut split: bool = depth < min_depth;
+        if !split && depth < max_depth {
+            // compare surface against bilinear interpolation of the corners
+            let (ua, va) = param(i, j);
+            let (ub, vb) = param(i + size, j + size);
+            let p00: Point3f = position(ua, va);
+            let p10: Point3f = position(ub, va);
+            let p01: Point3f = position(ua, vb);
+            let p11: Point3f = position(ub, vb);
+            let fractions: [Float; 3] = [0.25, 0.5, 0.75];
+            'test: for s in fractions.iter() {
+                for t in fractions.iter() {
+                    let p_surface: Point3f = position(lerp(*s, ua, ub), lerp(*t, va, vb));
+                    let p_bilinear: Point3f = lerp(*s, lerp(*t, p00, p01), lerp(*t, p10, p11));
+                    if pnt3_distancef(&p_surface, &p_bilinear) > chordal_error {
+                        split = true;
+                        break 'test;
+                    }
+                }
+            }
+        }
+        if split {
+            let half: u32 = size / 2;
+            todo.push((i, j, half, depth + 1));
+            todo.push((i + half, j, half, depth + 1));
+            todo.push((i, j + half, half, depth + 1));
+            todo.push((i + half, j + half, half, depth + 1));
+        } else {
+            leaves.push((i, j, size));
+        }
+    }
+    let mut corners: HashSet<(u32, u32)> = HashSet::with_capacity(4 * leaves.len());
+    for (i, j, size) in &leaves {
+        corners.insert((*i, *j));
+        corners.insert((*i + *size, *j));
+        corners.insert((*i, *j + *size));
+        corners.insert((*i + *size, *j + *size));
+    }
+    // create (shared) vertices and triangles
+    let mut tess: NurbsTessellation = NurbsTessellation {
+        p: Vec::with_capacity(corners.len()),
+        n: Vec::with_capacity(corners.len()),
+        uv: Vec::with_capacity(corners.len()),
+        vertex_indices: Vec::with_capacity(6 * leaves.len()),
+    };
+    let mut vertex_ids: HashMap<(u32, u32), u32> = HashMap::with_capacity(corners.len());
+    let mut get_vertex = |tess: &mut NurbsTessellation, i: u32, j: u32| -> u32 {
+        if let Some(id) = vertex_ids.get(&(i, j)) {
+            return *id;
+        }
+        let (u, v) = param(i, j);
+        let mut dpdu: Vector3f = Vector3f::default();
+        let mut dpdv: Vector3f = Vector3f::default();
+        let pt: Point3f = nurbs_evaluate_surface(
+            u_order,
+            u_knot,
+            ucp,
+            u,
+            v_order,
+            v_knot,
+            vcp,
+            v,
+            cp,
+            Some(&mut dpdu),
+            Some(&mut dpdv),
+        );
+        let mut nv: Vector3f = vec3_cross_vec3(&dpdu, &dpdv);
+        if nv.length_squared() == 0.0 as Float {
+            // degenerate derivatives (e.g. at a pole), nudge towards
+            // the center of the parametric domain
+            let un: Float = lerp(0.001 as Float, u, (u0 + u1) * 0.5 as Float);
+            let vn: Float = lerp(0.001 as Float, v, (v0 + v1) * 0.5 as Float);
+            nurbs_evaluate_surface(
+                u_order,
+                u_knot,
+                ucp,
+                un,
+                v_order,
+                v_knot,
+                vcp,
+                vn,
+                cp,
+                Some(&mut dpdu),
+                Some(&mut dpdv),
+            );
+            nv = vec3_cross_vec3(&dpdu, &dpdv);
+        }
+        let id: u32 = tess.p.len() as u32;
+        tess.p.push(pt);
+        if nv.length_squared() > 0.0 as Float {
+            tess.n.push(Normal3f::from(nv.normalize()));
+        } else {
+            tess.n.push(Normal3f::default());
+        }
+        tess.uv.push(Point2f { x: u, y: v });
+        vertex_ids.insert((i, j), id);
+        id
+    };
+    for (i, j, size) in leaves {
+        // walk counter-clockwise along the boundary of the cell
+        // collecting all vertices of neighboring (finer) cells
+        let mut boundary: Vec<(u32, u32)> = Vec::with_capacity(4);
+        for k in 0..size {
+            if corners.contains(&(i + k, j)) {
+                boundary.push((i + k, j));
+            }
+        }
+        for k in 0..size {
+            if corners.contains(&(i + size, j + k)) {
+                boundary.push((i + size, j + k));
+            }
+        }
+        for k in 0..size {
+            if corners.contains(&(i + size - k, j + size)) {
+                boundary.push((i + size - k, j + size));
+            }
+        }
+        for k in 0..size {
+            if corners.contains(&(i, j + size - k)) {
+                boundary.push((i, j + size - k));
+            }
+        }
+        if boundary.len() == 4 {
+            let v00: u32 = get_vertex(&mut tess, i, j);
+            let v10: u32 = get_vertex(&mut tess, i + size, j);
+            let v11: u32 = get_vertex(&mut tess, i + size, j + size);
+            let v01: u32 = get_vertex(&mut tess, i, j + size);
+            tess.vertex_indices
+                .extend_from_slice(&[v00, v10, v11, v00, v11, v01]);
+        } else {
+            // triangle fan around the center of the cell
+            let center: u32 = get_vertex(&mut tess, i + size / 2, j + size / 2);
+            let ids: Vec<u32> = boundary
+                .iter()
+                .map(|(bi, bj)| get_vertex(&mut tess, *bi, *bj))
+                .collect();
+            for k in 0..ids.len() {
+                tess.vertex_indices
+                    .extend_from_slice(&[center, ids[k], ids[(k + 1) % ids.len()]]);
+            }
+        }
+    }
+    tess
+}
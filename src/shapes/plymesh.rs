@@ -4,7 +4,7 @@ use std::convert::TryInto;
 use std::fs::File;
 use std::hash::BuildHasher;
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::string::String;
 use std::sync::Arc;
 use std::vec::Vec;
@@ -12,31 +12,91 @@ use std::vec::Vec;
 use ply_rs::parser;
 use ply_rs::ply;
 // pbrt
+use crate::accelerators::bvhcache;
+use crate::accelerators::bvhcache::MeshData;
 use crate::core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
 use crate::core::paramset::ParamSet;
 use crate::core::pbrt::Float;
 use crate::core::shape::Shape;
 use crate::core::texture::Texture;
 use crate::core::transform::Transform;
-use crate::shapes::triangle::{Triangle, TriangleMesh};
+use crate::shapes::triangle::{create_triangle_mesh_shapes, TriangleMesh};
 use crate::textures::constant::ConstantTexture;
 
-pub fn create_ply_mesh<S: BuildHasher>(
-    o2w: &Transform,
-    w2o: &Transform,
-    reverse_orientation: bool,
-    params: &ParamSet,
-    float_textures: Arc<HashMap<String, Arc<dyn Texture<Float> + Send + Sync>, S>>,
-    search_directory: Option<&PathBuf>,
-) -> Vec<Arc<Shape>> {
-    let mut filename: String = params.find_one_string("filename", String::new());
-    if let Some(ref search_directory) = search_directory {
-        let mut path_buf: PathBuf = PathBuf::from("/");
-        path_buf.push(search_directory);
-        path_buf.push(filename);
-        filename = String::from(path_buf.to_str().unwrap());
+/// Converts a scalar integer PLY property (e.g. a per-face material
+/// index) to `i32`.
+fn ply_property_to_i32(property: &ply::Property) -> Option<i32> {
+    match *property {
+        ply::Property::Char(i) => Some(i as i32),
+        ply::Property::UChar(i) => Some(i as i32),
+        ply::Property::Short(i) => Some(i as i32),
+        ply::Property::UShort(i) => Some(i as i32),
+        ply::Property::Int(i) => Some(i),
+        ply::Property::UInt(i) => Some(i as i32),
+        _ => None,
+    }
+}
+
+/// Reads only the vertex positions (and normals, if present) of a PLY
+/// file, e.g. for additional time samples of a deforming mesh.
+fn read_ply_vertices(filename: &str) -> (Vec<Point3f>, Vec<Normal3f>) {
+    let result = File::open(filename);
+    if result.is_err() {
+        panic!("Couldn't open PLY file {:?}", filename);
+    }
+    let mut buf_reader = BufReader::new(result.unwrap());
+    let parser = parser::Parser::<ply::DefaultElement>::new();
+    let result = parser.read_header(&mut buf_reader);
+    if result.is_err() {
+        panic!("Unable to read the header of PLY file  {:?}", filename);
+    }
+    let header = result.unwrap();
+    let result = parser.read_payload(&mut buf_reader, &header);
+    if result.is_err() {
+        panic!("Unable to read the payload of PLY file  {:?}", filename);
+    }
+    let mut p: Vec<Point3f> = Vec::new();
+    let mut n: Vec<Normal3f> = Vec::new();
+    if let Some(list) = result.unwrap().remove("vertex") {
+        for elem in list.into_iter() {
+            let mut pnt: Point3f = Point3f::default();
+            let mut nrm: Normal3f = Normal3f::default();
+            let mut has_normal: bool = false;
+            for (name, property) in elem.into_iter() {
+                if let ply::Property::Float(value) = property {
+                    match name.as_ref() {
+                        "x" => pnt.x = value,
+                        "y" => pnt.y = value,
+                        "z" => pnt.z = value,
+                        "nx" => {
+                            has_normal = true;
+                            nrm.x = value;
+                        }
+                        "ny" => {
+                            has_normal = true;
+                            nrm.y = value;
+                        }
+                        "nz" => {
+                            has_normal = true;
+                            nrm.z = value;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            p.push(pnt);
+            if has_normal {
+                n.push(nrm);
+            }
+        }
     }
-    let result = File::open(&filename);
+    (p, n)
+}
+
+/// Reads the vertices (with optional normals and uv coordinates) and
+/// the triangulated faces of a PLY file.
+fn read_ply_mesh(filename: &str) -> MeshData {
+    let result = File::open(filename);
     if result.is_err() {
         panic!("Couldn't open PLY file {:?}", filename);
     }
@@ -63,6 +123,7 @@ pub fn create_ply_mesh<S: BuildHasher>(
     let mut has_normals: bool = false;
     let mut has_uvs: bool = false;
     let mut tm_vertex_indices: Vec<u32> = Vec::new();
+    let mut face_indices: Vec<i32> = Vec::new();
     for (name, list) in payload.into_iter() {
         match name.as_ref() {
             "vertex" => {
@@ -135,6 +196,8 @@ pub fn create_ply_mesh<S: BuildHasher>(
             "face" => {
                 for elem in list.into_iter() {
                     let mut nrm: Normal3f = Normal3f::default();
+                    let n_triangles_before: usize = tm_vertex_indices.len() / 3;
+                    let mut face_index: Option<i32> = None;
                     for (name2, list2) in elem.into_iter() {
                         match name2.as_ref() {
                             "vertex_indices" => {
@@ -206,14 +269,68 @@ pub fn create_ply_mesh<S: BuildHasher>(
                                     nrm.z = z;
                                 }
                             }
+                            "face_indices" | "material_id" => {
+                                face_index = ply_property_to_i32(&list2);
+                            }
                             _ => unreachable!(),
                         }
                     }
+                    if let Some(face_index) = face_index {
+                        // quads were split into two triangles sharing the face index
+                        let n_triangles: usize = tm_vertex_indices.len() / 3 - n_triangles_before;
+                        for _ in 0..n_triangles {
+                            face_indices.push(face_index);
+                        }
+                    }
                 }
             }
             _ => unreachable!(),
         }
     }
+    MeshData {
+        p,
+        n,
+        uv: uvs,
+        vertex_indices: tm_vertex_indices,
+        face_indices,
+    }
+}
+
+pub fn create_ply_mesh<S: BuildHasher>(
+    o2w: &Transform,
+    w2o: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+    float_textures: Arc<HashMap<String, Arc<dyn Texture<Float> + Send + Sync>, S>>,
+    search_directory: Option<&PathBuf>,
+    cache_directory: Option<&Path>,
+) -> Vec<Arc<Shape>> {
+    let mut filename: String = params.find_one_string("filename", String::new());
+    if let Some(ref search_directory) = search_directory {
+        let mut path_buf: PathBuf = PathBuf::from("/");
+        path_buf.push(search_directory);
+        path_buf.push(filename);
+        filename = String::from(path_buf.to_str().unwrap());
+    }
+    let key: Option<u64> = cache_directory.and_then(|_| bvhcache::mesh_key(&filename));
+    let cached: Option<MeshData> = match (cache_directory, key) {
+        (Some(directory), Some(key)) => bvhcache::load_mesh(directory, key),
+        _ => None,
+    };
+    let mesh_data: MeshData = cached.unwrap_or_else(|| {
+        let mesh_data: MeshData = read_ply_mesh(&filename);
+        if let (Some(directory), Some(key)) = (cache_directory, key) {
+            bvhcache::store_mesh(directory, key, &mesh_data);
+        }
+        mesh_data
+    });
+    let MeshData {
+        p,
+        n,
+        uv: uvs,
+        vertex_indices: tm_vertex_indices,
+        mut face_indices,
+    } = mesh_data;
     // for i in 0..p.len() {
     //     println!("{:?}: {:?}", i, p[i]);
     // }
@@ -233,6 +350,14 @@ pub fn create_ply_mesh<S: BuildHasher>(
     for item in p.iter().take(n_vertices) {
         p_ws.push(o2w.transform_point(item));
     }
+    if !face_indices.is_empty() && face_indices.len() != tm_vertex_indices.len() / 3 {
+        println!(
+            "WARNING: plymesh: Number of face indices {} doesn't match number of faces {}. Ignoring them.",
+            face_indices.len(),
+            tm_vertex_indices.len() / 3
+        );
+        face_indices = Vec::new();
+    }
     let s_ws: Vec<Vector3f> = Vec::new();
     // look up an alpha texture, if applicable
     let mut alpha_tex: Option<Arc<dyn Texture<Float> + Send + Sync>> = None;
@@ -267,7 +392,7 @@ pub fn create_ply_mesh<S: BuildHasher>(
     } else if params.find_one_float("shadowalpha", 1.0 as Float) == 0.0 as Float {
         shadow_alpha_tex = Some(Arc::new(ConstantTexture::new(0.0 as Float)));
     }
-    let mesh = Arc::new(TriangleMesh::new(
+    let mut mesh = TriangleMesh::new(
         *o2w,
         *w2o,
         reverse_orientation,
@@ -280,14 +405,52 @@ pub fn create_ply_mesh<S: BuildHasher>(
         uvs,
         alpha_tex,
         shadow_alpha_tex,
-    ));
-    let mut shapes: Vec<Arc<Shape>> = Vec::new();
-    for id in 0..mesh.n_triangles {
-        let triangle = Arc::new(Shape::Trngl(Triangle::new(
-            mesh.clone(),
-            id.try_into().unwrap(),
-        )));
-        shapes.push(triangle.clone());
+        face_indices,
+    );
+    // vertex-animated meshes read one more PLY file (with the same
+    // topology) per additional time sample
+    let motion_filenames: Vec<String> = params.find_string("motionfilenames");
+    if !motion_filenames.is_empty() {
+        let mut times: Vec<Float> = params.find_float("times");
+        if times.len() != motion_filenames.len() + 1 {
+            if !times.is_empty() {
+                println!(
+                    "WARNING: plymesh: {} \"times\" given for {} time samples. Spacing them evenly.",
+                    times.len(),
+                    motion_filenames.len() + 1
+                );
+            }
+            times = (0..=motion_filenames.len())
+                .map(|i| i as Float / motion_filenames.len() as Float)
+                .collect();
+        }
+        let mut motion_p: Vec<Vec<Point3f>> = Vec::new();
+        let mut motion_n: Vec<Vec<Normal3f>> = Vec::new();
+        for motion_filename in motion_filenames {
+            let mut path_buf: PathBuf = PathBuf::from("/");
+            if let Some(ref search_directory) = search_directory {
+                path_buf.push(search_directory);
+            }
+            path_buf.push(motion_filename);
+            let (mp, mn) = read_ply_vertices(path_buf.to_str().unwrap());
+            if mp.len() != n_vertices {
+                panic!(
+                    "plymesh: {:?} has {} vertices, expected {}",
+                    path_buf,
+                    mp.len(),
+                    n_vertices
+                );
+            }
+            motion_p.push(mp.iter().map(|p| o2w.transform_point(p)).collect());
+            if !mesh.n.is_empty() && mn.len() == n_vertices {
+                motion_n.push(mn.iter().map(|n| o2w.transform_normal(n)).collect());
+            }
+        }
+        if motion_n.len() != motion_p.len() {
+            // use the normals of the first time sample only
+            motion_n = Vec::new();
+        }
+        mesh.set_motion_samples(times, motion_p, motion_n);
     }
-    shapes
+    create_triangle_mesh_shapes(mesh, params)
 }
@@ -0,0 +1,381 @@
+// std
+use std::f32::consts::PI;
+use std::sync::Arc;
+// pbrt
+use crate::accelerators::elementbvh::ElementBVH;
+use crate::core::geometry::{
+    nrm_abs_dot_vec3f, pnt3_distance_squaredf, vec3_coordinate_system, vec3_dot_vec3f,
+};
+use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f};
+use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::{clamp_t, gamma, Float, Spectrum};
+use crate::core::sampling::uniform_sample_sphere;
+use crate::core::shape::Shape;
+use crate::core::transform::Transform;
+
+/// Maximum number of points stored in a leaf of the internal BVH.
+const MAX_POINTS_IN_LEAF: usize = 4;
+
+/// A single particle, position (in world space) and radius are
+/// stored together.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PointSample {
+    pub p: Point3f,
+    pub radius: Float,
+}
+
+impl PointSample {
+    fn bounds(&self) -> Bounds3f {
+        let r: Vector3f = Vector3f {
+            x: self.radius,
+            y: self.radius,
+            z: self.radius,
+        };
+        Bounds3f::new(self.p - r, self.p + r)
+    }
+}
+
+/// A cloud of spheres (e.g. particles of a simulation) stored in a
+/// single shape with its own bounding volume hierarchy, instead of
+/// one shape and primitive per sphere.
+pub struct PointCloud {
+    /// points in BVH order
+    pub points: Vec<PointSample>,
+    /// optional per-point colors (empty or one per point)
+    pub colors: Vec<Spectrum>,
+    /// original index of each point (reported as face index)
+    pub point_ids: Vec<u32>,
+    bvh: ElementBVH,
+    area: Float,
+    // inherited from class Shape (see shape.h)
+    pub object_to_world: Transform,
+    pub world_to_object: Transform,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+}
+
+impl PointCloud {
+    /// Creates a point cloud from object space positions, radii (one
+    /// per point or a single one for all) and optional per-point
+    /// colors.
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        p: &[Point3f],
+        radii: &[Float],
+        colors: Vec<Spectrum>,
+    ) -> Self {
+        assert!(radii.len() == 1 || radii.len() == p.len());
+        assert!(colors.is_empty() || colors.len() == p.len());
+        // radii are scaled by the largest scale factor of the transform
+        let scale: Float = [
+            Vector3f {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Vector3f {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+        ]
+        .iter()
+        .fold(0.0 as Float, |s, v| {
+            s.max(object_to_world.transform_vector(v).length())
+        });
+        let points: Vec<PointSample> = p
+            .iter()
+            .enumerate()
+            .map(|(i, p)| PointSample {
+                p: object_to_world.transform_point(p),
+                radius: scale * if radii.len() == 1 { radii[0] } else { radii[i] },
+            })
+            .collect();
+        let mut cloud: PointCloud = PointCloud {
+            points,
+            colors,
+            point_ids: Vec::new(),
+            bvh: ElementBVH::default(),
+            area: 0.0 as Float,
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+        };
+        cloud.area = cloud
+            .points
+            .iter()
+            .map(|ps| 4.0 as Float * PI * ps.radius * ps.radius)
+            .sum();
+        cloud.build_bvh();
+        cloud
+    }
+    fn build_bvh(&mut self) {
+        let bounds: Vec<Bounds3f> = self.points.iter().map(|ps| ps.bounds()).collect();
+        let (bvh, order) = ElementBVH::new(&bounds, MAX_POINTS_IN_LEAF);
+        self.bvh = bvh;
+        // store points (and colors) in the order the leaves refer to
+        self.points = order.iter().map(|i| self.points[*i as usize]).collect();
+        if !self.colors.is_empty() {
+            self.colors = order.iter().map(|i| self.colors[*i as usize]).collect();
+        }
+        self.point_ids = order;
+    }
+    /// Returns the parametric distance of the first intersection of
+    /// the (world space) ray with the sphere around *ps*.
+    fn intersect_sphere(ps: &PointSample, ray: &Ray) -> Option<Float> {
+        let oc: Vector3f = ray.o - ps.p;
+        let a: Float = vec3_dot_vec3f(&ray.d, &ray.d);
+        let b: Float = vec3_dot_vec3f(&oc, &ray.d);
+        // more accurate discriminant (see pbrt-v4)
+        let f: Vector3f = oc - ray.d * (b / a);
+        let discrim: Float = a * (ps.radius * ps.radius - vec3_dot_vec3f(&f, &f));
+        if discrim < 0.0 as Float {
+            return None;
+        }
+        let c: Float = vec3_dot_vec3f(&oc, &oc) - ps.radius * ps.radius;
+        let q: Float = if b < 0.0 as Float {
+            -b + discrim.sqrt()
+        } else {
+            -b - discrim.sqrt()
+        };
+        if q == 0.0 as Float {
+            return None;
+        }
+        let mut t0: Float = c / q;
+        let mut t1: Float = q / a;
+        if t0 > t1 {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        let t_max: Float = ray.t_max.get();
+        if t0 > 0.0 as Float && t0 < t_max {
+            Some(t0)
+        } else if t1 > 0.0 as Float && t1 < t_max {
+            Some(t1)
+        } else {
+            None
+        }
+    }
+    /// Traverses the internal BVH and returns the parametric distance
+    /// and the index of the closest point hit.
+    fn intersect_points(&self, r: &Ray, any_hit: bool) -> Option<(Float, usize)> {
+        self.bvh.intersect(r, any_hit, |i, ray| {
+            PointCloud::intersect_sphere(&self.points[i], ray)
+        })
+    }
+    /// Fills in the surface interaction at *p_hit* on the sphere of
+    /// point *index*.
+    fn interaction<'a>(
+        &self,
+        index: usize,
+        p_hit: &Point3f,
+        wo: &Vector3f,
+        time: Float,
+    ) -> SurfaceInteraction<'a> {
+        let ps: &PointSample = &self.points[index];
+        // reproject hit point to sphere surface
+        let mut d: Vector3f = *p_hit - ps.p;
+        if d.length_squared() == 0.0 as Float {
+            d.z = 1.0 as Float;
+        }
+        let n: Vector3f = d.normalize();
+        let p: Point3f = ps.p + n * ps.radius;
+        let p_error: Vector3f = (Vector3f::from(ps.p).abs() + (n * ps.radius).abs()) * gamma(5_i32);
+        let mut dpdu: Vector3f = Vector3f::default();
+        let mut dpdv: Vector3f = Vector3f::default();
+        vec3_coordinate_system(&n, &mut dpdu, &mut dpdv);
+        if self.reverse_orientation {
+            dpdv = -dpdv;
+        }
+        let uv: Point2f = Point2f {
+            x: (n.y.atan2(n.x) + PI) / (2.0 as Float * PI),
+            y: clamp_t(n.z, -1.0, 1.0).acos() / PI,
+        };
+        let mut si: SurfaceInteraction = SurfaceInteraction::new(
+            &p,
+            &p_error,
+            uv,
+            wo,
+            &dpdu,
+            &dpdv,
+            &Normal3f::default(),
+            &Normal3f::default(),
+            time,
+            None,
+        );
+        si.face_index = self.point_ids[index] as i32;
+        if !self.colors.is_empty() {
+            si.vertex_color = Some(self.colors[index]);
+        }
+        si
+    }
+    // Shape
+    pub fn object_bound(&self) -> Bounds3f {
+        self.world_to_object.transform_bounds(&self.world_bound())
+    }
+    pub fn world_bound(&self) -> Bounds3f {
+        self.bvh.bounds()
+    }
+    pub fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        if let Some((t, index)) = self.intersect_points(r, false) {
+            *isect = self.interaction(index, &r.position(t), &-r.d, r.time);
+            *t_hit = t;
+            true
+        } else {
+            false
+        }
+    }
+    pub fn intersect_p(&self, r: &Ray) -> bool {
+        self.intersect_points(r, true).is_some()
+    }
+    pub fn get_reverse_orientation(&self) -> bool {
+        self.reverse_orientation
+    }
+    pub fn get_transform_swaps_handedness(&self) -> bool {
+        self.transform_swaps_handedness
+    }
+    pub fn get_object_to_world(&self) -> Transform {
+        self.object_to_world
+    }
+    pub fn area(&self) -> Float {
+        self.area
+    }
+    /// Picks a point uniformly and a position uniformly on its
+    /// sphere, *pdf* is the resulting area density.
+    pub fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
+        let n_points: usize = self.points.len();
+        let scaled: Float = u.x * n_points as Float;
+        let index: usize = (scaled as usize).min(n_points - 1);
+        let u_remapped: Point2f = Point2f {
+            x: (scaled - index as Float).min(1.0 as Float - Float::EPSILON),
+            y: u.y,
+        };
+        let ps: &PointSample = &self.points[index];
+        let n: Vector3f = uniform_sample_sphere(u_remapped);
+        let mut it: InteractionCommon = InteractionCommon {
+            p: ps.p + n * ps.radius,
+            p_error: (Vector3f::from(ps.p).abs() + (n * ps.radius).abs()) * gamma(5_i32),
+            n: Normal3f::from(n),
+            ..Default::default()
+        };
+        if self.reverse_orientation {
+            it.n *= -1.0 as Float;
+        }
+        *pdf = self.point_pdf(index);
+        it
+    }
+    /// Area density of _sample()_ returning the point *iref*, which
+    /// lies on the sphere whose surface is closest to it.
+    pub fn pdf(&self, iref: &InteractionCommon) -> Float {
+        // the point may be offset slightly from the surface (e.g. the
+        // origin of a ray leaving an area light)
+        let p: Point3f = iref.p;
+        let delta: Float = 1e-4 as Float * (1.0 as Float + p.x.abs().max(p.y.abs()).max(p.z.abs()));
+        let mut closest: Option<usize> = None;
+        let mut closest_d: Float = delta;
+        self.bvh.for_each_near(&p, delta, |i| {
+            let ps: &PointSample = &self.points[i];
+            let d: Float = (pnt3_distance_squaredf(&p, &ps.p).sqrt() - ps.radius).abs();
+            if d <= closest_d {
+                closest = Some(i);
+                closest_d = d;
+            }
+        });
+        match closest {
+            Some(index) => self.point_pdf(index),
+            None => 0.0 as Float,
+        }
+    }
+    fn point_pdf(&self, index: usize) -> Float {
+        let radius: Float = self.points[index].radius;
+        1.0 as Float / (self.points.len() as Float * 4.0 as Float * PI * radius * radius)
+    }
+    pub fn sample_with_ref_point(
+        &self,
+        iref: &InteractionCommon,
+        u: Point2f,
+        pdf: &mut Float,
+    ) -> InteractionCommon {
+        let intr: InteractionCommon = self.sample(u, pdf);
+        let mut wi: Vector3f = intr.p - iref.p;
+        if wi.length_squared() == 0.0 as Float {
+            *pdf = 0.0 as Float;
+        } else {
+            wi = wi.normalize();
+            // convert from area measure, as returned by the Sample()
+            // call above, to solid angle measure.
+            *pdf *= pnt3_distance_squaredf(&iref.p, &intr.p) / nrm_abs_dot_vec3f(&intr.n, &-wi);
+            if (*pdf).is_infinite() {
+                *pdf = 0.0 as Float;
+            }
+        }
+        intr
+    }
+    pub fn pdf_with_ref_point(&self, iref: &dyn Interaction, wi: &Vector3f) -> Float {
+        // intersect sample ray with area light geometry
+        let ray: Ray = iref.spawn_ray(wi);
+        if let Some((t, index)) = self.intersect_points(&ray, false) {
+            let isect_light: SurfaceInteraction =
+                self.interaction(index, &ray.position(t), &-ray.d, ray.time);
+            // convert light sample weight to solid angle measure
+            let mut pdf: Float = pnt3_distance_squaredf(iref.get_p(), &isect_light.common.p)
+                / nrm_abs_dot_vec3f(&isect_light.common.n, &-(*wi))
+                * self.point_pdf(index);
+            if pdf.is_infinite() {
+                pdf = 0.0 as Float;
+            }
+            pdf
+        } else {
+            0.0 as Float
+        }
+    }
+}
+
+pub fn create_points_shape(
+    o2w: &Transform,
+    w2o: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Shape>> {
+    let p: Vec<Point3f> = params.find_point3f("P");
+    if p.is_empty() {
+        println!("ERROR: Must provide \"P\" to points shape. Ignoring shape.");
+        return Vec::new();
+    }
+    let mut radii: Vec<Float> = params.find_float("radius");
+    if radii.is_empty() {
+        radii.push(1.0 as Float);
+    } else if radii.len() != 1 && radii.len() != p.len() {
+        println!(
+            "ERROR: points shape has {} \"radius\" values for {} points. Using the first one.",
+            radii.len(),
+            p.len()
+        );
+        radii.truncate(1);
+    }
+    let mut colors: Vec<Spectrum> = params.find_spectrum("Cs");
+    if !colors.is_empty() && colors.len() != p.len() {
+        println!(
+            "ERROR: points shape has {} \"Cs\" colors for {} points. Ignoring them.",
+            colors.len(),
+            p.len()
+        );
+        colors = Vec::new();
+    }
+    vec![Arc::new(Shape::Pnts(PointCloud::new(
+        *o2w,
+        *w2o,
+        reverse_orientation,
+        &p,
+        &radii,
+        colors,
+    )))]
+}
@@ -0,0 +1,566 @@
+// std
+use std::f32::consts::PI;
+use std::sync::Arc;
+// pbrt
+use crate::core::geometry::{bnd3_expand, bnd3_union_bnd3f, vec3_coordinate_system};
+use crate::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f};
+use crate::core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::{clamp_t, lerp, Float};
+use crate::core::shape::Shape;
+use crate::core::transform::Transform;
+
+/// A node of a signed distance function tree. Leaves are primitives
+/// (centered at the origin), inner nodes combine or modify their
+/// children.
+#[derive(Clone)]
+pub enum SdfNode {
+    /// sphere with the given radius
+    Sphere { radius: Float },
+    /// box with the given half extents
+    Box { half_extents: Vector3f },
+    /// torus around the z axis
+    Torus { major_radius: Float, minor_radius: Float },
+    /// moves the child by *offset*
+    Translate { offset: Vector3f, child: Box<SdfNode> },
+    /// (hard) union of all children
+    Union { children: Vec<SdfNode> },
+    /// union of all children, blended over a distance of *k*
+    SmoothUnion { k: Float, children: Vec<SdfNode> },
+    /// repeats the child every *period* units, *count* times in
+    /// positive and negative direction along each axis
+    Repeat {
+        period: Vector3f,
+        count: [i32; 3],
+        child: Box<SdfNode>,
+    },
+    /// a user provided distance function and its (conservative) bounds
+    Function {
+        distance: Arc<dyn Fn(&Point3f) -> Float + Send + Sync>,
+        bounds: Bounds3f,
+    },
+}
+
+impl SdfNode {
+    /// Returns the signed distance (negative inside) from *p* to the
+    /// surface.
+    pub fn distance(&self, p: &Point3f) -> Float {
+        match self {
+            SdfNode::Sphere { radius } => Vector3f::from(*p).length() - radius,
+            SdfNode::Box { half_extents } => {
+                let q: Vector3f = Vector3f::from(*p).abs() - *half_extents;
+                let outside: Vector3f = Vector3f {
+                    x: q.x.max(0.0 as Float),
+                    y: q.y.max(0.0 as Float),
+                    z: q.z.max(0.0 as Float),
+                };
+                outside.length() + q.x.max(q.y.max(q.z)).min(0.0 as Float)
+            }
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let qx: Float = (p.x * p.x + p.y * p.y).sqrt() - major_radius;
+                (qx * qx + p.z * p.z).sqrt() - minor_radius
+            }
+            SdfNode::Translate { offset, child } => child.distance(&(*p - *offset)),
+            SdfNode::Union { children } => children
+                .iter()
+                .fold(Float::INFINITY, |d, child| d.min(child.distance(p))),
+            SdfNode::SmoothUnion { k, children } => {
+                let mut d: Float = Float::INFINITY;
+                for child in children {
+                    let dc: Float = child.distance(p);
+                    if d == Float::INFINITY || *k <= 0.0 as Float {
+                        d = d.min(dc);
+                    } else {
+                        // polynomial smooth minimum
+                        let h: Float =
+                            clamp_t(0.5 as Float + 0.5 as Float * (dc - d) / k, 0.0, 1.0);
+                        d = lerp(h, dc, d) - k * h * (1.0 as Float - h);
+                    }
+                }
+                d
+            }
+            SdfNode::Repeat {
+                period,
+                count,
+                child,
+            } => {
+                let repeat = |x: Float, period: Float, count: i32| -> Float {
+                    if period <= 0.0 as Float {
+                        x
+                    } else {
+                        let cell: Float =
+                            clamp_t((x / period).round(), -count as Float, count as Float);
+                        x - period * cell
+                    }
+                };
+                child.distance(&Point3f {
+                    x: repeat(p.x, period.x, count[0]),
+                    y: repeat(p.y, period.y, count[1]),
+                    z: repeat(p.z, period.z, count[2]),
+                })
+            }
+            SdfNode::Function { distance, .. } => distance(p),
+        }
+    }
+    /// Returns conservative (object space) bounds of the surface.
+    pub fn bounds(&self) -> Bounds3f {
+        match self {
+            SdfNode::Sphere { radius } => Bounds3f::new(
+                Point3f {
+                    x: -radius,
+                    y: -radius,
+                    z: -radius,
+                },
+                Point3f {
+                    x: *radius,
+                    y: *radius,
+                    z: *radius,
+                },
+            ),
+            SdfNode::Box { half_extents } => Bounds3f::new(
+                Point3f::default() - *half_extents,
+                Point3f::default() + *half_extents,
+            ),
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let r: Float = major_radius + minor_radius;
+                Bounds3f::new(
+                    Point3f {
+                        x: -r,
+                        y: -r,
+                        z: -minor_radius,
+                    },
+                    Point3f {
+                        x: r,
+                        y: r,
+                        z: *minor_radius,
+                    },
+                )
+            }
+            SdfNode::Translate { offset, child } => {
+                let b: Bounds3f = child.bounds();
+                Bounds3f::new(b.p_min + *offset, b.p_max + *offset)
+            }
+            SdfNode::Union { children } => SdfNode::union_bounds(children),
+            SdfNode::SmoothUnion { k, children } => {
+                // blending can't grow the surface by more than k/4
+                bnd3_expand(
+                    &SdfNode::union_bounds(children),
+                    0.25 as Float * k.max(0.0 as Float),
+                )
+            }
+            SdfNode::Repeat {
+                period,
+                count,
+                child,
+            } => {
+                let b: Bounds3f = child.bounds();
+                let extent: Vector3f = Vector3f {
+                    x: period.x.max(0.0 as Float) * count[0] as Float,
+                    y: period.y.max(0.0 as Float) * count[1] as Float,
+                    z: period.z.max(0.0 as Float) * count[2] as Float,
+                };
+                Bounds3f::new(b.p_min - extent, b.p_max + extent)
+            }
+            SdfNode::Function { bounds, .. } => *bounds,
+        }
+    }
+    fn union_bounds(children: &[SdfNode]) -> Bounds3f {
+        let mut children = children.iter();
+        if let Some(first) = children.next() {
+            children.fold(first.bounds(), |b, child| {
+                bnd3_union_bnd3f(&b, &child.bounds())
+            })
+        } else {
+            Bounds3f::default()
+        }
+    }
+    /// Parses a tree from a textual description like
+    ///
+    /// smoothunion(0.2, sphere(1), translate(1.5 0 0, torus(1 0.25)))
+    ///
+    /// Known nodes are *sphere(radius)*, *box(x y z)* (half extents),
+    /// *torus(major minor)*, *translate(x y z, node)*, *union(node,
+    /// ...)*, *smoothunion(k, node, ...)* and *repeat(x y z, nx ny nz,
+    /// node)*.
+    pub fn parse(description: &str) -> Result<SdfNode, String> {
+        let mut tokens: Vec<String> = Vec::new();
+        let mut word: String = String::new();
+        for c in description.chars() {
+            if c == '(' || c == ')' || c == ',' || c.is_whitespace() {
+                if !word.is_empty() {
+                    tokens.push(word.clone());
+                    word.clear();
+                }
+                if !c.is_whitespace() {
+                    tokens.push(c.to_string());
+                }
+            } else {
+                word.push(c);
+            }
+        }
+        if !word.is_empty() {
+            tokens.push(word);
+        }
+        let mut pos: usize = 0;
+        let node: SdfNode = SdfNode::parse_node(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(format!("unexpected {:?} after SDF description", tokens[pos]));
+        }
+        Ok(node)
+    }
+    fn parse_node(tokens: &[String], pos: &mut usize) -> Result<SdfNode, String> {
+        let name: String = match tokens.get(*pos) {
+            Some(name) => name.to_lowercase(),
+            None => return Err(String::from("unexpected end of SDF description")),
+        };
+        *pos += 1;
+        if tokens.get(*pos).map(|t| t.as_str()) != Some("(") {
+            return Err(format!("expected \"(\" after {:?}", name));
+        }
+        *pos += 1;
+        // arguments are either lists of numbers or nodes
+        let mut numbers: Vec<Vec<Float>> = Vec::new();
+        let mut children: Vec<SdfNode> = Vec::new();
+        loop {
+            match tokens.get(*pos).map(|t| t.as_str()) {
+                None => return Err(format!("missing \")\" for {:?}", name)),
+                Some(")") => {
+                    *pos += 1;
+                    break;
+                }
+                Some(",") => *pos += 1,
+                Some(token) => {
+                    if token.starts_with(|c: char| c.is_alphabetic()) {
+                        children.push(SdfNode::parse_node(tokens, pos)?);
+                    } else {
+                        let mut values: Vec<Float> = Vec::new();
+                        while let Some(token) = tokens.get(*pos) {
+                            if token == "," || token == ")" {
+                                break;
+                            }
+                            match token.parse::<Float>() {
+                                Ok(value) => values.push(value),
+                                Err(_) => {
+                                    return Err(format!("can't parse {:?} as number", token))
+                                }
+                            }
+                            *pos += 1;
+                        }
+                        numbers.push(values);
+                    }
+                }
+            }
+        }
+        let vector = |values: &Vec<Float>| -> Result<Vector3f, String> {
+            if values.len() == 3 {
+                Ok(Vector3f {
+                    x: values[0],
+                    y: values[1],
+                    z: values[2],
+                })
+            } else {
+                Err(format!("{:?} expects three numbers per vector", name))
+            }
+        };
+        let arity = |n_numbers: usize, n_children: usize| -> Result<(), String> {
+            if numbers.len() == n_numbers
+                && (children.len() == n_children || n_children == usize::MAX && !children.is_empty())
+            {
+                Ok(())
+            } else {
+                Err(format!("wrong number of arguments for {:?}", name))
+            }
+        };
+        match name.as_str() {
+            "sphere" => {
+                arity(1, 0)?;
+                if numbers[0].len() != 1 {
+                    return Err(String::from("\"sphere\" expects a radius"));
+                }
+                Ok(SdfNode::Sphere {
+                    radius: numbers[0][0],
+                })
+            }
+            "box" => {
+                arity(1, 0)?;
+                Ok(SdfNode::Box {
+                    half_extents: vector(&numbers[0])?,
+                })
+            }
+            "torus" => {
+                arity(1, 0)?;
+                if numbers[0].len() != 2 {
+                    return Err(String::from("\"torus\" expects two radii"));
+                }
+                Ok(SdfNode::Torus {
+                    major_radius: numbers[0][0],
+                    minor_radius: numbers[0][1],
+                })
+            }
+            "translate" => {
+                arity(1, 1)?;
+                Ok(SdfNode::Translate {
+                    offset: vector(&numbers[0])?,
+                    child: Box::new(children.pop().unwrap()),
+                })
+            }
+            "union" => {
+                arity(0, usize::MAX)?;
+                Ok(SdfNode::Union { children })
+            }
+            "smoothunion" => {
+                arity(1, usize::MAX)?;
+                if numbers[0].len() != 1 {
+                    return Err(String::from("\"smoothunion\" expects a blend distance"));
+                }
+                Ok(SdfNode::SmoothUnion {
+                    k: numbers[0][0],
+                    children,
+                })
+            }
+            "repeat" => {
+                arity(2, 1)?;
+                let count: Vector3f = vector(&numbers[1])?;
+                Ok(SdfNode::Repeat {
+                    period: vector(&numbers[0])?,
+                    count: [count.x as i32, count.y as i32, count.z as i32],
+                    child: Box::new(children.pop().unwrap()),
+                })
+            }
+            _ => Err(format!("unknown SDF node {:?}", name)),
+        }
+    }
+}
+
+// see e.g. John C. Hart, "Sphere Tracing: A Geometric Method for the
+// Antialiased Ray Tracing of Implicit Surfaces"
+
+/// An implicit surface (the zero set of a signed distance function),
+/// intersected by sphere tracing.
+#[derive(Clone)]
+pub struct SignedDistanceField {
+    pub root: SdfNode,
+    pub bounds: Bounds3f,
+    /// upper limit of sphere tracing steps per ray
+    pub max_steps: i32,
+    /// distance to the surface accepted as a hit
+    pub epsilon: Float,
+    // inherited from class Shape (see shape.h)
+    pub object_to_world: Transform,
+    pub world_to_object: Transform,
+    pub reverse_orientation: bool,
+    pub transform_swaps_handedness: bool,
+}
+
+impl SignedDistanceField {
+    pub fn new(
+        object_to_world: Transform,
+        world_to_object: Transform,
+        reverse_orientation: bool,
+        root: SdfNode,
+        max_steps: i32,
+        epsilon: Float,
+    ) -> Self {
+        let epsilon: Float = epsilon.max(Float::EPSILON);
+        SignedDistanceField {
+            // Shape
+            object_to_world,
+            world_to_object,
+            reverse_orientation,
+            transform_swaps_handedness: object_to_world.swaps_handedness(),
+            // SignedDistanceField
+            bounds: bnd3_expand(&root.bounds(), epsilon),
+            root,
+            max_steps,
+            epsilon,
+        }
+    }
+    /// Marches along the (object space) ray and returns the parametric
+    /// distance of the first surface hit.
+    fn sphere_trace(&self, ray: &Ray) -> Option<Float> {
+        let mut t0: Float = 0.0;
+        let mut t1: Float = 0.0;
+        if !self.bounds.intersect_b(ray, &mut t0, &mut t1) {
+            return None;
+        }
+        let d_length: Float = ray.d.length();
+        if d_length == 0.0 as Float {
+            return None;
+        }
+        let mut t: Float = t0;
+        for _ in 0..self.max_steps {
+            let d: Float = self.root.distance(&ray.position(t));
+            if d.abs() < self.epsilon {
+                if t <= 0.0 as Float || t >= ray.t_max.get() {
+                    return None;
+                }
+                return Some(t);
+            }
+            // the distance is a safe step size in both directions
+            t += d.abs() / d_length;
+            if t > t1 {
+                return None;
+            }
+        }
+        None
+    }
+    /// Returns the (normalized) gradient of the distance function.
+    fn gradient(&self, p: &Point3f) -> Vector3f {
+        let h: Float = self.epsilon;
+        let dx: Vector3f = Vector3f {
+            x: h,
+            y: 0.0,
+            z: 0.0,
+        };
+        let dy: Vector3f = Vector3f {
+            x: 0.0,
+            y: h,
+            z: 0.0,
+        };
+        let dz: Vector3f = Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: h,
+        };
+        let g: Vector3f = Vector3f {
+            x: self.root.distance(&(*p + dx)) - self.root.distance(&(*p - dx)),
+            y: self.root.distance(&(*p + dy)) - self.root.distance(&(*p - dy)),
+            z: self.root.distance(&(*p + dz)) - self.root.distance(&(*p - dz)),
+        };
+        if g.length_squared() > 0.0 as Float {
+            g.normalize()
+        } else {
+            Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            }
+        }
+    }
+    // Shape
+    pub fn object_bound(&self) -> Bounds3f {
+        self.bounds
+    }
+    pub fn world_bound(&self) -> Bounds3f {
+        // in C++: Bounds3f Shape::WorldBound() const { return (*ObjectToWorld)(ObjectBound()); }
+        self.object_to_world.transform_bounds(&self.object_bound())
+    }
+    pub fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        // transform _Ray_ to object space
+        let mut o_err: Vector3f = Vector3f::default();
+        let mut d_err: Vector3f = Vector3f::default();
+        let ray: Ray = self
+            .world_to_object
+            .transform_ray_with_error(r, &mut o_err, &mut d_err);
+        let t_shape_hit: Float = match self.sphere_trace(&ray) {
+            Some(t) => t,
+            None => return false,
+        };
+        let p_hit: Point3f = ray.position(t_shape_hit);
+        // the normal is the gradient of the distance function
+        let n: Vector3f = self.gradient(&p_hit);
+        let mut dpdu: Vector3f = Vector3f::default();
+        let mut dpdv: Vector3f = Vector3f::default();
+        vec3_coordinate_system(&n, &mut dpdu, &mut dpdv);
+        if self.reverse_orientation {
+            dpdv = -dpdv;
+        }
+        // spherical mapping of the normal for $(u,v)$
+        let uv_hit: Point2f = Point2f {
+            x: (n.y.atan2(n.x) + PI) / (2.0 as Float * PI),
+            y: clamp_t(n.z, -1.0, 1.0).acos() / PI,
+        };
+        // the hit point is only known up to _epsilon_
+        let p_error: Vector3f = Vector3f {
+            x: 2.0 as Float * self.epsilon,
+            y: 2.0 as Float * self.epsilon,
+            z: 2.0 as Float * self.epsilon,
+        };
+        let wo: Vector3f = -ray.d;
+        *isect = SurfaceInteraction::new(
+            &p_hit,
+            &p_error,
+            uv_hit,
+            &wo,
+            &dpdu,
+            &dpdv,
+            &Normal3f::default(),
+            &Normal3f::default(),
+            ray.time,
+            None,
+        );
+        self.object_to_world.transform_surface_interaction(isect);
+        *t_hit = t_shape_hit;
+        true
+    }
+    pub fn intersect_p(&self, r: &Ray) -> bool {
+        // transform _Ray_ to object space
+        let mut o_err: Vector3f = Vector3f::default();
+        let mut d_err: Vector3f = Vector3f::default();
+        let ray: Ray = self
+            .world_to_object
+            .transform_ray_with_error(r, &mut o_err, &mut d_err);
+        self.sphere_trace(&ray).is_some()
+    }
+    pub fn get_reverse_orientation(&self) -> bool {
+        self.reverse_orientation
+    }
+    pub fn get_transform_swaps_handedness(&self) -> bool {
+        self.transform_swaps_handedness
+    }
+    pub fn get_object_to_world(&self) -> Transform {
+        self.object_to_world
+    }
+    pub fn area(&self) -> Float {
+        println!("FATAL: SignedDistanceField::area not implemented.");
+        0.0 as Float
+    }
+    pub fn sample(&self, _u: Point2f, _pdf: &mut Float) -> InteractionCommon {
+        println!("FATAL: SignedDistanceField::sample not implemented.");
+        InteractionCommon::default()
+    }
+    pub fn sample_with_ref_point(
+        &self,
+        _iref: &InteractionCommon,
+        u: Point2f,
+        pdf: &mut Float,
+    ) -> InteractionCommon {
+        let intr: InteractionCommon = self.sample(u, pdf);
+        *pdf = 0.0 as Float;
+        intr
+    }
+    pub fn pdf_with_ref_point(&self, _iref: &dyn Interaction, _wi: &Vector3f) -> Float {
+        0.0 as Float
+    }
+}
+
+pub fn create_sdf_shape(
+    o2w: &Transform,
+    w2o: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+) -> Vec<Arc<Shape>> {
+    let description: String = params.find_one_string("sdf", String::new());
+    let max_steps: i32 = params.find_one_int("maxsteps", 256);
+    let epsilon: Float = params.find_one_float("epsilon", 1e-4 as Float);
+    match SdfNode::parse(&description) {
+        Ok(root) => vec![Arc::new(Shape::Sdf(SignedDistanceField::new(
+            *o2w,
+            *w2o,
+            reverse_orientation,
+            root,
+            max_steps,
+            epsilon,
+        )))],
+        Err(message) => {
+            println!("ERROR: sdf: {}. Ignoring shape.", message);
+            Vec::new()
+        }
+    }
+}
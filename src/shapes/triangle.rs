@@ -3,23 +3,84 @@ use std::cell::Cell;
 use std::mem;
 use std::sync::Arc;
 // pbrt
+use crate::accelerators::elementbvh::ElementBVH;
 use crate::core::geometry::{
     bnd3_union_pnt3f, nrm_abs_dot_vec3f, nrm_faceforward_nrm, pnt3_abs, pnt3_distance_squaredf,
     pnt3_permutef, vec3_coordinate_system, vec3_cross_nrm, vec3_cross_vec3, vec3_max_componentf,
     vec3_max_dimensionf, vec3_permutef,
 };
 use crate::core::geometry::{
-    Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector2f, Vector3f, XYEnum,
+    Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector2f, Vector3f, XYEnum, XYZEnum,
 };
 use crate::core::interaction::{Interaction, InteractionCommon, Shading, SurfaceInteraction};
+use crate::core::paramset::ParamSet;
+use crate::core::pbrt::clamp_t;
 use crate::core::pbrt::gamma;
+use crate::core::pbrt::lerp;
 use crate::core::pbrt::Float;
 // use crate::core::sampling::uniform_sample_triangle;
+use crate::core::profiler::{Prof, ProfilePhase};
+use crate::core::sampling::Distribution1D;
+use crate::core::shape::Shape;
+use crate::core::stats::{self, Percent};
 use crate::core::texture::Texture;
 use crate::core::transform::Transform;
 
 // see triangle.h
 
+/// A unit vector stored in 32 bits, two 16-bit coordinates of the
+/// octahedral projection (see pbrt-v4's *OctahedralVector*).
+#[derive(Debug, Default, Copy, Clone)]
+pub struct OctahedralVector {
+    x: u16,
+    y: u16,
+}
+
+impl OctahedralVector {
+    pub fn new(v: &Normal3f) -> Self {
+        let l1: Float = v.x.abs() + v.y.abs() + v.z.abs();
+        if l1 == 0.0 as Float {
+            return OctahedralVector::default();
+        }
+        let (vx, vy, vz) = (v.x / l1, v.y / l1, v.z / l1);
+        if vz >= 0.0 as Float {
+            OctahedralVector {
+                x: OctahedralVector::encode(vx),
+                y: OctahedralVector::encode(vy),
+            }
+        } else {
+            // unfold the lower hemisphere
+            OctahedralVector {
+                x: OctahedralVector::encode(
+                    (1.0 as Float - vy.abs()) * (1.0 as Float).copysign(vx),
+                ),
+                y: OctahedralVector::encode(
+                    (1.0 as Float - vx.abs()) * (1.0 as Float).copysign(vy),
+                ),
+            }
+        }
+    }
+    fn encode(f: Float) -> u16 {
+        (clamp_t(
+            (f + 1.0 as Float) / 2.0 as Float,
+            0.0 as Float,
+            1.0 as Float,
+        ) * 65535.0 as Float)
+            .round() as u16
+    }
+    pub fn to_normal(self) -> Normal3f {
+        let mut x: Float = -1.0 as Float + 2.0 as Float * (self.x as Float / 65535.0 as Float);
+        let mut y: Float = -1.0 as Float + 2.0 as Float * (self.y as Float / 65535.0 as Float);
+        let z: Float = 1.0 as Float - (x.abs() + y.abs());
+        if z < 0.0 as Float {
+            let xo: Float = x;
+            x = (1.0 as Float - y.abs()) * (1.0 as Float).copysign(xo);
+            y = (1.0 as Float - xo.abs()) * (1.0 as Float).copysign(y);
+        }
+        Normal3f { x, y, z }.normalize()
+    }
+}
+
 #[derive(Clone)]
 pub struct TriangleMesh {
     /// the total number of triangles in the mesh
@@ -38,6 +99,22 @@ pub struct TriangleMesh {
     pub uv: Vec<Point2f>,
     pub alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
     pub shadow_alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
+    /// an optional vector of per-face indices (can be empty)
+    pub face_indices: Vec<i32>,
+    /// times of the vertex animation samples (empty for static meshes),
+    /// *p* and *n* hold the first sample
+    pub motion_times: Vec<Float>,
+    /// vertex positions for the remaining time samples
+    pub motion_p: Vec<Vec<Point3f>>,
+    /// normal vectors for the remaining time samples (can be empty)
+    pub motion_n: Vec<Vec<Normal3f>>,
+    /// octahedral encoded normals of a compressed mesh (replace *n*)
+    pub n_oct: Vec<OctahedralVector>,
+    /// 16-bit quantized (u, v) values of a compressed mesh (replace
+    /// *uv*), relative to *uv_offset* and *uv_scale*
+    pub uv_quantized: Vec<[u16; 2]>,
+    pub uv_offset: Point2f,
+    pub uv_scale: Vector2f,
     // inherited from class Shape (see shape.h)
     pub object_to_world: Transform, // TODO: not pub?
     pub world_to_object: Transform, // TODO: not pub?
@@ -59,6 +136,7 @@ impl TriangleMesh {
         uv: Vec<Point2f>,
         alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
         shadow_alpha_mask: Option<Arc<dyn Texture<Float> + Send + Sync>>,
+        face_indices: Vec<i32>,
     ) -> Self {
         TriangleMesh {
             // Shape
@@ -76,25 +154,163 @@ impl TriangleMesh {
             uv,
             alpha_mask,
             shadow_alpha_mask,
+            face_indices,
+            motion_times: Vec::new(),
+            motion_p: Vec::new(),
+            motion_n: Vec::new(),
+            n_oct: Vec::new(),
+            uv_quantized: Vec::new(),
+            uv_offset: Point2f::default(),
+            uv_scale: Vector2f::default(),
         }
     }
-}
-
-#[derive(Clone)]
-pub struct Triangle {
-    mesh: Arc<TriangleMesh>,
-    pub id: u32,
-}
-
-impl Triangle {
-    pub fn new(mesh: Arc<TriangleMesh>, tri_number: u32) -> Self {
-        Triangle {
-            mesh,
-            id: tri_number,
+    /// Replaces the (first sample of the) normals by octahedral
+    /// encoded ones and quantizes the (u, v) values to 16 bits within
+    /// their bounds. This roughly halves the memory used for shading
+    /// attributes at the cost of some precision.
+    pub fn compress(&mut self) {
+        if self.has_normals() {
+            self.n_oct = self.n.iter().map(OctahedralVector::new).collect();
+            self.n = Vec::new();
+        }
+        if !self.uv.is_empty() {
+            let mut uv_min: Point2f = self.uv[0];
+            let mut uv_max: Point2f = self.uv[0];
+            for uv in &self.uv {
+                uv_min.x = uv_min.x.min(uv.x);
+                uv_min.y = uv_min.y.min(uv.y);
+                uv_max.x = uv_max.x.max(uv.x);
+                uv_max.y = uv_max.y.max(uv.y);
+            }
+            self.uv_offset = uv_min;
+            self.uv_scale = (uv_max - uv_min) / 65535.0 as Float;
+            let quantize = |f: Float, scale: Float| -> u16 {
+                if scale > 0.0 as Float {
+                    (f / scale).round().min(65535.0 as Float) as u16
+                } else {
+                    0_u16
+                }
+            };
+            self.uv_quantized = self
+                .uv
+                .iter()
+                .map(|uv| {
+                    [
+                        quantize(uv.x - uv_min.x, self.uv_scale.x),
+                        quantize(uv.y - uv_min.y, self.uv_scale.y),
+                    ]
+                })
+                .collect();
+            self.uv = Vec::new();
         }
     }
-    pub fn get_uvs(&self) -> [Point2f; 3] {
-        if self.mesh.uv.is_empty() {
+    pub fn has_normals(&self) -> bool {
+        !self.n.is_empty() || !self.n_oct.is_empty()
+    }
+    pub fn has_uvs(&self) -> bool {
+        !self.uv.is_empty() || !self.uv_quantized.is_empty()
+    }
+    /// Returns the (u, v) value of a vertex.
+    pub fn get_uv(&self, vertex: usize) -> Point2f {
+        if self.uv_quantized.is_empty() {
+            self.uv[vertex]
+        } else {
+            let q: [u16; 2] = self.uv_quantized[vertex];
+            Point2f {
+                x: self.uv_offset.x + q[0] as Float * self.uv_scale.x,
+                y: self.uv_offset.y + q[1] as Float * self.uv_scale.y,
+            }
+        }
+    }
+    /// Turns the mesh into a deforming one. The vertex positions (and
+    /// normals) given to _TriangleMesh::new()_ belong to the first time
+    /// in *times*, *p* (and *n*) provide one vector per remaining time.
+    pub fn set_motion_samples(
+        &mut self,
+        times: Vec<Float>,
+        p: Vec<Vec<Point3f>>,
+        n: Vec<Vec<Normal3f>>,
+    ) {
+        assert!(times.len() == p.len() + 1);
+        assert!(n.is_empty() || n.len() == p.len());
+        self.motion_times = times;
+        self.motion_p = p;
+        self.motion_n = n;
+    }
+    /// Returns the two time samples enclosing *time* and the
+    /// interpolation weight between them.
+    fn motion_segment(&self, time: Float) -> (usize, usize, Float) {
+        let times: &Vec<Float> = &self.motion_times;
+        let last: usize = times.len() - 1;
+        if time <= times[0] {
+            return (0, 0, 0.0 as Float);
+        }
+        if time >= times[last] {
+            return (last, last, 0.0 as Float);
+        }
+        let mut i: usize = 0;
+        while i + 1 < last && time > times[i + 1] {
+            i += 1;
+        }
+        let dt: Float = times[i + 1] - times[i];
+        let t: Float = if dt > 0.0 as Float {
+            (time - times[i]) / dt
+        } else {
+            0.0 as Float
+        };
+        (i, i + 1, t)
+    }
+    fn p_at_sample(&self, sample: usize, vertex: usize) -> Point3f {
+        if sample == 0 {
+            self.p[vertex]
+        } else {
+            self.motion_p[sample - 1][vertex]
+        }
+    }
+    fn n_at_sample(&self, sample: usize, vertex: usize) -> Normal3f {
+        if sample == 0 || self.motion_n.is_empty() {
+            if self.n_oct.is_empty() {
+                self.n[vertex]
+            } else {
+                self.n_oct[vertex].to_normal()
+            }
+        } else {
+            self.motion_n[sample - 1][vertex]
+        }
+    }
+    /// Returns the (world space) position of a vertex at the given time.
+    pub fn get_p(&self, vertex: usize, time: Float) -> Point3f {
+        if self.motion_times.is_empty() {
+            self.p[vertex]
+        } else {
+            let (i0, i1, t) = self.motion_segment(time);
+            lerp(
+                t,
+                self.p_at_sample(i0, vertex),
+                self.p_at_sample(i1, vertex),
+            )
+        }
+    }
+    /// Returns the (world space) normal of a vertex at the given time.
+    pub fn get_n(&self, vertex: usize, time: Float) -> Normal3f {
+        if self.motion_times.is_empty() {
+            self.n_at_sample(0, vertex)
+        } else {
+            let (i0, i1, t) = self.motion_segment(time);
+            self.n_at_sample(i0, vertex) * (1.0 as Float - t) + self.n_at_sample(i1, vertex) * t
+        }
+    }
+    /// Returns the per-face index of the triangle (or zero if the mesh
+    /// doesn't provide any).
+    pub fn get_face_index(&self, id: u32) -> i32 {
+        if self.face_indices.is_empty() {
+            0
+        } else {
+            self.face_indices[id as usize]
+        }
+    }
+    pub fn get_uvs(&self, id: u32) -> [Point2f; 3] {
+        if !self.has_uvs() {
             [
                 Point2f { x: 0.0, y: 0.0 },
                 Point2f { x: 1.0, y: 0.0 },
@@ -102,42 +318,83 @@ impl Triangle {
             ]
         } else {
             [
-                self.mesh.uv[self.mesh.vertex_indices[(self.id * 3) as usize] as usize],
-                self.mesh.uv[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize],
-                self.mesh.uv[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize],
+                self.get_uv(self.vertex_indices[(id * 3) as usize] as usize),
+                self.get_uv(self.vertex_indices[(id * 3) as usize + 1] as usize),
+                self.get_uv(self.vertex_indices[(id * 3) as usize + 2] as usize),
             ]
         }
     }
-    // Shape
-    pub fn object_bound(&self) -> Bounds3f {
-        let idx1: usize = (self.id * 3) as usize;
-        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
-        let p0: &Point3f = &self.mesh.p[idx[0] as usize];
-        let p1: &Point3f = &self.mesh.p[idx[1] as usize];
-        let p2: &Point3f = &self.mesh.p[idx[2] as usize];
-        bnd3_union_pnt3f(
-            &Bounds3f::new(
-                self.mesh.world_to_object.transform_point(p0),
-                self.mesh.world_to_object.transform_point(p1),
-            ),
-            &self.mesh.world_to_object.transform_point(p2),
-        )
+    pub fn triangle_world_bound(&self, id: u32) -> Bounds3f {
+        let idx1: usize = (id * 3) as usize;
+        let idx = &self.vertex_indices[idx1..(idx1 + 3)];
+        let p0: &Point3f = &self.p[idx[0] as usize];
+        let p1: &Point3f = &self.p[idx[1] as usize];
+        let p2: &Point3f = &self.p[idx[2] as usize];
+        let mut bounds: Bounds3f = bnd3_union_pnt3f(&Bounds3f::new(*p0, *p1), p2);
+        // cover all time samples of a deforming mesh
+        for p in &self.motion_p {
+            for i in idx {
+                bounds = bnd3_union_pnt3f(&bounds, &p[*i as usize]);
+            }
+        }
+        bounds
     }
-    pub fn world_bound(&self) -> Bounds3f {
-        let idx1: usize = (self.id * 3) as usize;
-        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
-        let p0: &Point3f = &self.mesh.p[idx[0] as usize];
-        let p1: &Point3f = &self.mesh.p[idx[1] as usize];
-        let p2: &Point3f = &self.mesh.p[idx[2] as usize];
-        bnd3_union_pnt3f(&Bounds3f::new(*p0, *p1), p2)
+    /// Bounds of the part of triangle *id* between the planes *min*
+    /// and *max* along the given *axis* (used by spatial BVH
+    /// splits). Deforming meshes return their world bounds clipped to
+    /// the slab.
+    pub fn triangle_clipped_world_bound(
+        &self,
+        id: u32,
+        axis: XYZEnum,
+        min: Float,
+        max: Float,
+    ) -> Bounds3f {
+        let mut bounds: Bounds3f = Bounds3f::default();
+        if !self.motion_p.is_empty() {
+            bounds = self.triangle_world_bound(id);
+            bounds.p_min[axis] = bounds.p_min[axis].max(min);
+            bounds.p_max[axis] = bounds.p_max[axis].min(max);
+            return bounds;
+        }
+        let idx1: usize = (id * 3) as usize;
+        let idx = &self.vertex_indices[idx1..(idx1 + 3)];
+        for i in 0..3 {
+            // add vertex inside slab and edge intersections with both planes
+            let p0: &Point3f = &self.p[idx[i] as usize];
+            let p1: &Point3f = &self.p[idx[(i + 1) % 3] as usize];
+            if p0[axis] >= min && p0[axis] <= max {
+                bounds = bnd3_union_pnt3f(&bounds, p0);
+            }
+            for plane in [min, max].iter() {
+                if (p0[axis] < *plane && p1[axis] > *plane)
+                    || (p0[axis] > *plane && p1[axis] < *plane)
+                {
+                    let t: Float = (*plane - p0[axis]) / (p1[axis] - p0[axis]);
+                    let mut p: Point3f = *p0 + (*p1 - *p0) * t;
+                    p[axis] = *plane;
+                    bounds = bnd3_union_pnt3f(&bounds, &p);
+                }
+            }
+        }
+        bounds
     }
-    pub fn intersect(&self, ray: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+    pub fn intersect_triangle(
+        &self,
+        id: u32,
+        ray: &Ray,
+        t_hit: &mut Float,
+        isect: &mut SurfaceInteraction,
+    ) -> bool {
+        let _p = ProfilePhase::new(Prof::TriIntersect);
+        stats::count_primitive_test();
+        stats::add_percent(Percent::TriangleHits, 0, 1);
         // get triangle vertices in _p0_, _p1_, and _p2_
-        let idx1: usize = (self.id * 3) as usize;
-        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
-        let p0: &Point3f = &self.mesh.p[idx[0] as usize];
-        let p1: &Point3f = &self.mesh.p[idx[1] as usize];
-        let p2: &Point3f = &self.mesh.p[idx[2] as usize];
+        let idx1: usize = (id * 3) as usize;
+        let idx = &self.vertex_indices[idx1..(idx1 + 3)];
+        let p0: &Point3f = &self.get_p(idx[0] as usize, ray.time);
+        let p1: &Point3f = &self.get_p(idx[1] as usize, ray.time);
+        let p2: &Point3f = &self.get_p(idx[2] as usize, ray.time);
         // translate vertices based on ray origin
         let mut p0t: Point3f = *p0
             - Vector3f {
@@ -272,7 +529,7 @@ impl Triangle {
             return false;
         }
         // compute triangle partial derivatives
-        let uv: [Point2f; 3] = self.get_uvs();
+        let uv: [Point2f; 3] = self.get_uvs(id);
         // compute deltas for triangle partial derivatives
         let duv02: Vector2f = uv[0] - uv[2];
         let duv12: Vector2f = uv[1] - uv[2];
@@ -310,9 +567,9 @@ impl Triangle {
         let uv_hit: Point2f = uv[0] * b0 + uv[1] * b1 + uv[2] * b2;
         // test intersection against alpha texture, if present
         // TODO: testAlphaTexture
-        if let Some(alpha_mask) = &self.mesh.alpha_mask {
+        if let Some(alpha_mask) = &self.alpha_mask {
             let wo: Vector3f = -ray.d;
-            let isect_local: SurfaceInteraction = SurfaceInteraction::new(
+            let mut isect_local: SurfaceInteraction = SurfaceInteraction::new(
                 &p_hit,
                 &Vector3f::default(),
                 uv_hit,
@@ -324,6 +581,7 @@ impl Triangle {
                 ray.time,
                 None,
             );
+            isect_local.face_index = self.get_face_index(id);
             if alpha_mask.evaluate(&isect_local) == 0.0 as Float {
                 return false;
             }
@@ -342,15 +600,15 @@ impl Triangle {
             dndu,
             dndv,
         };
-        if !self.mesh.n.is_empty() || !self.mesh.s.is_empty() {
+        if self.has_normals() || !self.s.is_empty() {
             // initialize _Triangle_ shading geometry
 
             // compute shading normal _ns_ for triangle
             let mut ns: Normal3f;
-            if !self.mesh.n.is_empty() {
-                let n0 = self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
-                let n1 = self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
-                let n2 = self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+            if self.has_normals() {
+                let n0 = self.get_n(idx[0] as usize, ray.time);
+                let n1 = self.get_n(idx[1] as usize, ray.time);
+                let n2 = self.get_n(idx[2] as usize, ray.time);
                 ns = n0 * b0 + n1 * b1 + n2 * b2;
                 if ns.length_squared() > 0.0 {
                     ns = ns.normalize();
@@ -362,10 +620,10 @@ impl Triangle {
             }
             // compute shading tangent _ss_ for triangle
             let mut ss: Vector3f;
-            if !self.mesh.s.is_empty() {
-                let s0 = self.mesh.s[self.mesh.vertex_indices[(self.id * 3) as usize] as usize];
-                let s1 = self.mesh.s[self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize];
-                let s2 = self.mesh.s[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+            if !self.s.is_empty() {
+                let s0 = self.s[self.vertex_indices[(id * 3) as usize] as usize];
+                let s1 = self.s[self.vertex_indices[(id * 3) as usize + 1] as usize];
+                let s2 = self.s[self.vertex_indices[(id * 3) as usize + 2] as usize];
                 ss = s0 * b0 + s1 * b1 + s2 * b2;
                 if ss.length_squared() > 0.0 {
                     ss = ss.normalize();
@@ -386,16 +644,14 @@ impl Triangle {
             // compute $\dndu$ and $\dndv$ for triangle shading geometry
             let dndu: Normal3f;
             let dndv: Normal3f;
-            if !self.mesh.n.is_empty() {
+            if self.has_normals() {
                 // compute deltas for triangle partial derivatives of normal
                 let duv02: Vector2f = uv[0] - uv[2];
                 let duv12: Vector2f = uv[1] - uv[2];
-                let dn1: Normal3f = self.mesh.n
-                    [self.mesh.vertex_indices[(self.id * 3) as usize] as usize]
-                    - self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
-                let dn2: Normal3f = self.mesh.n
-                    [self.mesh.vertex_indices[(self.id * 3) as usize + 1] as usize]
-                    - self.mesh.n[self.mesh.vertex_indices[(self.id * 3) as usize + 2] as usize];
+                let dn1: Normal3f =
+                    self.get_n(idx[0] as usize, ray.time) - self.get_n(idx[2] as usize, ray.time);
+                let dn2: Normal3f =
+                    self.get_n(idx[1] as usize, ray.time) - self.get_n(idx[2] as usize, ray.time);
                 let determinant: Float = duv02.x * duv12.y - duv02.y * duv12.x;
                 let degenerate_uv: bool = determinant.abs() < 1e-8;
                 if degenerate_uv {
@@ -441,18 +697,21 @@ impl Triangle {
         isect.bsdf = None;
         // isect.bssrdf = None;
         isect.shape = None;
+        isect.face_index = self.get_face_index(id);
         *t_hit = t;
+        stats::add_percent(Percent::TriangleHits, 1, 0);
         true
     }
-    pub fn intersect_p(&self, ray: &Ray) -> bool {
-        // TODO: ProfilePhase p(Prof::TriIntersectP);
-        // TODO: ++nTests;
+    pub fn intersect_p_triangle(&self, id: u32, ray: &Ray) -> bool {
+        let _p = ProfilePhase::new(Prof::TriIntersectP);
+        stats::count_primitive_test();
+        stats::add_percent(Percent::TriangleHits, 0, 1);
         // get triangle vertices in _p0_, _p1_, and _p2_
-        let idx1: usize = (self.id * 3) as usize;
-        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
-        let p0: &Point3f = &self.mesh.p[idx[0] as usize];
-        let p1: &Point3f = &self.mesh.p[idx[1] as usize];
-        let p2: &Point3f = &self.mesh.p[idx[2] as usize];
+        let idx1: usize = (id * 3) as usize;
+        let idx = &self.vertex_indices[idx1..(idx1 + 3)];
+        let p0: &Point3f = &self.get_p(idx[0] as usize, ray.time);
+        let p1: &Point3f = &self.get_p(idx[1] as usize, ray.time);
+        let p2: &Point3f = &self.get_p(idx[2] as usize, ray.time);
         // translate vertices based on ray origin
         let mut p0t: Point3f = *p0
             - Vector3f {
@@ -587,11 +846,11 @@ impl Triangle {
             return false;
         }
         // TODO: if (testAlphaTexture && (mesh->alphaMask || mesh->shadowAlphaMask)) { ... }
-        if self.mesh.alpha_mask.is_some() || self.mesh.shadow_alpha_mask.is_some() {
+        if self.alpha_mask.is_some() || self.shadow_alpha_mask.is_some() {
             // compute triangle partial derivatives
             let mut dpdu: Vector3f = Vector3f::default();
             let mut dpdv: Vector3f = Vector3f::default();
-            let uv: [Point2f; 3] = self.get_uvs();
+            let uv: [Point2f; 3] = self.get_uvs(id);
             // compute deltas for triangle partial derivatives
             let duv02: Vector2f = uv[0] - uv[2];
             let duv12: Vector2f = uv[1] - uv[2];
@@ -626,7 +885,7 @@ impl Triangle {
             let p_hit: Point3f = *p0 * b0 + *p1 * b1 + *p2 * b2;
             let uv_hit: Point2f = uv[0] * b0 + uv[1] * b1 + uv[2] * b2;
             let wo: Vector3f = -ray.d;
-            let isect_local: SurfaceInteraction = SurfaceInteraction::new(
+            let mut isect_local: SurfaceInteraction = SurfaceInteraction::new(
                 &p_hit,
                 &Vector3f::default(),
                 uv_hit,
@@ -638,50 +897,57 @@ impl Triangle {
                 ray.time,
                 None,
             );
-            if let Some(alpha_mask) = &self.mesh.alpha_mask {
+            isect_local.face_index = self.get_face_index(id);
+            if let Some(alpha_mask) = &self.alpha_mask {
                 if alpha_mask.evaluate(&isect_local) == 0.0 as Float {
                     return false;
                 }
             }
-            if let Some(shadow_alpha_mask) = &self.mesh.shadow_alpha_mask {
+            if let Some(shadow_alpha_mask) = &self.shadow_alpha_mask {
                 if shadow_alpha_mask.evaluate(&isect_local) == 0.0 as Float {
                     return false;
                 }
             }
         }
-        // TODO: ++nHits;
+        stats::add_percent(Percent::TriangleHits, 1, 0);
         true
     }
-    pub fn get_reverse_orientation(&self) -> bool {
-        self.mesh.reverse_orientation
+    /// The time of the first vertex animation sample (zero for static
+    /// meshes).
+    pub fn start_time(&self) -> Float {
+        self.motion_times.first().copied().unwrap_or(0.0 as Float)
     }
-    pub fn get_transform_swaps_handedness(&self) -> bool {
-        self.mesh.transform_swaps_handedness
-    }
-    pub fn get_object_to_world(&self) -> Transform {
-        self.mesh.object_to_world
-    }
-    pub fn area(&self) -> Float {
-        let idx1: usize = (self.id * 3) as usize;
-        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
+    /// Area of triangle *id* at the given time (deforming meshes
+    /// interpolate their vertices).
+    pub fn triangle_area(&self, id: u32, time: Float) -> Float {
+        let idx1: usize = (id * 3) as usize;
+        let idx = &self.vertex_indices[idx1..(idx1 + 3)];
         // get triangle vertices in _p0_, _p1_, and _p2_
-        let p0: &Point3f = &self.mesh.p[idx[0] as usize];
-        let p1: &Point3f = &self.mesh.p[idx[1] as usize];
-        let p2: &Point3f = &self.mesh.p[idx[2] as usize];
-        0.5 as Float * vec3_cross_vec3(&(*p1 - *p0), &(*p2 - *p0)).length()
+        let p0: Point3f = self.get_p(idx[0] as usize, time);
+        let p1: Point3f = self.get_p(idx[1] as usize, time);
+        let p2: Point3f = self.get_p(idx[2] as usize, time);
+        0.5 as Float * vec3_cross_vec3(&(p1 - p0), &(p2 - p0)).length()
     }
-    pub fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
-        let idx1: usize = (self.id * 3) as usize;
-        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
+    /// Samples a point uniformly on triangle *id* at the given time
+    /// (deforming meshes interpolate their vertices).
+    pub fn sample_triangle(
+        &self,
+        id: u32,
+        u: Point2f,
+        time: Float,
+        pdf: &mut Float,
+    ) -> InteractionCommon {
+        let idx1: usize = (id * 3) as usize;
+        let idx = &self.vertex_indices[idx1..(idx1 + 3)];
         // avoid calling uniform_sample_triangle!!!
         // let b: Point2f = uniform_sample_triangle(u);
         let su0: Float = u[XYEnum::X].sqrt();
         let bx: Float = 1.0 as Float - su0;
         let by: Float = u[XYEnum::Y] * su0;
         // get triangle vertices in _p0_, _p1_, and _p2_
-        let p0: &Point3f = &self.mesh.p[idx[0] as usize];
-        let p1: &Point3f = &self.mesh.p[idx[1] as usize];
-        let p2: &Point3f = &self.mesh.p[idx[2] as usize];
+        let p0: &Point3f = &self.get_p(idx[0] as usize, time);
+        let p1: &Point3f = &self.get_p(idx[1] as usize, time);
+        let p2: &Point3f = &self.get_p(idx[2] as usize, time);
         // let bx = b[XYEnum::X];
         // let by = b[XYEnum::Y];
         let it_p = p0 * bx + p1 * by + p2 * (1.0 as Float - bx - by);
@@ -689,12 +955,12 @@ impl Triangle {
         let mut it_n = Normal3f::from(vec3_cross_vec3(&(p1 - p0), &(p2 - p0))).normalize();
         // ensure correct orientation of the geometric normal; follow
         // the same approach as was used in Triangle::Intersect().
-        if !self.mesh.n.is_empty() {
-            let ns: Normal3f = self.mesh.n[idx[0] as usize] * bx
-                + self.mesh.n[idx[1] as usize] * by
-                + self.mesh.n[idx[2] as usize] * (1.0 as Float - bx - by);
+        if self.has_normals() {
+            let ns: Normal3f = self.get_n(idx[0] as usize, time) * bx
+                + self.get_n(idx[1] as usize, time) * by
+                + self.get_n(idx[2] as usize, time) * (1.0 as Float - bx - by);
             it_n = nrm_faceforward_nrm(&it_n, &ns);
-        } else if self.mesh.reverse_orientation ^ self.mesh.transform_swaps_handedness {
+        } else if self.reverse_orientation ^ self.transform_swaps_handedness {
             it_n *= -1.0 as Float;
         }
         // compute error bounds for sampled point on triangle
@@ -711,20 +977,112 @@ impl Triangle {
         *pdf = 1.0 as Float / area;
         InteractionCommon {
             p: it_p,
-            time: 0.0 as Float,
+            time,
             p_error: it_p_error,
             wo: Vector3f::default(),
             n: it_n,
             medium_interface: None,
         }
     }
+}
+
+#[derive(Clone)]
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    pub id: u32,
+}
+
+impl Triangle {
+    pub fn new(mesh: Arc<TriangleMesh>, tri_number: u32) -> Self {
+        Triangle {
+            mesh,
+            id: tri_number,
+        }
+    }
+    /// Returns the per-face index of the triangle (or zero if the mesh
+    /// doesn't provide any).
+    pub fn get_face_index(&self) -> i32 {
+        self.mesh.get_face_index(self.id)
+    }
+    pub fn get_uvs(&self) -> [Point2f; 3] {
+        self.mesh.get_uvs(self.id)
+    }
+    // Shape
+    pub fn object_bound(&self) -> Bounds3f {
+        let idx1: usize = (self.id * 3) as usize;
+        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
+        let p0: &Point3f = &self.mesh.p[idx[0] as usize];
+        let p1: &Point3f = &self.mesh.p[idx[1] as usize];
+        let p2: &Point3f = &self.mesh.p[idx[2] as usize];
+        let mut bounds: Bounds3f = bnd3_union_pnt3f(
+            &Bounds3f::new(
+                self.mesh.world_to_object.transform_point(p0),
+                self.mesh.world_to_object.transform_point(p1),
+            ),
+            &self.mesh.world_to_object.transform_point(p2),
+        );
+        // cover all time samples of a deforming mesh
+        for p in &self.mesh.motion_p {
+            for i in idx {
+                bounds = bnd3_union_pnt3f(
+                    &bounds,
+                    &self.mesh.world_to_object.transform_point(&p[*i as usize]),
+                );
+            }
+        }
+        bounds
+    }
+    pub fn world_bound(&self) -> Bounds3f {
+        self.mesh.triangle_world_bound(self.id)
+    }
+    /// The (world space) vertices of the triangle.
+    pub fn vertices(&self) -> [Point3f; 3] {
+        let idx1: usize = (self.id * 3) as usize;
+        let idx = &self.mesh.vertex_indices[idx1..(idx1 + 3)];
+        [
+            self.mesh.p[idx[0] as usize],
+            self.mesh.p[idx[1] as usize],
+            self.mesh.p[idx[2] as usize],
+        ]
+    }
+    /// See _TriangleMesh::triangle_clipped_world_bound()_.
+    pub fn clipped_world_bound(&self, axis: XYZEnum, min: Float, max: Float) -> Bounds3f {
+        self.mesh
+            .triangle_clipped_world_bound(self.id, axis, min, max)
+    }
+    pub fn intersect(&self, ray: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        self.mesh.intersect_triangle(self.id, ray, t_hit, isect)
+    }
+    pub fn intersect_p(&self, ray: &Ray) -> bool {
+        self.mesh.intersect_p_triangle(self.id, ray)
+    }
+    pub fn get_reverse_orientation(&self) -> bool {
+        self.mesh.reverse_orientation
+    }
+    pub fn get_transform_swaps_handedness(&self) -> bool {
+        self.mesh.transform_swaps_handedness
+    }
+    pub fn get_object_to_world(&self) -> Transform {
+        self.mesh.object_to_world
+    }
+    /// Area at the first time sample of a deforming mesh.
+    pub fn area(&self) -> Float {
+        self.mesh.triangle_area(self.id, self.mesh.start_time())
+    }
+    /// Samples the triangle at the first time sample of a deforming
+    /// mesh.
+    pub fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
+        self.mesh
+            .sample_triangle(self.id, u, self.mesh.start_time(), pdf)
+    }
     pub fn sample_with_ref_point(
         &self,
         iref: &InteractionCommon,
         u: Point2f,
         pdf: &mut Float,
     ) -> InteractionCommon {
-        let intr: InteractionCommon = self.sample(u, pdf);
+        // sample the triangle at the time of the reference point
+        let intr: InteractionCommon = self.mesh.sample_triangle(self.id, u, iref.time, pdf);
         let mut wi: Vector3f = intr.p - iref.p;
         if wi.length_squared() == 0.0 as Float {
             *pdf = 0.0 as Float;
@@ -750,7 +1108,8 @@ impl Triangle {
         if self.intersect(&ray, &mut t_hit, &mut isect_light) {
             // convert light sample weight to solid angle measure
             let mut pdf: Float = pnt3_distance_squaredf(&iref.get_p(), &isect_light.common.p)
-                / (nrm_abs_dot_vec3f(&isect_light.common.n, &-(*wi)) * self.area());
+                / (nrm_abs_dot_vec3f(&isect_light.common.n, &-(*wi))
+                    * self.mesh.triangle_area(self.id, iref.get_time()));
             if pdf.is_infinite() {
                 pdf = 0.0 as Float;
             }
@@ -760,3 +1119,202 @@ impl Triangle {
         }
     }
 }
+
+/// Maximum number of triangles stored in a leaf of the internal BVH
+/// of a compact mesh.
+const MAX_TRIANGLES_IN_LEAF: usize = 4;
+
+/// A whole (compressed) triangle mesh as a single shape. Instead of
+/// one *Triangle* shape and primitive per face, the triangles are
+/// referenced by their index into the mesh by an internal bounding
+/// volume hierarchy. Vertex positions stay at full precision,
+/// normals are octahedral encoded and (u, v) values quantized to 16
+/// bits.
+pub struct CompactTriangleMesh {
+    /// triangles in BVH order (boxed to keep _Shape_ small)
+    pub mesh: Box<TriangleMesh>,
+    bvh: ElementBVH,
+    area: Float,
+    /// picks the triangles proportional to their area
+    area_distribution: Distribution1D,
+}
+
+impl CompactTriangleMesh {
+    pub fn new(mut mesh: TriangleMesh) -> Self {
+        mesh.compress();
+        let bounds: Vec<Bounds3f> = (0..mesh.n_triangles)
+            .map(|id| mesh.triangle_world_bound(id))
+            .collect();
+        let (bvh, order) = ElementBVH::new(&bounds, MAX_TRIANGLES_IN_LEAF);
+        // store the triangles in the order the leaves refer to
+        let mut vertex_indices: Vec<u32> = Vec::with_capacity(mesh.vertex_indices.len());
+        for id in &order {
+            let idx1: usize = (*id * 3) as usize;
+            vertex_indices.extend_from_slice(&mesh.vertex_indices[idx1..(idx1 + 3)]);
+        }
+        mesh.vertex_indices = vertex_indices;
+        if !mesh.face_indices.is_empty() {
+            mesh.face_indices = order
+                .iter()
+                .map(|id| mesh.face_indices[*id as usize])
+                .collect();
+        }
+        let areas: Vec<Float> = (0..mesh.n_triangles)
+            .map(|id| mesh.triangle_area(id, mesh.start_time()))
+            .collect();
+        let area: Float = areas.iter().sum();
+        CompactTriangleMesh {
+            mesh: Box::new(mesh),
+            bvh,
+            area,
+            area_distribution: Distribution1D::new(areas),
+        }
+    }
+    // Shape
+    pub fn object_bound(&self) -> Bounds3f {
+        self.mesh
+            .world_to_object
+            .transform_bounds(&self.world_bound())
+    }
+    pub fn world_bound(&self) -> Bounds3f {
+        self.bvh.bounds()
+    }
+    pub fn intersect(&self, r: &Ray, t_hit: &mut Float, isect: &mut SurfaceInteraction) -> bool {
+        // closer hits overwrite *isect*, the last one found is kept
+        let hit: Option<(Float, usize)> = self.bvh.intersect(r, false, |id, ray| {
+            let mut t: Float = 0.0;
+            if self.mesh.intersect_triangle(id as u32, ray, &mut t, isect) {
+                Some(t)
+            } else {
+                None
+            }
+        });
+        if let Some((t, _id)) = hit {
+            *t_hit = t;
+            true
+        } else {
+            false
+        }
+    }
+    pub fn intersect_p(&self, r: &Ray) -> bool {
+        self.bvh
+            .intersect(r, true, |id, ray| {
+                if self.mesh.intersect_p_triangle(id as u32, ray) {
+                    Some(ray.t_max.get())
+                } else {
+                    None
+                }
+            })
+            .is_some()
+    }
+    pub fn get_reverse_orientation(&self) -> bool {
+        self.mesh.reverse_orientation
+    }
+    pub fn get_transform_swaps_handedness(&self) -> bool {
+        self.mesh.transform_swaps_handedness
+    }
+    pub fn get_object_to_world(&self) -> Transform {
+        self.mesh.object_to_world
+    }
+    pub fn area(&self) -> Float {
+        self.area
+    }
+    /// Picks a triangle proportional to its area and a position
+    /// uniformly on it, so the points are uniformly distributed over
+    /// the whole mesh and *pdf* is _1 / area()_. Deforming meshes are
+    /// sampled at their first time sample.
+    pub fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
+        self.sample_at_time(u, self.mesh.start_time(), pdf)
+    }
+    /// Like _sample()_, but positions the sampled point on the
+    /// triangle at the given time. The triangles are still picked
+    /// proportional to their area at the first time sample.
+    fn sample_at_time(&self, u: Point2f, time: Float, pdf: &mut Float) -> InteractionCommon {
+        let mut triangle_pdf: Float = 0.0 as Float;
+        let id: usize = self
+            .area_distribution
+            .sample_discrete(u.x, Some(&mut triangle_pdf));
+        // reuse the part of _u.x_ within the triangle's CDF segment
+        let cdf: &[Float] = &self.area_distribution.cdf;
+        let width: Float = cdf[id + 1] - cdf[id];
+        let x: Float = if width > 0.0 as Float {
+            (u.x - cdf[id]) / width
+        } else {
+            0.0 as Float
+        };
+        let u_remapped: Point2f = Point2f {
+            x: clamp_t(x, 0.0 as Float, 1.0 as Float - Float::EPSILON),
+            y: u.y,
+        };
+        let it: InteractionCommon = self.mesh.sample_triangle(id as u32, u_remapped, time, pdf);
+        *pdf *= triangle_pdf;
+        it
+    }
+    pub fn sample_with_ref_point(
+        &self,
+        iref: &InteractionCommon,
+        u: Point2f,
+        pdf: &mut Float,
+    ) -> InteractionCommon {
+        let intr: InteractionCommon = self.sample_at_time(u, iref.time, pdf);
+        let mut wi: Vector3f = intr.p - iref.p;
+        if wi.length_squared() == 0.0 as Float {
+            *pdf = 0.0 as Float;
+        } else {
+            wi = wi.normalize();
+            // convert from area measure, as returned by the Sample()
+            // call above, to solid angle measure.
+            *pdf *= pnt3_distance_squaredf(&iref.p, &intr.p) / nrm_abs_dot_vec3f(&intr.n, &-wi);
+            if (*pdf).is_infinite() {
+                *pdf = 0.0 as Float;
+            }
+        }
+        intr
+    }
+    pub fn pdf_with_ref_point(&self, iref: &dyn Interaction, wi: &Vector3f) -> Float {
+        // intersect sample ray with area light geometry
+        let ray: Ray = iref.spawn_ray(wi);
+        let mut isect_light: SurfaceInteraction = SurfaceInteraction::default();
+        let hit: Option<(Float, usize)> = self.bvh.intersect(&ray, false, |id, ray| {
+            let mut t: Float = 0.0;
+            if self
+                .mesh
+                .intersect_triangle(id as u32, ray, &mut t, &mut isect_light)
+            {
+                Some(t)
+            } else {
+                None
+            }
+        });
+        if let Some((_t, id)) = hit {
+            // density of _sample_at_time()_ for the hit triangle (which
+            // is _1 / area()_ unless the mesh deforms)
+            let area_pdf: Float = self.area_distribution.discrete_pdf(id)
+                / self.mesh.triangle_area(id as u32, iref.get_time());
+            // convert light sample weight to solid angle measure
+            let mut pdf: Float = pnt3_distance_squaredf(iref.get_p(), &isect_light.common.p)
+                * area_pdf
+                / nrm_abs_dot_vec3f(&isect_light.common.n, &-(*wi));
+            if pdf.is_infinite() {
+                pdf = 0.0 as Float;
+            }
+            pdf
+        } else {
+            0.0 as Float
+        }
+    }
+}
+
+/// Creates the shapes for a mesh, one *Triangle* per face or (for
+/// `"bool compact" true`) a single *CompactTriangleMesh*.
+pub fn create_triangle_mesh_shapes(mesh: TriangleMesh, params: &ParamSet) -> Vec<Arc<Shape>> {
+    if params.find_one_bool("compact", false) {
+        return vec![Arc::new(Shape::TrnglMsh(CompactTriangleMesh::new(mesh)))];
+    }
+    let mesh = Arc::new(mesh);
+    let mut shapes: Vec<Arc<Shape>> = Vec::new();
+    for id in 0..mesh.n_triangles {
+        shapes.push(Arc::new(Shape::Trngl(Triangle::new(mesh.clone(), id))));
+    }
+    shapes
+}
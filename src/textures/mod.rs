@@ -15,6 +15,7 @@
 //! - PtexTexture
 //! - ScaleTexture
 //! - UVTexture
+//! - VertexColorTexture
 //! - WindyTexture
 //! - WrinkledTexture
 //!
@@ -50,5 +51,6 @@ pub mod imagemap;
 pub mod marble;
 pub mod mix;
 pub mod scale;
+pub mod vertexcolor;
 pub mod windy;
 pub mod wrinkled;
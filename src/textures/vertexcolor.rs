@@ -0,0 +1,26 @@
+// pbrt
+use crate::core::interaction::SurfaceInteraction;
+use crate::core::pbrt::Spectrum;
+use crate::core::texture::Texture;
+
+/// Returns the per-vertex (or per-point) color of the shape that was
+/// hit, or a default color for shapes without colors.
+pub struct VertexColorTexture {
+    pub default: Spectrum,
+}
+
+impl VertexColorTexture {
+    pub fn new(default: Spectrum) -> Self {
+        VertexColorTexture { default }
+    }
+}
+
+impl Texture<Spectrum> for VertexColorTexture {
+    fn evaluate(&self, si: &SurfaceInteraction) -> Spectrum {
+        if let Some(color) = si.vertex_color {
+            color
+        } else {
+            self.default
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! A damaged BVH cache entry has to be treated as a cache miss: the
+//! BVH is built again and the image doesn't change.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn corrupted_entry_is_rebuilt(name: &str, width: i32) {
+    let directory: PathBuf = common::test_directory(name);
+    let cache: PathBuf = directory.join("cache");
+    let scene: PathBuf = directory.join("scene.pbrt");
+    let accelerator: String = format!(
+        "Accelerator \"bvh\" \"integer maxnodeprims\" 1 \"integer width\" {} \"string cachedir\" \"{}\"",
+        width,
+        cache.display()
+    );
+    fs::write(
+        &scene,
+        common::scene(
+            &format!(
+                "Sampler \"halton\" \"integer pixelsamples\" 4\n{}",
+                accelerator
+            ),
+            r#"Integrator "directlighting""#,
+        ),
+    )
+    .unwrap();
+    // the first render stores the BVH in the cache
+    common::render(&directory.join("first"), &scene, &[]);
+    let entries: Vec<PathBuf> = fs::read_dir(&cache)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |e| e == "bvh"))
+        .collect();
+    assert_eq!(entries.len(), 1);
+    // overwrite the offsets and primitive counts of the last node
+    corrupt_end(&entries[0], 8);
+    common::render(&directory.join("second"), &scene, &[]);
+    common::assert_same_image(&directory.join("first"), &directory.join("second"));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+fn corrupt_end(path: &Path, n_bytes: usize) {
+    let mut bytes: Vec<u8> = fs::read(path).unwrap();
+    let len: usize = bytes.len();
+    for byte in &mut bytes[(len - n_bytes)..] {
+        *byte = 0xff_u8;
+    }
+    fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn corrupted_binary_bvh_is_rebuilt() {
+    corrupted_entry_is_rebuilt("bvhcache_binary", 2);
+}
+
+#[test]
+fn corrupted_wide_bvh_is_rebuilt() {
+    corrupted_entry_is_rebuilt("bvhcache_wide", 4);
+}
@@ -0,0 +1,97 @@
+//! A render which is resumed from a checkpoint has to give the same
+//! image as an uninterrupted render. The tests resume tiles which have
+//! some of their samples already, the sampler has to continue their
+//! sample sequences.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn write_scene(directory: &Path, name: &str, sampler: &str, integrator: &str) -> PathBuf {
+    let scene: PathBuf = directory.join(name);
+    fs::write(&scene, common::scene(sampler, integrator)).unwrap();
+    scene
+}
+
+#[test]
+fn resumed_progressive_equals_uninterrupted() {
+    let directory: PathBuf = common::test_directory("checkpoint_progressive");
+    // a pixel sampler with precomputed tables, a path uses more
+    // dimensions than it precomputes
+    let scene: PathBuf = write_scene(
+        &directory,
+        "scene.pbrt",
+        r#"Sampler "stratified" "integer xsamples" 8 "integer ysamples" 8"#,
+        r#"Integrator "path" "integer maxdepth" 5"#,
+    );
+    let uninterrupted: PathBuf = directory.join("uninterrupted");
+    common::render(&uninterrupted, &scene, &[]);
+    // kill a progressive render after its first checkpoint, which is
+    // written during the first pass
+    let resumed: PathBuf = directory.join("resumed");
+    fs::create_dir_all(&resumed).unwrap();
+    let checkpoint: PathBuf = resumed.join("render.checkpoint");
+    let checkpoint_arg: &str = checkpoint.to_str().unwrap();
+    let deadline: Instant = Instant::now() + Duration::from_secs(120);
+    {
+        let mut render: common::ChildGuard = common::ChildGuard(
+            common::rs_pbrt(&resumed)
+                .args([
+                    "--nthreads",
+                    "1",
+                    "--progressive",
+                    "--checkpoint-interval",
+                    "0",
+                ])
+                .args(["--checkpoint", checkpoint_arg])
+                .arg(&scene)
+                .spawn()
+                .unwrap(),
+        );
+        while !checkpoint.exists() {
+            assert!(
+                render.0.try_wait().unwrap().is_none(),
+                "render finished without a checkpoint"
+            );
+            assert!(Instant::now() < deadline, "render timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+        // the guard kills the render
+    }
+    common::render(&resumed, &scene, &["--resume", checkpoint_arg]);
+    common::assert_same_image(&uninterrupted, &resumed);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn resumed_bdpt_equals_uninterrupted() {
+    let directory: PathBuf = common::test_directory("checkpoint_bdpt");
+    // the random sampler doesn't precompute anything, its samples
+    // don't depend on the samples per pixel
+    let integrator: &str = r#"Integrator "bdpt" "integer maxdepth" 5"#;
+    let scene_4spp: PathBuf = write_scene(
+        &directory,
+        "scene_4spp.pbrt",
+        r#"Sampler "random" "integer pixelsamples" 4"#,
+        integrator,
+    );
+    let scene_8spp: PathBuf = write_scene(
+        &directory,
+        "scene_8spp.pbrt",
+        r#"Sampler "random" "integer pixelsamples" 8"#,
+        integrator,
+    );
+    let uninterrupted: PathBuf = directory.join("uninterrupted");
+    common::render(&uninterrupted, &scene_8spp, &[]);
+    // render 4 samples per pixel first, then add the missing ones
+    let resumed: PathBuf = directory.join("resumed");
+    let checkpoint: PathBuf = directory.join("render.checkpoint");
+    let checkpoint_arg: &str = checkpoint.to_str().unwrap();
+    common::render(&resumed, &scene_4spp, &["--checkpoint", checkpoint_arg]);
+    common::render(&resumed, &scene_8spp, &["--resume", checkpoint_arg]);
+    common::assert_same_image(&uninterrupted, &resumed);
+    let _ = fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,99 @@
+//! Helpers for the tests which run the **rs_pbrt** executable.
+
+// not every test uses all of them
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A small scene with a sphere on a ground plane. *sampler* and
+/// *integrator* are inserted as they are.
+pub fn scene(sampler: &str, integrator: &str) -> String {
+    format!(
+        r#"
+LookAt 3 4 1.5  .5 .5 0  0 0 1
+Camera "perspective" "float fov" 45
+{}
+{}
+Film "image" "integer xresolution" [80] "integer yresolution" [48]
+WorldBegin
+LightSource "infinite" "rgb L" [.4 .45 .5]
+LightSource "distant" "point from" [-30 40 100] "rgb L" [2 2 2]
+AttributeBegin
+  Material "matte" "rgb Kd" [.8 .2 .2]
+  Shape "sphere" "float radius" 1
+AttributeEnd
+AttributeBegin
+  Material "matte" "rgb Kd" [.5 .5 .5]
+  Shape "trianglemesh" "integer indices" [0 1 2 0 2 3]
+    "point P" [-20 -20 -1  20 -20 -1  20 20 -1  -20 20 -1]
+AttributeEnd
+WorldEnd
+"#,
+        sampler, integrator
+    )
+}
+
+/// A new (empty) directory for the test *name*.
+pub fn test_directory(name: &str) -> PathBuf {
+    let directory: PathBuf =
+        std::env::temp_dir().join(format!("rs_pbrt_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Runs **rs_pbrt** in *directory*, where it writes `pbrt.png`.
+pub fn rs_pbrt(directory: &Path) -> Command {
+    let mut command: Command = Command::new(env!("CARGO_BIN_EXE_rs_pbrt"));
+    command.current_dir(directory).stdout(Stdio::null());
+    command
+}
+
+/// Kills the process (if it's still running) when the test ends, even
+/// if an assertion failed.
+pub struct ChildGuard(pub Child);
+
+impl ChildGuard {
+    /// Waits until *deadline* for the process to exit.
+    pub fn wait_until(&mut self, deadline: Instant) -> ExitStatus {
+        loop {
+            if let Some(status) = self.0.try_wait().unwrap() {
+                return status;
+            }
+            assert!(Instant::now() < deadline, "render timed out");
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Renders *scene* in *directory* with the additional *args*.
+pub fn render(directory: &Path, scene: &Path, args: &[&str]) {
+    fs::create_dir_all(directory).unwrap();
+    let status = rs_pbrt(directory).args(args).arg(scene).status().unwrap();
+    assert!(status.success());
+}
+
+/// Compares the `pbrt.png` images in two directories. The values may
+/// differ by one, because the pixel sums are added in a different
+/// order.
+pub fn assert_same_image(expected: &Path, actual: &Path) {
+    let expected = image::open(expected.join("pbrt.png")).unwrap().to_rgb8();
+    let actual = image::open(actual.join("pbrt.png")).unwrap().to_rgb8();
+    assert_eq!(expected.dimensions(), actual.dimensions());
+    for (e, a) in expected.pixels().zip(actual.pixels()) {
+        for c in 0..3 {
+            assert!((e[c] as i32 - a[c] as i32).abs() <= 1);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Area lights on deforming meshes have to be sampled at the time of
+//! the shading point: a vertex-animated light seen at its last time
+//! sample lights the scene like a static light placed there.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+const HEADER: &str = r#"
+LookAt 3 4 3  0 0 0  0 0 1
+Camera "perspective" "float fov" 45 "float shutteropen" 1 "float shutterclose" 1
+Sampler "halton" "integer pixelsamples" 4
+Integrator "directlighting"
+Film "image" "integer xresolution" [64] "integer yresolution" [48]
+WorldBegin
+AttributeBegin
+  Material "matte" "rgb Kd" [.5 .5 .5]
+  Shape "trianglemesh" "integer indices" [0 1 2 0 2 3]
+    "point P" [-20 -20 0  20 -20 0  20 20 0  -20 20 0]
+AttributeEnd
+"#;
+
+/// The quad light at its final position.
+const QUAD: &str = "-1 -1 2  1 -1 2  1 1 2  -1 1 2";
+
+/// Renders the deforming and the static light with the additional
+/// shape parameters *params* and compares them.
+fn compare_lights(name: &str, params: &str) {
+    let directory: PathBuf = common::test_directory(name);
+    let light = |p: &str, times: &str| {
+        format!(
+            "{}AttributeBegin\n\
+             AreaLightSource \"diffuse\" \"rgb L\" [4 4 4] \"bool twosided\" [\"true\"]\n\
+             Shape \"trianglemesh\" \"integer indices\" [0 1 2 0 2 3]\n\
+             \"point P\" [{}]{}{}\n\
+             AttributeEnd\nWorldEnd\n",
+            HEADER, p, times, params
+        )
+    };
+    // a small quad far away at time 0, which moves to _QUAD_ at time 1
+    let deforming: String = light(
+        &format!("40 40 10  40.1 40 10  40.1 40.1 10  40 40.1 10  {}", QUAD),
+        " \"float times\" [0 1]",
+    );
+    let deforming_scene: PathBuf = directory.join("deforming.pbrt");
+    let static_scene: PathBuf = directory.join("static.pbrt");
+    fs::write(&deforming_scene, deforming).unwrap();
+    fs::write(&static_scene, light(QUAD, "")).unwrap();
+    common::render(&directory.join("deforming"), &deforming_scene, &[]);
+    common::render(&directory.join("static"), &static_scene, &[]);
+    common::assert_same_image(&directory.join("static"), &directory.join("deforming"));
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn deforming_area_light_equals_static_light() {
+    compare_lights("deforming", "");
+}
+
+#[test]
+fn deforming_compact_area_light_equals_static_light() {
+    compare_lights("deforming_compact", " \"bool compact\" [\"true\"]");
+}
@@ -0,0 +1,206 @@
+//! Renders a small scene with a coordinator and several workers on
+//! localhost and compares the image with a local render.
+
+mod common;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Writes the test scene to *directory*, renders the reference image
+/// into `local` and returns the scene's path.
+fn setup(directory: &Path) -> PathBuf {
+    let scene: PathBuf = directory.join("scene.pbrt");
+    fs::write(
+        &scene,
+        common::scene(
+            r#"Sampler "halton" "integer pixelsamples" 4"#,
+            r#"Integrator "path""#,
+        ),
+    )
+    .unwrap();
+    common::render(&directory.join("local"), &scene, &[]);
+    scene
+}
+
+/// A free port on localhost.
+fn local_address() -> String {
+    let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+/// The `HELLO` message of a worker rendering the scene of _setup()_.
+fn hello() -> Vec<u8> {
+    let mut hello: Vec<u8> = b"rs_pbrtD".to_vec();
+    hello.extend_from_slice(&2_u32.to_le_bytes());
+    for v in &[0_i32, 0, 80, 48] {
+        hello.extend_from_slice(&v.to_le_bytes());
+    }
+    hello.extend_from_slice(&4_i64.to_le_bytes());
+    hello
+}
+
+fn spawn_coordinator(directory: &Path, scene: &Path, args: &[&str]) -> common::ChildGuard {
+    common::ChildGuard(
+        common::rs_pbrt(directory)
+            .args(args)
+            .arg(scene)
+            .spawn()
+            .unwrap(),
+    )
+}
+
+fn spawn_worker(directory: &Path, scene: &Path, address: &str) -> common::ChildGuard {
+    common::ChildGuard(
+        common::rs_pbrt(directory)
+            .args(["--nthreads", "2", "--worker", address])
+            .arg(scene)
+            .spawn()
+            .unwrap(),
+    )
+}
+
+#[test]
+fn distributed_localhost() {
+    let directory: PathBuf = common::test_directory("distributed");
+    let distributed: PathBuf = directory.join("distributed");
+    fs::create_dir_all(&distributed).unwrap();
+    let scene: PathBuf = setup(&directory);
+    // one coordinator and three workers with two threads each
+    let address: String = local_address();
+    let deadline: Instant = Instant::now() + Duration::from_secs(120);
+    let mut coordinator: common::ChildGuard =
+        spawn_coordinator(&distributed, &scene, &["--coordinator", &address]);
+    let workers: Vec<common::ChildGuard> = (0..3)
+        .map(|_| spawn_worker(&distributed, &scene, &address))
+        .collect();
+    for mut worker in workers {
+        assert!(worker.wait_until(deadline).success());
+    }
+    assert!(coordinator.wait_until(deadline).success());
+    // the tiles are seeded like in a local render, only the order in
+    // which they are merged differs
+    common::assert_same_image(&directory.join("local"), &distributed);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn stalled_worker_loses_its_tile() {
+    let directory: PathBuf = common::test_directory("distributed_timeout");
+    let distributed: PathBuf = directory.join("distributed");
+    fs::create_dir_all(&distributed).unwrap();
+    let scene: PathBuf = setup(&directory);
+    let address: String = local_address();
+    let deadline: Instant = Instant::now() + Duration::from_secs(120);
+    let mut coordinator: common::ChildGuard = spawn_coordinator(
+        &distributed,
+        &scene,
+        &["--coordinator", &address, "--worker-timeout", "1"],
+    );
+    // a worker which takes a tile and never sends the result
+    let mut stalled: TcpStream = loop {
+        match TcpStream::connect(&address) {
+            Ok(stream) => break stream,
+            Err(_) => {
+                assert!(Instant::now() < deadline, "coordinator not listening");
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+    };
+    stalled.write_all(&hello()).unwrap();
+    let mut tile: [u8; 12] = [0_u8; 12];
+    stalled.read_exact(&mut tile).unwrap();
+    // the real worker has to render the stalled tile as well
+    let mut worker: common::ChildGuard = spawn_worker(&distributed, &scene, &address);
+    assert!(worker.wait_until(deadline).success());
+    assert!(coordinator.wait_until(deadline).success());
+    common::assert_same_image(&directory.join("local"), &distributed);
+    drop(stalled);
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn slow_tile_keeps_its_worker() {
+    let directory: PathBuf = common::test_directory("distributed_slow");
+    let scene: PathBuf = directory.join("scene.pbrt");
+    // a single tile, which takes longer than the worker timeout
+    fs::write(
+        &scene,
+        common::scene(
+            r#"Sampler "halton" "integer pixelsamples" 2048"#,
+            r#"Integrator "path""#,
+        )
+        .replace("[80]", "[16]")
+        .replace("[48]", "[16]"),
+    )
+    .unwrap();
+    let address: String = local_address();
+    let deadline: Instant = Instant::now() + Duration::from_secs(600);
+    let mut coordinator: common::ChildGuard = spawn_coordinator(
+        &directory,
+        &scene,
+        &["--coordinator", &address, "--worker-timeout", "1"],
+    );
+    let mut worker: common::ChildGuard = common::ChildGuard(
+        common::rs_pbrt(&directory)
+            .args(["--nthreads", "1", "--worker", &address])
+            .arg(&scene)
+            .spawn()
+            .unwrap(),
+    );
+    assert!(worker.wait_until(deadline).success());
+    assert!(coordinator.wait_until(deadline).success());
+    assert!(directory.join("pbrt.png").exists());
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn tile_lost_too_often_aborts_the_render() {
+    let directory: PathBuf = common::test_directory("distributed_lost");
+    let scene: PathBuf = directory.join("scene.pbrt");
+    fs::write(
+        &scene,
+        common::scene(
+            r#"Sampler "halton" "integer pixelsamples" 4"#,
+            r#"Integrator "path""#,
+        ),
+    )
+    .unwrap();
+    let address: String = local_address();
+    let deadline: Instant = Instant::now() + Duration::from_secs(120);
+    let mut coordinator: common::ChildGuard =
+        spawn_coordinator(&directory, &scene, &["--coordinator", &address]);
+    // workers which take a tile and disconnect right away, until the
+    // coordinator gives up
+    while coordinator.0.try_wait().unwrap().is_none() {
+        assert!(Instant::now() < deadline, "coordinator didn't give up");
+        if let Ok(mut crashing) = TcpStream::connect(&address) {
+            let mut tile: [u8; 12] = [0_u8; 12];
+            let _ = crashing
+                .write_all(&hello())
+                .and_then(|_| crashing.read_exact(&mut tile));
+        } else {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+    assert!(!directory.join("pbrt.png").exists());
+    let _ = fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn distributed_rejects_progressive() {
+    let directory: PathBuf = common::test_directory("distributed_progressive");
+    let scene: PathBuf = directory.join("scene.pbrt");
+    fs::write(&scene, "").unwrap();
+    let status = common::rs_pbrt(&directory)
+        .args(["--coordinator", "127.0.0.1:0", "--progressive"])
+        .arg(&scene)
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+    let _ = fs::remove_dir_all(&directory);
+}
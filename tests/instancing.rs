@@ -0,0 +1,50 @@
+//! Object instances have to look like the same shapes placed
+//! directly in the scene: instances with an identity transform are
+//! hit, and the hit keeps the instance's material.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+const HEADER: &str = r#"
+LookAt 0 -6 2  0 0 0  0 0 1
+Camera "perspective" "float fov" 45
+Sampler "halton" "integer pixelsamples" 4
+Integrator "directlighting"
+Film "image" "integer xresolution" [64] "integer yresolution" [48]
+WorldBegin
+LightSource "distant" "point from" [-3 -4 10] "rgb L" [3 3 3]
+"#;
+
+const SPHERE: &str = r#"
+  Material "matte" "rgb Kd" [.8 .2 .2]
+  Shape "sphere" "float radius" 1
+"#;
+
+#[test]
+fn instances_equal_shapes() {
+    let directory: PathBuf = common::test_directory("instancing");
+    // one instance without and one with a transform
+    let instanced: String = format!(
+        "{}ObjectBegin \"sphere\"{}ObjectEnd\n\
+         ObjectInstance \"sphere\"\n\
+         AttributeBegin\nTranslate 2.5 0 0\nObjectInstance \"sphere\"\nAttributeEnd\n\
+         WorldEnd\n",
+        HEADER, SPHERE
+    );
+    let direct: String = format!(
+        "{}AttributeBegin{}AttributeEnd\n\
+         AttributeBegin\nTranslate 2.5 0 0{}AttributeEnd\n\
+         WorldEnd\n",
+        HEADER, SPHERE, SPHERE
+    );
+    let instanced_scene: PathBuf = directory.join("instanced.pbrt");
+    let direct_scene: PathBuf = directory.join("direct.pbrt");
+    fs::write(&instanced_scene, instanced).unwrap();
+    fs::write(&direct_scene, direct).unwrap();
+    common::render(&directory.join("instanced"), &instanced_scene, &[]);
+    common::render(&directory.join("direct"), &direct_scene, &[]);
+    common::assert_same_image(&directory.join("direct"), &directory.join("instanced"));
+    let _ = fs::remove_dir_all(&directory);
+}
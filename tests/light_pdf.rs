@@ -0,0 +1,192 @@
+//! The densities a **DiffuseAreaLight** reports in _pdf_le()_ have to
+//! match the ones _sample_le()_ returned for the same ray, otherwise
+//! the MIS weights of light paths (e.g. in BDPT) are wrong.
+
+use std::sync::Arc;
+
+use pbrt::core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray};
+use pbrt::core::interaction::{InteractionCommon, SurfaceInteraction};
+use pbrt::core::medium::MediumInterface;
+use pbrt::core::pbrt::{Float, Spectrum};
+use pbrt::core::rng::Rng;
+use pbrt::core::shape::{CustomShape, Shape};
+use pbrt::core::transform::Transform;
+use pbrt::lights::diffuse::DiffuseAreaLight;
+use pbrt::shapes::bilinearpatch::{BilinearPatch, BilinearPatchMesh};
+use pbrt::shapes::points::PointCloud;
+
+fn area_light(shape: Shape) -> DiffuseAreaLight {
+    DiffuseAreaLight::new(
+        &Transform::default(),
+        &MediumInterface::new(None, None),
+        &Spectrum::new(1.0 as Float),
+        1,
+        Arc::new(shape),
+        false,
+    )
+}
+
+/// Compares the densities of _sample_le()_ and _pdf_le()_ for many
+/// emitted rays.
+fn assert_consistent_pdfs(light: &DiffuseAreaLight) {
+    let mut rng: Rng = Rng::new();
+    for _ in 0..1000 {
+        let u1: Point2f = Point2f {
+            x: rng.uniform_float(),
+            y: rng.uniform_float(),
+        };
+        let u2: Point2f = Point2f {
+            x: rng.uniform_float(),
+            y: rng.uniform_float(),
+        };
+        let mut ray: Ray = Ray::default();
+        let mut n: Normal3f = Normal3f::default();
+        let mut pdf_pos: Float = 0.0 as Float;
+        let mut pdf_dir: Float = 0.0 as Float;
+        light.sample_le(
+            u1,
+            u2,
+            0.0 as Float,
+            &mut ray,
+            &mut n,
+            &mut pdf_pos,
+            &mut pdf_dir,
+        );
+        let mut pdf_le_pos: Float = 0.0 as Float;
+        let mut pdf_le_dir: Float = 0.0 as Float;
+        light.pdf_le(&ray, &n, &mut pdf_le_pos, &mut pdf_le_dir);
+        assert!(
+            (pdf_le_pos - pdf_pos).abs() <= 1e-3 as Float * pdf_pos,
+            "pdf_le() {} != sample_le() {}",
+            pdf_le_pos,
+            pdf_pos
+        );
+        assert!((pdf_le_dir - pdf_dir).abs() <= 1e-3 as Float * pdf_dir);
+    }
+}
+
+#[test]
+fn skewed_bilinear_patch() {
+    // not a rectangle, so the patch isn't sampled uniformly by area
+    let p: Vec<Point3f> = vec![
+        Point3f {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 2.0,
+            y: 1.5,
+            z: 0.7,
+        },
+    ];
+    let mesh: BilinearPatchMesh = BilinearPatchMesh::new(
+        Transform::default(),
+        Transform::default(),
+        false,
+        1,
+        vec![0, 1, 2, 3],
+        4,
+        p,
+        Vec::new(),
+        Vec::new(),
+    );
+    let patch: BilinearPatch = BilinearPatch::new(Arc::new(mesh), 0);
+    assert_consistent_pdfs(&area_light(Shape::Blnr(patch)));
+}
+
+#[test]
+fn points_with_different_radii() {
+    // overlapping spheres, each picked with the same probability
+    let p: Vec<Point3f> = vec![
+        Point3f {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 0.5,
+            y: 0.0,
+            z: 0.0,
+        },
+        Point3f {
+            x: 3.0,
+            y: 1.0,
+            z: -2.0,
+        },
+    ];
+    let cloud: PointCloud = PointCloud::new(
+        Transform::default(),
+        Transform::default(),
+        false,
+        &p,
+        &[1.0, 0.25, 0.05],
+        Vec::new(),
+    );
+    assert_consistent_pdfs(&area_light(Shape::Pnts(cloud)));
+}
+
+/// The unit square in the xy-plane, sampled with a density that
+/// grows linearly with x.
+struct LinearSquare;
+
+impl CustomShape for LinearSquare {
+    fn object_bound(&self) -> Bounds3f {
+        Bounds3f::new(
+            Point3f {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point3f {
+                x: 1.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        )
+    }
+    fn get_object_to_world(&self) -> Transform {
+        Transform::default()
+    }
+    fn intersect(&self, _r: &Ray, _t_hit: &mut Float, _isect: &mut SurfaceInteraction) -> bool {
+        false
+    }
+    fn area(&self) -> Float {
+        1.0 as Float
+    }
+    fn sample(&self, u: Point2f, pdf: &mut Float) -> InteractionCommon {
+        let p: Point3f = Point3f {
+            x: u.x.sqrt(),
+            y: u.y,
+            z: 0.0,
+        };
+        *pdf = 2.0 as Float * p.x;
+        InteractionCommon {
+            p,
+            n: Normal3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            ..Default::default()
+        }
+    }
+    fn pdf(&self, iref: &InteractionCommon) -> Float {
+        2.0 as Float * iref.p.x
+    }
+}
+
+#[test]
+fn custom_shape_with_point_dependent_pdf() {
+    assert_consistent_pdfs(&area_light(Shape::Custom(Arc::new(LinearSquare))));
+}
@@ -0,0 +1,30 @@
+//! A progressive render continues the sample sequence of each pixel
+//! in every pass, so it has to give the same image as a render
+//! without passes.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn progressive_equals_single_pass() {
+    let directory: PathBuf = common::test_directory("progressive");
+    let scene: PathBuf = directory.join("scene.pbrt");
+    // a pixel sampler with precomputed tables, a path tracer uses
+    // more dimensions than it precomputes
+    fs::write(
+        &scene,
+        common::scene(
+            r#"Sampler "stratified" "integer xsamples" 4 "integer ysamples" 4"#,
+            r#"Integrator "path" "integer maxdepth" 5"#,
+        ),
+    )
+    .unwrap();
+    let single: PathBuf = directory.join("single");
+    let progressive: PathBuf = directory.join("progressive");
+    common::render(&single, &scene, &[]);
+    common::render(&progressive, &scene, &["--progressive"]);
+    common::assert_same_image(&single, &progressive);
+    let _ = fs::remove_dir_all(&directory);
+}
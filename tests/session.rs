@@ -0,0 +1,166 @@
+//! Renders the scene of _common::scene()_ (built with the API calls
+//! instead of the parser) in a **RenderSession** and pauses or
+//! cancels it.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use pbrt::core::api::{
+    pbrt_attribute_begin, pbrt_attribute_end, pbrt_camera, pbrt_film, pbrt_init, pbrt_integrator,
+    pbrt_light_source, pbrt_look_at, pbrt_material, pbrt_render_session, pbrt_sampler, pbrt_shape,
+    pbrt_world_begin,
+};
+use pbrt::core::api::{ApiState, BsdfState};
+use pbrt::core::geometry::Point3f;
+use pbrt::core::paramset::ParamSet;
+use pbrt::core::pbrt::{Float, Spectrum};
+use pbrt::core::session::{RenderSession, TileCallback};
+
+/// 5 x 3 tiles of 16 x 16 pixels.
+const N_TILES: usize = 15;
+
+/// The film writes `pbrt.png` into the current directory, which all
+/// tests of this file share.
+static CURRENT_DIR: Mutex<()> = Mutex::new(());
+
+/// Makes a new test directory the current one until the guard gets
+/// dropped.
+fn enter_test_directory(name: &str) -> (MutexGuard<'static, ()>, PathBuf) {
+    let guard: MutexGuard<'static, ()> = CURRENT_DIR.lock().unwrap_or_else(|e| e.into_inner());
+    let directory: PathBuf = common::test_directory(name);
+    std::env::set_current_dir(&directory).unwrap();
+    (guard, directory)
+}
+
+fn params(key_word: &str, name: &str) -> ParamSet {
+    let mut params: ParamSet = ParamSet::default();
+    params.key_word = String::from(key_word);
+    params.name = String::from(name);
+    params
+}
+
+fn matte(api_state: &mut ApiState, r: Float, g: Float, b: Float) {
+    let mut material: ParamSet = params("Material", "matte");
+    material.add_rgb_spectrum(String::from("Kd"), Spectrum::rgb(r, g, b));
+    pbrt_material(api_state, material);
+}
+
+/// Starts rendering the test scene with *integrator*, *tiles* counts
+/// the merged tiles.
+fn start_session(integrator: &str, tiles: &Arc<AtomicUsize>) -> Result<RenderSession, String> {
+    let (mut api_state, mut bsdf_state): (ApiState, BsdfState) = pbrt_init(2_u8);
+    pbrt_look_at(&mut api_state, 3.0, 4.0, 1.5, 0.5, 0.5, 0.0, 0.0, 0.0, 1.0);
+    let mut camera: ParamSet = params("Camera", "perspective");
+    camera.add_float(String::from("fov"), 45.0);
+    pbrt_camera(&mut api_state, camera);
+    let mut sampler: ParamSet = params("Sampler", "halton");
+    sampler.add_int(String::from("pixelsamples"), 4);
+    pbrt_sampler(&mut api_state, sampler);
+    pbrt_integrator(&mut api_state, params("Integrator", integrator));
+    let mut film: ParamSet = params("Film", "image");
+    film.add_int(String::from("xresolution"), 80);
+    film.add_int(String::from("yresolution"), 48);
+    pbrt_film(&mut api_state, film);
+    pbrt_world_begin(&mut api_state);
+    let mut infinite: ParamSet = params("LightSource", "infinite");
+    infinite.add_rgb_spectrum(String::from("L"), Spectrum::rgb(0.4, 0.45, 0.5));
+    pbrt_light_source(&mut api_state, infinite);
+    let mut distant: ParamSet = params("LightSource", "distant");
+    distant.add_point3f(
+        String::from("from"),
+        Point3f {
+            x: -30.0,
+            y: 40.0,
+            z: 100.0,
+        },
+    );
+    distant.add_rgb_spectrum(String::from("L"), Spectrum::rgb(2.0, 2.0, 2.0));
+    pbrt_light_source(&mut api_state, distant);
+    pbrt_attribute_begin(&mut api_state);
+    matte(&mut api_state, 0.8, 0.2, 0.2);
+    let mut sphere: ParamSet = params("Shape", "sphere");
+    sphere.add_float(String::from("radius"), 1.0);
+    pbrt_shape(&mut api_state, &mut bsdf_state, sphere);
+    pbrt_attribute_end(&mut api_state);
+    pbrt_attribute_begin(&mut api_state);
+    matte(&mut api_state, 0.5, 0.5, 0.5);
+    let mut ground: ParamSet = params("Shape", "trianglemesh");
+    ground.add_ints(String::from("indices"), vec![0, 1, 2, 0, 2, 3]);
+    ground.add_point3fs(
+        String::from("P"),
+        vec![
+            -20.0, -20.0, -1.0, 20.0, -20.0, -1.0, 20.0, 20.0, -1.0, -20.0, 20.0, -1.0,
+        ],
+    );
+    pbrt_shape(&mut api_state, &mut bsdf_state, ground);
+    pbrt_attribute_end(&mut api_state);
+    let tiles: Arc<AtomicUsize> = tiles.clone();
+    let on_tile: TileCallback = Arc::new(move |_| {
+        tiles.fetch_add(1, Ordering::SeqCst);
+    });
+    pbrt_render_session(&api_state, Some(on_tile))
+}
+
+fn image_written(directory: &Path) -> bool {
+    directory.join("pbrt.png").exists()
+}
+
+#[test]
+fn callback_fires_once_per_tile() {
+    let (_guard, directory) = enter_test_directory("session_tiles");
+    let tiles: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let mut session: RenderSession = start_session("path", &tiles).unwrap();
+    session.wait();
+    assert!(session.is_finished());
+    assert_eq!(tiles.load(Ordering::SeqCst), N_TILES);
+    assert!(session.get_rgb().iter().any(|v| *v > 0.0 as Float));
+    assert!(image_written(&directory));
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn cancelled_session_writes_no_image() {
+    let (_guard, directory) = enter_test_directory("session_cancel");
+    let tiles: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let mut session: RenderSession = start_session("path", &tiles).unwrap();
+    session.cancel();
+    session.wait();
+    assert!(session.is_cancelled());
+    // tiles which were being rendered are finished first
+    assert!(tiles.load(Ordering::SeqCst) < N_TILES);
+    assert!(!image_written(&directory));
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn paused_session_merges_no_new_tiles() {
+    let (_guard, directory) = enter_test_directory("session_pause");
+    let tiles: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let mut session: RenderSession = start_session("path", &tiles).unwrap();
+    session.pause();
+    // give the tiles which were being rendered time to be merged
+    thread::sleep(Duration::from_secs(1));
+    let n_paused: usize = tiles.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_secs(1));
+    assert_eq!(tiles.load(Ordering::SeqCst), n_paused);
+    assert!(n_paused < N_TILES);
+    assert!(!session.is_finished());
+    session.resume();
+    session.wait();
+    assert_eq!(tiles.load(Ordering::SeqCst), N_TILES);
+    assert!(image_written(&directory));
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn session_rejects_bdpt() {
+    let (_guard, directory) = enter_test_directory("session_bdpt");
+    let tiles: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    assert!(start_session("bdpt", &tiles).is_err());
+    let _ = std::fs::remove_dir_all(&directory);
+}